@@ -0,0 +1,584 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use sha2::{Digest, Sha256};
+
+use crate::command::{Command, COMMAND_TABLE};
+use crate::config::Config;
+use crate::protocol::frame::Frame;
+use crate::utils;
+
+pub const DEFAULT_USER: &str = "default";
+
+/// Command categories, as used by `+@<category>` and `-@<category>` rules.
+pub mod category {
+    pub const KEYSPACE: u32 = 1 << 0;
+    pub const READ: u32 = 1 << 1;
+    pub const WRITE: u32 = 1 << 2;
+    pub const SET: u32 = 1 << 3;
+    pub const SORTEDSET: u32 = 1 << 4;
+    pub const LIST: u32 = 1 << 5;
+    pub const HASH: u32 = 1 << 6;
+    pub const STRING: u32 = 1 << 7;
+    pub const BITMAP: u32 = 1 << 8;
+    pub const HYPERLOGLOG: u32 = 1 << 9;
+    pub const GEO: u32 = 1 << 10;
+    pub const STREAM: u32 = 1 << 11;
+    pub const PUBSUB: u32 = 1 << 12;
+    pub const ADMIN: u32 = 1 << 13;
+    pub const FAST: u32 = 1 << 14;
+    pub const SLOW: u32 = 1 << 15;
+    pub const BLOCKING: u32 = 1 << 16;
+    pub const DANGEROUS: u32 = 1 << 17;
+    pub const CONNECTION: u32 = 1 << 18;
+    pub const TRANSACTION: u32 = 1 << 19;
+    pub const SCRIPTING: u32 = 1 << 20;
+}
+
+pub static CATEGORIES: &[(&str, u32)] = &[
+    ("keyspace", category::KEYSPACE),
+    ("read", category::READ),
+    ("write", category::WRITE),
+    ("set", category::SET),
+    ("sortedset", category::SORTEDSET),
+    ("list", category::LIST),
+    ("hash", category::HASH),
+    ("string", category::STRING),
+    ("bitmap", category::BITMAP),
+    ("hyperloglog", category::HYPERLOGLOG),
+    ("geo", category::GEO),
+    ("stream", category::STREAM),
+    ("pubsub", category::PUBSUB),
+    ("admin", category::ADMIN),
+    ("fast", category::FAST),
+    ("slow", category::SLOW),
+    ("blocking", category::BLOCKING),
+    ("dangerous", category::DANGEROUS),
+    ("connection", category::CONNECTION),
+    ("transaction", category::TRANSACTION),
+    ("scripting", category::SCRIPTING),
+];
+
+pub fn find_category(name: &str) -> Option<u32> {
+    if name.eq_ignore_ascii_case("all") {
+        return Some(u32::MAX);
+    }
+    CATEGORIES
+        .iter()
+        .find(|(cat, _)| cat.eq_ignore_ascii_case(name))
+        .map(|(_, bits)| *bits)
+}
+
+#[derive(Debug, Clone)]
+pub struct User {
+    name: String,
+    enabled: bool,
+    nopass: bool,
+    /// sha256 of each password, hex encoded
+    passwords: Vec<String>,
+    /// command rules in the order they were applied, e.g. `+@all -flushdb`
+    command_rules: Vec<String>,
+    allowed_commands: HashSet<&'static str>,
+    key_patterns: Vec<String>,
+}
+
+impl User {
+    fn new(name: &str) -> User {
+        User {
+            name: name.to_string(),
+            enabled: false,
+            nopass: false,
+            passwords: Vec::new(),
+            command_rules: Vec::new(),
+            allowed_commands: HashSet::new(),
+            key_patterns: Vec::new(),
+        }
+    }
+
+    fn new_default() -> User {
+        let mut user = User::new(DEFAULT_USER);
+        for rule in ["on", "nopass", "~*", "+@all"] {
+            user.apply_rule(rule).unwrap();
+        }
+        user
+    }
+
+    pub fn apply_rule(&mut self, rule: &str) -> Result<(), String> {
+        let lower = rule.to_lowercase();
+        match &lower[..] {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.apply_rule("~*")?,
+            "resetkeys" => self.key_patterns.clear(),
+            "allcommands" => self.apply_rule("+@all")?,
+            "nocommands" => self.apply_rule("-@all")?,
+            "reset" => {
+                for rule in ["resetpass", "resetkeys", "nocommands", "off"] {
+                    self.apply_rule(rule)?;
+                }
+            }
+            "" => return Err("Syntax error".into()),
+            _ => match rule.as_bytes()[0] {
+                b'>' => {
+                    self.nopass = false;
+                    let hash = hash_password(&rule[1..]);
+                    if !self.passwords.contains(&hash) {
+                        self.passwords.push(hash);
+                    }
+                }
+                b'#' => {
+                    let hash = &lower[1..];
+                    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        return Err("The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters".into());
+                    }
+                    self.nopass = false;
+                    if !self.passwords.iter().any(|p| p == hash) {
+                        self.passwords.push(hash.to_string());
+                    }
+                }
+                b'<' | b'!' => {
+                    let hash = match rule.strip_prefix('<') {
+                        Some(password) => hash_password(password),
+                        None => lower[1..].to_string(),
+                    };
+                    match self.passwords.iter().position(|p| *p == hash) {
+                        Some(i) => {
+                            self.passwords.remove(i);
+                        }
+                        None => return Err("no such password".into()),
+                    }
+                }
+                b'~' => {
+                    let pattern = rule[1..].to_string();
+                    if pattern == "*" {
+                        self.key_patterns.clear();
+                    } else if self.key_patterns.iter().any(|p| p == "*") {
+                        return Ok(());
+                    }
+                    if !self.key_patterns.contains(&pattern) {
+                        self.key_patterns.push(pattern);
+                    }
+                }
+                b'+' | b'-' => self.apply_command_rule(&lower)?,
+                _ => return Err("Syntax error".into()),
+            },
+        }
+        Ok(())
+    }
+
+    fn apply_command_rule(&mut self, rule: &str) -> Result<(), String> {
+        let allow = rule.starts_with('+');
+        let names: Vec<&'static str> = match rule[1..].strip_prefix('@') {
+            Some(cat) => {
+                let bits = match find_category(cat) {
+                    Some(bits) => bits,
+                    None => return Err("Unknown command or category name in ACL".into()),
+                };
+                COMMAND_TABLE
+                    .iter()
+                    .filter(|(_, cats)| cats & bits != 0)
+                    .map(|(name, _)| *name)
+                    .collect()
+            }
+            None => match COMMAND_TABLE.iter().find(|(name, _)| *name == &rule[1..]) {
+                Some((name, _)) => vec![*name],
+                None => return Err("Unknown command or category name in ACL".into()),
+            },
+        };
+
+        for name in names {
+            if allow {
+                self.allowed_commands.insert(name);
+            } else {
+                self.allowed_commands.remove(name);
+            }
+        }
+
+        if rule == "+@all" || rule == "-@all" {
+            self.command_rules.clear();
+        }
+        self.command_rules.push(rule.to_string());
+        Ok(())
+    }
+
+    fn check_password(&self, password: &str) -> bool {
+        let hash = hash_password(password);
+        self.passwords
+            .iter()
+            .any(|p| utils::secure_eq(p.as_bytes(), hash.as_bytes()))
+    }
+
+    fn can_access(&self, key: &str) -> bool {
+        self.key_patterns
+            .iter()
+            .any(|p| p == "*" || utils::backtrack_match(key.as_bytes(), p.as_bytes()))
+    }
+
+    fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![if self.enabled { "on" } else { "off" }];
+        if self.nopass {
+            flags.push("nopass");
+        }
+        flags
+    }
+
+    fn commands_description(&self) -> String {
+        if self.command_rules.is_empty() {
+            "-@all".to_string()
+        } else {
+            self.command_rules.join(" ")
+        }
+    }
+
+    fn keys_description(&self) -> String {
+        let patterns: Vec<String> = self.key_patterns.iter().map(|p| format!("~{}", p)).collect();
+        patterns.join(" ")
+    }
+
+    /// The user as a single line of rules, the format of ACL LIST and of the ACL file.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("user {}", self.name)];
+        parts.extend(self.flags().into_iter().map(String::from));
+        parts.extend(self.passwords.iter().map(|p| format!("#{}", p)));
+        let keys = self.keys_description();
+        if !keys.is_empty() {
+            parts.push(keys);
+        }
+        parts.push(self.commands_description());
+        parts.join(" ")
+    }
+
+    pub fn frame(&self) -> Frame {
+        let bulk = |s: &str| Frame::Bulk(s.as_bytes().to_vec());
+        Frame::Array(vec![
+            bulk("flags"),
+            Frame::Array(self.flags().into_iter().map(bulk).collect()),
+            bulk("passwords"),
+            Frame::Array(self.passwords.iter().map(|p| bulk(p)).collect()),
+            bulk("commands"),
+            bulk(&self.commands_description()),
+            bulk("keys"),
+            bulk(&self.keys_description()),
+        ])
+    }
+}
+
+/// The registry of ACL users, persisted to the ACL file on every change.
+#[derive(Debug)]
+pub struct Acl {
+    file: PathBuf,
+    users: RwLock<BTreeMap<String, User>>,
+}
+
+impl Acl {
+    pub fn load(file: PathBuf) -> crate::Result<Acl> {
+        let mut users = BTreeMap::new();
+
+        match std::fs::read_to_string(&file) {
+            Ok(content) => {
+                for (i, line) in content.lines().enumerate() {
+                    let args: Vec<&str> = line.split_whitespace().collect();
+                    if args.is_empty() || args[0].starts_with('#') {
+                        continue;
+                    }
+                    if args.len() < 2 || args[0] != "user" {
+                        return Err(format!("{} line {}: should start with user keyword", file.display(), i + 1).into());
+                    }
+                    let mut user = User::new(args[1]);
+                    for rule in &args[2..] {
+                        if let Err(err) = user.apply_rule(rule) {
+                            return Err(format!("{} line {}: {}", file.display(), i + 1, err).into());
+                        }
+                    }
+                    users.insert(user.name.clone(), user);
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("failed to read acl file {}, {}", file.display(), err).into()),
+        }
+
+        users
+            .entry(DEFAULT_USER.to_string())
+            .or_insert_with(User::new_default);
+
+        Ok(Acl {
+            file,
+            users: RwLock::new(users),
+        })
+    }
+
+    /// The user a new connection starts as, `None` when it has to AUTH first.
+    pub fn initial_user(&self, config: &Config) -> Option<String> {
+        if !config.props().require_pass.is_empty() {
+            return None;
+        }
+        let users = self.users.read().unwrap();
+        match users.get(DEFAULT_USER) {
+            Some(user) if user.enabled && user.nopass => Some(DEFAULT_USER.to_string()),
+            _ => None,
+        }
+    }
+
+    /// `requirepass` acts as a password of the default user and disables its `nopass` flag.
+    pub fn authenticate(&self, config: &Config, username: &str, password: &str) -> bool {
+        let users = self.users.read().unwrap();
+        let user = match users.get(username) {
+            Some(user) if user.enabled => user,
+            _ => return false,
+        };
+        if username == DEFAULT_USER {
+            let requirepass = config.props().require_pass.clone();
+            if !requirepass.is_empty() {
+                return utils::secure_eq(requirepass.as_bytes(), password.as_bytes())
+                    || user.check_password(password);
+            }
+        }
+        user.nopass || user.check_password(password)
+    }
+
+    pub fn check(&self, username: &str, cmd: &Command) -> Result<(), Frame> {
+        if let Command::UNKNOWN(..) = cmd {
+            return Ok(());
+        }
+        let users = self.users.read().unwrap();
+        let user = match users.get(username) {
+            Some(user) if user.enabled => user,
+            _ => return Err(Frame::Error("NOAUTH Authentication required.".to_string())),
+        };
+        let name = cmd.name();
+        if !user.allowed_commands.contains(name) {
+            return Err(Frame::Error(format!(
+                "NOPERM User {} has no permissions to run the '{}' command",
+                username, name
+            )));
+        }
+        if !cmd.keys().into_iter().all(|key| user.can_access(key)) {
+            return Err(Frame::Error("NOPERM No permissions to access a key".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn set_user(&self, name: &str, rules: &[String]) -> Result<(), String> {
+        let mut users = self.users.write().unwrap();
+        let mut user = users.get(name).cloned().unwrap_or_else(|| User::new(name));
+        for rule in rules {
+            if let Err(err) = user.apply_rule(rule) {
+                return Err(format!("ERR Error in ACL SETUSER modifier '{}': {}", rule, err));
+            }
+        }
+        users.insert(name.to_string(), user);
+        self.save(&users)
+    }
+
+    pub fn get_user(&self, name: &str) -> Option<Frame> {
+        self.users.read().unwrap().get(name).map(|user| user.frame())
+    }
+
+    pub fn del_users(&self, names: &[String]) -> Result<usize, String> {
+        if names.iter().any(|name| name == DEFAULT_USER) {
+            return Err("ERR The 'default' user cannot be removed".into());
+        }
+        let mut users = self.users.write().unwrap();
+        let deleted = names.iter().filter(|name| users.remove(*name).is_some()).count();
+        if deleted > 0 {
+            self.save(&users)?;
+        }
+        Ok(deleted)
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.users.read().unwrap().values().map(|user| user.describe()).collect()
+    }
+
+    fn save(&self, users: &BTreeMap<String, User>) -> Result<(), String> {
+        let mut content = String::new();
+        for user in users.values() {
+            content.push_str(&user.describe());
+            content.push('\n');
+        }
+        let mut tmp = self.file.clone().into_os_string();
+        tmp.push(".tmp");
+        let saved = std::fs::write(&tmp, content).and_then(|_| std::fs::rename(&tmp, &self.file));
+        match saved {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                log::error!("failed to save acl file {}, {}", self.file.display(), err);
+                Err(format!("ERR There was an error trying to save the ACLs: {}", err))
+            }
+        }
+    }
+}
+
+fn hash_password(password: &str) -> String {
+    let digest = Sha256::digest(password.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{protocol::parse::Parse, server::shared::testing::temporary_dir};
+
+    const SECRET_HASH: &str = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b";
+
+    fn user(rules: &[&str]) -> Result<User, String> {
+        let mut user = User::new("alice");
+        for rule in rules {
+            user.apply_rule(rule)?;
+        }
+        Ok(user)
+    }
+
+    fn command(args: &[&str]) -> Command {
+        let args = args.iter().map(|arg| Frame::Bulk(arg.as_bytes().to_vec())).collect();
+        Parse::new(Frame::Array(args)).and_then(Parse::into_command).unwrap()
+    }
+
+    /// Runs `test` on the users of an ACL file of `content`, removed afterwards.
+    fn with_acl<R>(content: &str, test: impl FnOnce(&Path, crate::Result<Acl>) -> R) -> R {
+        let file = temporary_dir("acl");
+        std::fs::write(&file, content).unwrap();
+        let result = test(&file, Acl::load(file.clone()));
+        let _ = std::fs::remove_file(&file);
+        result
+    }
+
+    fn error(result: Result<(), Frame>) -> String {
+        match result {
+            Err(Frame::Error(err)) => err,
+            _ => panic!("not refused"),
+        }
+    }
+
+
+    #[test]
+    fn passwords() {
+        let alice = user(&["on", ">secret", ">other"]).unwrap();
+        assert!(alice.check_password("secret") && alice.check_password("other"));
+        assert!(!alice.check_password("nope"));
+        let alice = user(&[">secret", "<secret"]).unwrap();
+        assert!(!alice.check_password("secret"));
+        let alice = user(&[&format!("#{}", SECRET_HASH)]).unwrap();
+        assert!(alice.check_password("secret"));
+        let alice = user(&[&format!("#{}", SECRET_HASH), &format!("!{}", SECRET_HASH)]).unwrap();
+        assert!(alice.passwords.is_empty());
+        let alice = user(&[">secret", "nopass"]).unwrap();
+        assert!(alice.nopass && alice.passwords.is_empty());
+        assert_eq!(user(&["<missing"]).unwrap_err(), "no such password");
+        assert!(user(&["#abc"]).unwrap_err().starts_with("The password hash must be exactly 64 characters"));
+    }
+
+    #[test]
+    fn key_patterns() {
+        let alice = user(&["~cache:*", "~session:*"]).unwrap();
+        assert!(alice.can_access("cache:1") && alice.can_access("session:2"));
+        assert!(!alice.can_access("other"));
+        // every key covers the other patterns
+        let alice = user(&["~cache:*", "allkeys", "~more:*"]).unwrap();
+        assert_eq!(alice.keys_description(), "~*");
+        let alice = user(&["~*", "resetkeys"]).unwrap();
+        assert!(!alice.can_access("cache:1"));
+    }
+
+    #[test]
+    fn command_rules() {
+        let alice = user(&["+@read", "-get", "+set"]).unwrap();
+        assert!(alice.allowed_commands.contains("strlen"));
+        assert!(!alice.allowed_commands.contains("get"));
+        assert!(alice.allowed_commands.contains("set"));
+        assert!(!alice.allowed_commands.contains("del"));
+        assert_eq!(alice.commands_description(), "+@read -get +set");
+        // all or nothing starts the description over
+        let alice = user(&["+get", "+@all", "-flushdb"]).unwrap();
+        assert_eq!(alice.commands_description(), "+@all -flushdb");
+        assert_eq!(user(&[]).unwrap().commands_description(), "-@all");
+        assert_eq!(user(&["+nope"]).unwrap_err(), "Unknown command or category name in ACL");
+        assert_eq!(user(&["+@nope"]).unwrap_err(), "Unknown command or category name in ACL");
+        assert_eq!(user(&["what"]).unwrap_err(), "Syntax error");
+    }
+
+    #[test]
+    fn reset_disables_everything() {
+        let alice = user(&["on", ">secret", "~*", "+@all", "reset"]).unwrap();
+        assert!(!alice.enabled && alice.passwords.is_empty() && alice.key_patterns.is_empty());
+        assert!(alice.allowed_commands.is_empty());
+    }
+
+    #[test]
+    fn described_as_rules() {
+        let alice = user(&["on", ">secret", "~cache:*", "+@read"]).unwrap();
+        assert_eq!(alice.describe(), format!("user alice on #{} ~cache:* +@read", SECRET_HASH));
+        assert_eq!(User::new_default().describe(), "user default on nopass ~* +@all");
+    }
+
+    #[test]
+    fn loaded_from_and_saved_to_the_file() {
+        let content = format!("# users\n\nuser alice on #{} ~cache:* +@read\n", SECRET_HASH);
+        with_acl(&content, |file, acl| {
+            let acl = acl.unwrap();
+            assert_eq!(acl.list(), [format!("user alice on #{} ~cache:* +@read", SECRET_HASH), "user default on nopass ~* +@all".to_string()]);
+            acl.set_user("bob", &["on".to_string(), "nopass".to_string()]).unwrap();
+            assert_eq!(acl.del_users(&["alice".to_string(), "nobody".to_string()]), Ok(1));
+            let saved = std::fs::read_to_string(file).unwrap();
+            assert_eq!(saved, "user bob on nopass -@all\nuser default on nopass ~* +@all\n");
+        });
+        with_acl("alice on\n", |_, acl| {
+            assert!(acl.unwrap_err().to_string().ends_with("line 1: should start with user keyword"));
+        });
+        with_acl("user alice +nope\n", |_, acl| {
+            assert!(acl.unwrap_err().to_string().ends_with("line 1: Unknown command or category name in ACL"));
+        });
+    }
+
+    #[test]
+    fn users_are_changed_all_or_nothing() {
+        with_acl("", |_, acl| {
+            let acl = acl.unwrap();
+            let err = acl.set_user("alice", &["on".to_string(), "+nope".to_string()]).unwrap_err();
+            assert_eq!(err, "ERR Error in ACL SETUSER modifier '+nope': Unknown command or category name in ACL");
+            assert!(acl.get_user("alice").is_none());
+            assert_eq!(acl.del_users(&[DEFAULT_USER.to_string()]), Err("ERR The 'default' user cannot be removed".into()));
+        });
+    }
+
+    #[test]
+    fn commands_are_checked() {
+        with_acl("user alice on nopass ~cache:* +@read\nuser bob off nopass ~* +@all\n", |_, acl| {
+            let acl = acl.unwrap();
+            assert!(acl.check("alice", &command(&["get", "cache:1"])).is_ok());
+            assert_eq!(error(acl.check("alice", &command(&["get", "other"]))), "NOPERM No permissions to access a key");
+            assert_eq!(
+                error(acl.check("alice", &command(&["set", "cache:1", "v"]))),
+                "NOPERM User alice has no permissions to run the 'set' command"
+            );
+            assert_eq!(error(acl.check("bob", &command(&["get", "k"]))), "NOAUTH Authentication required.");
+            assert_eq!(error(acl.check("nobody", &command(&["get", "k"]))), "NOAUTH Authentication required.");
+            assert!(acl.check(DEFAULT_USER, &command(&["flushdb"])).is_ok());
+        });
+    }
+
+    #[test]
+    fn requirepass_is_a_password_of_the_default_user() {
+        with_acl("", |_, acl| {
+            let acl = acl.unwrap();
+            let config = Config::load(None).unwrap();
+            assert_eq!(acl.initial_user(&config), Some(DEFAULT_USER.to_string()));
+            assert!(acl.authenticate(&config, DEFAULT_USER, "anything"));
+            config.set(&[("requirepass".to_string(), "secret".to_string())]).unwrap();
+            assert_eq!(acl.initial_user(&config), None);
+            assert!(acl.authenticate(&config, DEFAULT_USER, "secret"));
+            assert!(!acl.authenticate(&config, DEFAULT_USER, "anything"));
+        });
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{
+    acl::{find_category, CATEGORIES},
+    command::COMMAND_TABLE,
+    protocol::{frame::Frame, parse::Parse, ParseError},
+};
+
+#[derive(Debug)]
+pub enum Acl {
+    SetUser(String, Vec<String>),
+    GetUser(String),
+    DelUser(Vec<String>),
+    List,
+    WhoAmI,
+    Cat(Option<String>),
+}
+
+impl Acl {
+    pub fn parse(mut parse: Parse) -> Result<Acl, ParseError> {
+        let sub = match parse.next() {
+            Ok(frame) => frame.into_string()?.to_lowercase(),
+            Err(ParseError::EOF) => {
+                return Err("ERR wrong number of arguments for 'acl' command".into())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let wrong_args = || -> ParseError {
+            format!("ERR wrong number of arguments for 'acl|{}' command", sub).into()
+        };
+
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        match (&sub[..], args.len()) {
+            ("setuser", 1..) => {
+                let name = args.remove(0);
+                Ok(Acl::SetUser(name, args))
+            }
+            ("getuser", 1) => Ok(Acl::GetUser(args.remove(0))),
+            ("deluser", 1..) => Ok(Acl::DelUser(args)),
+            ("list", 0) => Ok(Acl::List),
+            ("whoami", 0) => Ok(Acl::WhoAmI),
+            ("cat", 0) => Ok(Acl::Cat(None)),
+            ("cat", 1) => Ok(Acl::Cat(Some(args.remove(0)))),
+            ("setuser", _) | ("getuser", _) | ("deluser", _) | ("list", _) | ("whoami", _)
+            | ("cat", _) => Err(wrong_args()),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try ACL HELP.", sub).into()),
+        }
+    }
+
+    /// Runs against the user registry on the connection, `user` is the connection's current user.
+    pub fn execute(self, registry: &crate::acl::Acl, user: &str) -> Frame {
+        let bulk = |s: &str| Frame::Bulk(s.as_bytes().to_vec());
+        match self {
+            Acl::SetUser(name, rules) => match registry.set_user(&name, &rules) {
+                Ok(_) => Frame::Str(b"OK".to_vec()),
+                Err(err) => Frame::Error(err),
+            },
+            Acl::GetUser(name) => registry.get_user(&name).unwrap_or(Frame::Nil),
+            Acl::DelUser(names) => match registry.del_users(&names) {
+                Ok(deleted) => deleted.into(),
+                Err(err) => Frame::Error(err),
+            },
+            Acl::List => Frame::Array(registry.list().iter().map(|line| bulk(line)).collect()),
+            Acl::WhoAmI => bulk(user),
+            Acl::Cat(None) => Frame::Array(CATEGORIES.iter().map(|(name, _)| bulk(name)).collect()),
+            Acl::Cat(Some(category)) => match find_category(&category) {
+                Some(bits) => Frame::Array(
+                    COMMAND_TABLE
+                        .iter()
+                        .filter(|(_, cats)| cats & bits != 0)
+                        .map(|(name, _)| bulk(name))
+                        .collect(),
+                ),
+                None => Frame::Error(format!("ERR Unknown category '{}'", category)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::testing::{show, temporary_dir},
+    };
+
+    fn parse(args: &[&str]) -> Result<Acl, String> {
+        let frames = std::iter::once("acl")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::ACL(acl)) => Ok(acl),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn subcommands() {
+        let setuser = parse(&["SETUSER", "alice", "on", ">secret"]).unwrap();
+        assert!(matches!(setuser, Acl::SetUser(name, rules) if name == "alice" && rules == ["on", ">secret"]));
+        assert!(matches!(parse(&["setuser", "alice"]), Ok(Acl::SetUser(_, rules)) if rules.is_empty()));
+        assert!(matches!(parse(&["deluser", "a", "b"]), Ok(Acl::DelUser(names)) if names.len() == 2));
+        assert!(matches!(parse(&["cat"]), Ok(Acl::Cat(None))));
+        assert!(matches!(parse(&["cat", "geo"]), Ok(Acl::Cat(Some(category))) if category == "geo"));
+
+        assert_eq!(parse(&["getuser"]).unwrap_err(), "ERR wrong number of arguments for 'acl|getuser' command");
+        assert_eq!(parse(&["whoami", "x"]).unwrap_err(), "ERR wrong number of arguments for 'acl|whoami' command");
+        assert_eq!(parse(&["cat", "geo", "set"]).unwrap_err(), "ERR wrong number of arguments for 'acl|cat' command");
+        assert_eq!(parse(&["log"]).unwrap_err(), "ERR unknown subcommand 'log'. Try ACL HELP.");
+        assert_eq!(parse(&[]).unwrap_err(), "ERR wrong number of arguments for 'acl' command");
+    }
+
+    #[test]
+    fn replies() {
+        // a missing file starts with just the default user
+        let registry = crate::acl::Acl::load(temporary_dir("acl")).unwrap();
+        let execute = |args: &[&str]| show(&parse(args).unwrap().execute(&registry, "default"));
+
+        assert_eq!(execute(&["whoami"]), "\"default\"");
+        assert_eq!(execute(&["getuser", "nobody"]), "(nil)");
+        assert_eq!(execute(&["deluser", "nobody"]), "(integer) 0");
+        assert_eq!(execute(&["deluser", "default"]), "(error) ERR The 'default' user cannot be removed");
+        assert_eq!(execute(&["cat", "nope"]), "(error) ERR Unknown category 'nope'");
+        assert!(execute(&["cat"]).contains("\"hyperloglog\""));
+        let geo = execute(&["cat", "geo"]);
+        assert!(geo.contains("\"geoadd\"") && !geo.contains("\"set\""));
+    }
+}
@@ -0,0 +1,39 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct Append {
+    key: String,
+    value: Vec<u8>,
+}
+
+impl Append {
+    pub fn parse(mut parse: Parse) -> Result<Append, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let value = parse.next()?.into_vec()?;
+        match parse.fin() {
+            Ok(_) => Ok(Append { key, value }),
+            Err(_) => Err("ERR wrong number of arguments for 'append' command".into()),
+        }
+    }
+}
+
+impl Execable for Append {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let len = shared.string_len(&self.key)?;
+        if self.value.is_empty() && !shared.is_exists(&self.key) {
+            shared.put_string(&self.key, b"", None)?;
+        }
+        let len = shared.set_string_range(&self.key, len, &self.value)?;
+        shared.notify(notify::STRING, "append", &self.key);
+        Ok(Some(Frame::Integer(len as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
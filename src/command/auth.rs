@@ -0,0 +1,135 @@
+use crate::{
+    acl::{Acl, DEFAULT_USER},
+    config::Config,
+    protocol::{frame::Frame, parse::Parse, ParseError},
+};
+
+#[derive(Debug)]
+pub struct Auth {
+    username: Option<String>,
+    password: String,
+}
+
+impl Auth {
+    pub fn new(username: Option<String>, password: String) -> Auth {
+        Auth { username, password }
+    }
+
+    pub fn parse(parse: Parse) -> Result<Auth, ParseError> {
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) if args.len() <= 2 => args,
+            Ok(_) => return Err("ERR syntax error".into()),
+            Err(ParseError::EOF) => {
+                return Err("ERR wrong number of arguments for 'auth' command".into())
+            }
+            Err(e) => return Err(e),
+        };
+        let password = args.pop().unwrap();
+        let username = args.pop();
+        Ok(Auth { username, password })
+    }
+
+    /// Checks the credentials against the ACL users, `requirepass` being the default user's password.
+    /// Runs on the connection rather than the db worker, since the outcome is per connection.
+    pub fn authenticate(self, acl: &Acl, config: &Config, user: &mut Option<String>) -> Frame {
+        if self.username.is_none() && config.props().require_pass.is_empty() && acl.initial_user(config).is_some() {
+            return Frame::Error(
+                "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?"
+                    .to_string(),
+            );
+        }
+
+        let username = self.username.unwrap_or_else(|| DEFAULT_USER.to_string());
+        if acl.authenticate(config, &username, &self.password) {
+            *user = Some(username);
+            Frame::Str(b"OK".to_vec())
+        } else {
+            Frame::Error("WRONGPASS invalid username-password pair or user is disabled.".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::testing::{show, temporary_dir},
+    };
+
+    fn parse(args: &[&str]) -> Result<Auth, String> {
+        let frames = std::iter::once("auth")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::AUTH(auth)) => Ok(auth),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Authenticates with `args` against an ACL file of `users`, returning the reply and the user.
+    fn authenticate(users: &str, requirepass: &str, args: &[&str]) -> (Frame, Option<String>) {
+        let file = temporary_dir("auth");
+        std::fs::write(&file, users).unwrap();
+        let acl = Acl::load(file.clone()).unwrap();
+        let _ = std::fs::remove_file(&file);
+        let config = Config::load(None).unwrap();
+        config.set(&[("requirepass".to_string(), requirepass.to_string())]).unwrap();
+        let mut user = None;
+        let reply = parse(args).unwrap().authenticate(&acl, &config, &mut user);
+        (reply, user)
+    }
+
+    fn error(reply: Frame) -> String {
+        match reply {
+            Frame::Error(err) => err,
+            reply => panic!("not refused: {:?}", reply),
+        }
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(parse(&[]).unwrap_err(), "ERR wrong number of arguments for 'auth' command");
+        assert_eq!(parse(&["a", "b", "c"]).unwrap_err(), "ERR syntax error");
+        let auth = parse(&["secret"]).unwrap();
+        assert_eq!((auth.username, auth.password.as_str()), (None, "secret"));
+        let auth = parse(&["alice", "secret"]).unwrap();
+        assert_eq!((auth.username.as_deref(), auth.password.as_str()), (Some("alice"), "secret"));
+    }
+
+    #[test]
+    fn without_a_password_configured() {
+        let (reply, user) = authenticate("", "", &["secret"]);
+        assert!(error(reply).starts_with("ERR AUTH <password> called without any password configured"));
+        assert_eq!(user, None);
+    }
+
+    #[test]
+    fn with_requirepass() {
+        let (reply, user) = authenticate("", "secret", &["secret"]);
+        assert_eq!(show(&reply), "OK");
+        assert_eq!(user.as_deref(), Some(DEFAULT_USER));
+        let (reply, user) = authenticate("", "secret", &["default", "secret"]);
+        assert_eq!(show(&reply), "OK");
+        assert_eq!(user.as_deref(), Some(DEFAULT_USER));
+        let (reply, user) = authenticate("", "secret", &["wrong"]);
+        assert_eq!(error(reply), "WRONGPASS invalid username-password pair or user is disabled.");
+        assert_eq!(user, None);
+    }
+
+    #[test]
+    fn named_users() {
+        let users = "user alice on >secret ~* +@all\nuser bob off >secret ~* +@all\n";
+        let (reply, user) = authenticate(users, "", &["alice", "secret"]);
+        assert_eq!(show(&reply), "OK");
+        assert_eq!(user.as_deref(), Some("alice"));
+        let (reply, _) = authenticate(users, "", &["alice", "wrong"]);
+        assert!(error(reply).starts_with("WRONGPASS"));
+        let (reply, _) = authenticate(users, "", &["bob", "secret"]);
+        assert!(error(reply).starts_with("WRONGPASS"));
+        let (reply, _) = authenticate(users, "", &["nobody", "secret"]);
+        assert!(error(reply).starts_with("WRONGPASS"));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct BitCount {
+    key: String,
+    range: BitRange,
+}
+
+/// The `start end [BYTE|BIT]` range of BITCOUNT and BITPOS, `end` defaulting to the end of the string.
+#[derive(Debug)]
+pub(crate) struct BitRange {
+    start: i64,
+    end: Option<i64>,
+    bits: bool,
+}
+
+/// A range resolved against a string: the first and last byte, inclusive, and the bits of
+/// those bytes that are out of range when it is given in bits.
+#[derive(Debug)]
+pub(crate) struct ByteSpan {
+    pub start: usize,
+    pub end: usize,
+    pub first_mask: u8,
+    pub last_mask: u8,
+}
+
+impl BitCount {
+    pub fn parse(mut parse: Parse) -> Result<BitCount, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if args.len() == 1 || args.len() > 3 {
+            return Err("ERR syntax error".into());
+        }
+        let range = BitRange::parse(&args)?;
+        Ok(BitCount { key, range })
+    }
+}
+
+impl BitRange {
+    /// Parses `[start [end [BYTE|BIT]]]`, the whole string when empty.
+    pub(crate) fn parse(args: &[String]) -> Result<BitRange, ParseError> {
+        let number = |arg: &String| match arg.parse::<i64>() {
+            Ok(n) => Ok(n),
+            Err(_) => Err(ParseError::from("ERR value is not an integer or out of range")),
+        };
+        let start = args.first().map(number).transpose()?.unwrap_or(0);
+        let end = args.get(1).map(number).transpose()?;
+        let bits = match args.get(2).map(|unit| unit.to_lowercase()).as_deref() {
+            None | Some("byte") => false,
+            Some("bit") => true,
+            Some(_) => return Err("ERR syntax error".into()),
+        };
+        Ok(BitRange { start, end, bits })
+    }
+
+    pub(crate) fn end_given(&self) -> bool {
+        self.end.is_some()
+    }
+
+    /// Resolves negative and out of range indexes like redis, `None` when nothing is in range.
+    pub(crate) fn span(&self, len: usize) -> Option<ByteSpan> {
+        let total = if self.bits { len as i64 * 8 } else { len as i64 };
+        let end = self.end.unwrap_or(-1);
+        if self.start < 0 && end < 0 && self.start > end {
+            return None;
+        }
+        let start = if self.start < 0 { (total + self.start).max(0) } else { self.start };
+        let end = if end < 0 { (total + end).max(0) } else { end.min(total - 1) };
+        if total == 0 || start > end {
+            return None;
+        }
+        if !self.bits {
+            return Some(ByteSpan {
+                start: start as usize,
+                end: end as usize,
+                first_mask: 0,
+                last_mask: 0,
+            });
+        }
+        Some(ByteSpan {
+            start: (start >> 3) as usize,
+            end: (end >> 3) as usize,
+            first_mask: (0xff00u16 >> (start & 7)) as u8,
+            last_mask: (0xffu16 >> ((end & 7) + 1)) as u8,
+        })
+    }
+}
+
+impl Execable for BitCount {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let len = shared.string_len(&self.key)?;
+        let span = match self.range.span(len) {
+            Some(span) => span,
+            None => return Ok(Some(Frame::Integer(0))),
+        };
+        let bytes = shared.get_string_range(&self.key, span.start, span.end + 1)?;
+        let mut count: u64 = bytes.iter().map(|b| b.count_ones() as u64).sum();
+        if let (Some(first), Some(last)) = (bytes.first(), bytes.last()) {
+            count -= (first & span.first_mask).count_ones() as u64;
+            count -= (last & span.last_mask).count_ones() as u64;
+        }
+        Ok(Some(Frame::Integer(count as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    fn range(args: &[&str]) -> Result<BitRange, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        BitRange::parse(&args).map_err(|err| err.to_string())
+    }
+
+    /// The first and last byte of a range over `len` bytes with the masks, `None` when empty.
+    fn span(args: &[&str], len: usize) -> Option<(usize, usize, u8, u8)> {
+        let span = range(args).unwrap().span(len)?;
+        Some((span.start, span.end, span.first_mask, span.last_mask))
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(range(&["x"]).unwrap_err(), "ERR value is not an integer or out of range");
+        assert_eq!(range(&["0", "1", "bits"]).unwrap_err(), "ERR syntax error");
+        assert!(range(&["0", "1", "BIT"]).unwrap().bits);
+        assert!(!range(&[]).unwrap().end_given());
+
+        assert_eq!(span(&[], 6), Some((0, 5, 0, 0)));
+        assert_eq!(span(&["-2", "-1"], 6), Some((4, 5, 0, 0)));
+        assert_eq!(span(&["-100", "100"], 6), Some((0, 5, 0, 0)));
+        assert_eq!(span(&["3", "1"], 6), None);
+        assert_eq!(span(&["-1", "-2"], 6), None);
+        assert_eq!(span(&["6", "10"], 6), None);
+        assert_eq!(span(&[], 0), None);
+        // bits 5 to 30 are the last 3 bits of the first byte to the first 7 of the fourth
+        assert_eq!(span(&["5", "30", "bit"], 6), Some((0, 3, 0b1111_1000, 0b0000_0001)));
+        assert_eq!(span(&["8", "15", "bit"], 6), Some((1, 1, 0, 0)));
+    }
+
+    #[test]
+    fn counts() {
+        with_shared("bitcount", |shared| {
+            run(shared, &["set", "k", "foobar"]);
+            // the examples of the BITCOUNT documentation
+            assert_eq!(show(&run(shared, &["bitcount", "k"])), "(integer) 26");
+            assert_eq!(show(&run(shared, &["bitcount", "k", "0", "0"])), "(integer) 4");
+            assert_eq!(show(&run(shared, &["bitcount", "k", "1", "1"])), "(integer) 6");
+            assert_eq!(show(&run(shared, &["bitcount", "k", "1", "1", "byte"])), "(integer) 6");
+            assert_eq!(show(&run(shared, &["bitcount", "k", "5", "30", "bit"])), "(integer) 17");
+            assert_eq!(show(&run(shared, &["bitcount", "k", "-1", "-2"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["bitcount", "missing"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["bitcount", "k", "0"])), "(error) ERR syntax error");
+        });
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::{setbit::MAX_BIT_OFFSET, Execable};
+
+/// BITFIELD, and BITFIELD_RO when only GET is allowed.
+#[derive(Debug)]
+pub struct BitField {
+    key: String,
+    ops: Vec<Op>,
+}
+
+#[derive(Debug)]
+enum Op {
+    Get(Field),
+    Set(Field, i64, Overflow),
+    IncrBy(Field, i64, Overflow),
+}
+
+/// What SET and INCRBY do with a value that doesn't fit the field.
+#[derive(Debug, Clone, Copy)]
+enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+/// An integer of `bits` bits at a bit offset, the most significant bit first.
+#[derive(Debug, Clone, Copy)]
+struct Field {
+    signed: bool,
+    bits: u32,
+    offset: usize,
+}
+
+impl BitField {
+    pub fn parse(mut parse: Parse, read_only: bool) -> Result<BitField, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) => args.into_iter(),
+            Err(ParseError::EOF) => Vec::new().into_iter(),
+            Err(e) => return Err(e),
+        };
+
+        let mut ops = Vec::new();
+        let mut overflow = Overflow::Wrap;
+        while let Some(subcommand) = args.next() {
+            let subcommand = subcommand.to_lowercase();
+            if read_only && subcommand != "get" {
+                return Err("ERR BITFIELD_RO only supports the GET subcommand".into());
+            }
+            match &subcommand[..] {
+                "get" => ops.push(Op::Get(Field::parse(args.next(), args.next())?)),
+                "set" | "incrby" => {
+                    let field = Field::parse(args.next(), args.next())?;
+                    let value = match args.next().map(|value| value.parse::<i64>()) {
+                        Some(Ok(value)) => value,
+                        Some(Err(_)) => return Err("ERR value is not an integer or out of range".into()),
+                        None => return Err("ERR syntax error".into()),
+                    };
+                    match &subcommand[..] {
+                        "set" => ops.push(Op::Set(field, value, overflow)),
+                        _ => ops.push(Op::IncrBy(field, value, overflow)),
+                    }
+                }
+                "overflow" => {
+                    overflow = match args.next().map(|kind| kind.to_lowercase()).as_deref() {
+                        Some("wrap") => Overflow::Wrap,
+                        Some("sat") => Overflow::Sat,
+                        Some("fail") => Overflow::Fail,
+                        Some(_) => return Err("ERR Invalid OVERFLOW type specified".into()),
+                        None => return Err("ERR syntax error".into()),
+                    }
+                }
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+        Ok(BitField { key, ops })
+    }
+}
+
+impl Field {
+    fn parse(kind: Option<String>, offset: Option<String>) -> Result<Field, ParseError> {
+        let (kind, offset) = match (kind, offset) {
+            (Some(kind), Some(offset)) => (kind, offset),
+            _ => return Err("ERR syntax error".into()),
+        };
+        let signed = kind.starts_with(['i', 'I']);
+        let bits = match kind.get(1..).map(|bits| bits.parse::<u32>()) {
+            Some(Ok(bits)) if kind.starts_with(['u', 'U']) && (1..=63).contains(&bits) => bits,
+            Some(Ok(bits)) if signed && (1..=64).contains(&bits) => bits,
+            _ => {
+                return Err(
+                    "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+                        .into(),
+                )
+            }
+        };
+        // `#n` is the n-th field of this type
+        let offset = match offset.strip_prefix('#') {
+            Some(index) => index.parse::<i64>().ok().and_then(|index| index.checked_mul(bits as i64)),
+            None => offset.parse::<i64>().ok(),
+        };
+        match offset {
+            Some(offset) if offset >= 0 && offset.checked_add(bits as i64).is_some_and(|end| end <= MAX_BIT_OFFSET) => {
+                Ok(Field { signed, bits, offset: offset as usize })
+            }
+            _ => Err("ERR bit offset is not an integer or out of range".into()),
+        }
+    }
+
+    /// The bytes the field spans.
+    fn bytes(&self) -> (usize, usize) {
+        (self.offset / 8, (self.offset + self.bits as usize - 1) / 8 + 1)
+    }
+
+    fn read(&self, shared: &Shared, key: &str) -> crate::Result<i64> {
+        let (start, end) = self.bytes();
+        let mut bytes = shared.get_string_range(key, start, end)?;
+        bytes.resize(end - start, 0);
+        let mut value = 0u64;
+        for i in 0..self.bits as usize {
+            let bit = self.offset - start * 8 + i;
+            value = value << 1 | ((bytes[bit / 8] >> (7 - bit % 8)) & 1) as u64;
+        }
+        if self.signed && self.bits < 64 && value >> (self.bits - 1) & 1 == 1 {
+            value |= u64::MAX << self.bits;
+        }
+        Ok(value as i64)
+    }
+
+    fn write(&self, shared: &mut Shared, key: &str, value: i64) -> crate::Result<()> {
+        let (start, end) = self.bytes();
+        let mut bytes = shared.get_string_range(key, start, end)?;
+        bytes.resize(end - start, 0);
+        for i in 0..self.bits as usize {
+            let bit = self.offset - start * 8 + i;
+            let mask = 0x80u8 >> (bit % 8);
+            match (value as u64) >> (self.bits as usize - 1 - i) & 1 {
+                1 => bytes[bit / 8] |= mask,
+                _ => bytes[bit / 8] &= !mask,
+            }
+        }
+        shared.set_string_range(key, start, &bytes)?;
+        Ok(())
+    }
+
+    /// Fits `value` into the field as the overflow behavior says, `None` when it fails.
+    fn fit(&self, value: i128, overflow: Overflow) -> Option<i64> {
+        let (min, max) = match self.signed {
+            true => (-(1i128 << (self.bits - 1)), (1i128 << (self.bits - 1)) - 1),
+            false => (0, (1i128 << self.bits) - 1),
+        };
+        if (min..=max).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Wrap => {
+                let low = value & ((1i128 << self.bits) - 1);
+                match self.signed && low > max {
+                    true => Some((low - (1i128 << self.bits)) as i64),
+                    false => Some(low as i64),
+                }
+            }
+            Overflow::Sat if value > max => Some(max as i64),
+            Overflow::Sat => Some(min as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+impl Execable for BitField {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let mut replies = Vec::with_capacity(self.ops.len());
+        let mut written = false;
+        for op in self.ops.iter() {
+            let reply = match *op {
+                Op::Get(field) => Frame::Integer(field.read(shared, &self.key)?),
+                Op::Set(field, value, overflow) => {
+                    let old = field.read(shared, &self.key)?;
+                    // unsigned values are taken as the bits of the 64 bit integer, like redis
+                    let value = if field.signed { value as i128 } else { value as u64 as i128 };
+                    match field.fit(value, overflow) {
+                        Some(value) => {
+                            field.write(shared, &self.key, value)?;
+                            written = true;
+                            Frame::Integer(old)
+                        }
+                        None => Frame::Nil,
+                    }
+                }
+                Op::IncrBy(field, increment, overflow) => {
+                    let old = field.read(shared, &self.key)?;
+                    match field.fit(old as i128 + increment as i128, overflow) {
+                        Some(value) => {
+                            field.write(shared, &self.key, value)?;
+                            written = true;
+                            Frame::Integer(value)
+                        }
+                        None => Frame::Nil,
+                    }
+                }
+            };
+            replies.push(reply);
+        }
+        if written {
+            shared.notify(notify::STRING, "setbit", &self.key);
+        }
+        Ok(Some(Frame::Array(replies)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::testing::{run, show, with_shared},
+    };
+
+    fn parse(args: &[&str]) -> Result<BitField, String> {
+        let frames = args.iter().map(|arg| Frame::Bulk(arg.as_bytes().to_vec())).collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::BITFIELD(bitfield) | Command::BITFIELD_RO(bitfield)) => Ok(bitfield),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn field(kind: &str, offset: &str) -> Result<Field, String> {
+        Field::parse(Some(kind.to_string()), Some(offset.to_string())).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn field_types_and_offsets() {
+        let invalid = "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.";
+        for kind in ["u64", "i65", "u0", "x8", "i", "u-1"] {
+            assert_eq!(field(kind, "0").unwrap_err(), invalid, "{}", kind);
+        }
+        assert!(field("U63", "0").is_ok() && field("I64", "0").is_ok());
+        assert_eq!(field("u8", "#2").unwrap().offset, 16);
+        assert_eq!(field("i5", "100").unwrap().offset, 100);
+        let out_of_range = "ERR bit offset is not an integer or out of range";
+        assert_eq!(field("u8", "-1").unwrap_err(), out_of_range);
+        assert_eq!(field("u8", "#-1").unwrap_err(), out_of_range);
+        assert_eq!(field("u8", &(MAX_BIT_OFFSET - 7).to_string()).unwrap_err(), out_of_range);
+        assert!(field("u8", &(MAX_BIT_OFFSET - 8).to_string()).is_ok());
+        assert_eq!(field("u8", "x").unwrap_err(), out_of_range);
+    }
+
+    #[test]
+    fn subcommands() {
+        assert!(parse(&["bitfield", "k"]).unwrap().ops.is_empty());
+        assert_eq!(parse(&["bitfield", "k", "get", "u8"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["bitfield", "k", "set", "u8", "0"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["bitfield", "k", "set", "u8", "0", "x"]).unwrap_err(), "ERR value is not an integer or out of range");
+        assert_eq!(parse(&["bitfield", "k", "overflow", "nope"]).unwrap_err(), "ERR Invalid OVERFLOW type specified");
+        assert_eq!(parse(&["bitfield", "k", "flip", "u8", "0"]).unwrap_err(), "ERR syntax error");
+        assert!(parse(&["bitfield_ro", "k", "GET", "u8", "0"]).is_ok());
+        assert_eq!(
+            parse(&["bitfield_ro", "k", "set", "u8", "0", "1"]).unwrap_err(),
+            "ERR BITFIELD_RO only supports the GET subcommand"
+        );
+        // OVERFLOW applies to the operations after it
+        let bitfield = parse(&["bitfield", "k", "incrby", "u2", "0", "1", "overflow", "fail", "set", "u2", "0", "1"]).unwrap();
+        assert!(matches!(bitfield.ops[..], [Op::IncrBy(_, 1, Overflow::Wrap), Op::Set(_, 1, Overflow::Fail)]));
+    }
+
+    #[test]
+    fn overflows() {
+        let (u8, i8) = (field("u8", "0").unwrap(), field("i8", "0").unwrap());
+        assert_eq!(u8.fit(256, Overflow::Wrap), Some(0));
+        assert_eq!(u8.fit(-1, Overflow::Wrap), Some(255));
+        assert_eq!(i8.fit(128, Overflow::Wrap), Some(-128));
+        assert_eq!(i8.fit(-129, Overflow::Wrap), Some(127));
+        assert_eq!(u8.fit(300, Overflow::Sat), Some(255));
+        assert_eq!(u8.fit(-5, Overflow::Sat), Some(0));
+        assert_eq!(i8.fit(-200, Overflow::Sat), Some(-128));
+        assert_eq!(i8.fit(128, Overflow::Fail), None);
+        assert_eq!(i8.fit(127, Overflow::Fail), Some(127));
+        let i64 = field("i64", "0").unwrap();
+        assert_eq!(i64.fit(i64::MAX as i128 + 1, Overflow::Wrap), Some(i64::MIN));
+    }
+
+    #[test]
+    fn fields_across_bytes() {
+        with_shared("bitfield", |shared| {
+            // the examples of the BITFIELD documentation
+            assert_eq!(show(&run(shared, &["bitfield", "k", "incrby", "i5", "100", "1", "get", "u4", "0"])), "[(integer) 1, (integer) 0]");
+            let incr = ["bitfield", "counters", "incrby", "u2", "100", "1", "overflow", "sat", "incrby", "u2", "102", "1"];
+            for expected in ["[(integer) 1, (integer) 1]", "[(integer) 2, (integer) 2]", "[(integer) 3, (integer) 3]", "[(integer) 0, (integer) 3]"] {
+                assert_eq!(show(&run(shared, &incr)), expected);
+            }
+
+            assert_eq!(show(&run(shared, &["bitfield", "b", "set", "u8", "#1", "255", "get", "u16", "4"])), "[(integer) 0, (integer) 4080]");
+            assert_eq!(show(&run(shared, &["get", "b"])), "\"\0\u{fffd}\"");
+            assert_eq!(show(&run(shared, &["bitfield", "b", "set", "i8", "4", "-2", "get", "u24", "0"])), "[(integer) 15, (integer) 1044224]");
+            assert_eq!(show(&run(shared, &["bitfield", "b", "overflow", "fail", "incrby", "i8", "4", "-127"])), "[(nil)]");
+            assert_eq!(show(&run(shared, &["bitfield_ro", "b", "get", "i8", "4"])), "[(integer) -2]");
+            assert_eq!(show(&run(shared, &["bitfield_ro", "missing", "get", "i64", "0"])), "[(integer) 0]");
+        });
+    }
+}
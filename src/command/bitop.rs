@@ -0,0 +1,137 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct BitOp {
+    op: Op,
+    dest: String,
+    keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+impl BitOp {
+    pub fn parse(mut parse: Parse) -> Result<BitOp, ParseError> {
+        let op = parse.next()?.into_string()?;
+        let dest = parse.next()?.into_string()?;
+        let keys = parse.remaining_into_string_vec()?;
+        let op = match &op.to_lowercase()[..] {
+            "and" => Op::And,
+            "or" => Op::Or,
+            "xor" => Op::Xor,
+            "not" => Op::Not,
+            _ => return Err("ERR syntax error".into()),
+        };
+        if matches!(op, Op::Not) && keys.len() != 1 {
+            return Err("ERR BITOP NOT must be called with a single source key.".into());
+        }
+        Ok(BitOp { op, dest, keys })
+    }
+}
+
+impl Execable for BitOp {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let mut values = Vec::with_capacity(self.keys.len());
+        for key in self.keys.iter() {
+            values.push(shared.get_string(key)?.map(|(value, _)| value).unwrap_or_default());
+        }
+        let len = values.iter().map(Vec::len).max().unwrap_or(0);
+        // shorter strings are padded with zero bytes
+        let mut result = values[0].clone();
+        result.resize(len, 0);
+        match self.op {
+            Op::Not => result.iter_mut().for_each(|b| *b = !*b),
+            op => {
+                for value in values[1..].iter() {
+                    for (i, b) in result.iter_mut().enumerate() {
+                        let other = value.get(i).copied().unwrap_or(0);
+                        match op {
+                            Op::And => *b &= other,
+                            Op::Or => *b |= other,
+                            _ => *b ^= other,
+                        }
+                    }
+                }
+            }
+        }
+
+        // the destination is replaced in a single write, an empty result removes it
+        if result.is_empty() {
+            shared.del(&self.dest);
+        } else {
+            shared.put_string(&self.dest, &result, None)?;
+            shared.notify(notify::STRING, "set", &self.dest);
+        }
+        Ok(Some(Frame::Integer(len as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        let mut keys = vec![self.dest.as_str()];
+        keys.extend(self.keys.iter().map(String::as_str));
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    #[test]
+    fn pads_shorter_strings_with_zeros() {
+        with_shared("bitop-padding", |shared| {
+            run(shared, &["set", "a", "abc"]);
+            run(shared, &["set", "b", "a"]);
+            assert_eq!(show(&run(shared, &["bitop", "and", "dest", "a", "b"])), "(integer) 3");
+            assert_eq!(show(&run(shared, &["get", "dest"])), "\"a\0\0\"");
+            assert_eq!(show(&run(shared, &["bitop", "or", "dest", "a", "b", "missing"])), "(integer) 3");
+            assert_eq!(show(&run(shared, &["get", "dest"])), "\"abc\"");
+            assert_eq!(show(&run(shared, &["bitop", "xor", "dest", "a", "a"])), "(integer) 3");
+            assert_eq!(show(&run(shared, &["bitcount", "dest"])), "(integer) 0");
+        });
+    }
+
+    #[test]
+    fn not_of_a_chunked_string() {
+        with_shared("bitop-not", |shared| {
+            run(shared, &["setbit", "bits", "32768", "1"]);
+            assert_eq!(show(&run(shared, &["bitop", "not", "dest", "bits"])), "(integer) 4097");
+            assert_eq!(show(&run(shared, &["getbit", "dest", "0"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["getbit", "dest", "32768"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["bitcount", "dest"])), "(integer) 32775");
+            // the result can be changed bit by bit afterwards
+            assert_eq!(show(&run(shared, &["setbit", "dest", "32768", "1"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["bitcount", "dest"])), "(integer) 32776");
+        });
+    }
+
+    #[test]
+    fn replaces_the_destination() {
+        with_shared("bitop-replace", |shared| {
+            run(shared, &["geoadd", "dest", "13.361389", "38.115556", "Palermo"]);
+            run(shared, &["set", "a", "x"]);
+            assert_eq!(show(&run(shared, &["bitop", "or", "dest", "a"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["get", "dest"])), "\"x\"");
+            // an empty result deletes it
+            assert_eq!(show(&run(shared, &["bitop", "and", "dest", "missing"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["exists", "dest"])), "(integer) 0");
+        });
+    }
+
+    #[test]
+    fn not_takes_a_single_key() {
+        with_shared("bitop-not-keys", |shared| {
+            let reply = run(shared, &["bitop", "not", "dest", "a", "b"]);
+            assert_eq!(show(&reply), "(error) ERR BITOP NOT must be called with a single source key.");
+        });
+    }
+}
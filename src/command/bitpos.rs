@@ -0,0 +1,130 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::{bitcount::BitRange, Execable};
+
+#[derive(Debug)]
+pub struct BitPos {
+    key: String,
+    bit: bool,
+    range: BitRange,
+}
+
+impl BitPos {
+    pub fn parse(mut parse: Parse) -> Result<BitPos, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let bit = parse.next()?.into_string()?;
+        let args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let bit = match &bit[..] {
+            "0" => false,
+            "1" => true,
+            _ => return Err("ERR The bit argument must be 1 or 0.".into()),
+        };
+        if args.len() > 3 {
+            return Err("ERR syntax error".into());
+        }
+        let range = BitRange::parse(&args)?;
+        Ok(BitPos { key, bit, range })
+    }
+}
+
+impl Execable for BitPos {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let len = shared.string_len(&self.key)?;
+        if !shared.is_exists(&self.key) {
+            // a missing key is an empty string of zero bits
+            return Ok(Some(Frame::Integer(if self.bit { -1 } else { 0 })));
+        }
+        let span = match self.range.span(len) {
+            Some(span) => span,
+            None => return Ok(Some(Frame::Integer(-1))),
+        };
+        let mut bytes = shared.get_string_range(&self.key, span.start, span.end + 1)?;
+        // the bits out of range are set to the opposite of the one looked for
+        let last = bytes.len() - 1;
+        if self.bit {
+            bytes[0] &= !span.first_mask;
+            bytes[last] &= !span.last_mask;
+        } else {
+            bytes[0] |= span.first_mask;
+            bytes[last] |= span.last_mask;
+        }
+        let found = bytes.iter().enumerate().find_map(|(i, byte)| match self.bit {
+            true if *byte != 0 => Some(i * 8 + byte.leading_zeros() as usize),
+            false if *byte != 0xff => Some(i * 8 + byte.leading_ones() as usize),
+            _ => None,
+        });
+        let pos = match found {
+            Some(pos) => (span.start * 8 + pos) as i64,
+            // looking for a clear bit with no end given, the string is taken as padded with zeros
+            None if !self.bit && !self.range.end_given() => ((span.end + 1) * 8) as i64,
+            None => -1,
+        };
+        Ok(Some(Frame::Integer(pos)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::testing::{run, show, with_shared},
+    };
+
+    fn parse(args: &[&str]) -> Result<BitPos, String> {
+        let frames = std::iter::once("bitpos")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::BITPOS(bitpos)) => Ok(bitpos),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(parse(&["k", "2"]).unwrap_err(), "ERR The bit argument must be 1 or 0.");
+        assert_eq!(parse(&["k", "1", "0", "1", "bit", "x"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["k", "1", "0", "1", "bits"]).unwrap_err(), "ERR syntax error");
+        assert!(!parse(&["k", "0"]).unwrap().bit);
+    }
+
+    #[test]
+    fn positions() {
+        with_shared("bitpos", |shared| {
+            let bitpos = |shared: &mut _, args: &[&str]| show(&run(shared, &[&["bitpos", "k"], args].concat()));
+            // the examples of the BITPOS documentation, "\xff\xf0\x00" then "\x00\xff\xf0"
+            run(shared, &["bitfield", "k", "set", "u24", "0", &0xfff000.to_string()]);
+            assert_eq!(bitpos(shared, &["0"]), "(integer) 12");
+            run(shared, &["bitfield", "k", "set", "u24", "0", &0x00fff0.to_string()]);
+            assert_eq!(bitpos(shared, &["1", "0"]), "(integer) 8");
+            assert_eq!(bitpos(shared, &["1", "2"]), "(integer) 16");
+            assert_eq!(bitpos(shared, &["1", "2", "-1", "byte"]), "(integer) 16");
+            assert_eq!(bitpos(shared, &["1", "7", "15", "bit"]), "(integer) 8");
+            assert_eq!(bitpos(shared, &["1", "7", "-3", "bit"]), "(integer) 8");
+            run(shared, &["bitfield", "k", "set", "u24", "0", "0"]);
+            assert_eq!(bitpos(shared, &["1"]), "(integer) -1");
+            assert_eq!(bitpos(shared, &["1", "7", "-3", "bit"]), "(integer) -1");
+
+            // all ones, a clear bit is past the end unless an end is given
+            run(shared, &["bitfield", "k", "set", "u24", "0", &0xffffff.to_string()]);
+            assert_eq!(bitpos(shared, &["0"]), "(integer) 24");
+            assert_eq!(bitpos(shared, &["0", "0", "-1"]), "(integer) -1");
+            assert_eq!(show(&run(shared, &["bitpos", "missing", "0"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["bitpos", "missing", "1"])), "(integer) -1");
+        });
+    }
+}
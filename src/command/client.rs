@@ -0,0 +1,334 @@
+use std::time::Duration;
+
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::clients::{Client as ClientInfo, Clients},
+};
+
+#[derive(Debug)]
+pub enum Client {
+    Id,
+    Info,
+    List(ClientFilter),
+    SetName(String),
+    GetName,
+    /// The old `CLIENT KILL addr` form, answered with OK instead of a count.
+    KillAddr(String),
+    Kill(ClientFilter),
+    Pause(Duration, bool),
+    Unpause,
+    NoEvict(bool),
+}
+
+/// Rejects client names that would break the CLIENT LIST output, shared with HELLO SETNAME.
+pub fn check_name(name: &str) -> Result<(), ParseError> {
+    if name.chars().any(|c| c <= ' ' || c > '~') {
+        return Err("ERR Client names cannot contain spaces, newlines or special characters.".into());
+    }
+    Ok(())
+}
+
+/// Selects clients for CLIENT LIST and CLIENT KILL.
+#[derive(Debug, Default)]
+pub struct ClientFilter {
+    ids: Vec<u64>,
+    addr: Option<String>,
+    laddr: Option<String>,
+    user: Option<String>,
+    /// `Some(false)` when asked for a type no client of this server has.
+    normal: Option<bool>,
+    skip_me: bool,
+}
+
+impl Client {
+    pub fn parse(mut parse: Parse) -> Result<Client, ParseError> {
+        let sub = match parse.next() {
+            Ok(frame) => frame.into_string()?.to_lowercase(),
+            Err(ParseError::EOF) => {
+                return Err("ERR wrong number of arguments for 'client' command".into())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let wrong_args = || -> ParseError {
+            format!("ERR wrong number of arguments for 'client|{}' command", sub).into()
+        };
+
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        match (&sub[..], args.len()) {
+            ("id", 0) => Ok(Client::Id),
+            ("info", 0) => Ok(Client::Info),
+            ("list", _) => Ok(Client::List(ClientFilter::parse(args, false)?)),
+            ("setname", 1) => {
+                let name = args.remove(0);
+                check_name(&name)?;
+                Ok(Client::SetName(name))
+            }
+            ("getname", 0) => Ok(Client::GetName),
+            ("kill", 1) => Ok(Client::KillAddr(args.remove(0))),
+            ("kill", n) if n > 0 && n % 2 == 0 => Ok(Client::Kill(ClientFilter::parse(args, true)?)),
+            ("pause", 1 | 2) => {
+                let timeout = match args[0].parse::<u64>() {
+                    Ok(timeout) => Duration::from_millis(timeout),
+                    Err(_) => return Err("ERR timeout is not an integer or out of range".into()),
+                };
+                let write_only = match args.get(1).map(|mode| mode.to_lowercase()).as_deref() {
+                    None | Some("all") => false,
+                    Some("write") => true,
+                    Some(_) => return Err("ERR syntax error".into()),
+                };
+                Ok(Client::Pause(timeout, write_only))
+            }
+            ("unpause", 0) => Ok(Client::Unpause),
+            ("no-evict", 1) => match &args[0].to_lowercase()[..] {
+                "on" => Ok(Client::NoEvict(true)),
+                "off" => Ok(Client::NoEvict(false)),
+                _ => Err("ERR syntax error".into()),
+            },
+            ("id", _) | ("info", _) | ("setname", _) | ("getname", _) | ("kill", 0) | ("pause", _)
+            | ("unpause", _) | ("no-evict", _) => Err(wrong_args()),
+            ("kill", _) => Err("ERR syntax error".into()),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try CLIENT HELP.", sub).into()),
+        }
+    }
+
+    /// Runs against the client registry on the connection, `me` being the connection's own client.
+    pub fn execute(self, registry: &Clients, me: &ClientInfo) -> Frame {
+        let bulk = |s: String| Frame::Bulk(s.into_bytes());
+        match self {
+            Client::Id => Frame::Integer(me.id as i64),
+            Client::Info => bulk(me.describe() + "\n"),
+            Client::List(filter) => {
+                let lines: String = registry
+                    .list()
+                    .iter()
+                    .filter(|client| filter.matches(client, me))
+                    .map(|client| client.describe() + "\n")
+                    .collect();
+                bulk(lines)
+            }
+            Client::SetName(name) => {
+                me.state().name = name;
+                Frame::Str(b"OK".to_vec())
+            }
+            Client::GetName => match &me.state().name[..] {
+                "" => Frame::Nil,
+                name => bulk(name.to_string()),
+            },
+            Client::KillAddr(addr) => match registry.list().iter().find(|client| client.addr == addr) {
+                Some(client) => {
+                    client.kill();
+                    Frame::Str(b"OK".to_vec())
+                }
+                None => Frame::Error("ERR No such client".to_string()),
+            },
+            Client::Kill(filter) => {
+                let mut killed = 0;
+                for client in registry.list().iter().filter(|client| filter.matches(client, me)) {
+                    client.kill();
+                    killed += 1;
+                }
+                Frame::Integer(killed)
+            }
+            Client::Pause(timeout, write_only) => {
+                registry.pause(timeout, write_only);
+                Frame::Str(b"OK".to_vec())
+            }
+            Client::Unpause => {
+                registry.unpause();
+                Frame::Str(b"OK".to_vec())
+            }
+            Client::NoEvict(on) => {
+                me.state().no_evict = on;
+                Frame::Str(b"OK".to_vec())
+            }
+        }
+    }
+}
+
+impl ClientFilter {
+    /// Parses `<filter> <value>` pairs, `kill` enabling the filters only CLIENT KILL accepts.
+    fn parse(args: Vec<String>, kill: bool) -> Result<ClientFilter, ParseError> {
+        let mut filter = ClientFilter {
+            skip_me: kill,
+            ..ClientFilter::default()
+        };
+        let mut args = args.into_iter();
+        while let Some(name) = args.next() {
+            let name = name.to_lowercase();
+            let value = match args.next() {
+                Some(value) => value,
+                None => return Err("ERR syntax error".into()),
+            };
+            match &name[..] {
+                "id" if kill => match value.parse::<u64>() {
+                    Ok(id) => filter.ids.push(id),
+                    Err(_) => return Err("ERR client-id should be greater than 0".into()),
+                },
+                "id" => {
+                    // CLIENT LIST ID takes every remaining argument as an id
+                    for id in std::iter::once(value).chain(args.by_ref()) {
+                        match id.parse::<u64>() {
+                            Ok(id) => filter.ids.push(id),
+                            Err(_) => return Err("ERR Invalid client ID".into()),
+                        }
+                    }
+                }
+                "type" => match &value.to_lowercase()[..] {
+                    "normal" => filter.normal = Some(true),
+                    "master" | "replica" | "slave" | "pubsub" => filter.normal = Some(false),
+                    _ => return Err(format!("ERR Unknown client type '{}'", value).into()),
+                },
+                "addr" if kill => filter.addr = Some(value),
+                "laddr" if kill => filter.laddr = Some(value),
+                "user" if kill => filter.user = Some(value),
+                "skipme" if kill => match &value.to_lowercase()[..] {
+                    "yes" => filter.skip_me = true,
+                    "no" => filter.skip_me = false,
+                    _ => return Err("ERR syntax error".into()),
+                },
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+        Ok(filter)
+    }
+
+    fn matches(&self, client: &ClientInfo, me: &ClientInfo) -> bool {
+        if self.skip_me && client.id == me.id {
+            return false;
+        }
+        if !self.ids.is_empty() && !self.ids.contains(&client.id) {
+            return false;
+        }
+        if self.normal == Some(false) {
+            return false;
+        }
+        if let Some(addr) = &self.addr {
+            if *addr != client.addr {
+                return false;
+            }
+        }
+        if let Some(laddr) = &self.laddr {
+            if *laddr != client.laddr {
+                return false;
+            }
+        }
+        if let Some(user) = &self.user {
+            if *user != client.state().user {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{command::Command, server::shared::testing::show};
+
+    fn parse(args: &[&str]) -> Result<Client, String> {
+        let frames = std::iter::once("client")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::CLIENT(client)) => Ok(client),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn execute(registry: &Clients, me: &ClientInfo, args: &[&str]) -> String {
+        show(&parse(args).unwrap().execute(registry, me))
+    }
+
+    /// The ids in the lines of a CLIENT LIST reply.
+    fn ids(list: &str) -> Vec<&str> {
+        list.trim_matches('"')
+            .lines()
+            .map(|line| line.split(' ').next().unwrap().trim_start_matches("id="))
+            .collect()
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(parse(&[]).unwrap_err(), "ERR wrong number of arguments for 'client' command");
+        assert_eq!(parse(&["id", "x"]).unwrap_err(), "ERR wrong number of arguments for 'client|id' command");
+        assert_eq!(parse(&["setname"]).unwrap_err(), "ERR wrong number of arguments for 'client|setname' command");
+        assert_eq!(parse(&["kill"]).unwrap_err(), "ERR wrong number of arguments for 'client|kill' command");
+        assert_eq!(parse(&["kill", "id", "1", "skipme"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["nope"]).unwrap_err(), "ERR unknown subcommand 'nope'. Try CLIENT HELP.");
+        assert!(matches!(parse(&["KILL", "127.0.0.1:1"]), Ok(Client::KillAddr(_))));
+    }
+
+    #[test]
+    fn names_without_special_characters() {
+        assert!(parse(&["setname", "worker-1"]).is_ok());
+        for name in ["my name", "tab\t", "new\nline", "caf\u{e9}"] {
+            assert_eq!(
+                parse(&["setname", name]).unwrap_err(),
+                "ERR Client names cannot contain spaces, newlines or special characters."
+            );
+        }
+    }
+
+    #[test]
+    fn pause_options() {
+        assert!(matches!(parse(&["pause", "100"]), Ok(Client::Pause(timeout, false)) if timeout == Duration::from_millis(100)));
+        assert!(matches!(parse(&["pause", "100", "WRITE"]), Ok(Client::Pause(_, true))));
+        assert!(matches!(parse(&["pause", "100", "all"]), Ok(Client::Pause(_, false))));
+        assert_eq!(parse(&["pause", "-1"]).unwrap_err(), "ERR timeout is not an integer or out of range");
+        assert_eq!(parse(&["pause", "100", "read"]).unwrap_err(), "ERR syntax error");
+        assert!(matches!(parse(&["no-evict", "ON"]), Ok(Client::NoEvict(true))));
+        assert_eq!(parse(&["no-evict", "maybe"]).unwrap_err(), "ERR syntax error");
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(parse(&["list", "id", "1", "x"]).unwrap_err(), "ERR Invalid client ID");
+        assert_eq!(parse(&["kill", "id", "x"]).unwrap_err(), "ERR client-id should be greater than 0");
+        assert_eq!(parse(&["list", "type", "nope"]).unwrap_err(), "ERR Unknown client type 'nope'");
+        // the address filters are only for CLIENT KILL
+        assert_eq!(parse(&["list", "addr", "127.0.0.1:1"]).unwrap_err(), "ERR syntax error");
+        assert!(parse(&["kill", "addr", "127.0.0.1:1", "skipme", "no"]).is_ok());
+        assert_eq!(parse(&["kill", "skipme", "maybe"]).unwrap_err(), "ERR syntax error");
+    }
+
+    #[test]
+    fn listed_and_killed() {
+        let registry = Clients::default();
+        let me = registry.register("127.0.0.1:1".to_string(), "127.0.0.1:6379".to_string(), Some("default"));
+        registry.register("127.0.0.1:2".to_string(), "127.0.0.1:6379".to_string(), Some("alice"));
+        registry.register("127.0.0.1:3".to_string(), "127.0.0.1:6380".to_string(), Some("alice"));
+
+        assert_eq!(execute(&registry, &me, &["id"]), "(integer) 1");
+        assert_eq!(ids(&execute(&registry, &me, &["list"])), ["1", "2", "3"]);
+        assert_eq!(ids(&execute(&registry, &me, &["list", "id", "3", "1", "9"])), ["1", "3"]);
+        assert_eq!(ids(&execute(&registry, &me, &["list", "type", "normal"])), ["1", "2", "3"]);
+        assert_eq!(execute(&registry, &me, &["list", "type", "pubsub"]), "\"\"");
+
+        assert_eq!(execute(&registry, &me, &["kill", "user", "alice", "laddr", "127.0.0.1:6380"]), "(integer) 1");
+        assert_eq!(execute(&registry, &me, &["kill", "user", "default"]), "(integer) 0");
+        assert_eq!(execute(&registry, &me, &["kill", "user", "default", "skipme", "no"]), "(integer) 1");
+        assert_eq!(execute(&registry, &me, &["kill", "127.0.0.1:2"]), "OK");
+        assert_eq!(execute(&registry, &me, &["kill", "127.0.0.1:9"]), "(error) ERR No such client");
+    }
+
+    #[test]
+    fn own_name_and_flags() {
+        let registry = Clients::default();
+        let me = registry.register("127.0.0.1:1".to_string(), "127.0.0.1:6379".to_string(), None);
+        assert_eq!(execute(&registry, &me, &["getname"]), "(nil)");
+        assert_eq!(execute(&registry, &me, &["setname", "worker"]), "OK");
+        assert_eq!(execute(&registry, &me, &["getname"]), "\"worker\"");
+        assert_eq!(execute(&registry, &me, &["no-evict", "on"]), "OK");
+        let info = execute(&registry, &me, &["info"]);
+        assert!(info.contains(" name=worker ") && info.contains(" flags=e "), "{}", info);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    config::{find_param, registry::Apply},
+    logger,
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub enum Config {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    Rewrite,
+    ResetStat,
+}
+
+impl Config {
+    pub fn parse(mut parse: Parse) -> Result<Config, ParseError> {
+        let sub = match parse.next() {
+            Ok(frame) => frame.into_string()?.to_lowercase(),
+            Err(ParseError::EOF) => {
+                return Err("ERR wrong number of arguments for 'config' command".into())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let wrong_args = || -> ParseError {
+            format!("ERR wrong number of arguments for 'config|{}' command", sub).into()
+        };
+
+        match &sub[..] {
+            "get" => {
+                let patterns = parse.remaining_into_string_vec().map_err(|_| wrong_args())?;
+                Ok(Config::Get(patterns))
+            }
+            "set" => {
+                let args = parse.remaining_into_string_vec().map_err(|_| wrong_args())?;
+                if args.len() % 2 != 0 {
+                    return Err(wrong_args());
+                }
+                let mut pairs = Vec::with_capacity(args.len() / 2);
+                let mut args = args.into_iter();
+                while let (Some(name), Some(value)) = (args.next(), args.next()) {
+                    pairs.push((name, value));
+                }
+                Ok(Config::Set(pairs))
+            }
+            "rewrite" => match parse.fin() {
+                Ok(_) => Ok(Config::Rewrite),
+                Err(_) => Err(wrong_args()),
+            },
+            "resetstat" => match parse.fin() {
+                Ok(_) => Ok(Config::ResetStat),
+                Err(_) => Err(wrong_args()),
+            },
+            _ => Err(format!("ERR unknown subcommand '{}'. Try CONFIG HELP.", sub).into()),
+        }
+    }
+}
+
+impl Execable for Config {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        match self {
+            Config::Get(patterns) => {
+                let pairs = shared.config().get(&patterns);
+                let mut frames = Vec::with_capacity(pairs.len() * 2);
+                for (name, value) in pairs {
+                    frames.push(Frame::Bulk(name.as_bytes().to_vec()));
+                    frames.push(Frame::Bulk(value.into_bytes()));
+                }
+                Ok(Some(Frame::Array(frames)))
+            }
+            Config::Set(pairs) => {
+                let config = shared.config().clone();
+                let previous = match config.set(&pairs) {
+                    Ok(previous) => previous,
+                    Err(err) => return Ok(Some(Frame::Error(err))),
+                };
+
+                let mut rocksdb_opts = Vec::new();
+                let mut log_level_changed = false;
+                for (name, value) in pairs.iter() {
+                    let param = find_param(name).unwrap();
+                    match param.apply {
+                        Apply::Rocksdb => {
+                            let value = (param.get)(&config.props());
+                            let value = match &value[..] {
+                                "yes" => "true".to_string(),
+                                "no" => "false".to_string(),
+                                _ => value,
+                            };
+                            rocksdb_opts.push((param.rocksdb_option().unwrap(), value));
+                        }
+                        Apply::LogLevel => log_level_changed = true,
+                        Apply::Lazy => {}
+                    }
+                    log::info!("config set {} {}", param.name, value);
+                }
+
+                if !rocksdb_opts.is_empty() {
+                    let opts: Vec<(&str, &str)> =
+                        rocksdb_opts.iter().map(|(k, v)| (*k, v.as_str())).collect();
+                    if let Err(err) = shared.set_db_options(&opts) {
+                        config.restore(previous);
+                        return Ok(Some(Frame::Error(format!("ERR CONFIG SET failed - {}", err))));
+                    }
+                }
+                if log_level_changed {
+                    logger::set_level(config.props().get_log_level());
+                }
+                Ok(Some(Frame::Str(b"OK".to_vec())))
+            }
+            Config::Rewrite => match shared.config().rewrite() {
+                Ok(_) => Ok(Some(Frame::Str(b"OK".to_vec()))),
+                Err(err) => {
+                    log::warn!("CONFIG REWRITE failed: {}", err);
+                    let msg = err.to_string();
+                    if msg.starts_with("ERR") {
+                        Ok(Some(Frame::Error(msg)))
+                    } else {
+                        Ok(Some(Frame::Error(format!("ERR Rewriting config file: {}", msg))))
+                    }
+                }
+            },
+            Config::ResetStat => {
+                shared.stats().reset();
+                Ok(Some(Frame::Str(b"OK".to_vec())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    fn parse(args: &[&str]) -> Result<Config, String> {
+        let frames = std::iter::once("config")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::CONFIG(config)) => Ok(config),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn subcommands() {
+        assert!(matches!(parse(&["GET", "max*", "port"]), Ok(Config::Get(patterns)) if patterns == ["max*", "port"]));
+        let set = parse(&["set", "maxclients", "10", "loglevel", "debug"]).unwrap();
+        assert!(matches!(set, Config::Set(pairs) if pairs == [
+            ("maxclients".to_string(), "10".to_string()),
+            ("loglevel".to_string(), "debug".to_string()),
+        ]));
+        assert!(matches!(parse(&["rewrite"]), Ok(Config::Rewrite)));
+        assert!(matches!(parse(&["resetstat"]), Ok(Config::ResetStat)));
+
+        assert_eq!(parse(&["get"]).unwrap_err(), "ERR wrong number of arguments for 'config|get' command");
+        assert_eq!(parse(&["set", "maxclients"]).unwrap_err(), "ERR wrong number of arguments for 'config|set' command");
+        assert_eq!(parse(&["set"]).unwrap_err(), "ERR wrong number of arguments for 'config|set' command");
+        assert_eq!(parse(&["rewrite", "now"]).unwrap_err(), "ERR wrong number of arguments for 'config|rewrite' command");
+        assert_eq!(parse(&["reset"]).unwrap_err(), "ERR unknown subcommand 'reset'. Try CONFIG HELP.");
+        assert_eq!(parse(&[]).unwrap_err(), "ERR wrong number of arguments for 'config' command");
+    }
+}
@@ -33,4 +33,8 @@ impl Execable for Del {
 
         Ok(Some(num.into()))
     }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
 }
\ No newline at end of file
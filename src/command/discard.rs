@@ -0,0 +1,14 @@
+use crate::protocol::{parse::Parse, ParseError};
+
+/// Drops the commands queued since MULTI.
+#[derive(Debug)]
+pub struct Discard {}
+
+impl Discard {
+    pub fn parse(mut parse: Parse) -> Result<Discard, ParseError> {
+        match parse.fin() {
+            Ok(_) => Ok(Discard {}),
+            Err(_) => Err("ERR wrong number of arguments for 'discard' command".into()),
+        }
+    }
+}
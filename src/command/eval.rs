@@ -0,0 +1,126 @@
+use crate::protocol::{parse::Parse, ParseError};
+
+/// The script an EVAL runs, its body or the SHA1 of a cached one.
+#[derive(Debug)]
+pub enum Source {
+    Body(String),
+    Sha(String),
+}
+
+/// EVAL, EVALSHA and their read-only variants, run by the scripting engine of the db worker.
+#[derive(Debug)]
+pub struct Eval {
+    source: Source,
+    keys: Vec<String>,
+    args: Vec<Vec<u8>>,
+    /// the script can't call write commands
+    read_only: bool,
+    /// the user running the script, see `set_user`
+    user: String,
+}
+
+impl Eval {
+    /// `name` is the command parsed, for the error messages.
+    pub fn parse(mut parse: Parse, name: &str, read_only: bool) -> Result<Eval, ParseError> {
+        let wrong_args = || -> ParseError { format!("ERR wrong number of arguments for '{}' command", name).into() };
+        let script = parse.next().map_err(|_| wrong_args())?.into_string()?;
+        let (keys, args) = parse_keys_and_args(&mut parse).map_err(|err| match err {
+            ParseError::EOF => wrong_args(),
+            err => err,
+        })?;
+        let source = match name.starts_with("evalsha") {
+            true => Source::Sha(script.to_lowercase()),
+            false => Source::Body(script),
+        };
+        Ok(Eval { source, keys, args, read_only, user: String::new() })
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    pub fn args(&self) -> &[Vec<u8>] {
+        &self.args
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The user the commands of the script are checked against, none being allowed until set.
+    pub fn set_user(&mut self, user: &str) {
+        self.user = user.to_string();
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
+}
+
+/// Parses `numkeys key [key ...] arg [arg ...]`, also used by FCALL.
+pub fn parse_keys_and_args(parse: &mut Parse) -> Result<(Vec<String>, Vec<Vec<u8>>), ParseError> {
+    let numkeys = match parse.next()?.into_string()?.parse::<i64>() {
+        Ok(numkeys) if numkeys < 0 => return Err("ERR Number of keys can't be negative".into()),
+        Ok(numkeys) => numkeys as usize,
+        Err(_) => return Err("ERR value is not an integer or out of range".into()),
+    };
+    let mut args = match parse.remaining_into_vec() {
+        Ok(args) => args,
+        Err(ParseError::EOF) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    if numkeys > args.len() {
+        return Err("ERR Number of keys can't be greater than number of args".into());
+    }
+    let rest = args.split_off(numkeys);
+    let keys = args.into_iter().map(String::from_utf8).collect::<Result<_, _>>();
+    match keys {
+        Ok(keys) => Ok((keys, rest)),
+        Err(_) => Err("ERR invalid key".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{command::Command, protocol::frame::Frame};
+
+    fn parse(args: &[&str]) -> Result<Eval, String> {
+        let frames = args.iter().map(|arg| Frame::Bulk(arg.as_bytes().to_vec())).collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::EVAL(eval) | Command::EVALSHA(eval) | Command::EVAL_RO(eval) | Command::EVALSHA_RO(eval)) => {
+                Ok(eval)
+            }
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn keys_and_args() {
+        let eval = parse(&["eval", "return 1", "2", "a", "b", "x"]).unwrap();
+        assert_eq!((eval.keys(), eval.args()), (vec!["a", "b"], &[b"x".to_vec()][..]));
+        assert!(matches!(eval.source(), Source::Body(body) if body == "return 1"));
+        assert!(!eval.is_read_only());
+        let eval = parse(&["eval", "return 1", "0"]).unwrap();
+        assert!(eval.keys().is_empty() && eval.args().is_empty());
+
+        assert_eq!(parse(&["eval", "return 1", "-1"]).unwrap_err(), "ERR Number of keys can't be negative");
+        assert_eq!(parse(&["eval", "return 1", "2", "a"]).unwrap_err(), "ERR Number of keys can't be greater than number of args");
+        assert_eq!(parse(&["eval", "return 1", "x"]).unwrap_err(), "ERR value is not an integer or out of range");
+        assert_eq!(parse(&["eval", "return 1"]).unwrap_err(), "ERR wrong number of arguments for 'eval' command");
+        assert_eq!(parse(&["evalsha_ro"]).unwrap_err(), "ERR wrong number of arguments for 'evalsha_ro' command");
+    }
+
+    #[test]
+    fn cached_scripts() {
+        let eval = parse(&["evalsha", "E0E1F9FABFC9D4800C877A703B823AC0578FF8DB", "0"]).unwrap();
+        assert!(matches!(eval.source(), Source::Sha(sha) if sha == "e0e1f9fabfc9d4800c877a703b823ac0578ff8db"));
+        assert!(parse(&["evalsha_ro", "e0e1", "0"]).unwrap().is_read_only());
+        assert!(parse(&["eval_ro", "return 1", "0"]).unwrap().is_read_only());
+    }
+}
@@ -0,0 +1,37 @@
+use crate::protocol::{parse::Parse, ParseError};
+
+use super::Command;
+
+/// Runs the commands queued since MULTI. The connection fills in the queue and the keys it
+/// watches, the db worker applies the queue back to back and writes the changes in a single batch.
+#[derive(Debug, Default)]
+pub struct Exec {
+    commands: Vec<Command>,
+    /// keys WATCHed with their versions at the time, EXEC does nothing if one was modified since
+    watched: Vec<(String, u64)>,
+}
+
+impl Exec {
+    pub fn parse(mut parse: Parse) -> Result<Exec, ParseError> {
+        match parse.fin() {
+            Ok(_) => Ok(Exec::default()),
+            Err(_) => Err("ERR wrong number of arguments for 'exec' command".into()),
+        }
+    }
+
+    pub fn new(commands: Vec<Command>, watched: Vec<(String, u64)>) -> Exec {
+        Exec { commands, watched }
+    }
+
+    pub fn into_parts(self) -> (Vec<Command>, Vec<(String, u64)>) {
+        (self.commands, self.watched)
+    }
+
+    pub fn is_write(&self) -> bool {
+        self.commands.iter().any(Command::is_write)
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        self.commands.iter().flat_map(|cmd| cmd.keys()).collect()
+    }
+}
@@ -35,4 +35,8 @@ impl Execable for Exists {
         let frame = cnt.into();
         return Ok(Some(frame));
     }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use crate::command::eval::parse_keys_and_args;
+use crate::protocol::{parse::Parse, ParseError};
+
+/// FCALL and FCALL_RO, calling a function of a library loaded with FUNCTION LOAD.
+#[derive(Debug)]
+pub struct FCall {
+    function: String,
+    keys: Vec<String>,
+    args: Vec<Vec<u8>>,
+    /// only functions flagged no-writes can be called
+    read_only: bool,
+    /// the user running the function, see `set_user`
+    user: String,
+}
+
+impl FCall {
+    /// `name` is the command parsed, for the error messages.
+    pub fn parse(mut parse: Parse, name: &str, read_only: bool) -> Result<FCall, ParseError> {
+        let wrong_args = || -> ParseError { format!("ERR wrong number of arguments for '{}' command", name).into() };
+        let function = parse.next().map_err(|_| wrong_args())?.into_string()?;
+        let (keys, args) = parse_keys_and_args(&mut parse).map_err(|err| match err {
+            ParseError::EOF => wrong_args(),
+            err => err,
+        })?;
+        Ok(FCall { function, keys, args, read_only, user: String::new() })
+    }
+
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    pub fn args(&self) -> &[Vec<u8>] {
+        &self.args
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The user the commands of the function are checked against, none being allowed until set.
+    pub fn set_user(&mut self, user: &str) {
+        self.user = user.to_string();
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
+}
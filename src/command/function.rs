@@ -0,0 +1,171 @@
+use crate::protocol::{parse::Parse, ParseError};
+
+/// FUNCTION, managing the function libraries of the db worker. KILL is run by the connection like
+/// SCRIPT KILL.
+#[derive(Debug)]
+pub enum Function {
+    Load { code: String, replace: bool },
+    Delete(String),
+    Flush,
+    Kill,
+    List { pattern: Option<String>, with_code: bool },
+    Dump,
+    Restore { payload: Vec<u8>, policy: RestorePolicy },
+}
+
+/// What FUNCTION RESTORE does with the libraries already loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestorePolicy {
+    /// keep them, failing if the payload has a library of the same name
+    Append,
+    /// keep those the payload has no library of the same name for
+    Replace,
+    /// delete them all first
+    Flush,
+}
+
+impl Function {
+    pub fn parse(mut parse: Parse) -> Result<Function, ParseError> {
+        let sub = match parse.next() {
+            Ok(frame) => frame.into_string()?.to_lowercase(),
+            Err(ParseError::EOF) => return Err("ERR wrong number of arguments for 'function' command".into()),
+            Err(e) => return Err(e),
+        };
+        // the payload of RESTORE is binary, the other arguments are all text
+        if sub == "restore" {
+            return Function::parse_restore(parse);
+        }
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let wrong_args =
+            || -> ParseError { format!("ERR wrong number of arguments for 'function|{}' command", sub).into() };
+        match &sub[..] {
+            "load" => {
+                let code = args.pop().ok_or_else(wrong_args)?;
+                match &args[..] {
+                    [] => Ok(Function::Load { code, replace: false }),
+                    [option] if option.eq_ignore_ascii_case("replace") => Ok(Function::Load { code, replace: true }),
+                    [option, ..] => Err(format!("ERR Unknown option given: {}", option).into()),
+                }
+            }
+            "delete" if args.len() == 1 => Ok(Function::Delete(args.remove(0))),
+            "flush" => match &args[..] {
+                // libraries are dropped right away either way
+                [] => Ok(Function::Flush),
+                [option] if matches!(&option.to_lowercase()[..], "async" | "sync") => Ok(Function::Flush),
+                [_] => Err("ERR FUNCTION FLUSH only supports SYNC|ASYNC option".into()),
+                _ => Err(wrong_args()),
+            },
+            "kill" if args.is_empty() => Ok(Function::Kill),
+            "dump" if args.is_empty() => Ok(Function::Dump),
+            "list" => {
+                let (mut pattern, mut with_code) = (None, false);
+                let mut args = args.into_iter();
+                while let Some(option) = args.next() {
+                    match &option.to_lowercase()[..] {
+                        "withcode" => with_code = true,
+                        "libraryname" => match args.next() {
+                            Some(name) if pattern.is_none() => pattern = Some(name),
+                            Some(_) => return Err("ERR library name argument was already given".into()),
+                            None => return Err("ERR library name argument was not given".into()),
+                        },
+                        _ => return Err(format!("ERR Unknown argument {}", option).into()),
+                    }
+                }
+                Ok(Function::List { pattern, with_code })
+            }
+            "delete" | "kill" | "dump" => Err(wrong_args()),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try FUNCTION HELP.", sub).into()),
+        }
+    }
+
+    fn parse_restore(mut parse: Parse) -> Result<Function, ParseError> {
+        let mut args = match parse.remaining_into_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let policy = match args.len() {
+            1 => RestorePolicy::Append,
+            2 => match &String::from_utf8_lossy(&args[1]).to_lowercase()[..] {
+                "append" => RestorePolicy::Append,
+                "replace" => RestorePolicy::Replace,
+                "flush" => RestorePolicy::Flush,
+                _ => {
+                    let err = "ERR Wrong restore policy given, value should be either FLUSH, APPEND or REPLACE.";
+                    return Err(err.into());
+                }
+            },
+            _ => return Err("ERR wrong number of arguments for 'function|restore' command".into()),
+        };
+        Ok(Function::Restore { payload: args.swap_remove(0), policy })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{command::Command, protocol::frame::Frame};
+
+    fn parse(args: &[&[u8]]) -> Result<Function, String> {
+        let frames = std::iter::once(&b"function"[..])
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::FUNCTION(function)) => Ok(function),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn load() {
+        assert!(matches!(parse(&[b"load", b"code"]), Ok(Function::Load { replace: false, .. })));
+        assert!(matches!(parse(&[b"LOAD", b"REPLACE", b"code"]), Ok(Function::Load { replace: true, code }) if code == "code"));
+        assert_eq!(parse(&[b"load", b"now", b"code"]).unwrap_err(), "ERR Unknown option given: now");
+        assert_eq!(parse(&[b"load"]).unwrap_err(), "ERR wrong number of arguments for 'function|load' command");
+    }
+
+    #[test]
+    fn other_subcommands() {
+        assert!(matches!(parse(&[b"delete", b"lib"]), Ok(Function::Delete(name)) if name == "lib"));
+        assert_eq!(parse(&[b"delete"]).unwrap_err(), "ERR wrong number of arguments for 'function|delete' command");
+        assert!(matches!(parse(&[b"flush", b"ASYNC"]), Ok(Function::Flush)));
+        assert_eq!(parse(&[b"flush", b"later"]).unwrap_err(), "ERR FUNCTION FLUSH only supports SYNC|ASYNC option");
+        assert!(matches!(parse(&[b"kill"]), Ok(Function::Kill)));
+        assert_eq!(parse(&[b"dump", b"x"]).unwrap_err(), "ERR wrong number of arguments for 'function|dump' command");
+        assert_eq!(parse(&[b"stats"]).unwrap_err(), "ERR unknown subcommand 'stats'. Try FUNCTION HELP.");
+        assert_eq!(parse(&[]).unwrap_err(), "ERR wrong number of arguments for 'function' command");
+    }
+
+    #[test]
+    fn list() {
+        assert!(matches!(parse(&[b"list"]), Ok(Function::List { pattern: None, with_code: false })));
+        let list = parse(&[b"list", b"WITHCODE", b"libraryname", b"my*"]).unwrap();
+        assert!(matches!(list, Function::List { pattern: Some(pattern), with_code: true } if pattern == "my*"));
+        assert_eq!(parse(&[b"list", b"libraryname"]).unwrap_err(), "ERR library name argument was not given");
+        assert_eq!(
+            parse(&[b"list", b"libraryname", b"a", b"libraryname", b"b"]).unwrap_err(),
+            "ERR library name argument was already given"
+        );
+        assert_eq!(parse(&[b"list", b"all"]).unwrap_err(), "ERR Unknown argument all");
+    }
+
+    #[test]
+    fn restore() {
+        // the payload is binary
+        let payload = &[1, 0xff, 0][..];
+        assert!(matches!(parse(&[b"restore", payload]), Ok(Function::Restore { policy: RestorePolicy::Append, payload: p }) if p == payload));
+        assert!(matches!(parse(&[b"restore", payload, b"FLUSH"]), Ok(Function::Restore { policy: RestorePolicy::Flush, .. })));
+        assert!(matches!(parse(&[b"restore", payload, b"replace"]), Ok(Function::Restore { policy: RestorePolicy::Replace, .. })));
+        assert_eq!(
+            parse(&[b"restore", payload, b"merge"]).unwrap_err(),
+            "ERR Wrong restore policy given, value should be either FLUSH, APPEND or REPLACE."
+        );
+        assert_eq!(parse(&[b"restore"]).unwrap_err(), "ERR wrong number of arguments for 'function|restore' command");
+    }
+}
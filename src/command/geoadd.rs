@@ -0,0 +1,113 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{geohash, notify, shared::{SetCondition, Shared}},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct GeoAdd {
+    key: String,
+    condition: SetCondition,
+    changed: bool,
+    members: Vec<(f64, Vec<u8>)>,
+}
+
+impl GeoAdd {
+    pub fn parse(mut parse: Parse) -> Result<GeoAdd, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let args = parse.remaining_into_string_vec()?;
+
+        let (mut nx, mut xx, mut changed) = (false, false, false);
+        let mut at = 0;
+        while let Some(option) = args.get(at) {
+            match &option.to_lowercase()[..] {
+                "nx" => nx = true,
+                "xx" => xx = true,
+                "ch" => changed = true,
+                _ => break,
+            }
+            at += 1;
+        }
+        if nx && xx {
+            return Err("ERR XX and NX options at the same time are not compatible".into());
+        }
+        let triples = &args[at..];
+        if triples.is_empty() || triples.len() % 3 != 0 {
+            return Err("ERR syntax error. Try GEOADD key [x1] [y1] [name1] [x2] [y2] [name2] ... ".into());
+        }
+
+        let mut members = Vec::with_capacity(triples.len() / 3);
+        for triple in triples.chunks(3) {
+            let (long, lat) = long_lat(&triple[0], &triple[1])?;
+            members.push((geohash::score(long, lat), triple[2].as_bytes().to_vec()));
+        }
+        let condition = match (nx, xx) {
+            (true, _) => SetCondition::IfMissing,
+            (_, true) => SetCondition::IfExists,
+            _ => SetCondition::Always,
+        };
+        Ok(GeoAdd {
+            key,
+            condition,
+            changed,
+            members,
+        })
+    }
+}
+
+/// Parses a longitude and latitude pair, checking it is a position geohashes can hold.
+pub(crate) fn long_lat(long: &str, lat: &str) -> Result<(f64, f64), ParseError> {
+    let (long, lat) = match (long.parse::<f64>(), lat.parse::<f64>()) {
+        (Ok(long), Ok(lat)) => (long, lat),
+        _ => return Err("ERR value is not a valid float".into()),
+    };
+    if !geohash::is_valid(long, lat) {
+        return Err(format!("ERR invalid longitude,latitude pair {:.6},{:.6}", long, lat).into());
+    }
+    Ok((long, lat))
+}
+
+impl Execable for GeoAdd {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let (added, changed) = shared.zadd(&self.key, &self.members, self.condition)?;
+        if added + changed > 0 {
+            shared.notify(notify::ZSET, "zadd", &self.key);
+        }
+        let count = if self.changed { added + changed } else { added };
+        Ok(Some(Frame::Integer(count as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    #[test]
+    fn members_read_back_like_redis() {
+        with_shared("geoadd", |shared| {
+            let reply = run(shared, &["geoadd", "Sicily", "13.361389", "38.115556", "Palermo", "15.087269", "37.502669", "Catania"]);
+            assert_eq!(show(&reply), "(integer) 2");
+            let reply = run(shared, &["geopos", "Sicily", "Palermo", "NonExisting"]);
+            assert_eq!(show(&reply), "[[\"13.36138933897018433\", \"38.11555639549629859\"], (nil)]");
+            assert_eq!(show(&run(shared, &["geodist", "Sicily", "Palermo", "Catania"])), "\"166274.1516\"");
+            assert_eq!(show(&run(shared, &["geodist", "Sicily", "Palermo", "Catania", "km"])), "\"166.2742\"");
+            assert_eq!(show(&run(shared, &["geodist", "Sicily", "Palermo", "NonExisting"])), "(nil)");
+            let reply = run(shared, &["geohash", "Sicily", "Palermo", "Catania"]);
+            assert_eq!(show(&reply), "[\"sqc8b49rny0\", \"sqdtr74hyu0\"]");
+        });
+    }
+
+    #[test]
+    fn positions_out_of_range_are_refused() {
+        with_shared("geoadd-range", |shared| {
+            let reply = run(shared, &["geoadd", "Sicily", "181", "0", "Nowhere"]);
+            assert!(show(&reply).starts_with("(error) ERR invalid longitude,latitude pair"));
+            assert_eq!(show(&run(shared, &["exists", "Sicily"])), "(integer) 0");
+        });
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{geohash, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct GeoDist {
+    key: String,
+    member1: String,
+    member2: String,
+    unit: f64,
+}
+
+impl GeoDist {
+    pub fn parse(mut parse: Parse) -> Result<GeoDist, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let member1 = parse.next()?.into_string()?;
+        let member2 = parse.next()?.into_string()?;
+        let unit = match parse.next() {
+            Ok(unit) => meters_per_unit(&unit.into_string()?)?,
+            Err(ParseError::EOF) => 1.0,
+            Err(e) => return Err(e),
+        };
+        if parse.fin().is_err() {
+            return Err("ERR syntax error".into());
+        }
+        Ok(GeoDist {
+            key,
+            member1,
+            member2,
+            unit,
+        })
+    }
+}
+
+/// The meters in a distance unit of the geo commands.
+pub(crate) fn meters_per_unit(unit: &str) -> Result<f64, ParseError> {
+    match &unit.to_lowercase()[..] {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err("ERR unsupported unit provided. please use M, KM, FT, MI".into()),
+    }
+}
+
+/// A distance as the geo commands reply it.
+pub(crate) fn distance_frame(meters: f64, unit: f64) -> Frame {
+    Frame::Bulk(format!("{:.4}", meters / unit).into_bytes())
+}
+
+impl Execable for GeoDist {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let score1 = shared.zscore(&self.key, self.member1.as_bytes())?;
+        let score2 = shared.zscore(&self.key, self.member2.as_bytes())?;
+        let (score1, score2) = match (score1, score2) {
+            (Some(score1), Some(score2)) => (score1, score2),
+            _ => return Ok(Some(Frame::Nil)),
+        };
+        let (long1, lat1) = geohash::position(score1);
+        let (long2, lat2) = geohash::position(score2);
+        Ok(Some(distance_frame(geohash::distance(long1, lat1, long2, lat2), self.unit)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
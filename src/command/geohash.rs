@@ -0,0 +1,41 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{geohash, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct GeoHash {
+    key: String,
+    members: Vec<String>,
+}
+
+impl GeoHash {
+    pub fn parse(mut parse: Parse) -> Result<GeoHash, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let members = match parse.remaining_into_string_vec() {
+            Ok(members) => members,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(GeoHash { key, members })
+    }
+}
+
+impl Execable for GeoHash {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let mut hashes = Vec::with_capacity(self.members.len());
+        for member in self.members.iter() {
+            match shared.zscore(&self.key, member.as_bytes())? {
+                Some(score) => hashes.push(Frame::Bulk(geohash::hash_string(score).into_bytes())),
+                None => hashes.push(Frame::Nil),
+            }
+        }
+        Ok(Some(Frame::Array(hashes)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{geohash, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct GeoPos {
+    key: String,
+    members: Vec<String>,
+}
+
+impl GeoPos {
+    pub fn parse(mut parse: Parse) -> Result<GeoPos, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let members = match parse.remaining_into_string_vec() {
+            Ok(members) => members,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(GeoPos { key, members })
+    }
+}
+
+/// A longitude and latitude pair, printed with 17 decimals and trailing zeros cut like redis does.
+pub(crate) fn position_frame(score: f64) -> Frame {
+    let human = |degrees: f64| {
+        let text = format!("{:.17}", degrees);
+        let text = text.trim_end_matches('0').trim_end_matches('.');
+        Frame::Bulk(text.as_bytes().to_vec())
+    };
+    let (long, lat) = geohash::position(score);
+    Frame::Array(vec![human(long), human(lat)])
+}
+
+impl Execable for GeoPos {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let mut positions = Vec::with_capacity(self.members.len());
+        for member in self.members.iter() {
+            match shared.zscore(&self.key, member.as_bytes())? {
+                Some(score) => positions.push(position_frame(score)),
+                None => positions.push(Frame::Nil),
+            }
+        }
+        Ok(Some(Frame::Array(positions)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        geohash::{self, Shape},
+        notify,
+        shared::Shared,
+    },
+};
+
+use super::{
+    geoadd::long_lat,
+    geodist::{distance_frame, meters_per_unit},
+    geopos::position_frame,
+    Execable,
+};
+
+/// GEOSEARCH, and GEOSEARCHSTORE when `store` names the destination.
+#[derive(Debug)]
+pub struct GeoSearch {
+    key: String,
+    store: Option<String>,
+    from: Center,
+    shape: Shape,
+    unit: f64,
+    order: Option<Order>,
+    count: Option<(usize, bool)>,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+    store_dist: bool,
+}
+
+#[derive(Debug)]
+enum Center {
+    Member(String),
+    LongLat(f64, f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Order {
+    Asc,
+    Desc,
+}
+
+/// A member found, with its distance from the center in meters and its score.
+struct Found {
+    member: Vec<u8>,
+    distance: f64,
+    score: f64,
+}
+
+impl GeoSearch {
+    pub fn parse(mut parse: Parse, store: bool) -> Result<GeoSearch, ParseError> {
+        let name = if store { "GEOSEARCHSTORE" } else { "GEOSEARCH" };
+        let store = match store {
+            true => Some(parse.next()?.into_string()?),
+            false => None,
+        };
+        let key = parse.next()?.into_string()?;
+        let mut args = parse.remaining_into_string_vec()?.into_iter();
+        let mut next = || args.next().ok_or_else(|| ParseError::from("ERR syntax error"));
+
+        let (mut from, mut by) = (None, None);
+        let (mut order, mut count) = (None, None);
+        let (mut with_coord, mut with_dist, mut with_hash, mut store_dist) = (false, false, false, false);
+        let mut from_count = 0;
+        let mut by_count = 0;
+        while let Ok(arg) = next() {
+            match &arg.to_lowercase()[..] {
+                "frommember" => {
+                    from = Some(Center::Member(next()?));
+                    from_count += 1;
+                }
+                "fromlonlat" => {
+                    let (long, lat) = long_lat(&next()?, &next()?)?;
+                    from = Some(Center::LongLat(long, lat));
+                    from_count += 1;
+                }
+                "byradius" => {
+                    let radius = match next()?.parse::<f64>() {
+                        Ok(radius) if radius < 0.0 => return Err("ERR radius cannot be negative".into()),
+                        Ok(radius) => radius,
+                        Err(_) => return Err("ERR need numeric radius".into()),
+                    };
+                    let unit = meters_per_unit(&next()?)?;
+                    by = Some((Shape::Radius(radius * unit), unit));
+                    by_count += 1;
+                }
+                "bybox" => {
+                    let width = next()?.parse::<f64>().map_err(|_| ParseError::from("ERR need numeric width"))?;
+                    let height = next()?.parse::<f64>().map_err(|_| ParseError::from("ERR need numeric height"))?;
+                    if width < 0.0 || height < 0.0 {
+                        return Err("ERR height or width cannot be negative".into());
+                    }
+                    let unit = meters_per_unit(&next()?)?;
+                    by = Some((Shape::Box(width * unit, height * unit), unit));
+                    by_count += 1;
+                }
+                "asc" => order = Some(Order::Asc),
+                "desc" => order = Some(Order::Desc),
+                "count" => {
+                    let n = match next()?.parse::<i64>() {
+                        Ok(n) if n <= 0 => return Err("ERR COUNT must be > 0".into()),
+                        Ok(n) => n as usize,
+                        Err(_) => return Err("ERR value is not an integer or out of range".into()),
+                    };
+                    count = Some((n, false));
+                }
+                "any" => match count.as_mut() {
+                    Some((_, any)) => *any = true,
+                    None => return Err("ERR the ANY argument requires COUNT argument".into()),
+                },
+                "withcoord" => with_coord = true,
+                "withdist" => with_dist = true,
+                "withhash" => with_hash = true,
+                "storedist" if store.is_some() => store_dist = true,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        let from = match from {
+            Some(from) if from_count == 1 => from,
+            _ => {
+                return Err(format!("ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for {}", name).into())
+            }
+        };
+        let (shape, unit) = match by {
+            Some(by) if by_count == 1 => by,
+            _ => return Err(format!("ERR exactly one of BYRADIUS and BYBOX can be specified for {}", name).into()),
+        };
+        if store.is_some() && (with_coord || with_dist || with_hash) {
+            return Err(format!("ERR {} is not compatible with WITHDIST, WITHHASH and WITHCOORD options", name).into());
+        }
+        Ok(GeoSearch {
+            key,
+            store,
+            from,
+            shape,
+            unit,
+            order,
+            count,
+            with_coord,
+            with_dist,
+            with_hash,
+            store_dist,
+        })
+    }
+
+    /// The members within the shape, scanning only the score ranges of the geohash boxes around it.
+    fn search(&self, shared: &Shared) -> crate::Result<Vec<Found>> {
+        let (long, lat) = match &self.from {
+            Center::LongLat(long, lat) => (*long, *lat),
+            Center::Member(member) => match shared.zscore(&self.key, member.as_bytes())? {
+                Some(score) => geohash::position(score),
+                None => return Err("ERR could not decode requested zset member".into()),
+            },
+        };
+        let mut found = Vec::new();
+        for (min, max) in geohash::search_ranges(long, lat, self.shape) {
+            for (score, member) in shared.zrange_by_score(&self.key, min, max)? {
+                let (point_long, point_lat) = geohash::position(score);
+                if let Some(distance) = geohash::distance_in_shape(long, lat, self.shape, point_long, point_lat) {
+                    found.push(Found { member, distance, score });
+                }
+                if matches!(self.count, Some((count, true)) if found.len() == count) {
+                    return Ok(found);
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    fn reply(&self, found: &Found) -> Frame {
+        let member = Frame::Bulk(found.member.clone());
+        if !(self.with_coord || self.with_dist || self.with_hash) {
+            return member;
+        }
+        let mut item = vec![member];
+        if self.with_dist {
+            item.push(distance_frame(found.distance, self.unit));
+        }
+        if self.with_hash {
+            item.push(Frame::Integer(found.score as i64));
+        }
+        if self.with_coord {
+            item.push(position_frame(found.score));
+        }
+        Frame::Array(item)
+    }
+}
+
+impl Execable for GeoSearch {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let mut found = match shared.is_exists(&self.key) {
+            true => self.search(shared)?,
+            false => Vec::new(),
+        };
+
+        // a COUNT without ANY sorts by distance even when no order is given, like redis
+        let order = match (self.order, self.count) {
+            (None, Some((_, false))) => Some(Order::Asc),
+            (order, _) => order,
+        };
+        match order {
+            Some(Order::Asc) => found.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+            Some(Order::Desc) => found.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+            None => {}
+        }
+        if let Some((count, _)) = self.count {
+            found.truncate(count);
+        }
+
+        let dest = match &self.store {
+            Some(dest) => dest,
+            None => return Ok(Some(Frame::Array(found.iter().map(|found| self.reply(found)).collect()))),
+        };
+        let members: Vec<(f64, Vec<u8>)> = found
+            .into_iter()
+            .map(|found| match self.store_dist {
+                true => (found.distance / self.unit, found.member),
+                false => (found.score, found.member),
+            })
+            .collect();
+        // the destination is replaced in a single write, by nothing when no member matched
+        if members.is_empty() {
+            shared.del(dest);
+        } else {
+            shared.zset_replace(dest, &members)?;
+            shared.notify(notify::ZSET, "geosearchstore", dest);
+        }
+        Ok(Some(Frame::Integer(members.len() as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        match &self.store {
+            Some(dest) => vec![dest.as_str(), self.key.as_str()],
+            None => vec![&self.key],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::{
+        testing::{run, show, with_shared},
+        Shared,
+    };
+
+    fn with_sicily(name: &str, test: impl FnOnce(&mut Shared)) {
+        with_shared(name, |shared| {
+            run(shared, &["geoadd", "Sicily", "13.361389", "38.115556", "Palermo", "15.087269", "37.502669", "Catania"]);
+            test(shared)
+        })
+    }
+
+    #[test]
+    fn search_like_redis() {
+        with_sicily("geosearch", |shared| {
+            let reply = run(shared, &["geosearch", "Sicily", "fromlonlat", "15", "37", "byradius", "200", "km", "asc", "withdist"]);
+            assert_eq!(show(&reply), "[[\"Catania\", \"56.4413\"], [\"Palermo\", \"190.4424\"]]");
+            let reply = run(shared, &["geosearch", "Sicily", "fromlonlat", "15", "37", "byradius", "100", "km"]);
+            assert_eq!(show(&reply), "[\"Catania\"]");
+            let reply = run(shared, &["geosearch", "Sicily", "fromlonlat", "15", "37", "bybox", "400", "400", "km", "desc"]);
+            assert_eq!(show(&reply), "[\"Palermo\", \"Catania\"]");
+            let reply = run(shared, &["geosearch", "Sicily", "frommember", "Palermo", "byradius", "200", "km", "count", "1", "asc"]);
+            assert_eq!(show(&reply), "[\"Palermo\"]");
+            let reply = run(shared, &["geosearch", "Sicily", "fromlonlat", "15", "37", "byradius", "200", "km", "storedist"]);
+            assert_eq!(show(&reply), "(error) ERR syntax error");
+        });
+    }
+
+    #[test]
+    fn store_replaces_the_destination() {
+        with_sicily("geosearchstore", |shared| {
+            run(shared, &["set", "dest", "value"]);
+            let reply = run(shared, &["geosearchstore", "dest", "Sicily", "fromlonlat", "15", "37", "byradius", "200", "km"]);
+            assert_eq!(show(&reply), "(integer) 2");
+            let reply = run(shared, &["geosearch", "dest", "fromlonlat", "15", "37", "byradius", "200", "km", "asc"]);
+            assert_eq!(show(&reply), "[\"Catania\", \"Palermo\"]");
+            // members left from the earlier set are gone
+            let reply = run(shared, &["geosearchstore", "dest", "Sicily", "fromlonlat", "15", "37", "byradius", "100", "km"]);
+            assert_eq!(show(&reply), "(integer) 1");
+            assert_eq!(shared.zscore("dest", b"Palermo").unwrap(), None);
+            assert!(shared.zscore("dest", b"Catania").unwrap().is_some());
+        });
+    }
+
+    #[test]
+    fn store_distances() {
+        with_sicily("geosearchstore-dist", |shared| {
+            let args = ["geosearchstore", "dest", "Sicily", "fromlonlat", "15", "37", "byradius", "200", "km", "storedist"];
+            assert_eq!(show(&run(shared, &args)), "(integer) 2");
+            let distance = shared.zscore("dest", b"Catania").unwrap().unwrap();
+            assert_eq!(format!("{:.4}", distance), "56.4413");
+        });
+    }
+
+    #[test]
+    fn store_of_nothing_deletes_the_destination() {
+        with_sicily("geosearchstore-empty", |shared| {
+            run(shared, &["geosearchstore", "dest", "Sicily", "fromlonlat", "15", "37", "byradius", "200", "km"]);
+            let reply = run(shared, &["geosearchstore", "dest", "Sicily", "fromlonlat", "0", "0", "byradius", "1", "km"]);
+            assert_eq!(show(&reply), "(integer) 0");
+            assert_eq!(show(&run(shared, &["exists", "dest"])), "(integer) 0");
+        });
+    }
+}
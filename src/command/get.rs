@@ -20,13 +20,14 @@ impl Get {
 impl Execable for Get {
     fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
         let key = &self.key;
-        let value_ref = shared.get(key);
-        if let Some(value) = value_ref {
+        let value = shared.get(key)?;
+        {
             let frame = value.frame()?;
             let fmt = match frame {
-                Frame::Str(_) | Frame::Nil => frame,
-                Frame::Bulk(b) => Frame::Str(b.to_vec()),
-                Frame::Integer(i) => Frame::Str(format!("{}", i).into_bytes()),
+                // bulk replies keep binary values such as bitmaps intact
+                Frame::Bulk(_) | Frame::Nil => frame,
+                Frame::Str(b) => Frame::Bulk(b),
+                Frame::Integer(i) => Frame::Bulk(format!("{}", i).into_bytes()),
                 _ => {
                     return Err(
                         "WRONGTYPE Operation against a key holding the wrong kind of value".into(),
@@ -35,7 +36,9 @@ impl Execable for Get {
             };
             return Ok(Some(fmt));
         }
+    }
 
-        return Ok(Some(Frame::Nil));
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
     }
 }
@@ -0,0 +1,37 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::{setbit::bit_offset, Execable};
+
+#[derive(Debug)]
+pub struct GetBit {
+    key: String,
+    offset: usize,
+}
+
+impl GetBit {
+    pub fn parse(mut parse: Parse) -> Result<GetBit, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let offset = parse.next()?.into_string()?;
+        if parse.fin().is_err() {
+            return Err("ERR wrong number of arguments for 'getbit' command".into());
+        }
+        let offset = bit_offset(&offset)?;
+        Ok(GetBit { key, offset })
+    }
+}
+
+impl Execable for GetBit {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let byte = self.offset / 8;
+        let current = shared.get_string_range(&self.key, byte, byte + 1)?.first().copied().unwrap_or(0);
+        let bit = current & (0x80u8 >> (self.offset % 8)) != 0;
+        Ok(Some(Frame::Integer(bit as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct GetDel {
+    key: String,
+}
+
+impl GetDel {
+    pub fn parse(mut parse: Parse) -> Result<GetDel, ParseError> {
+        let key = parse.next()?.into_string()?;
+        match parse.fin() {
+            Ok(_) => Ok(GetDel { key }),
+            Err(_) => Err("ERR wrong number of arguments for 'getdel' command".into()),
+        }
+    }
+}
+
+impl Execable for GetDel {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        match shared.get_string(&self.key)? {
+            Some((value, _)) => {
+                shared.del(&self.key);
+                Ok(Some(Frame::Bulk(value)))
+            }
+            None => Ok(Some(Frame::Nil)),
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+    utils,
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct GetEx {
+    key: String,
+    expire: Option<Expire>,
+}
+
+#[derive(Debug)]
+enum Expire {
+    At(u64),
+    Persist,
+}
+
+impl GetEx {
+    pub fn parse(mut parse: Parse) -> Result<GetEx, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) => args.into_iter(),
+            Err(ParseError::EOF) => Vec::new().into_iter(),
+            Err(e) => return Err(e),
+        };
+
+        let expire = match args.next().map(|arg| arg.to_lowercase()) {
+            None => None,
+            Some(option) if option == "persist" => Some(Expire::Persist),
+            Some(option) => {
+                let value = match args.next() {
+                    Some(value) => value,
+                    None => return Err("ERR syntax error".into()),
+                };
+                let value = match value.parse::<i64>() {
+                    Ok(value) => value,
+                    Err(_) => return Err("ERR value is not an integer or out of range".into()),
+                };
+                match expire_at(&option, value) {
+                    Some(Ok(at)) => Some(Expire::At(at)),
+                    Some(Err(_)) => return Err("ERR invalid expire time in 'getex' command".into()),
+                    None => return Err("ERR syntax error".into()),
+                }
+            }
+        };
+        if args.next().is_some() {
+            return Err("ERR syntax error".into());
+        }
+        Ok(GetEx { key, expire })
+    }
+}
+
+/// Turns the value of an `EX`, `PX`, `EXAT` or `PXAT` option into unix milliseconds.
+/// `None` for any other option, `Some(Err)` when the time isn't positive or overflows.
+pub(crate) fn expire_at(option: &str, value: i64) -> Option<Result<u64, ()>> {
+    let at = match option {
+        "ex" => value.checked_mul(1000).and_then(|ms| ms.checked_add(utils::now_millis() as i64)),
+        "px" => value.checked_add(utils::now_millis() as i64),
+        "exat" => value.checked_mul(1000),
+        "pxat" => Some(value),
+        _ => return None,
+    };
+    match at {
+        Some(at) if value > 0 => Some(Ok(at as u64)),
+        _ => Some(Err(())),
+    }
+}
+
+impl Execable for GetEx {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let value = match shared.get_string(&self.key)? {
+            Some((value, _)) => value,
+            None => return Ok(Some(Frame::Nil)),
+        };
+        match self.expire {
+            Some(Expire::At(at)) => shared.set_expire(&self.key, Some(at))?,
+            Some(Expire::Persist) => shared.set_expire(&self.key, None)?,
+            None => false,
+        };
+        Ok(Some(Frame::Bulk(value)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::testing::{run, show, with_shared},
+    };
+
+    fn parse(args: &[&str]) -> Result<GetEx, String> {
+        let frames = std::iter::once("getex")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::GETEX(getex)) => Ok(getex),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn expire_options() {
+        let now = utils::now_millis();
+        assert!(matches!(expire_at("ex", 10), Some(Ok(at)) if at >= now + 10_000));
+        assert!(matches!(expire_at("px", 10), Some(Ok(at)) if at >= now + 10));
+        assert_eq!(expire_at("exat", 10), Some(Ok(10_000)));
+        assert_eq!(expire_at("pxat", 10), Some(Ok(10)));
+        assert_eq!(expire_at("ex", 0), Some(Err(())));
+        assert_eq!(expire_at("pxat", -1), Some(Err(())));
+        assert_eq!(expire_at("ex", i64::MAX), Some(Err(())));
+        assert_eq!(expire_at("keepttl", 10), None);
+    }
+
+    #[test]
+    fn arguments() {
+        assert!(parse(&["k"]).unwrap().expire.is_none());
+        assert!(matches!(parse(&["k", "PERSIST"]).unwrap().expire, Some(Expire::Persist)));
+        assert!(matches!(parse(&["k", "pxat", "10"]).unwrap().expire, Some(Expire::At(10))));
+        assert_eq!(parse(&["k", "ex"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["k", "ex", "x"]).unwrap_err(), "ERR value is not an integer or out of range");
+        assert_eq!(parse(&["k", "ex", "0"]).unwrap_err(), "ERR invalid expire time in 'getex' command");
+        assert_eq!(parse(&["k", "keepttl", "10"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["k", "persist", "ex", "10"]).unwrap_err(), "ERR syntax error");
+    }
+
+    #[test]
+    fn sets_or_removes_the_ttl() {
+        with_shared("getex", |shared| {
+            assert_eq!(show(&run(shared, &["getex", "k", "ex", "100"])), "(nil)");
+            assert_eq!(show(&run(shared, &["ttl", "k"])), "(integer) -2");
+            run(shared, &["set", "k", "v"]);
+            assert_eq!(show(&run(shared, &["getex", "k", "ex", "100"])), "\"v\"");
+            assert_eq!(show(&run(shared, &["ttl", "k"])), "(integer) 100");
+            assert_eq!(show(&run(shared, &["getex", "k"])), "\"v\"");
+            assert_eq!(show(&run(shared, &["ttl", "k"])), "(integer) 100");
+            assert_eq!(show(&run(shared, &["getex", "k", "persist"])), "\"v\"");
+            assert_eq!(show(&run(shared, &["ttl", "k"])), "(integer) -1");
+            // a time in the past expires the key
+            assert_eq!(show(&run(shared, &["getex", "k", "pxat", "1"])), "\"v\"");
+            assert_eq!(show(&run(shared, &["get", "k"])), "(nil)");
+        });
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct GetRange {
+    key: String,
+    start: i64,
+    end: i64,
+}
+
+impl GetRange {
+    pub fn parse(mut parse: Parse) -> Result<GetRange, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let start = parse.next()?;
+        let end = parse.next()?;
+        if parse.fin().is_err() {
+            return Err("ERR wrong number of arguments for 'getrange' command".into());
+        }
+        match (start.into_decimal(), end.into_decimal()) {
+            (Ok(start), Ok(end)) => Ok(GetRange { key, start, end }),
+            _ => Err("ERR value is not an integer or out of range".into()),
+        }
+    }
+}
+
+impl Execable for GetRange {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let len = shared.string_len(&self.key)? as i64;
+        if self.start < 0 && self.end < 0 && self.start > self.end {
+            return Ok(Some(Frame::Bulk(Vec::new())));
+        }
+        let start = if self.start < 0 { (len + self.start).max(0) } else { self.start };
+        let end = if self.end < 0 { (len + self.end).max(0) } else { self.end.min(len - 1) };
+        if len == 0 || start > end {
+            return Ok(Some(Frame::Bulk(Vec::new())));
+        }
+        let value = shared.get_string_range(&self.key, start as usize, end as usize + 1)?;
+        Ok(Some(Frame::Bulk(value)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    #[test]
+    fn ranges() {
+        with_shared("getrange", |shared| {
+            run(shared, &["set", "k", "This is a string"]);
+            // the examples of the GETRANGE documentation
+            assert_eq!(show(&run(shared, &["getrange", "k", "0", "3"])), "\"This\"");
+            assert_eq!(show(&run(shared, &["getrange", "k", "-3", "-1"])), "\"ing\"");
+            assert_eq!(show(&run(shared, &["getrange", "k", "0", "-1"])), "\"This is a string\"");
+            assert_eq!(show(&run(shared, &["getrange", "k", "10", "100"])), "\"string\"");
+            assert_eq!(show(&run(shared, &["getrange", "k", "-1", "-2"])), "\"\"");
+            assert_eq!(show(&run(shared, &["getrange", "k", "5", "3"])), "\"\"");
+            assert_eq!(show(&run(shared, &["getrange", "k", "16", "20"])), "\"\"");
+            assert_eq!(show(&run(shared, &["getrange", "missing", "0", "-1"])), "\"\"");
+            assert_eq!(show(&run(shared, &["getrange", "k", "x", "1"])), "(error) ERR value is not an integer or out of range");
+            assert_eq!(
+                show(&run(shared, &["getrange", "k", "0", "1", "2"])),
+                "(error) ERR wrong number of arguments for 'getrange' command"
+            );
+        });
+    }
+}
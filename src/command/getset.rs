@@ -0,0 +1,39 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct GetSet {
+    key: String,
+    value: Vec<u8>,
+}
+
+impl GetSet {
+    pub fn parse(mut parse: Parse) -> Result<GetSet, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let value = parse.next()?.into_vec()?;
+        match parse.fin() {
+            Ok(_) => Ok(GetSet { key, value }),
+            Err(_) => Err("ERR wrong number of arguments for 'getset' command".into()),
+        }
+    }
+}
+
+impl Execable for GetSet {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let old = shared.get_string(&self.key)?;
+        shared.put_string(&self.key, &self.value, None)?;
+        shared.notify(notify::STRING, "set", &self.key);
+        match old {
+            Some((value, _)) => Ok(Some(Frame::Bulk(value))),
+            None => Ok(Some(Frame::Nil)),
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{
+    acl::Acl,
+    command::{auth::Auth, client::check_name},
+    config::Config,
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::clients::Client as ClientInfo,
+};
+
+/// HELLO, switching the connection to RESP2 or RESP3 and optionally authenticating and naming it.
+#[derive(Debug)]
+pub struct Hello {
+    protover: Option<i64>,
+    auth: Option<Auth>,
+    setname: Option<String>,
+}
+
+impl Hello {
+    pub fn parse(parse: Parse) -> Result<Hello, ParseError> {
+        let args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut args = args.into_iter();
+        let protover = match args.next() {
+            Some(protover) => match protover.parse::<i64>() {
+                Ok(protover @ (2 | 3)) => Some(protover),
+                Ok(_) => return Err("NOPROTO unsupported protocol version".into()),
+                Err(_) => return Err("ERR Protocol version is not an integer or out of range".into()),
+            },
+            None => None,
+        };
+
+        let mut hello = Hello { protover, auth: None, setname: None };
+        while let Some(option) = args.next() {
+            match &option.to_lowercase()[..] {
+                "auth" => match (args.next(), args.next()) {
+                    (Some(username), Some(password)) => hello.auth = Some(Auth::new(Some(username), password)),
+                    _ => return Err(format!("ERR Syntax error in HELLO option '{}'", option).into()),
+                },
+                "setname" => match args.next() {
+                    Some(name) => {
+                        check_name(&name)?;
+                        hello.setname = Some(name);
+                    }
+                    None => return Err(format!("ERR Syntax error in HELLO option '{}'", option).into()),
+                },
+                _ => return Err(format!("ERR Syntax error in HELLO option '{}'", option).into()),
+            }
+        }
+        Ok(hello)
+    }
+
+    /// Authenticates when asked to, then names the client and replies the server properties.
+    /// Runs on the connection, which switches protocol when `resp3` changed.
+    pub fn execute(
+        self,
+        acl: &Acl,
+        config: &Config,
+        user: &mut Option<String>,
+        me: &ClientInfo,
+        resp3: &mut bool,
+    ) -> Frame {
+        if let Some(auth) = self.auth {
+            if let err @ Frame::Error(_) = auth.authenticate(acl, config, user) {
+                return err;
+            }
+        }
+        if user.is_none() {
+            return Frame::Error(
+                "NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH \
+                 <user> <pass> option can be used to authenticate the client and select the RESP protocol version at \
+                 the same time"
+                    .to_string(),
+            );
+        }
+        if let Some(name) = self.setname {
+            me.state().name = name;
+        }
+        if let Some(protover) = self.protover {
+            *resp3 = protover == 3;
+        }
+
+        let bulk = |s: &str| Frame::Bulk(s.as_bytes().to_vec());
+        Frame::Map(vec![
+            (bulk("server"), bulk("redis")),
+            (bulk("version"), bulk("7.0.0")),
+            (bulk("proto"), Frame::Integer(if *resp3 { 3 } else { 2 })),
+            (bulk("id"), Frame::Integer(me.id as i64)),
+            (bulk("mode"), bulk("standalone")),
+            (bulk("role"), bulk("master")),
+            (bulk("modules"), Frame::Array(Vec::new())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::{
+            clients::Clients,
+            shared::testing::{show, temporary_dir},
+        },
+    };
+
+    fn parse(args: &[&str]) -> Result<Hello, String> {
+        let frames = std::iter::once("hello")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::HELLO(hello)) => Ok(hello),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Runs HELLO with `args` for a connection of `user`, returning the reply and whether it
+    /// switched to RESP3.
+    fn execute(args: &[&str], requirepass: &str, user: &mut Option<String>) -> (String, bool) {
+        let file = temporary_dir("hello");
+        let acl = Acl::load(file).unwrap();
+        let config = Config::load(None).unwrap();
+        config.set(&[("requirepass".to_string(), requirepass.to_string())]).unwrap();
+        let me = Clients::default().register("127.0.0.1:1".to_string(), "127.0.0.1:6379".to_string(), None);
+        let mut resp3 = false;
+        let reply = parse(args).unwrap().execute(&acl, &config, user, &me, &mut resp3);
+        (show(&reply), resp3)
+    }
+
+    #[test]
+    fn options() {
+        assert!(parse(&[]).is_ok());
+        assert_eq!(parse(&["4"]).unwrap_err(), "NOPROTO unsupported protocol version");
+        assert_eq!(parse(&["three"]).unwrap_err(), "ERR Protocol version is not an integer or out of range");
+        assert_eq!(parse(&["3", "auth", "alice"]).unwrap_err(), "ERR Syntax error in HELLO option 'auth'");
+        assert_eq!(parse(&["3", "SETNAME"]).unwrap_err(), "ERR Syntax error in HELLO option 'SETNAME'");
+        assert_eq!(parse(&["3", "nope"]).unwrap_err(), "ERR Syntax error in HELLO option 'nope'");
+        assert_eq!(
+            parse(&["3", "setname", "a b"]).unwrap_err(),
+            "ERR Client names cannot contain spaces, newlines or special characters."
+        );
+        let hello = parse(&["2", "AUTH", "alice", "secret", "setname", "worker"]).unwrap();
+        assert_eq!((hello.protover, hello.setname.as_deref()), (Some(2), Some("worker")));
+        assert!(hello.auth.is_some());
+    }
+
+    #[test]
+    fn switches_protocol() {
+        let mut user = Some("default".to_string());
+        let (reply, resp3) = execute(&["3"], "", &mut user);
+        assert!(resp3);
+        assert!(reply.starts_with("{\"server\": \"redis\", \"version\": \"7.0.0\", \"proto\": (integer) 3, \"id\": (integer) 1,"), "{}", reply);
+        let (reply, resp3) = execute(&[], "", &mut user);
+        assert!(!resp3 && reply.contains("\"proto\": (integer) 2"));
+    }
+
+    #[test]
+    fn authenticates_first() {
+        let mut user = None;
+        let (reply, resp3) = execute(&["3"], "secret", &mut user);
+        assert!(reply.starts_with("(error) NOAUTH HELLO must be called with the client already authenticated"));
+        assert!(!resp3);
+        let (reply, _) = execute(&["3", "auth", "default", "wrong"], "secret", &mut user);
+        assert!(reply.starts_with("(error) WRONGPASS"));
+        assert_eq!(user, None);
+        let (reply, resp3) = execute(&["3", "auth", "default", "secret"], "secret", &mut user);
+        assert!(resp3 && reply.contains("\"proto\": (integer) 3"));
+        assert_eq!(user.as_deref(), Some("default"));
+    }
+}
@@ -1,10 +1,11 @@
 use crate::{
     protocol::{frame::Frame, parse::Parse, ParseError},
-    server::{shared::Shared, value::Value},
+    server::{notify, shared::Shared, value::Value},
 };
 
 use super::Execable;
 
+/// INCR, INCRBY, DECR and DECRBY, a decrement being stored as a negative `num`.
 #[derive(Debug)]
 pub struct IncrBy {
     key: String,
@@ -12,15 +13,32 @@ pub struct IncrBy {
 }
 
 impl IncrBy {
-    pub fn parse(mut parse: Parse,incr :bool) -> Result<IncrBy, ParseError> {
+    pub fn parse(mut parse: Parse, name: &str) -> Result<IncrBy, ParseError> {
         let key = parse.next()?.into_string()?;
 
-        let num = if incr {1} else {parse.next()?.into_decimal()?};
+        let num = match name {
+            "incr" => 1,
+            "decr" => -1,
+            _ => {
+                let num = match parse.next()?.into_string()?.parse::<i64>() {
+                    Ok(num) => num,
+                    Err(_) => return Err("ERR value is not an integer or out of range".into()),
+                };
+                if name == "decrby" {
+                    match num.checked_neg() {
+                        Some(num) => num,
+                        None => return Err("ERR decrement would overflow".into()),
+                    }
+                } else {
+                    num
+                }
+            }
+        };
 
         match parse.fin() {
             Ok(_) => return Ok(IncrBy { key, num }),
             Err(_) => {
-                return Err("ERR wrong number of arguments for 'incrby | incr' command".into())
+                return Err(format!("ERR wrong number of arguments for '{}' command", name).into())
             }
         }
     }
@@ -28,22 +46,102 @@ impl IncrBy {
 
 impl Execable for IncrBy {
     fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
-        let key = self.key;
-        let num = self.num;
-
-        let optref = shared.get(&key);
-        let value = match optref {
-            Some(mut r) => {
-                let increment =  r.incr(num)?;
-                shared.default_set(&key, r)?;
-                increment
-            },
-            None => {
-                let value_ref = Value::Bytes("1".into());
-                shared.default_set(&key, value_ref)?;
-                1
-            }
+        // a merge write can't be held back with the rest of a transaction
+        if shared.config().props().counter_merge && !shared.in_transaction() {
+            let value = shared.merge_counter(&self.key, self.num)?;
+            shared.notify(notify::STRING, "incrby", &self.key);
+            return Ok(Some(Frame::Integer(value)));
+        }
+        let (value, expire_at) = match shared.get_string(&self.key)? {
+            Some((value, expire_at)) => (Value::Bytes(value), expire_at),
+            None => (Value::None, None),
         };
+        let value = value.incr(self.num)?;
+        shared.put_string(&self.key, value.to_string().as_bytes(), expire_at)?;
+        shared.notify(notify::STRING, "incrby", &self.key);
         return Ok(Some(Frame::Integer(value)));
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::{
+            testing::{run, show, with_shared},
+            WRONGTYPE,
+        },
+    };
+
+    fn parse(args: &[&str]) -> Result<IncrBy, String> {
+        let frames = args.iter().map(|arg| Frame::Bulk(arg.as_bytes().to_vec())).collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::INCR(incr) | Command::INCRBY(incr) | Command::DECR(incr) | Command::DECRBY(incr)) => Ok(incr),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn decrements_are_negated() {
+        assert_eq!(parse(&["incr", "k"]).unwrap().num, 1);
+        assert_eq!(parse(&["decr", "k"]).unwrap().num, -1);
+        assert_eq!(parse(&["incrby", "k", "-5"]).unwrap().num, -5);
+        assert_eq!(parse(&["decrby", "k", "5"]).unwrap().num, -5);
+        assert_eq!(parse(&["decrby", "k", &i64::MAX.to_string()]).unwrap().num, -i64::MAX);
+        assert_eq!(parse(&["decrby", "k", &i64::MIN.to_string()]).unwrap_err(), "ERR decrement would overflow");
+        assert_eq!(parse(&["incrby", "k", "1.5"]).unwrap_err(), "ERR value is not an integer or out of range");
+        assert_eq!(parse(&["decr", "k", "1"]).unwrap_err(), "ERR wrong number of arguments for 'decr' command");
+    }
+
+    #[test]
+    fn counters() {
+        with_shared("incrby", |shared| {
+            assert_eq!(show(&run(shared, &["incr", "n"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["incrby", "n", "10"])), "(integer) 11");
+            assert_eq!(show(&run(shared, &["decrby", "n", "20"])), "(integer) -9");
+            assert_eq!(show(&run(shared, &["decr", "n"])), "(integer) -10");
+            assert_eq!(show(&run(shared, &["get", "n"])), "\"-10\"");
+
+            run(shared, &["set", "max", &i64::MAX.to_string()]);
+            assert_eq!(show(&run(shared, &["incr", "max"])), "(error) ERR increment or decrement would overflow");
+            run(shared, &["set", "text", "abc"]);
+            assert_eq!(show(&run(shared, &["incr", "text"])), "(error) ERR value is not an integer or out of range");
+            run(shared, &["geoadd", "geo", "13.361389", "38.115556", "palermo"]);
+            assert_eq!(show(&run(shared, &["incr", "geo"])), format!("(error) {}", WRONGTYPE));
+        });
+    }
+
+    #[test]
+    fn keeps_the_ttl() {
+        with_shared("incrby_ttl", |shared| {
+            run(shared, &["set", "n", "1", "ex", "100"]);
+            run(shared, &["incr", "n"]);
+            assert_eq!(show(&run(shared, &["ttl", "n"])), "(integer) 100");
+            run(shared, &["incrbyfloat", "n", "0.5"]);
+            assert_eq!(show(&run(shared, &["ttl", "n"])), "(integer) 100");
+        });
+    }
+
+    #[test]
+    fn float_counters() {
+        with_shared("incrbyfloat", |shared| {
+            assert_eq!(show(&run(shared, &["incrbyfloat", "f", "10.5"])), "\"10.5\"");
+            assert_eq!(show(&run(shared, &["incrbyfloat", "f", "0.1"])), "\"10.6\"");
+            assert_eq!(show(&run(shared, &["incrbyfloat", "f", "-5"])), "\"5.6\"");
+            run(shared, &["set", "e", "5.0e3"]);
+            assert_eq!(show(&run(shared, &["incrbyfloat", "e", "2.0e2"])), "\"5200\"");
+            assert_eq!(show(&run(shared, &["incrbyfloat", "e", "nan"])), "(error) ERR value is not a valid float");
+            run(shared, &["set", "big", "1e308"]);
+            assert_eq!(
+                show(&run(shared, &["incrbyfloat", "big", "1e308"])),
+                "(error) ERR increment would produce NaN or Infinity"
+            );
+        });
+    }
 }
@@ -0,0 +1,43 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared, value::{self, Value}},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct IncrByFloat {
+    key: String,
+    num: f64,
+}
+
+impl IncrByFloat {
+    pub fn parse(mut parse: Parse) -> Result<IncrByFloat, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let num = match value::parse_float(&parse.next()?.into_vec()?) {
+            Some(num) => num,
+            None => return Err("ERR value is not a valid float".into()),
+        };
+        match parse.fin() {
+            Ok(_) => Ok(IncrByFloat { key, num }),
+            Err(_) => Err("ERR wrong number of arguments for 'incrbyfloat' command".into()),
+        }
+    }
+}
+
+impl Execable for IncrByFloat {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let (value, expire_at) = match shared.get_string(&self.key)? {
+            Some((value, expire_at)) => (Value::Bytes(value), expire_at),
+            None => (Value::None, None),
+        };
+        let value = value::format_float(value.incr_float(self.num)?);
+        shared.put_string(&self.key, value.as_bytes(), expire_at)?;
+        shared.notify(notify::STRING, "incrbyfloat", &self.key);
+        Ok(Some(Frame::Bulk(value.into_bytes())))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -3,6 +3,7 @@
 use crate::protocol::{frame::Frame, parse::Parse, ParseError};
 
 use crate::command::Execable;
+use crate::server::stats::Stats;
 
 #[derive(Debug)]
 pub struct Info {
@@ -38,37 +39,38 @@ impl Info {
 
 impl Execable for Info {
     fn apply(self, shared: &mut crate::server::shared::Shared) -> crate::Result<Option<Frame>> {
-        let mut buf = String::new();
+        let section = self.section.map(|s| s.to_lowercase()).unwrap_or_default();
+        let all = matches!(&section[..], "" | "all" | "default" | "everything");
 
-        match self.section {
-            Some(s) => match &s.to_lowercase()[..] {
-                "all" | "keyspace" | "" => {
-                    buf.push_str(
-                        "# Keyspace
-                        ",
-                    );
-                    let str = format!("db0:keys={},expires=0,avg_ttl=0", shared.len());
-                    buf.push_str(&str[..]);
-                    buf.push_str(
-                        "
-                        ",
-                    );
-                }
-                _ => {}
-            },
-            None => {
-                buf.push_str(
-                    "# Keyspace
-                    ",
-                );
-                let str = format!("db0:keys={},expires=0,avg_ttl=0", shared.len());
-                buf.push_str(&str[..]);
-                buf.push_str(
-                    "
-                    ",
-                );
-            }
+        let mut sections: Vec<String> = Vec::new();
+
+        if all || section == "clients" {
+            sections.push(format!(
+                "# Clients\r\nconnected_clients:{}\r\nmaxclients:{}\r\n",
+                Stats::get(&shared.stats().connected_clients),
+                shared.config().props().max_clients,
+            ));
+        }
+
+        if all || section == "stats" {
+            let stats = shared.stats();
+            sections.push(format!(
+                "# Stats\r\ntotal_connections_received:{}\r\ntotal_commands_processed:{}\r\nrejected_connections:{}\r\n\
+                 expired_keys:{}\r\n",
+                Stats::get(&stats.total_connections_received),
+                Stats::get(&stats.total_commands_processed),
+                Stats::get(&stats.rejected_connections),
+                Stats::get(&stats.expired_keys),
+            ));
+        }
+
+        if all || section == "keyspace" {
+            sections.push(format!(
+                "# Keyspace\r\ndb0:keys={},expires=0,avg_ttl=0\r\n",
+                shared.len()
+            ));
         }
-        Ok(Some(Frame::Bulk(buf.into_bytes())))
+
+        Ok(Some(Frame::Bulk(sections.join("\r\n").into_bytes())))
     }
 }
@@ -0,0 +1,210 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::{Shared, WRONGTYPE},
+};
+
+use super::Execable;
+
+/// The largest table of subsequence lengths LCS builds, 512MB like redis' proto-max-bulk-len.
+const MAX_TABLE_SIZE: usize = 512 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct Lcs {
+    key1: String,
+    key2: String,
+    len: bool,
+    idx: bool,
+    min_match_len: usize,
+    with_match_len: bool,
+}
+
+impl Lcs {
+    pub fn parse(mut parse: Parse) -> Result<Lcs, ParseError> {
+        let key1 = parse.next()?.into_string()?;
+        let key2 = parse.next()?.into_string()?;
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) => args.into_iter(),
+            Err(ParseError::EOF) => Vec::new().into_iter(),
+            Err(e) => return Err(e),
+        };
+
+        let mut lcs = Lcs {
+            key1,
+            key2,
+            len: false,
+            idx: false,
+            min_match_len: 0,
+            with_match_len: false,
+        };
+        while let Some(arg) = args.next() {
+            match &arg.to_lowercase()[..] {
+                "len" => lcs.len = true,
+                "idx" => lcs.idx = true,
+                "withmatchlen" => lcs.with_match_len = true,
+                "minmatchlen" => {
+                    lcs.min_match_len = match args.next().map(|len| len.parse::<i64>()) {
+                        Some(Ok(len)) => len.max(0) as usize,
+                        Some(Err(_)) => return Err("ERR value is not an integer or out of range".into()),
+                        None => return Err("ERR syntax error".into()),
+                    }
+                }
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+        if lcs.len && lcs.idx {
+            return Err("ERR If you want both the length and indexes, please just use IDX.".into());
+        }
+        Ok(lcs)
+    }
+
+    fn value(shared: &Shared, key: &str) -> crate::Result<Vec<u8>> {
+        match shared.get_string(key) {
+            Ok(value) => Ok(value.map(|(value, _)| value).unwrap_or_default()),
+            Err(err) if err.to_string() == WRONGTYPE => {
+                Err("ERR The specified keys must contain string values".into())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Execable for Lcs {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let a = Lcs::value(shared, &self.key1)?;
+        let b = Lcs::value(shared, &self.key2)?;
+        let width = b.len() + 1;
+        let cells = (a.len() + 1).checked_mul(width).filter(|cells| cells * 4 <= MAX_TABLE_SIZE);
+        let cells = match cells {
+            Some(cells) => cells,
+            None => return Err("ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len".into()),
+        };
+
+        // table[i * width + j] is the length of the LCS of a[..i] and b[..j]
+        let mut table = vec![0u32; cells];
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                table[i * width + j] = match a[i - 1] == b[j - 1] {
+                    true => table[(i - 1) * width + j - 1] + 1,
+                    false => table[(i - 1) * width + j].max(table[i * width + j - 1]),
+                };
+            }
+        }
+        let len = table[a.len() * width + b.len()] as usize;
+        if self.len {
+            return Ok(Some(Frame::Integer(len as i64)));
+        }
+
+        // walks back from the end collecting the subsequence and, for IDX, the ranges matching in
+        // both strings, the same way redis does so the matches come in the same order
+        let mut result = vec![0u8; len];
+        let mut matches = Vec::new();
+        let (mut i, mut j, mut idx) = (a.len(), b.len(), len);
+        let mut range: Option<(usize, usize, usize, usize)> = None;
+        while i > 0 && j > 0 {
+            let mut emit = false;
+            if a[i - 1] == b[j - 1] {
+                result[idx - 1] = a[i - 1];
+                match range.as_mut() {
+                    None => range = Some((i - 1, i - 1, j - 1, j - 1)),
+                    Some((a_start, _, b_start, _)) if *a_start == i && *b_start == j => {
+                        *a_start -= 1;
+                        *b_start -= 1;
+                    }
+                    Some(_) => emit = true,
+                }
+                if matches!(range, Some((0, _, _, _)) | Some((_, _, 0, _))) {
+                    emit = true;
+                }
+                idx -= 1;
+                i -= 1;
+                j -= 1;
+            } else {
+                if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                    i -= 1;
+                } else {
+                    j -= 1;
+                }
+                emit = range.is_some();
+            }
+
+            if emit {
+                if let Some((a_start, a_end, b_start, b_end)) = range.take() {
+                    let match_len = a_end - a_start + 1;
+                    if self.min_match_len == 0 || match_len >= self.min_match_len {
+                        let mut item = vec![
+                            Frame::Array(vec![Frame::Integer(a_start as i64), Frame::Integer(a_end as i64)]),
+                            Frame::Array(vec![Frame::Integer(b_start as i64), Frame::Integer(b_end as i64)]),
+                        ];
+                        if self.with_match_len {
+                            item.push(Frame::Integer(match_len as i64));
+                        }
+                        matches.push(Frame::Array(item));
+                    }
+                }
+            }
+        }
+
+        if !self.idx {
+            return Ok(Some(Frame::Bulk(result)));
+        }
+        Ok(Some(Frame::Array(vec![
+            Frame::Bulk(b"matches".to_vec()),
+            Frame::Array(matches),
+            Frame::Bulk(b"len".to_vec()),
+            Frame::Integer(len as i64),
+        ])))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key1, &self.key2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    /// The examples of the LCS documentation.
+    fn with_texts(name: &str, test: impl FnOnce(&mut crate::server::shared::Shared)) {
+        with_shared(name, |shared| {
+            run(shared, &["mset", "key1", "ohmytext", "key2", "mynewtext"]);
+            test(shared)
+        })
+    }
+
+    #[test]
+    fn common_subsequence_and_length() {
+        with_texts("lcs", |shared| {
+            assert_eq!(show(&run(shared, &["lcs", "key1", "key2"])), "\"mytext\"");
+            assert_eq!(show(&run(shared, &["lcs", "key1", "key2", "len"])), "(integer) 6");
+            assert_eq!(show(&run(shared, &["lcs", "key1", "missing"])), "\"\"");
+        });
+    }
+
+    #[test]
+    fn match_indexes() {
+        with_texts("lcs-idx", |shared| {
+            let reply = run(shared, &["lcs", "key1", "key2", "idx"]);
+            // integers shown bare to keep the nesting readable
+            let expected = "[\"matches\", [[[4, 7], [5, 8]], [[2, 3], [0, 1]]], \"len\", 6]";
+            assert_eq!(show(&reply).replace("(integer) ", ""), expected);
+            let reply = run(shared, &["lcs", "key1", "key2", "idx", "minmatchlen", "4", "withmatchlen"]);
+            let expected = "[\"matches\", [[[4, 7], [5, 8], 4]], \"len\", 6]";
+            assert_eq!(show(&reply).replace("(integer) ", ""), expected);
+        });
+    }
+
+    #[test]
+    fn options() {
+        with_texts("lcs-options", |shared| {
+            let reply = run(shared, &["lcs", "key1", "key2", "len", "idx"]);
+            assert_eq!(show(&reply), "(error) ERR If you want both the length and indexes, please just use IDX.");
+            let reply = run(shared, &["lcs", "key1", "key2", "minmatchlen", "x"]);
+            assert_eq!(show(&reply), "(error) ERR value is not an integer or out of range");
+            assert_eq!(show(&run(shared, &["lcs", "key1", "key2", "foo"])), "(error) ERR syntax error");
+            run(shared, &["geoadd", "zset", "13.361389", "38.115556", "Palermo"]);
+            let reply = run(shared, &["lcs", "key1", "zset"]);
+            assert_eq!(show(&reply), "(error) ERR The specified keys must contain string values");
+        });
+    }
+}
@@ -30,14 +30,18 @@ impl super::Execable for MGet {
         let keys = self.keys;
         let mut result = Vec::with_capacity(keys.len());
         for key in keys {
+            // keys of other types read as nil, like redis does
             match shared.get(&key) {
-                Some(Value::Bytes(r)) => {
-                    result.push(r.into());
+                Ok(Value::Bytes(r)) => {
+                    result.push(Frame::Bulk(r));
                 },
-                Some(Value::None) | None => result.push(Frame::Nil),
-                _ => return Err("WRONGTYPE Operation against a key holding the wrong kind of value".into())
+                Ok(Value::None) | Err(_) => result.push(Frame::Nil),
             }
         }
         Ok(Some(result.into()))
     }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
 }
\ No newline at end of file
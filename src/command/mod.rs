@@ -11,8 +11,66 @@ use crate::protocol::parse::Parse;
 use crate::server::shared::Shared;
 use crate::command::strlen::StrLen;
 use crate::command::mset::MSet;
+use crate::acl::category::*;
+use std::time::Duration;
 
+use self::acl::Acl;
+use self::auth::Auth;
+use self::hello::Hello;
+use self::eval::Eval;
+use self::script::Script;
+use self::fcall::FCall;
+use self::function::Function;
+use self::client::Client;
+use self::shutdown::Shutdown;
+use self::append::Append;
+use self::getrange::GetRange;
+use self::setrange::SetRange;
+use self::getset::GetSet;
+use self::getdel::GetDel;
+use self::getex::GetEx;
+use self::setnx::SetNx;
+use self::setex::SetEx;
+use self::msetnx::MSetNx;
+use self::setbit::SetBit;
+use self::getbit::GetBit;
+use self::bitcount::BitCount;
+use self::bitpos::BitPos;
+use self::bitop::BitOp;
+use self::bitfield::BitField;
+use self::lcs::Lcs;
+use self::pfadd::PfAdd;
+use self::pfcount::PfCount;
+use self::pfmerge::PfMerge;
+use self::geoadd::GeoAdd;
+use self::geodist::GeoDist;
+use self::geopos::GeoPos;
+use self::geohash::GeoHash;
+use self::geosearch::GeoSearch;
+use self::xadd::XAdd;
+use self::xrange::XRange;
+use self::xlen::XLen;
+use self::xdel::XDel;
+use self::xtrim::XTrim;
+use self::xread::XRead;
+use self::xgroup::XGroup;
+use self::xack::XAck;
+use self::xpending::XPending;
+use self::xclaim::XClaim;
+use self::xautoclaim::XAutoClaim;
+use self::xinfo::XInfo;
+use self::multi::Multi;
+use self::exec::Exec;
+use self::discard::Discard;
+use self::watch::Watch;
+use self::unwatch::Unwatch;
+use self::subscribe::Subscribe;
+use self::unsubscribe::Unsubscribe;
+use self::publish::Publish;
+use self::pubsub::PubSub;
+use self::config::Config;
 use self::incrby::IncrBy;
+use self::incrbyfloat::IncrByFloat;
 use self::keys::Keys;
 
 use self::scan::Scan;
@@ -29,11 +87,67 @@ pub mod exists;
 pub mod ttl;
 pub mod flushdb;
 pub mod incrby;
+pub mod incrbyfloat;
 pub mod mget;
 pub mod strlen;
 pub mod mset;
 pub mod keys;
 pub mod scan;
+pub mod config;
+pub mod auth;
+pub mod hello;
+pub mod eval;
+pub mod script;
+pub mod fcall;
+pub mod function;
+pub mod acl;
+pub mod client;
+pub mod shutdown;
+pub mod append;
+pub mod getrange;
+pub mod setrange;
+pub mod getset;
+pub mod getdel;
+pub mod getex;
+pub mod setnx;
+pub mod setex;
+pub mod msetnx;
+pub mod setbit;
+pub mod getbit;
+pub mod bitcount;
+pub mod bitpos;
+pub mod bitop;
+pub mod bitfield;
+pub mod lcs;
+pub mod pfadd;
+pub mod pfcount;
+pub mod pfmerge;
+pub mod geoadd;
+pub mod geodist;
+pub mod geopos;
+pub mod geohash;
+pub mod geosearch;
+pub mod xadd;
+pub mod xrange;
+pub mod xlen;
+pub mod xdel;
+pub mod xtrim;
+pub mod xread;
+pub mod xgroup;
+pub mod xack;
+pub mod xpending;
+pub mod xclaim;
+pub mod xautoclaim;
+pub mod xinfo;
+pub mod multi;
+pub mod exec;
+pub mod discard;
+pub mod watch;
+pub mod unwatch;
+pub mod subscribe;
+pub mod unsubscribe;
+pub mod publish;
+pub mod pubsub;
 
 
 #[derive(Debug)]
@@ -42,6 +156,10 @@ pub enum Command {
     SET(Set),
     DEL(Del),
     INCR(IncrBy),
+    INCRBY(IncrBy),
+    DECR(IncrBy),
+    DECRBY(IncrBy),
+    INCRBYFLOAT(IncrByFloat),
     PING,
     FLUSHDB(Flushdb),
     EXISTS(Exists),
@@ -55,11 +173,385 @@ pub enum Command {
     MSET(MSet),
     KEYS(Keys),
     SCAN(Scan),
+    CONFIG(Config),
+    AUTH(Auth),
+    ACL(Acl),
+    CLIENT(Client),
+    SHUTDOWN(Shutdown),
+    APPEND(Append),
+    GETRANGE(GetRange),
+    SETRANGE(SetRange),
+    GETSET(GetSet),
+    GETDEL(GetDel),
+    GETEX(GetEx),
+    SETNX(SetNx),
+    SETEX(SetEx),
+    PSETEX(SetEx),
+    MSETNX(MSetNx),
+    SETBIT(SetBit),
+    GETBIT(GetBit),
+    BITCOUNT(BitCount),
+    BITPOS(BitPos),
+    BITOP(BitOp),
+    BITFIELD(BitField),
+    #[allow(non_camel_case_types)]
+    BITFIELD_RO(BitField),
+    LCS(Lcs),
+    PFADD(PfAdd),
+    PFCOUNT(PfCount),
+    PFMERGE(PfMerge),
+    GEOADD(GeoAdd),
+    GEODIST(GeoDist),
+    GEOPOS(GeoPos),
+    GEOHASH(GeoHash),
+    GEOSEARCH(GeoSearch),
+    GEOSEARCHSTORE(GeoSearch),
+    XADD(XAdd),
+    XRANGE(XRange),
+    XREVRANGE(XRange),
+    XLEN(XLen),
+    XDEL(XDel),
+    XTRIM(XTrim),
+    XREAD(XRead),
+    XREADGROUP(XRead),
+    XGROUP(XGroup),
+    XACK(XAck),
+    XPENDING(XPending),
+    XCLAIM(XClaim),
+    XAUTOCLAIM(XAutoClaim),
+    XINFO(XInfo),
+    MULTI(Multi),
+    EXEC(Exec),
+    DISCARD(Discard),
+    WATCH(Watch),
+    UNWATCH(Unwatch),
+    SUBSCRIBE(Subscribe),
+    UNSUBSCRIBE(Unsubscribe),
+    PSUBSCRIBE(Subscribe),
+    PUNSUBSCRIBE(Unsubscribe),
+    PUBLISH(Publish),
+    PUBSUB(PubSub),
+    SSUBSCRIBE(Subscribe),
+    SUNSUBSCRIBE(Unsubscribe),
+    SPUBLISH(Publish),
+    HELLO(Hello),
+    EVAL(Eval),
+    EVALSHA(Eval),
+    #[allow(non_camel_case_types)]
+    EVAL_RO(Eval),
+    #[allow(non_camel_case_types)]
+    EVALSHA_RO(Eval),
+    SCRIPT(Script),
+    FCALL(FCall),
+    #[allow(non_camel_case_types)]
+    FCALL_RO(FCall),
+    FUNCTION(Function),
 }
 
+/// Every command with its ACL categories, see `acl::category`.
+pub static COMMAND_TABLE: &[(&str, u32)] = &[
+    ("get", READ | STRING | FAST),
+    ("set", WRITE | STRING | SLOW),
+    ("del", KEYSPACE | WRITE | SLOW),
+    ("incr", WRITE | STRING | FAST),
+    ("incrby", WRITE | STRING | FAST),
+    ("decr", WRITE | STRING | FAST),
+    ("decrby", WRITE | STRING | FAST),
+    ("incrbyfloat", WRITE | STRING | FAST),
+    ("ping", FAST | CONNECTION),
+    ("flushdb", KEYSPACE | WRITE | SLOW | DANGEROUS),
+    ("exists", KEYSPACE | READ | FAST),
+    ("info", SLOW | DANGEROUS),
+    ("ttl", KEYSPACE | READ | FAST),
+    ("pttl", KEYSPACE | READ | FAST),
+    ("select", FAST | CONNECTION),
+    ("mget", READ | STRING | FAST),
+    ("strlen", READ | STRING | FAST),
+    ("mset", WRITE | STRING | SLOW),
+    ("keys", KEYSPACE | READ | SLOW | DANGEROUS),
+    ("scan", KEYSPACE | READ | SLOW),
+    ("config", ADMIN | SLOW | DANGEROUS),
+    ("auth", FAST | CONNECTION),
+    ("acl", ADMIN | SLOW | DANGEROUS),
+    ("client", ADMIN | SLOW | DANGEROUS | CONNECTION),
+    ("shutdown", ADMIN | SLOW | DANGEROUS),
+    ("append", WRITE | STRING | FAST),
+    ("getrange", READ | STRING | SLOW),
+    ("setrange", WRITE | STRING | SLOW),
+    ("getset", WRITE | STRING | FAST),
+    ("getdel", WRITE | STRING | FAST),
+    ("getex", WRITE | STRING | FAST),
+    ("setnx", WRITE | STRING | FAST),
+    ("setex", WRITE | STRING | SLOW),
+    ("psetex", WRITE | STRING | SLOW),
+    ("msetnx", WRITE | STRING | SLOW),
+    ("setbit", WRITE | BITMAP | SLOW),
+    ("getbit", READ | BITMAP | FAST),
+    ("bitcount", READ | BITMAP | SLOW),
+    ("bitpos", READ | BITMAP | SLOW),
+    ("bitop", WRITE | BITMAP | SLOW),
+    ("bitfield", WRITE | BITMAP | SLOW),
+    ("bitfield_ro", READ | BITMAP | FAST),
+    ("lcs", READ | STRING | SLOW),
+    ("pfadd", WRITE | HYPERLOGLOG | FAST),
+    ("pfcount", READ | HYPERLOGLOG | SLOW),
+    ("pfmerge", WRITE | HYPERLOGLOG | SLOW),
+    ("geoadd", WRITE | GEO | SLOW),
+    ("geodist", READ | GEO | SLOW),
+    ("geopos", READ | GEO | SLOW),
+    ("geohash", READ | GEO | SLOW),
+    ("geosearch", READ | GEO | SLOW),
+    ("geosearchstore", WRITE | GEO | SLOW),
+    ("xadd", WRITE | STREAM | FAST),
+    ("xrange", READ | STREAM | SLOW),
+    ("xrevrange", READ | STREAM | SLOW),
+    ("xlen", READ | STREAM | FAST),
+    ("xdel", WRITE | STREAM | FAST),
+    ("xtrim", WRITE | STREAM | SLOW),
+    ("xread", READ | STREAM | SLOW | BLOCKING),
+    ("xreadgroup", WRITE | STREAM | SLOW | BLOCKING),
+    ("xgroup", WRITE | STREAM | SLOW),
+    ("xack", WRITE | STREAM | FAST),
+    ("xpending", READ | STREAM | SLOW),
+    ("xclaim", WRITE | STREAM | FAST),
+    ("xautoclaim", WRITE | STREAM | FAST),
+    ("xinfo", READ | STREAM | SLOW),
+    ("multi", FAST | TRANSACTION),
+    ("exec", SLOW | TRANSACTION),
+    ("discard", FAST | TRANSACTION),
+    ("watch", FAST | TRANSACTION),
+    ("unwatch", FAST | TRANSACTION),
+    ("subscribe", PUBSUB | SLOW),
+    ("unsubscribe", PUBSUB | SLOW),
+    ("psubscribe", PUBSUB | SLOW),
+    ("punsubscribe", PUBSUB | SLOW),
+    ("publish", PUBSUB | FAST),
+    ("pubsub", PUBSUB | SLOW),
+    ("ssubscribe", PUBSUB | SLOW),
+    ("sunsubscribe", PUBSUB | SLOW),
+    ("spublish", PUBSUB | FAST),
+    ("hello", FAST | CONNECTION),
+    ("eval", SLOW | SCRIPTING),
+    ("evalsha", SLOW | SCRIPTING),
+    ("eval_ro", SLOW | SCRIPTING),
+    ("evalsha_ro", SLOW | SCRIPTING),
+    ("script", SLOW | SCRIPTING),
+    ("fcall", SLOW | SCRIPTING),
+    ("fcall_ro", SLOW | SCRIPTING),
+    ("function", SLOW | SCRIPTING),
+];
+
 impl Command {
 
-    pub async fn apply(self,shared :&mut Shared) -> crate::Result<Frame>{
+    pub fn name(&self) -> &str {
+        match self {
+            Command::GET(_) => "get",
+            Command::SET(_) => "set",
+            Command::DEL(_) => "del",
+            Command::INCR(_) => "incr",
+            Command::INCRBY(_) => "incrby",
+            Command::DECR(_) => "decr",
+            Command::DECRBY(_) => "decrby",
+            Command::INCRBYFLOAT(_) => "incrbyfloat",
+            Command::PING => "ping",
+            Command::FLUSHDB(_) => "flushdb",
+            Command::EXISTS(_) => "exists",
+            Command::INFO(_) => "info",
+            Command::TTL(_) => "ttl",
+            Command::PTTL(_) => "pttl",
+            Command::SELECT(_) => "select",
+            Command::MGET(_) => "mget",
+            Command::UNKNOWN(name, _) => name,
+            Command::STRLEN(_) => "strlen",
+            Command::MSET(_) => "mset",
+            Command::KEYS(_) => "keys",
+            Command::SCAN(_) => "scan",
+            Command::CONFIG(_) => "config",
+            Command::AUTH(_) => "auth",
+            Command::ACL(_) => "acl",
+            Command::CLIENT(_) => "client",
+            Command::SHUTDOWN(_) => "shutdown",
+            Command::APPEND(_) => "append",
+            Command::GETRANGE(_) => "getrange",
+            Command::SETRANGE(_) => "setrange",
+            Command::GETSET(_) => "getset",
+            Command::GETDEL(_) => "getdel",
+            Command::GETEX(_) => "getex",
+            Command::SETNX(_) => "setnx",
+            Command::SETEX(_) => "setex",
+            Command::PSETEX(_) => "psetex",
+            Command::MSETNX(_) => "msetnx",
+            Command::SETBIT(_) => "setbit",
+            Command::GETBIT(_) => "getbit",
+            Command::BITCOUNT(_) => "bitcount",
+            Command::BITPOS(_) => "bitpos",
+            Command::BITOP(_) => "bitop",
+            Command::BITFIELD(_) => "bitfield",
+            Command::BITFIELD_RO(_) => "bitfield_ro",
+            Command::LCS(_) => "lcs",
+            Command::PFADD(_) => "pfadd",
+            Command::PFCOUNT(_) => "pfcount",
+            Command::PFMERGE(_) => "pfmerge",
+            Command::GEOADD(_) => "geoadd",
+            Command::GEODIST(_) => "geodist",
+            Command::GEOPOS(_) => "geopos",
+            Command::GEOHASH(_) => "geohash",
+            Command::GEOSEARCH(_) => "geosearch",
+            Command::GEOSEARCHSTORE(_) => "geosearchstore",
+            Command::XADD(_) => "xadd",
+            Command::XRANGE(_) => "xrange",
+            Command::XREVRANGE(_) => "xrevrange",
+            Command::XLEN(_) => "xlen",
+            Command::XDEL(_) => "xdel",
+            Command::XTRIM(_) => "xtrim",
+            Command::XREAD(_) => "xread",
+            Command::XREADGROUP(_) => "xreadgroup",
+            Command::XGROUP(_) => "xgroup",
+            Command::XACK(_) => "xack",
+            Command::XPENDING(_) => "xpending",
+            Command::XCLAIM(_) => "xclaim",
+            Command::XAUTOCLAIM(_) => "xautoclaim",
+            Command::XINFO(_) => "xinfo",
+            Command::MULTI(_) => "multi",
+            Command::EXEC(_) => "exec",
+            Command::DISCARD(_) => "discard",
+            Command::WATCH(_) => "watch",
+            Command::UNWATCH(_) => "unwatch",
+            Command::SUBSCRIBE(_) => "subscribe",
+            Command::UNSUBSCRIBE(_) => "unsubscribe",
+            Command::PSUBSCRIBE(_) => "psubscribe",
+            Command::PUNSUBSCRIBE(_) => "punsubscribe",
+            Command::PUBLISH(_) => "publish",
+            Command::PUBSUB(_) => "pubsub",
+            Command::SSUBSCRIBE(_) => "ssubscribe",
+            Command::SUNSUBSCRIBE(_) => "sunsubscribe",
+            Command::SPUBLISH(_) => "spublish",
+            Command::HELLO(_) => "hello",
+            Command::EVAL(_) => "eval",
+            Command::EVALSHA(_) => "evalsha",
+            Command::EVAL_RO(_) => "eval_ro",
+            Command::EVALSHA_RO(_) => "evalsha_ro",
+            Command::SCRIPT(_) => "script",
+            Command::FCALL(_) => "fcall",
+            Command::FCALL_RO(_) => "fcall_ro",
+            Command::FUNCTION(_) => "function",
+        }
+    }
+
+    /// Records the user running a script or a function, the commands it calls being checked
+    /// against the ACL of that user.
+    pub fn set_script_user(&mut self, user: &str) {
+        match self {
+            Command::EVAL(eval) | Command::EVALSHA(eval) | Command::EVAL_RO(eval) | Command::EVALSHA_RO(eval) => {
+                eval.set_user(user)
+            }
+            Command::FCALL(fcall) | Command::FCALL_RO(fcall) => fcall.set_user(user),
+            _ => {}
+        }
+    }
+
+    /// The ACL categories of the command, 0 for unknown commands.
+    pub fn categories(&self) -> u32 {
+        let name = self.name();
+        COMMAND_TABLE
+            .iter()
+            .find(|(cmd, _)| *cmd == name)
+            .map_or(0, |(_, categories)| *categories)
+    }
+
+    /// How long the command may wait for data when it found none, `Some(None)` for no limit.
+    /// `None` for commands that never block.
+    pub fn block_timeout(&self) -> Option<Option<Duration>> {
+        match self {
+            Command::XREAD(xread) | Command::XREADGROUP(xread) => xread.block_timeout(),
+            _ => None,
+        }
+    }
+
+    /// A copy of a blocking command for the db worker to retry once its keys are written, with
+    /// what it waits for fixed at the state of the first attempt.
+    pub fn blocked_copy(&mut self, shared: &Shared) -> Option<Command> {
+        match self {
+            Command::XREAD(xread) => Some(Command::XREAD(xread.blocked_copy(shared))),
+            Command::XREADGROUP(xread) => Some(Command::XREADGROUP(xread.blocked_copy(shared))),
+            _ => None,
+        }
+    }
+
+    /// Whether the command writes, for EXEC whether one of the queued commands does.
+    pub fn is_write(&self) -> bool {
+        match self {
+            Command::EXEC(exec) => exec.is_write(),
+            // scripts may write, the read-only variants are refused writes
+            Command::EVAL(_) | Command::EVALSHA(_) | Command::FCALL(_) => true,
+            _ => self.categories() & WRITE != 0,
+        }
+    }
+
+    /// The keys the command reads or writes, checked against the ACL key patterns.
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            Command::GET(get) => get.keys(),
+            Command::SET(set) => set.keys(),
+            Command::DEL(del) => del.keys(),
+            Command::INCR(incrby) | Command::INCRBY(incrby) | Command::DECR(incrby) | Command::DECRBY(incrby) => {
+                incrby.keys()
+            }
+            Command::INCRBYFLOAT(incrbyfloat) => incrbyfloat.keys(),
+            Command::EXISTS(exists) => exists.keys(),
+            Command::TTL(ttl) | Command::PTTL(ttl) => ttl.keys(),
+            Command::MGET(mget) => mget.keys(),
+            Command::STRLEN(strlen) => strlen.keys(),
+            Command::MSET(mset) => mset.keys(),
+            Command::APPEND(append) => append.keys(),
+            Command::GETRANGE(getrange) => getrange.keys(),
+            Command::SETRANGE(setrange) => setrange.keys(),
+            Command::GETSET(getset) => getset.keys(),
+            Command::GETDEL(getdel) => getdel.keys(),
+            Command::GETEX(getex) => getex.keys(),
+            Command::SETNX(setnx) => setnx.keys(),
+            Command::SETEX(setex) | Command::PSETEX(setex) => setex.keys(),
+            Command::MSETNX(msetnx) => msetnx.keys(),
+            Command::SETBIT(setbit) => setbit.keys(),
+            Command::GETBIT(getbit) => getbit.keys(),
+            Command::BITCOUNT(bitcount) => bitcount.keys(),
+            Command::BITPOS(bitpos) => bitpos.keys(),
+            Command::BITOP(bitop) => bitop.keys(),
+            Command::BITFIELD(bitfield) | Command::BITFIELD_RO(bitfield) => bitfield.keys(),
+            Command::LCS(lcs) => lcs.keys(),
+            Command::PFADD(pfadd) => pfadd.keys(),
+            Command::PFCOUNT(pfcount) => pfcount.keys(),
+            Command::PFMERGE(pfmerge) => pfmerge.keys(),
+            Command::GEOADD(geoadd) => geoadd.keys(),
+            Command::GEODIST(geodist) => geodist.keys(),
+            Command::GEOPOS(geopos) => geopos.keys(),
+            Command::GEOHASH(geohash) => geohash.keys(),
+            Command::GEOSEARCH(geosearch) => geosearch.keys(),
+            Command::GEOSEARCHSTORE(geosearch) => geosearch.keys(),
+            Command::XADD(xadd) => xadd.keys(),
+            Command::XRANGE(xrange) => xrange.keys(),
+            Command::XREVRANGE(xrange) => xrange.keys(),
+            Command::XLEN(xlen) => xlen.keys(),
+            Command::XDEL(xdel) => xdel.keys(),
+            Command::XTRIM(xtrim) => xtrim.keys(),
+            Command::XREAD(xread) | Command::XREADGROUP(xread) => xread.keys(),
+            Command::XGROUP(xgroup) => xgroup.keys(),
+            Command::XACK(xack) => xack.keys(),
+            Command::XPENDING(xpending) => xpending.keys(),
+            Command::XCLAIM(xclaim) => xclaim.keys(),
+            Command::XAUTOCLAIM(xautoclaim) => xautoclaim.keys(),
+            Command::XINFO(xinfo) => xinfo.keys(),
+            Command::EXEC(exec) => exec.keys(),
+            Command::WATCH(watch) => watch.keys(),
+            Command::EVAL(eval) | Command::EVALSHA(eval) | Command::EVAL_RO(eval) | Command::EVALSHA_RO(eval) => {
+                eval.keys()
+            }
+            Command::FCALL(fcall) | Command::FCALL_RO(fcall) => fcall.keys(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn apply(self,shared :&mut Shared) -> crate::Result<Frame>{
         
         let result = match self {
             Command::UNKNOWN(cmd,_) => Ok(Some(Frame::Error(format!("ERR unknown command '{}'",cmd)))),
@@ -72,12 +564,80 @@ impl Command {
             Command::EXISTS(exists) => exists.apply(shared),
             Command::TTL(ttl) | Command::PTTL(ttl) => ttl.apply(shared),
             Command::FLUSHDB(flushdb) => flushdb.apply(shared),
-            Command::INCR(incrby) => incrby.apply(shared),
+            Command::INCR(incrby) | Command::INCRBY(incrby) | Command::DECR(incrby) | Command::DECRBY(incrby) => {
+                incrby.apply(shared)
+            }
+            Command::INCRBYFLOAT(incrbyfloat) => incrbyfloat.apply(shared),
             Command::MGET(mget) => mget.apply(shared),
             Command::STRLEN(strlen) => strlen.apply(shared),
             Command::MSET(meset) => meset.apply(shared),
             Command::KEYS(keys) => keys.apply(shared),
             Command::SCAN(scan) => scan.apply(shared),
+            Command::CONFIG(config) => config.apply(shared),
+            Command::APPEND(append) => append.apply(shared),
+            Command::GETRANGE(getrange) => getrange.apply(shared),
+            Command::SETRANGE(setrange) => setrange.apply(shared),
+            Command::GETSET(getset) => getset.apply(shared),
+            Command::GETDEL(getdel) => getdel.apply(shared),
+            Command::GETEX(getex) => getex.apply(shared),
+            Command::SETNX(setnx) => setnx.apply(shared),
+            Command::SETEX(setex) | Command::PSETEX(setex) => setex.apply(shared),
+            Command::MSETNX(msetnx) => msetnx.apply(shared),
+            Command::SETBIT(setbit) => setbit.apply(shared),
+            Command::GETBIT(getbit) => getbit.apply(shared),
+            Command::BITCOUNT(bitcount) => bitcount.apply(shared),
+            Command::BITPOS(bitpos) => bitpos.apply(shared),
+            Command::BITOP(bitop) => bitop.apply(shared),
+            Command::BITFIELD(bitfield) | Command::BITFIELD_RO(bitfield) => bitfield.apply(shared),
+            Command::LCS(lcs) => lcs.apply(shared),
+            Command::PFADD(pfadd) => pfadd.apply(shared),
+            Command::PFCOUNT(pfcount) => pfcount.apply(shared),
+            Command::PFMERGE(pfmerge) => pfmerge.apply(shared),
+            Command::GEOADD(geoadd) => geoadd.apply(shared),
+            Command::GEODIST(geodist) => geodist.apply(shared),
+            Command::GEOPOS(geopos) => geopos.apply(shared),
+            Command::GEOHASH(geohash) => geohash.apply(shared),
+            Command::GEOSEARCH(geosearch) => geosearch.apply(shared),
+            Command::GEOSEARCHSTORE(geosearch) => geosearch.apply(shared),
+            Command::XADD(xadd) => xadd.apply(shared),
+            Command::XRANGE(xrange) => xrange.apply(shared),
+            Command::XREVRANGE(xrange) => xrange.apply(shared),
+            Command::XLEN(xlen) => xlen.apply(shared),
+            Command::XDEL(xdel) => xdel.apply(shared),
+            Command::XTRIM(xtrim) => xtrim.apply(shared),
+            Command::XREAD(xread) | Command::XREADGROUP(xread) => xread.apply(shared),
+            Command::XGROUP(xgroup) => xgroup.apply(shared),
+            Command::XACK(xack) => xack.apply(shared),
+            Command::XPENDING(xpending) => xpending.apply(shared),
+            Command::XCLAIM(xclaim) => xclaim.apply(shared),
+            Command::XAUTOCLAIM(xautoclaim) => xautoclaim.apply(shared),
+            Command::XINFO(xinfo) => xinfo.apply(shared),
+            Command::WATCH(watch) => watch.apply(shared),
+            Command::UNWATCH(unwatch) => unwatch.apply(shared),
+            Command::PUBLISH(publish) | Command::SPUBLISH(publish) => publish.apply(shared),
+            Command::PUBSUB(pubsub) => pubsub.apply(shared),
+            Command::AUTH(_)
+            | Command::ACL(_)
+            | Command::CLIENT(_)
+            | Command::SHUTDOWN(_)
+            | Command::MULTI(_)
+            | Command::DISCARD(_)
+            | Command::SUBSCRIBE(_)
+            | Command::UNSUBSCRIBE(_)
+            | Command::PSUBSCRIBE(_)
+            | Command::PUNSUBSCRIBE(_)
+            | Command::SSUBSCRIBE(_)
+            | Command::SUNSUBSCRIBE(_)
+            | Command::HELLO(_) => Err(format!("ERR {} must be handled by the connection", self.name()).into()),
+            Command::EXEC(_)
+            | Command::EVAL(_)
+            | Command::EVALSHA(_)
+            | Command::EVAL_RO(_)
+            | Command::EVALSHA_RO(_)
+            | Command::SCRIPT(_)
+            | Command::FCALL(_)
+            | Command::FCALL_RO(_)
+            | Command::FUNCTION(_) => Err(format!("ERR {} must be handled by the db worker", self.name()).into()),
         };
 
         return match result{
@@ -91,4 +651,8 @@ impl Command {
 
 pub trait Execable {
     fn apply(self,shared :&mut Shared) -> crate::Result<Option<Frame>>;
+
+    fn keys(&self) -> Vec<&str> {
+        Vec::new()
+    }
 }
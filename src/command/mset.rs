@@ -1,4 +1,5 @@
-use crate::{protocol::{frame::{self, Frame}, parse::Parse, ParseError}, server::value::Value};
+use crate::protocol::{frame::{self, Frame}, parse::Parse, ParseError};
+use crate::server::notify;
 
 use super::Execable;
 
@@ -24,12 +25,23 @@ impl MSet {
 
 impl Execable for MSet {
     fn apply(self,shared :&mut crate::server::shared::Shared) -> crate::Result<Option<frame::Frame>> {
-        let mut pairs = self.pairs;
-        while pairs.len() != 0 && pairs.len() % 2 == 0 {
-            let value = Value::Bytes(pairs.pop().unwrap()); 
-            let key =String::from_utf8(pairs.pop().unwrap())?;
-            let _ = shared.set(&key, value, false);
+        let mut pairs = Vec::with_capacity(self.pairs.len() / 2);
+        let mut items = self.pairs.into_iter();
+        while let (Some(key), Some(value)) = (items.next(), items.next()) {
+            pairs.push((String::from_utf8(key)?, value));
+        }
+        shared.mset(&pairs, false)?;
+        for (key, _) in pairs.iter() {
+            shared.notify(notify::STRING, "set", key);
         }
         return Ok(Some(Frame::Str(b"OK".to_vec())))
     }
+
+    fn keys(&self) -> Vec<&str> {
+        self.pairs
+            .iter()
+            .step_by(2)
+            .map(|key| std::str::from_utf8(key).unwrap_or(""))
+            .collect()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,45 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct MSetNx {
+    pairs: Vec<(String, Vec<u8>)>,
+}
+
+impl MSetNx {
+    pub fn parse(mut parse: Parse) -> Result<MSetNx, ParseError> {
+        let frames = match parse.remaining() {
+            Ok(frames) if frames.len() % 2 == 0 => frames,
+            Ok(_) | Err(ParseError::EOF) => {
+                return Err("ERR wrong number of arguments for 'msetnx' command".into())
+            }
+            Err(e) => return Err(e),
+        };
+        let mut pairs = Vec::with_capacity(frames.len() / 2);
+        let mut frames = frames.into_iter();
+        while let (Some(key), Some(value)) = (frames.next(), frames.next()) {
+            pairs.push((key.into_string()?, value.into_vec()?));
+        }
+        Ok(MSetNx { pairs })
+    }
+}
+
+impl Execable for MSetNx {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        if !shared.mset(&self.pairs, true)? {
+            return Ok(Some(Frame::Integer(0)));
+        }
+        for (key, _) in self.pairs.iter() {
+            shared.notify(notify::STRING, "set", key);
+        }
+        Ok(Some(Frame::Integer(1)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.pairs.iter().map(|(key, _)| key.as_str()).collect()
+    }
+}
@@ -0,0 +1,14 @@
+use crate::protocol::{parse::Parse, ParseError};
+
+/// Starts queuing the commands of the connection until EXEC or DISCARD, see `Handler`.
+#[derive(Debug)]
+pub struct Multi {}
+
+impl Multi {
+    pub fn parse(mut parse: Parse) -> Result<Multi, ParseError> {
+        match parse.fin() {
+            Ok(_) => Ok(Multi {}),
+            Err(_) => Err("ERR wrong number of arguments for 'multi' command".into()),
+        }
+    }
+}
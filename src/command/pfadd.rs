@@ -0,0 +1,47 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{hll::Hll, notify, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct PfAdd {
+    key: String,
+    elements: Vec<Vec<u8>>,
+}
+
+impl PfAdd {
+    pub fn parse(mut parse: Parse) -> Result<PfAdd, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let elements = match parse.remaining_into_vec() {
+            Ok(elements) => elements,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(PfAdd { key, elements })
+    }
+}
+
+impl Execable for PfAdd {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        // creating the key counts as a change even without elements
+        let (mut hll, expire_at, mut changed) = match shared.get_string(&self.key)? {
+            Some((raw, expire_at)) => (Hll::decode(&raw)?, expire_at, false),
+            None => (Hll::new(), None, true),
+        };
+        for element in self.elements.iter() {
+            changed |= hll.add(element);
+        }
+        if changed {
+            let sparse_max = shared.config().props().hll_sparse_max_bytes;
+            shared.put_string(&self.key, &hll.encode(sparse_max), expire_at)?;
+            shared.notify(notify::STRING, "pfadd", &self.key);
+        }
+        Ok(Some(Frame::Integer(changed as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
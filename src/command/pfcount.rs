@@ -0,0 +1,51 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{hll::Hll, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct PfCount {
+    keys: Vec<String>,
+}
+
+impl PfCount {
+    pub fn parse(parse: Parse) -> Result<PfCount, ParseError> {
+        let keys = parse.remaining_into_string_vec()?;
+        Ok(PfCount { keys })
+    }
+}
+
+impl Execable for PfCount {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        if let [key] = &self.keys[..] {
+            let (raw, expire_at) = match shared.get_string(key)? {
+                Some(value) => value,
+                None => return Ok(Some(Frame::Integer(0))),
+            };
+            let mut hll = Hll::decode(&raw)?;
+            let cached = hll.is_cached();
+            let count = hll.count();
+            if !cached {
+                // keeps the cardinality for the next count, like redis does
+                let sparse_max = shared.config().props().hll_sparse_max_bytes;
+                shared.put_string(key, &hll.encode(sparse_max), expire_at)?;
+            }
+            return Ok(Some(Frame::Integer(count as i64)));
+        }
+
+        // several keys count their union
+        let mut union = Hll::new();
+        for key in self.keys.iter() {
+            if let Some((raw, _)) = shared.get_string(key)? {
+                union.merge(&Hll::decode(&raw)?);
+            }
+        }
+        Ok(Some(Frame::Integer(union.count() as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
+}
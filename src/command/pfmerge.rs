@@ -0,0 +1,49 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{hll::Hll, notify, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct PfMerge {
+    dest: String,
+    sources: Vec<String>,
+}
+
+impl PfMerge {
+    pub fn parse(mut parse: Parse) -> Result<PfMerge, ParseError> {
+        let dest = parse.next()?.into_string()?;
+        let sources = match parse.remaining_into_string_vec() {
+            Ok(sources) => sources,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(PfMerge { dest, sources })
+    }
+}
+
+impl Execable for PfMerge {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        // the destination is one of the inputs, its expire time is kept
+        let (mut merged, expire_at) = match shared.get_string(&self.dest)? {
+            Some((raw, expire_at)) => (Hll::decode(&raw)?, expire_at),
+            None => (Hll::new(), None),
+        };
+        for key in self.sources.iter() {
+            if let Some((raw, _)) = shared.get_string(key)? {
+                merged.merge(&Hll::decode(&raw)?);
+            }
+        }
+        let sparse_max = shared.config().props().hll_sparse_max_bytes;
+        shared.put_string(&self.dest, &merged.encode(sparse_max), expire_at)?;
+        shared.notify(notify::STRING, "pfadd", &self.dest);
+        Ok(Some(Frame::Str(b"OK".to_vec())))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        let mut keys = vec![self.dest.as_str()];
+        keys.extend(self.sources.iter().map(String::as_str));
+        keys
+    }
+}
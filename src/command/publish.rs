@@ -0,0 +1,40 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+/// PUBLISH, or SPUBLISH to a shard channel with `shard`.
+#[derive(Debug)]
+pub struct Publish {
+    channel: Vec<u8>,
+    message: Vec<u8>,
+    shard: bool,
+}
+
+impl Publish {
+    pub fn parse(mut parse: Parse, shard: bool) -> Result<Publish, ParseError> {
+        let wrong_args = || -> ParseError {
+            let name = if shard { "spublish" } else { "publish" };
+            format!("ERR wrong number of arguments for '{}' command", name).into()
+        };
+        let mut args = parse.remaining_into_vec().map_err(|_| wrong_args())?;
+        if args.len() != 2 {
+            return Err(wrong_args());
+        }
+        let message = args.pop().unwrap();
+        let channel = args.pop().unwrap();
+        Ok(Publish { channel, message, shard })
+    }
+}
+
+impl Execable for Publish {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let received = match self.shard {
+            true => shared.pubsub().publish_shard(&self.channel, &self.message),
+            false => shared.pubsub().publish(&self.channel, &self.message),
+        };
+        Ok(Some(Frame::Integer(received as i64)))
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub enum PubSub {
+    /// the shard channels with `shard`
+    Channels { pattern: Option<Vec<u8>>, shard: bool },
+    NumSub { channels: Vec<Vec<u8>>, shard: bool },
+    NumPat,
+}
+
+impl PubSub {
+    pub fn parse(mut parse: Parse) -> Result<PubSub, ParseError> {
+        let sub = match parse.next() {
+            Ok(frame) => frame.into_string()?.to_lowercase(),
+            Err(ParseError::EOF) => return Err("ERR wrong number of arguments for 'pubsub' command".into()),
+            Err(e) => return Err(e),
+        };
+        let mut args = match parse.remaining_into_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        match (&sub[..], args.len()) {
+            ("channels" | "shardchannels", 0 | 1) => Ok(PubSub::Channels {
+                pattern: args.pop(),
+                shard: sub == "shardchannels",
+            }),
+            ("numsub" | "shardnumsub", _) => Ok(PubSub::NumSub {
+                channels: args,
+                shard: sub == "shardnumsub",
+            }),
+            ("numpat", 0) => Ok(PubSub::NumPat),
+            ("channels" | "shardchannels" | "numpat", _) => {
+                Err(format!("ERR wrong number of arguments for 'pubsub|{}' command", sub).into())
+            }
+            _ => Err(format!("ERR unknown subcommand '{}'. Try PUBSUB HELP.", sub).into()),
+        }
+    }
+}
+
+impl Execable for PubSub {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let registry = shared.pubsub();
+        let frame = match self {
+            PubSub::Channels { pattern, shard } => {
+                Frame::Array(registry.channels(shard, pattern.as_deref()).into_iter().map(Frame::Bulk).collect())
+            }
+            PubSub::NumSub { channels, shard } => Frame::Array(
+                channels
+                    .into_iter()
+                    .flat_map(|channel| {
+                        let count = registry.numsub(shard, &channel) as i64;
+                        [Frame::Bulk(channel), Frame::Integer(count)]
+                    })
+                    .collect(),
+            ),
+            PubSub::NumPat => Frame::Integer(registry.numpat() as i64),
+        };
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    fn parse(args: &[&str]) -> Result<PubSub, String> {
+        let frames = std::iter::once("pubsub")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::PUBSUB(pubsub)) => Ok(pubsub),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn subcommands() {
+        assert!(matches!(parse(&["channels"]), Ok(PubSub::Channels { pattern: None, shard: false })));
+        let channels = parse(&["SHARDCHANNELS", "news.*"]).unwrap();
+        assert!(matches!(channels, PubSub::Channels { pattern: Some(pattern), shard: true } if pattern == b"news.*"));
+        assert!(matches!(parse(&["numsub"]), Ok(PubSub::NumSub { channels, shard: false }) if channels.is_empty()));
+        assert!(matches!(parse(&["shardnumsub", "a", "b"]), Ok(PubSub::NumSub { channels, shard: true }) if channels.len() == 2));
+        assert!(matches!(parse(&["numpat"]), Ok(PubSub::NumPat)));
+
+        assert_eq!(parse(&["channels", "a", "b"]).unwrap_err(), "ERR wrong number of arguments for 'pubsub|channels' command");
+        assert_eq!(parse(&["numpat", "a"]).unwrap_err(), "ERR wrong number of arguments for 'pubsub|numpat' command");
+        assert_eq!(parse(&["help2"]).unwrap_err(), "ERR unknown subcommand 'help2'. Try PUBSUB HELP.");
+        assert_eq!(parse(&[]).unwrap_err(), "ERR wrong number of arguments for 'pubsub' command");
+    }
+}
@@ -0,0 +1,39 @@
+use crate::protocol::{parse::Parse, ParseError};
+
+/// SCRIPT, managing the script cache of the db worker. KILL is run by the connection, the db
+/// worker being busy with the script to kill.
+#[derive(Debug)]
+pub enum Script {
+    Load(String),
+    Exists(Vec<String>),
+    Flush,
+    Kill,
+}
+
+impl Script {
+    pub fn parse(mut parse: Parse) -> Result<Script, ParseError> {
+        let sub = match parse.next() {
+            Ok(frame) => frame.into_string()?.to_lowercase(),
+            Err(ParseError::EOF) => return Err("ERR wrong number of arguments for 'script' command".into()),
+            Err(e) => return Err(e),
+        };
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        match (&sub[..], args.len()) {
+            ("load", 1) => Ok(Script::Load(args.remove(0))),
+            ("exists", n) if n > 0 => Ok(Script::Exists(args.iter().map(|sha| sha.to_lowercase()).collect())),
+            ("flush", 0) => Ok(Script::Flush),
+            // scripts are dropped right away either way
+            ("flush", 1) if matches!(&args[0].to_lowercase()[..], "async" | "sync") => Ok(Script::Flush),
+            ("flush", 1) => Err("ERR SCRIPT FLUSH only support SYNC|ASYNC option".into()),
+            ("kill", 0) => Ok(Script::Kill),
+            ("load", _) | ("exists", _) | ("flush", _) | ("kill", _) => {
+                Err(format!("ERR wrong number of arguments for 'script|{}' command", sub).into())
+            }
+            _ => Err(format!("ERR unknown subcommand '{}'. Try SCRIPT HELP.", sub).into()),
+        }
+    }
+}
@@ -1,20 +1,26 @@
-
-
-
 use crate::{
     protocol::{frame::Frame, parse::Parse, ParseError},
-    server::value::Value,
+    server::{notify, shared::{SetCondition, Shared}, value::Value},
 };
 
-use super::Execable;
+use super::{getex::expire_at, Execable};
 
 #[derive(Debug)]
 pub struct Set {
     key: String,
     value: Frame,
-    expire_mill: Option<i64>,
-    nx: Option<()>,
-    xx: Option<()>,
+    expire: Expire,
+    condition: SetCondition,
+    /// Reply with the old value, the GET option.
+    get: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Expire {
+    None,
+    At(u64),
+    /// KEEPTTL
+    Keep,
 }
 
 impl Set {
@@ -22,80 +28,70 @@ impl Set {
         let parse = |mut parse: Parse| {
             let key = parse.next()?.into_string()?;
             let value = parse.next()?;
-            let mut expire_mill: Option<i64> = None;
-            let mut nx: Option<()> = None;
-            let mut xx: Option<()> = None;
+            let mut expire = Expire::None;
+            let mut condition = SetCondition::Always;
+            let mut get = false;
+            // the option that set `expire`, repeating it is fine but mixing units isn't
+            let mut unit: Option<String> = None;
 
             loop {
-                match parse.next() {
-                    Ok(frame) => {
-                        let word = match frame {
-                            Frame::Str(_) | Frame::Bulk(_) => frame.into_string(),
-                            _ => return Err("ERR syntax error".into()),
-                        };
+                let word = match parse.next() {
+                    Ok(frame @ (Frame::Str(_) | Frame::Bulk(_))) => frame.into_string()?,
+                    Ok(_) => return Err("ERR syntax error".into()),
+                    Err(ParseError::EOF) => break,
+                    Err(e) => return Err(e),
+                };
 
-                        if let Ok(kw) = word {
-                            let keyword = &kw.to_lowercase()[..];
-                            match keyword {
-                                "nx" => nx = Some(()),
-                                "xx" => xx = Some(()),
-                                "ex" | "px" => {
-                                    let next = match parse.next() {
-                                        Ok(next) => next,
-                                        Err(ParseError::EOF) => {
-                                            return Err("ERR syntax error".into())
-                                        }
-                                        Err(e) => return Err(e.into()),
-                                    };
-                                    let expire: i64 = match next.into_decimal() {
-                                        Ok(mut i) => {
-                                            if keyword == "ex" {
-                                                i *= 1000;
-                                            }
-                                            i
-                                        }
-                                        Err(_) => return Err("ERR syntax error".into()),
-                                    };
-                                    expire_mill = Some(expire)
-                                }
-                                _ => return Err("ERR syntax error".into()),
+                let keyword = &word.to_lowercase()[..];
+                match keyword {
+                    "nx" if condition != SetCondition::IfExists => condition = SetCondition::IfMissing,
+                    "xx" if condition != SetCondition::IfMissing => condition = SetCondition::IfExists,
+                    "get" => get = true,
+                    "keepttl" if unit.is_none() => expire = Expire::Keep,
+                    "ex" | "px" | "exat" | "pxat"
+                        if expire != Expire::Keep && !matches!(unit.as_deref(), Some(u) if u != keyword) =>
+                    {
+                        let next = match parse.next() {
+                            Ok(next) => next,
+                            Err(ParseError::EOF) => return Err("ERR syntax error".into()),
+                            Err(e) => return Err(e),
+                        };
+                        let time = match next.into_string()?.parse::<i64>() {
+                            Ok(time) => time,
+                            Err(_) => {
+                                return Err("ERR value is not an integer or out of range".into())
                             }
-                        } else {
-                            return Err("ERR syntax error".into());
-                        }
-                    }
-                    Err(ParseError::EOF) => {
-                        let set = Set {
-                            key,
-                            value,
-                            expire_mill,
-                            nx,
-                            xx,
                         };
-                        return Ok(set);
+                        expire = match expire_at(keyword, time) {
+                            Some(Ok(at)) => Expire::At(at),
+                            _ => return Err("ERR invalid expire time in 'set' command".into()),
+                        };
+                        unit = Some(keyword.to_string());
                     }
                     _ => return Err("ERR syntax error".into()),
                 }
             }
-        };
 
-        let parse_rs = parse(p);
+            Ok(Set {
+                key,
+                value,
+                expire,
+                condition,
+                get,
+            })
+        };
 
-        match parse_rs {
-            Ok(_) => return parse_rs,
+        match parse(p) {
             Err(ParseError::EOF) => {
                 return Err("ERR wrong number of arguments for 'set' command".into())
             }
-            Err(_) => return Err("ERR syntax error".into()),
+            parse_rs => parse_rs,
         }
     }
 }
 
 impl Execable for Set {
-    fn apply(
-        self,
-        shared: &mut crate::server::shared::Shared,
-    ) -> crate::Result<Option<crate::protocol::frame::Frame>> {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
         let value = self.value;
 
         let valueref = match value {
@@ -105,22 +101,161 @@ impl Execable for Set {
             _ => return Err("invalid data type".into()),
         };
 
-        let _ = match self.expire_mill {
-            Some(i@1..) => i,
-            Some(_) => return Err("ERR invalid expire time in set".into()),
-            _ => -1,
+        // with GET a key of another type fails the whole command before anything is written
+        let old = match self.get {
+            true => shared.get_string(&self.key)?.map(|(value, _)| value),
+            false => None,
         };
 
-        let set_result = shared.set(
-            &self.key,
-            valueref,
-            self.nx.is_some()
-        );
+        let expire_at = match self.expire {
+            Expire::None => None,
+            Expire::At(at) => Some(at),
+            Expire::Keep => shared.expire_time(&self.key)?,
+        };
 
-        match set_result {
-            Ok(Some(_)) => Ok(Some(Frame::Str(b"OK".to_vec()))),
-            Ok(None) => Ok(Some(Frame::Nil)),
-            Err(err) => Err(err),
+        let set_result = shared.set(&self.key, valueref, self.condition, expire_at)?;
+        if set_result.is_some() {
+            shared.notify(notify::STRING, "set", &self.key);
+            if let Expire::At(_) = self.expire {
+                shared.notify(notify::GENERIC, "expire", &self.key);
+            }
         }
+
+        match (self.get, old, set_result) {
+            (true, Some(old), _) => Ok(Some(Frame::Bulk(old))),
+            (true, None, _) => Ok(Some(Frame::Nil)),
+            (false, _, Some(_)) => Ok(Some(Frame::Str(b"OK".to_vec()))),
+            (false, _, None) => Ok(Some(Frame::Nil)),
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::{
+            testing::{run, show, with_shared},
+            WRONGTYPE,
+        },
+    };
+
+    fn parse(args: &[&str]) -> Result<Set, String> {
+        let frames = std::iter::once("set")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::SET(set)) => Ok(set),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn value(shared: &Shared, key: &str) -> Option<Vec<u8>> {
+        shared.get_string(key).unwrap().map(|(value, _)| value)
+    }
+
+    #[test]
+    fn nx_and_xx_conflict() {
+        assert_eq!(parse(&["k", "v", "nx", "xx"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["k", "v", "xx", "nx"]).unwrap_err(), "ERR syntax error");
+        assert!(parse(&["k", "v", "nx", "nx"]).is_ok());
+        assert!(parse(&["k", "v", "xx", "get"]).is_ok());
+    }
+
+    #[test]
+    fn keepttl_conflicts_with_every_expire_option() {
+        for option in ["ex", "px", "exat", "pxat"] {
+            assert_eq!(parse(&["k", "v", "keepttl", option, "100"]).unwrap_err(), "ERR syntax error");
+            assert_eq!(parse(&["k", "v", option, "100", "keepttl"]).unwrap_err(), "ERR syntax error");
+        }
+        assert!(parse(&["k", "v", "keepttl", "nx", "get"]).is_ok());
+    }
+
+    #[test]
+    fn expire_units_cannot_be_mixed() {
+        let options = ["ex", "px", "exat", "pxat"];
+        for first in options {
+            for second in options.iter().filter(|second| **second != first) {
+                let args = ["k", "v", first, "100", second, "100"];
+                assert_eq!(parse(&args).unwrap_err(), "ERR syntax error", "{} then {}", first, second);
+            }
+            assert!(parse(&["k", "v", first, "100", first, "200"]).is_ok());
+        }
+    }
+
+    #[test]
+    fn bad_expire_times() {
+        assert_eq!(parse(&["k", "v", "ex", "0"]).unwrap_err(), "ERR invalid expire time in 'set' command");
+        assert_eq!(parse(&["k", "v", "px", "-1"]).unwrap_err(), "ERR invalid expire time in 'set' command");
+        assert_eq!(parse(&["k", "v", "ex", "ten"]).unwrap_err(), "ERR value is not an integer or out of range");
+        assert_eq!(parse(&["k", "v", "ex"]).unwrap_err(), "ERR syntax error");
+    }
+
+    #[test]
+    fn get_replies_with_the_old_value() {
+        with_shared("set-get", |shared| {
+            assert_eq!(show(&run(shared, &["set", "k", "first", "get"])), "(nil)");
+            assert_eq!(value(shared, "k").unwrap(), b"first");
+            assert_eq!(show(&run(shared, &["set", "k", "second", "get"])), "\"first\"");
+            assert_eq!(value(shared, "k").unwrap(), b"second");
+        });
+    }
+
+    #[test]
+    fn get_fails_on_another_type_without_writing() {
+        with_shared("set-get-wrongtype", |shared| {
+            run(shared, &["geoadd", "k", "13.361389", "38.115556", "palermo"]);
+            assert_eq!(show(&run(shared, &["set", "k", "v", "get"])), format!("(error) {}", WRONGTYPE));
+            assert!(shared.get_string("k").is_err());
+        });
+    }
+
+    #[test]
+    fn nx_only_sets_missing_keys() {
+        with_shared("set-nx", |shared| {
+            assert_eq!(show(&run(shared, &["set", "k", "first", "nx"])), "OK");
+            assert_eq!(show(&run(shared, &["set", "k", "second", "nx"])), "(nil)");
+            assert_eq!(value(shared, "k").unwrap(), b"first");
+
+            // with GET the old value is replied whether or not it was replaced
+            assert_eq!(show(&run(shared, &["set", "k", "third", "nx", "get"])), "\"first\"");
+            assert_eq!(value(shared, "k").unwrap(), b"first");
+        });
+    }
+
+    #[test]
+    fn xx_only_sets_existing_keys() {
+        with_shared("set-xx", |shared| {
+            assert_eq!(show(&run(shared, &["set", "k", "first", "xx"])), "(nil)");
+            assert_eq!(value(shared, "k"), None);
+
+            run(shared, &["set", "k", "first"]);
+            assert_eq!(show(&run(shared, &["set", "k", "second", "xx"])), "OK");
+            assert_eq!(value(shared, "k").unwrap(), b"second");
+        });
+    }
+
+    #[test]
+    fn keepttl_keeps_the_expire_time() {
+        with_shared("set-keepttl", |shared| {
+            run(shared, &["set", "k", "first", "ex", "100"]);
+            let expire_at = shared.expire_time("k").unwrap();
+            assert!(expire_at.is_some());
+
+            run(shared, &["set", "k", "second", "keepttl"]);
+            assert_eq!(shared.expire_time("k").unwrap(), expire_at);
+            assert_eq!(value(shared, "k").unwrap(), b"second");
+
+            // without it SET makes the key persistent
+            run(shared, &["set", "k", "third"]);
+            assert_eq!(shared.expire_time("k").unwrap(), None);
+        });
     }
 }
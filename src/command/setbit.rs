@@ -0,0 +1,123 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::Execable;
+
+/// Bits past the largest string, 512MB, are out of range.
+pub(crate) const MAX_BIT_OFFSET: i64 = 512 * 1024 * 1024 * 8;
+
+#[derive(Debug)]
+pub struct SetBit {
+    key: String,
+    offset: usize,
+    value: bool,
+}
+
+impl SetBit {
+    pub fn parse(mut parse: Parse) -> Result<SetBit, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let offset = parse.next()?.into_string()?;
+        let value = parse.next()?.into_string()?;
+        if parse.fin().is_err() {
+            return Err("ERR wrong number of arguments for 'setbit' command".into());
+        }
+        let offset = bit_offset(&offset)?;
+        let value = match &value[..] {
+            "0" => false,
+            "1" => true,
+            _ => return Err("ERR bit is not an integer or out of range".into()),
+        };
+        Ok(SetBit { key, offset, value })
+    }
+}
+
+/// Parses the bit offset of a bit command.
+pub(crate) fn bit_offset(offset: &str) -> Result<usize, ParseError> {
+    match offset.parse::<i64>() {
+        Ok(offset) if (0..MAX_BIT_OFFSET).contains(&offset) => Ok(offset as usize),
+        _ => Err("ERR bit offset is not an integer or out of range".into()),
+    }
+}
+
+impl Execable for SetBit {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let byte = self.offset / 8;
+        let mask = 0x80u8 >> (self.offset % 8);
+        let current = shared.get_string_range(&self.key, byte, byte + 1)?.first().copied().unwrap_or(0);
+        let updated = if self.value { current | mask } else { current & !mask };
+        shared.set_string_range(&self.key, byte, &[updated])?;
+        shared.notify(notify::STRING, "setbit", &self.key);
+        Ok(Some(Frame::Integer((current & mask != 0) as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    /// The last bit of the first chunk of a chunked string.
+    const LAST_BIT: &str = "32767";
+
+    #[test]
+    fn bits_on_both_sides_of_a_chunk_boundary() {
+        with_shared("setbit-boundary", |shared| {
+            assert_eq!(show(&run(shared, &["setbit", "bits", LAST_BIT, "1"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["strlen", "bits"])), "(integer) 4096");
+            assert_eq!(show(&run(shared, &["setbit", "bits", "32768", "1"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["strlen", "bits"])), "(integer) 4097");
+            assert_eq!(show(&run(shared, &["setbit", "bits", LAST_BIT, "1"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["getbit", "bits", LAST_BIT])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["getbit", "bits", "32768"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["getbit", "bits", "32769"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["getbit", "bits", "1000000"])), "(integer) 0");
+        });
+    }
+
+    #[test]
+    fn bitcount_and_bitpos_across_a_chunk_boundary() {
+        with_shared("bitcount-boundary", |shared| {
+            run(shared, &["setbit", "bits", LAST_BIT, "1"]);
+            run(shared, &["setbit", "bits", "32768", "1"]);
+            assert_eq!(show(&run(shared, &["bitcount", "bits"])), "(integer) 2");
+            assert_eq!(show(&run(shared, &["bitcount", "bits", "4095", "4096"])), "(integer) 2");
+            assert_eq!(show(&run(shared, &["bitcount", "bits", "4096", "-1"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["bitcount", "bits", "32760", "32775", "bit"])), "(integer) 2");
+            assert_eq!(show(&run(shared, &["bitpos", "bits", "1"])), "(integer) 32767");
+            assert_eq!(show(&run(shared, &["bitpos", "bits", "1", "4096"])), "(integer) 32768");
+            assert_eq!(show(&run(shared, &["bitpos", "bits", "0"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["bitpos", "bits", "0", "4095", "4095"])), "(integer) 32760");
+        });
+    }
+
+    #[test]
+    fn bitfield_across_a_chunk_boundary() {
+        with_shared("bitfield-boundary", |shared| {
+            run(shared, &["setbit", "bits", LAST_BIT, "1"]);
+            run(shared, &["setbit", "bits", "32768", "1"]);
+            assert_eq!(show(&run(shared, &["bitfield", "bits", "get", "u2", LAST_BIT])), "[(integer) 3]");
+            let reply = run(shared, &["bitfield", "bits", "set", "u8", "32764", "255", "get", "u16", "32760"]);
+            assert_eq!(show(&reply), "[(integer) 24, (integer) 4080]");
+            assert_eq!(show(&run(shared, &["bitcount", "bits"])), "(integer) 8");
+        });
+    }
+
+    #[test]
+    fn setbit_on_a_long_plain_string() {
+        with_shared("setbit-long-string", |shared| {
+            let value = "a".repeat(5000);
+            run(shared, &["set", "bits", &value]);
+            // 'a' is 0b01100001
+            assert_eq!(show(&run(shared, &["setbit", "bits", "39999", "0"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["getbit", "bits", "39998"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["getbit", "bits", "32769"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["strlen", "bits"])), "(integer) 5000");
+            assert_eq!(show(&run(shared, &["bitcount", "bits"])), "(integer) 14999");
+        });
+    }
+}
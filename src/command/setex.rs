@@ -0,0 +1,47 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::{getex::expire_at, Execable};
+
+/// SETEX, or PSETEX when the time is given in milliseconds.
+#[derive(Debug)]
+pub struct SetEx {
+    key: String,
+    expire_at: u64,
+    value: Vec<u8>,
+}
+
+impl SetEx {
+    pub fn parse(mut parse: Parse, millis: bool) -> Result<SetEx, ParseError> {
+        let name = if millis { "psetex" } else { "setex" };
+        let key = parse.next()?.into_string()?;
+        let time = parse.next()?;
+        let value = parse.next()?.into_vec()?;
+        if parse.fin().is_err() {
+            return Err(format!("ERR wrong number of arguments for '{}' command", name).into());
+        }
+        let time = match time.into_decimal() {
+            Ok(time) => time,
+            Err(_) => return Err("ERR value is not an integer or out of range".into()),
+        };
+        match expire_at(if millis { "px" } else { "ex" }, time) {
+            Some(Ok(expire_at)) => Ok(SetEx { key, expire_at, value }),
+            _ => Err(format!("ERR invalid expire time in '{}' command", name).into()),
+        }
+    }
+}
+
+impl Execable for SetEx {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        shared.put_string(&self.key, &self.value, Some(self.expire_at))?;
+        shared.notify(notify::STRING, "set", &self.key);
+        shared.notify(notify::GENERIC, "expire", &self.key);
+        Ok(Some(Frame::Str(b"OK".to_vec())))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct SetNx {
+    key: String,
+    value: Vec<u8>,
+}
+
+impl SetNx {
+    pub fn parse(mut parse: Parse) -> Result<SetNx, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let value = parse.next()?.into_vec()?;
+        match parse.fin() {
+            Ok(_) => Ok(SetNx { key, value }),
+            Err(_) => Err("ERR wrong number of arguments for 'setnx' command".into()),
+        }
+    }
+}
+
+impl Execable for SetNx {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        if shared.is_exists(&self.key) {
+            return Ok(Some(Frame::Integer(0)));
+        }
+        shared.put_string(&self.key, &self.value, None)?;
+        shared.notify(notify::STRING, "set", &self.key);
+        Ok(Some(Frame::Integer(1)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared},
+};
+
+use super::Execable;
+
+/// The largest string SETRANGE may grow, 512MB like redis' proto-max-bulk-len.
+const MAX_STRING_LEN: i64 = 512 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct SetRange {
+    key: String,
+    offset: usize,
+    value: Vec<u8>,
+}
+
+impl SetRange {
+    pub fn parse(mut parse: Parse) -> Result<SetRange, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let offset = parse.next()?;
+        let value = parse.next()?.into_vec()?;
+        if parse.fin().is_err() {
+            return Err("ERR wrong number of arguments for 'setrange' command".into());
+        }
+        let offset = match offset.into_decimal() {
+            Ok(offset) if offset < 0 => return Err("ERR offset is out of range".into()),
+            Ok(offset) => offset,
+            Err(_) => return Err("ERR value is not an integer or out of range".into()),
+        };
+        if offset.checked_add(value.len() as i64).is_none_or(|end| end > MAX_STRING_LEN) {
+            return Err("ERR string exceeds maximum allowed size (proto-max-bulk-len)".into());
+        }
+        Ok(SetRange {
+            key,
+            offset: offset as usize,
+            value,
+        })
+    }
+}
+
+impl Execable for SetRange {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        // with nothing to write a missing key isn't created
+        let len = shared.set_string_range(&self.key, self.offset, &self.value)?;
+        if !self.value.is_empty() {
+            shared.notify(notify::STRING, "setrange", &self.key);
+        }
+        Ok(Some(Frame::Integer(len as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    #[test]
+    fn overwrites_and_pads() {
+        with_shared("setrange", |shared| {
+            // the examples of the SETRANGE documentation
+            run(shared, &["set", "key1", "Hello World"]);
+            assert_eq!(show(&run(shared, &["setrange", "key1", "6", "Redis"])), "(integer) 11");
+            assert_eq!(show(&run(shared, &["get", "key1"])), "\"Hello Redis\"");
+            assert_eq!(show(&run(shared, &["setrange", "key2", "6", "Redis"])), "(integer) 11");
+            assert_eq!(show(&run(shared, &["get", "key2"])), "\"\0\0\0\0\0\0Redis\"");
+
+            // nothing written creates nothing
+            assert_eq!(show(&run(shared, &["setrange", "key3", "10", ""])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["exists", "key3"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["setrange", "key1", "100", ""])), "(integer) 11");
+        });
+    }
+
+    #[test]
+    fn offsets() {
+        with_shared("setrange-offsets", |shared| {
+            assert_eq!(show(&run(shared, &["setrange", "k", "-1", "v"])), "(error) ERR offset is out of range");
+            assert_eq!(
+                show(&run(shared, &["setrange", "k", "536870911", "vv"])),
+                "(error) ERR string exceeds maximum allowed size (proto-max-bulk-len)"
+            );
+            assert_eq!(show(&run(shared, &["setrange", "k", "x", "v"])), "(error) ERR value is not an integer or out of range");
+        });
+    }
+}
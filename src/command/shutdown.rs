@@ -0,0 +1,111 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        scripting::Running,
+        shutdown::{ShutdownMode, ShutdownRequest},
+    },
+};
+
+#[derive(Debug)]
+pub enum Shutdown {
+    Stop(ShutdownMode),
+    Abort,
+}
+
+impl Shutdown {
+    pub fn parse(parse: Parse) -> Result<Shutdown, ParseError> {
+        let args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut mode = ShutdownMode::default();
+        let (mut save, mut nosave, mut abort) = (false, false, false);
+        for arg in args.iter() {
+            match &arg.to_lowercase()[..] {
+                "save" => save = true,
+                "nosave" => nosave = true,
+                "now" => mode.now = true,
+                // there are no replicas nor a failing save to force past
+                "force" => {}
+                "abort" => abort = true,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+        if (save && nosave) || (abort && args.len() > 1) {
+            return Err("ERR syntax error".into());
+        }
+        if abort {
+            return Ok(Shutdown::Abort);
+        }
+        mode.save = !nosave;
+        Ok(Shutdown::Stop(mode))
+    }
+
+    /// Hands the request to the server, `None` meaning the connection should just close.
+    /// NOSAVE aborts the running script, which could otherwise keep the server from stopping.
+    pub fn execute(self, request: &ShutdownRequest, running: &Running) -> Option<Frame> {
+        match self {
+            Shutdown::Stop(mode) => {
+                if !mode.save {
+                    running.abort();
+                }
+                request.request(mode);
+                None
+            }
+            // a shutdown starts as soon as it is requested, with no replicas to wait for there is
+            // never one in progress to abort
+            Shutdown::Abort => Some(Frame::Error("ERR No shutdown in progress.".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    fn parse(args: &[&str]) -> Result<Shutdown, String> {
+        let frames = std::iter::once("shutdown")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::SHUTDOWN(shutdown)) => Ok(shutdown),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// The `(save, now)` of a stopping SHUTDOWN.
+    fn mode(args: &[&str]) -> (bool, bool) {
+        match parse(args).unwrap() {
+            Shutdown::Stop(mode) => (mode.save, mode.now),
+            Shutdown::Abort => panic!("parsed as abort"),
+        }
+    }
+
+    #[test]
+    fn saves_by_default() {
+        assert_eq!(mode(&[]), (true, false));
+        assert_eq!(mode(&["SAVE"]), (true, false));
+        assert_eq!(mode(&["nosave"]), (false, false));
+        assert_eq!(mode(&["nosave", "now", "force"]), (false, true));
+    }
+
+    #[test]
+    fn conflicting_options() {
+        assert_eq!(parse(&["save", "nosave"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["abort", "now"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["nosave", "abort"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["later"]).unwrap_err(), "ERR syntax error");
+        assert!(matches!(parse(&["ABORT"]), Ok(Shutdown::Abort)));
+    }
+
+    #[test]
+    fn nothing_to_abort() {
+        let reply = parse(&["abort"]).unwrap().execute(&ShutdownRequest::default(), &Running::default());
+        assert!(matches!(reply, Some(Frame::Error(err)) if err == "ERR No shutdown in progress."));
+    }
+}
@@ -23,13 +23,11 @@ impl StrLen {
 
 impl Execable for StrLen {
     fn apply(self,shared :&mut crate::server::shared::Shared) -> crate::Result<Option<frame::Frame>> {
-        let key = &self.key;
-        let value_ref = shared.get(key);
-        if let Some(value) = value_ref {
-            let frame = value.frame()?;
-            return Ok(Some(Frame::Str(frame.len().to_string().into_bytes())));
-        }
-        return Ok(Some(Frame::Str(b"0".to_vec())))
+        let len = shared.string_len(&self.key)?;
+        return Ok(Some(Frame::Integer(len as i64)))
+    }
 
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
     }
 }
\ No newline at end of file
@@ -0,0 +1,28 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::pubsub::{Kind, Subscription},
+};
+
+/// SUBSCRIBE, PSUBSCRIBE or SSUBSCRIBE depending on `kind`, run by the connection.
+#[derive(Debug)]
+pub struct Subscribe {
+    names: Vec<Vec<u8>>,
+    kind: Kind,
+}
+
+impl Subscribe {
+    pub fn parse(mut parse: Parse, kind: Kind) -> Result<Subscribe, ParseError> {
+        match parse.remaining_into_vec() {
+            Ok(names) => Ok(Subscribe { names, kind }),
+            Err(ParseError::EOF) => {
+                Err(format!("ERR wrong number of arguments for '{}' command", kind.subscribe_command()).into())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replies one frame for each name subscribed to.
+    pub fn execute(self, subscription: &mut Subscription) -> Vec<Frame> {
+        subscription.subscribe(self.kind, self.names)
+    }
+}
@@ -31,8 +31,17 @@ impl Ttl {
 impl Execable for Ttl {
     fn apply(
         self,
-        _: &mut crate::server::shared::Shared,
+        shared: &mut crate::server::shared::Shared,
     ) -> crate::Result<Option<frame::Frame>> {
-        Ok(Some((-1).into()))
+        let pttl = shared.pttl(&self.key)?;
+        if self.px || pttl < 0 {
+            return Ok(Some(pttl.into()));
+        }
+        // round to the nearest second like redis
+        Ok(Some(((pttl + 500) / 1000).into()))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
     }
 }
@@ -0,0 +1,28 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::pubsub::{Kind, Subscription},
+};
+
+/// UNSUBSCRIBE, PUNSUBSCRIBE or SUNSUBSCRIBE depending on `kind`, run by the connection. No
+/// names unsubscribes from everything of the kind.
+#[derive(Debug)]
+pub struct Unsubscribe {
+    names: Vec<Vec<u8>>,
+    kind: Kind,
+}
+
+impl Unsubscribe {
+    pub fn parse(mut parse: Parse, kind: Kind) -> Result<Unsubscribe, ParseError> {
+        let names = match parse.remaining_into_vec() {
+            Ok(names) => names,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Unsubscribe { names, kind })
+    }
+
+    /// Replies one frame for each name unsubscribed from.
+    pub fn execute(self, subscription: &mut Subscription) -> Vec<Frame> {
+        subscription.unsubscribe(self.kind, self.names)
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+/// Forgets the keys watched by the connection, which fills them in. A queued UNWATCH has none,
+/// EXEC forgets them anyway.
+#[derive(Debug, Default)]
+pub struct Unwatch {
+    keys: Vec<String>,
+}
+
+impl Unwatch {
+    pub fn parse(mut parse: Parse) -> Result<Unwatch, ParseError> {
+        match parse.fin() {
+            Ok(_) => Ok(Unwatch::default()),
+            Err(_) => Err("ERR wrong number of arguments for 'unwatch' command".into()),
+        }
+    }
+
+    pub fn new(keys: Vec<String>) -> Unwatch {
+        Unwatch { keys }
+    }
+}
+
+impl Execable for Unwatch {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        shared.unwatch(&self.keys);
+        Ok(Some(Frame::Str(b"OK".to_vec())))
+    }
+}
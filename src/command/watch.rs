@@ -0,0 +1,81 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+/// Watches keys for the next EXEC of the connection. The db worker replies the versions of the
+/// keys, which the connection keeps and answers OK.
+#[derive(Debug)]
+pub struct Watch {
+    keys: Vec<String>,
+}
+
+impl Watch {
+    pub fn parse(parse: Parse) -> Result<Watch, ParseError> {
+        match parse.remaining_into_string_vec() {
+            Ok(keys) => Ok(Watch { keys }),
+            Err(ParseError::EOF) => Err("ERR wrong number of arguments for 'watch' command".into()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Execable for Watch {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let versions = shared.watch(&self.keys);
+        Ok(Some(Frame::Array(versions.into_iter().map(|version| Frame::Integer(version as i64)).collect())))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    fn watched(keys: &[(&str, u64)]) -> Vec<(String, u64)> {
+        keys.iter().map(|(key, version)| (key.to_string(), *version)).collect()
+    }
+
+    #[test]
+    fn arity() {
+        with_shared("watch-arity", |shared| {
+            assert_eq!(show(&run(shared, &["watch"])), "(error) ERR wrong number of arguments for 'watch' command");
+            assert_eq!(show(&run(shared, &["unwatch", "k"])), "(error) ERR wrong number of arguments for 'unwatch' command");
+        });
+    }
+
+    #[test]
+    fn writes_give_watched_keys_a_new_version() {
+        with_shared("watch-versions", |shared| {
+            assert_eq!(show(&run(shared, &["watch", "a", "b"])), "[(integer) 0, (integer) 0]");
+            run(shared, &["set", "a", "1"]);
+            assert!(shared.is_modified(&watched(&[("a", 0)])));
+            assert!(!shared.is_modified(&watched(&[("b", 0)])));
+            assert_eq!(show(&run(shared, &["watch", "a"])), "[(integer) 1]");
+            run(shared, &["del", "a"]);
+            assert!(shared.is_modified(&watched(&[("b", 0), ("a", 1)])));
+        });
+    }
+
+    #[test]
+    fn keys_are_tracked_while_a_connection_watches_them() {
+        with_shared("watch-connections", |shared| {
+            run(shared, &["set", "c", "1"]);
+            // writes before the watch don't count
+            assert_eq!(show(&run(shared, &["watch", "c"])), "[(integer) 0]");
+            run(shared, &["watch", "c"]);
+            shared.unwatch(&["c".to_string()]);
+            run(shared, &["set", "c", "2"]);
+            assert!(shared.is_modified(&watched(&[("c", 0)])));
+            shared.unwatch(&["c".to_string()]);
+            // forgotten keys read as unmodified
+            assert!(!shared.is_modified(&watched(&[("c", 0)])));
+            assert_eq!(show(&run(shared, &["watch", "c"])), "[(integer) 0]");
+        });
+    }
+}
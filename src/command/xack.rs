@@ -0,0 +1,40 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        shared::Shared,
+        stream::{StreamId, INVALID_ID},
+    },
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct XAck {
+    key: String,
+    group: String,
+    ids: Vec<StreamId>,
+}
+
+impl XAck {
+    pub fn parse(mut parse: Parse) -> Result<XAck, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let group = parse.next()?.into_string()?;
+        let ids = parse
+            .remaining_into_string_vec()?
+            .iter()
+            .map(|id| StreamId::parse(id, 0).ok_or(ParseError::from(INVALID_ID)))
+            .collect::<Result<_, _>>()?;
+        Ok(XAck { key, group, ids })
+    }
+}
+
+impl Execable for XAck {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let acked = shared.xack(&self.key, &self.group, &self.ids)?;
+        Ok(Some(Frame::Integer(acked as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        notify,
+        shared::Shared,
+        stream::{NewId, Trim, INVALID_ID},
+    },
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct XAdd {
+    key: String,
+    id: NewId,
+    fields: Vec<(Vec<u8>, Vec<u8>)>,
+    create: bool,
+    trim: Option<Trim>,
+}
+
+impl XAdd {
+    pub fn parse(mut parse: Parse) -> Result<XAdd, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let mut args = parse.remaining_into_vec()?;
+        let words: Vec<String> = args.iter().map(|arg| String::from_utf8_lossy(arg).into_owned()).collect();
+
+        let (mut create, mut trim) = (true, None);
+        let mut at = 0;
+        loop {
+            if matches!(words.get(at), Some(word) if word.eq_ignore_ascii_case("nomkstream")) {
+                create = false;
+                at += 1;
+                continue;
+            }
+            match Trim::parse(&words[at..])? {
+                Some((parsed, used)) => {
+                    trim = Some(parsed);
+                    at += used;
+                }
+                None => break,
+            }
+        }
+        let id = words.get(at).ok_or(ParseError::EOF)?;
+        let id = NewId::parse(id).ok_or(INVALID_ID)?;
+
+        let values = args.split_off(at + 1);
+        if values.is_empty() || values.len() % 2 != 0 {
+            return Err("ERR wrong number of arguments for 'xadd' command".into());
+        }
+        let mut values = values.into_iter();
+        let mut fields = Vec::with_capacity(values.len() / 2);
+        while let (Some(field), Some(value)) = (values.next(), values.next()) {
+            fields.push((field, value));
+        }
+        Ok(XAdd { key, id, fields, create, trim })
+    }
+}
+
+impl Execable for XAdd {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let id = shared.xadd(&self.key, self.id, &self.fields, self.create, self.trim)?;
+        if id.is_some() {
+            shared.notify(notify::STREAM, "xadd", &self.key);
+        }
+        Ok(id.map(|id| Frame::Bulk(id.to_string().into_bytes())))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::testing::{run, show, with_shared};
+
+    #[test]
+    fn entries_read_back_in_order() {
+        with_shared("xadd", |shared| {
+            assert_eq!(show(&run(shared, &["xadd", "s", "1-1", "a", "1"])), "\"1-1\"");
+            assert_eq!(show(&run(shared, &["xadd", "s", "1-*", "b", "2"])), "\"1-2\"");
+            assert_eq!(show(&run(shared, &["xadd", "s", "2", "c", "3", "d", "4"])), "\"2-0\"");
+            assert_eq!(show(&run(shared, &["xlen", "s"])), "(integer) 3");
+            let reply = run(shared, &["xrange", "s", "-", "+"]);
+            let all = "[[\"1-1\", [\"a\", \"1\"]], [\"1-2\", [\"b\", \"2\"]], [\"2-0\", [\"c\", \"3\", \"d\", \"4\"]]]";
+            assert_eq!(show(&reply), all);
+            assert_eq!(show(&run(shared, &["xrange", "s", "1", "1"])), "[[\"1-1\", [\"a\", \"1\"]], [\"1-2\", [\"b\", \"2\"]]]");
+            assert_eq!(show(&run(shared, &["xrange", "s", "(1-1", "+", "count", "1"])), "[[\"1-2\", [\"b\", \"2\"]]]");
+            assert_eq!(show(&run(shared, &["xrevrange", "s", "+", "-", "count", "1"])), "[[\"2-0\", [\"c\", \"3\", \"d\", \"4\"]]]");
+            assert_eq!(show(&run(shared, &["xrange", "s", "3", "+"])), "[]");
+        });
+    }
+
+    #[test]
+    fn ids_have_to_grow() {
+        with_shared("xadd-ids", |shared| {
+            run(shared, &["xadd", "s", "5-5", "a", "1"]);
+            let reply = run(shared, &["xadd", "s", "5-5", "a", "1"]);
+            assert_eq!(
+                show(&reply),
+                "(error) ERR The ID specified in XADD is equal or smaller than the target stream top item"
+            );
+            let reply = run(shared, &["xadd", "t", "0-0", "a", "1"]);
+            assert_eq!(show(&reply), "(error) ERR The ID specified in XADD must be greater than 0-0");
+            assert_eq!(show(&run(shared, &["xadd", "s", "*", "a"])), "(error) ERR wrong number of arguments for 'xadd' command");
+        });
+    }
+
+    #[test]
+    fn nomkstream_leaves_a_missing_stream_missing() {
+        with_shared("xadd-nomkstream", |shared| {
+            assert_eq!(show(&run(shared, &["xadd", "s", "nomkstream", "*", "a", "1"])), "(nil)");
+            assert_eq!(show(&run(shared, &["exists", "s"])), "(integer) 0");
+        });
+    }
+
+    #[test]
+    fn trimmed_while_adding_and_with_xtrim() {
+        with_shared("xadd-trim", |shared| {
+            for id in 1..=5 {
+                run(shared, &["xadd", "s", &id.to_string(), "n", &id.to_string()]);
+            }
+            run(shared, &["xadd", "s", "maxlen", "4", "6", "n", "6"]);
+            assert_eq!(show(&run(shared, &["xlen", "s"])), "(integer) 4");
+            // `~` trims up to LIMIT entries at a time
+            assert_eq!(show(&run(shared, &["xtrim", "s", "maxlen", "~", "1", "limit", "2"])), "(integer) 2");
+            assert_eq!(show(&run(shared, &["xrange", "s", "-", "+"])), "[[\"5-0\", [\"n\", \"5\"]], [\"6-0\", [\"n\", \"6\"]]]");
+            assert_eq!(show(&run(shared, &["xtrim", "s", "minid", "6"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["xtrim", "s", "maxlen", "~", "5"])), "(integer) 0");
+            assert_eq!(show(&run(shared, &["xlen", "s"])), "(integer) 1");
+        });
+    }
+
+    #[test]
+    fn deleted_entries_are_skipped() {
+        with_shared("xdel", |shared| {
+            for id in 1..=3 {
+                run(shared, &["xadd", "s", &id.to_string(), "n", &id.to_string()]);
+            }
+            assert_eq!(show(&run(shared, &["xdel", "s", "2", "4"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["xlen", "s"])), "(integer) 2");
+            assert_eq!(show(&run(shared, &["xrange", "s", "-", "+"])), "[[\"1-0\", [\"n\", \"1\"]], [\"3-0\", [\"n\", \"3\"]]]");
+            // IDs keep growing past deleted ones
+            assert!(show(&run(shared, &["xadd", "s", "3", "n", "3"])).starts_with("(error)"));
+        });
+    }
+}
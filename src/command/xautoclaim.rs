@@ -0,0 +1,88 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        shared::Shared,
+        stream::{Claim, StreamId},
+    },
+};
+
+use super::{
+    xclaim::min_idle,
+    xrange::{entry_frame, id_frame},
+    Execable,
+};
+
+const DEFAULT_COUNT: usize = 100;
+
+#[derive(Debug)]
+pub struct XAutoClaim {
+    key: String,
+    group: String,
+    consumer: String,
+    start: StreamId,
+    count: usize,
+    claim: Claim,
+}
+
+impl XAutoClaim {
+    pub fn parse(mut parse: Parse) -> Result<XAutoClaim, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let group = parse.next()?.into_string()?;
+        let consumer = parse.next()?.into_string()?;
+        let min_idle = min_idle(&parse.next()?.into_string()?, "XAUTOCLAIM")?;
+        let start = StreamId::parse_bound(&parse.next()?.into_string()?, true)?;
+
+        let mut claim = Claim {
+            min_idle,
+            ..Claim::default()
+        };
+        let mut count = DEFAULT_COUNT;
+        let mut args = match parse.remaining_into_string_vec() {
+            Ok(args) => args.into_iter(),
+            Err(ParseError::EOF) => Vec::new().into_iter(),
+            Err(e) => return Err(e),
+        };
+        while let Some(option) = args.next() {
+            match &option.to_lowercase()[..] {
+                "count" => {
+                    count = match args.next().map(|count| count.parse::<i64>()) {
+                        Some(Ok(count)) if count >= 1 => count as usize,
+                        Some(Ok(_)) => return Err("ERR COUNT must be > 0".into()),
+                        _ => return Err("ERR value is not an integer or out of range".into()),
+                    }
+                }
+                "justid" => claim.just_id = true,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+        Ok(XAutoClaim {
+            key,
+            group,
+            consumer,
+            start,
+            count,
+            claim,
+        })
+    }
+}
+
+impl Execable for XAutoClaim {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let (next, claimed, deleted) = shared
+            .xautoclaim(&self.key, &self.group, &self.consumer, self.start, self.count, self.claim)?
+            .ok_or_else(|| format!("NOGROUP No such key '{}' or consumer group '{}'", self.key, self.group))?;
+        let claimed = claimed
+            .into_iter()
+            .map(|(id, fields)| match self.claim.just_id {
+                true => id_frame(id),
+                false => entry_frame(id, fields),
+            })
+            .collect();
+        let deleted = deleted.into_iter().map(id_frame).collect();
+        Ok(Some(Frame::Array(vec![id_frame(next), Frame::Array(claimed), Frame::Array(deleted)])))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        shared::Shared,
+        stream::{Claim, StreamId, INVALID_ID},
+    },
+    utils,
+};
+
+use super::{
+    xrange::{entry_frame, id_frame},
+    Execable,
+};
+
+#[derive(Debug)]
+pub struct XClaim {
+    key: String,
+    group: String,
+    consumer: String,
+    ids: Vec<StreamId>,
+    claim: Claim,
+    last_id: Option<StreamId>,
+}
+
+/// The minimum idle time of XCLAIM and XAUTOCLAIM, negative ones counting as 0.
+pub(crate) fn min_idle(arg: &str, name: &str) -> Result<u64, ParseError> {
+    match arg.parse::<i64>() {
+        Ok(idle) => Ok(idle.max(0) as u64),
+        Err(_) => Err(format!("ERR Invalid min-idle-time argument for {}", name).into()),
+    }
+}
+
+impl XClaim {
+    pub fn parse(mut parse: Parse) -> Result<XClaim, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let group = parse.next()?.into_string()?;
+        let consumer = parse.next()?.into_string()?;
+        let min_idle = min_idle(&parse.next()?.into_string()?, "XCLAIM")?;
+        let mut args = parse.remaining_into_string_vec()?.into_iter().peekable();
+
+        // IDs come first, the options start at the first argument that isn't one
+        let mut ids = Vec::new();
+        while let Some(id) = args.peek().and_then(|id| StreamId::parse(id, 0)) {
+            ids.push(id);
+            args.next();
+        }
+        if ids.is_empty() {
+            return Err(INVALID_ID.into());
+        }
+
+        let now = utils::now_millis();
+        let mut claim = Claim {
+            min_idle,
+            ..Claim::default()
+        };
+        let mut last_id = None;
+        let number = |value: Option<String>| match value.map(|value| value.parse::<i64>()) {
+            Some(Ok(value)) => Ok(value),
+            _ => Err(ParseError::from("ERR value is not an integer or out of range")),
+        };
+        while let Some(option) = args.next() {
+            match &option.to_lowercase()[..] {
+                "idle" => claim.delivery_time = Some(now.saturating_sub(number(args.next())?.max(0) as u64)),
+                "time" => claim.delivery_time = Some(number(args.next())?.max(0) as u64),
+                "retrycount" => claim.retry_count = Some(number(args.next())?.max(0) as u64),
+                "force" => claim.force = true,
+                "justid" => claim.just_id = true,
+                "lastid" => {
+                    let id = args.next().ok_or("ERR syntax error")?;
+                    last_id = Some(StreamId::parse(&id, 0).ok_or(INVALID_ID)?);
+                }
+                _ => return Err(format!("ERR Unrecognized XCLAIM option '{}'", option).into()),
+            }
+        }
+        Ok(XClaim {
+            key,
+            group,
+            consumer,
+            ids,
+            claim,
+            last_id,
+        })
+    }
+}
+
+impl Execable for XClaim {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let claimed = shared
+            .xclaim(&self.key, &self.group, &self.consumer, &self.ids, self.claim, self.last_id)?
+            .ok_or_else(|| format!("NOGROUP No such key '{}' or consumer group '{}'", self.key, self.group))?;
+        let claimed = claimed
+            .into_iter()
+            .map(|(id, fields)| match self.claim.just_id {
+                true => id_frame(id),
+                false => entry_frame(id, fields),
+            })
+            .collect();
+        Ok(Some(Frame::Array(claimed)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,42 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        notify,
+        shared::Shared,
+        stream::{StreamId, INVALID_ID},
+    },
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct XDel {
+    key: String,
+    ids: Vec<StreamId>,
+}
+
+impl XDel {
+    pub fn parse(mut parse: Parse) -> Result<XDel, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let ids = parse
+            .remaining_into_string_vec()?
+            .iter()
+            .map(|id| StreamId::parse(id, 0).ok_or(ParseError::from(INVALID_ID)))
+            .collect::<Result<_, _>>()?;
+        Ok(XDel { key, ids })
+    }
+}
+
+impl Execable for XDel {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let deleted = shared.xdel(&self.key, &self.ids)?;
+        if deleted > 0 {
+            shared.notify(notify::STREAM, "xdel", &self.key);
+        }
+        Ok(Some(Frame::Integer(deleted as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        notify,
+        shared::Shared,
+        stream::{StreamId, INVALID_ID},
+    },
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub enum XGroup {
+    /// `id` `None` stands for `$`, the last entry.
+    Create {
+        key: String,
+        group: String,
+        id: Option<StreamId>,
+        mkstream: bool,
+        entries_read: Option<u64>,
+    },
+    SetId {
+        key: String,
+        group: String,
+        id: Option<StreamId>,
+        entries_read: Option<u64>,
+    },
+    Destroy {
+        key: String,
+        group: String,
+    },
+    CreateConsumer {
+        key: String,
+        group: String,
+        consumer: String,
+    },
+    DelConsumer {
+        key: String,
+        group: String,
+        consumer: String,
+    },
+}
+
+impl XGroup {
+    pub fn parse(mut parse: Parse) -> Result<XGroup, ParseError> {
+        let sub = match parse.next() {
+            Ok(frame) => frame.into_string()?.to_lowercase(),
+            Err(ParseError::EOF) => return Err("ERR wrong number of arguments for 'xgroup' command".into()),
+            Err(e) => return Err(e),
+        };
+        let wrong_args = || -> ParseError { format!("ERR wrong number of arguments for 'xgroup|{}' command", sub).into() };
+        let args = parse.remaining_into_string_vec().map_err(|_| wrong_args())?;
+
+        match (&sub[..], &args[..]) {
+            ("create", [key, group, id, options @ ..]) | ("setid", [key, group, id, options @ ..]) => {
+                let create = sub == "create";
+                let id = match &id[..] {
+                    "$" => None,
+                    id => Some(StreamId::parse(id, 0).ok_or(INVALID_ID)?),
+                };
+                let (mut mkstream, mut entries_read) = (false, None);
+                let mut options = options.iter();
+                while let Some(option) = options.next() {
+                    match &option.to_lowercase()[..] {
+                        "mkstream" if create => mkstream = true,
+                        "entriesread" => {
+                            entries_read = match options.next().map(|read| read.parse::<i64>()) {
+                                Some(Ok(read)) if read >= 0 => Some(read as u64),
+                                Some(Ok(-1)) => None,
+                                Some(Ok(_)) => {
+                                    return Err("ERR value for ENTRIESREAD must be positive or -1".into())
+                                }
+                                _ => return Err("ERR value is not an integer or out of range".into()),
+                            }
+                        }
+                        _ => return Err("ERR syntax error".into()),
+                    }
+                }
+                let (key, group) = (key.clone(), group.clone());
+                match create {
+                    true => Ok(XGroup::Create { key, group, id, mkstream, entries_read }),
+                    false => Ok(XGroup::SetId { key, group, id, entries_read }),
+                }
+            }
+            ("destroy", [key, group]) => Ok(XGroup::Destroy {
+                key: key.clone(),
+                group: group.clone(),
+            }),
+            ("createconsumer", [key, group, consumer]) => Ok(XGroup::CreateConsumer {
+                key: key.clone(),
+                group: group.clone(),
+                consumer: consumer.clone(),
+            }),
+            ("delconsumer", [key, group, consumer]) => Ok(XGroup::DelConsumer {
+                key: key.clone(),
+                group: group.clone(),
+                consumer: consumer.clone(),
+            }),
+            ("create" | "setid" | "destroy" | "createconsumer" | "delconsumer", _) => Err(wrong_args()),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try XGROUP HELP.", sub).into()),
+        }
+    }
+}
+
+pub(crate) fn no_group(key: &str, group: &str) -> crate::Error {
+    format!("NOGROUP No such consumer group '{}' for key name '{}'", group, key).into()
+}
+
+impl Execable for XGroup {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let mkstream = matches!(self, XGroup::Create { mkstream: true, .. });
+        if !mkstream && shared.stream_meta(self.keys()[0])?.is_none() {
+            return Err("ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to \
+                        use the MKSTREAM option to create an empty stream automatically."
+                .into());
+        }
+        match self {
+            XGroup::Create { key, group, id, entries_read, .. } => {
+                match shared.xgroup_create(&key, &group, id, entries_read)? {
+                    true => {
+                        shared.notify(notify::STREAM, "xgroup-create", &key);
+                        Ok(Some(Frame::Str(b"OK".to_vec())))
+                    }
+                    false => Err("BUSYGROUP Consumer Group name already exists".into()),
+                }
+            }
+            XGroup::SetId { key, group, id, entries_read } => match shared.xgroup_setid(&key, &group, id, entries_read)? {
+                true => {
+                    shared.notify(notify::STREAM, "xgroup-setid", &key);
+                    Ok(Some(Frame::Str(b"OK".to_vec())))
+                }
+                false => Err(no_group(&key, &group)),
+            },
+            XGroup::Destroy { key, group } => {
+                let destroyed = shared.xgroup_destroy(&key, &group)?;
+                if destroyed {
+                    shared.notify(notify::STREAM, "xgroup-destroy", &key);
+                }
+                Ok(Some(Frame::Integer(destroyed as i64)))
+            }
+            XGroup::CreateConsumer { key, group, consumer } => {
+                match shared.xgroup_create_consumer(&key, &group, &consumer)? {
+                    Some(created) => {
+                        if created {
+                            shared.notify(notify::STREAM, "xgroup-createconsumer", &key);
+                        }
+                        Ok(Some(Frame::Integer(created as i64)))
+                    }
+                    None => Err(no_group(&key, &group)),
+                }
+            }
+            XGroup::DelConsumer { key, group, consumer } => {
+                match shared.xgroup_del_consumer(&key, &group, &consumer)? {
+                    Some(pending) => {
+                        shared.notify(notify::STREAM, "xgroup-delconsumer", &key);
+                        Ok(Some(Frame::Integer(pending as i64)))
+                    }
+                    None => Err(no_group(&key, &group)),
+                }
+            }
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            XGroup::Create { key, .. }
+            | XGroup::SetId { key, .. }
+            | XGroup::Destroy { key, .. }
+            | XGroup::CreateConsumer { key, .. }
+            | XGroup::DelConsumer { key, .. } => vec![key],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::shared::{
+        testing::{run, show, with_shared},
+        Shared,
+    };
+
+    /// A stream `s` of entries 1-0 and 2-0 with a group `g` that got none of them yet.
+    fn with_group(name: &str, test: impl FnOnce(&mut Shared)) {
+        with_shared(name, |shared| {
+            run(shared, &["xadd", "s", "1", "a", "1"]);
+            run(shared, &["xadd", "s", "2", "b", "2"]);
+            assert_eq!(show(&run(shared, &["xgroup", "create", "s", "g", "0"])), "OK");
+            test(shared)
+        })
+    }
+
+    #[test]
+    fn entries_are_delivered_once_per_group() {
+        with_group("xreadgroup", |shared| {
+            let reply = run(shared, &["xreadgroup", "group", "g", "alice", "count", "1", "streams", "s", ">"]);
+            assert_eq!(show(&reply), "[[\"s\", [[\"1-0\", [\"a\", \"1\"]]]]]");
+            let reply = run(shared, &["xreadgroup", "group", "g", "bob", "streams", "s", ">"]);
+            assert_eq!(show(&reply), "[[\"s\", [[\"2-0\", [\"b\", \"2\"]]]]]");
+            assert_eq!(show(&run(shared, &["xreadgroup", "group", "g", "bob", "streams", "s", ">"])), "(nil)");
+            // another group starts over
+            run(shared, &["xgroup", "create", "s", "other", "0"]);
+            let reply = run(shared, &["xreadgroup", "group", "other", "carol", "count", "5", "streams", "s", ">"]);
+            assert_eq!(show(&reply), "[[\"s\", [[\"1-0\", [\"a\", \"1\"]], [\"2-0\", [\"b\", \"2\"]]]]]");
+        });
+    }
+
+    #[test]
+    fn acknowledged_entries_leave_the_pending_list() {
+        with_group("xack", |shared| {
+            run(shared, &["xreadgroup", "group", "g", "alice", "count", "1", "streams", "s", ">"]);
+            run(shared, &["xreadgroup", "group", "g", "bob", "streams", "s", ">"]);
+            let reply = run(shared, &["xpending", "s", "g"]);
+            assert_eq!(show(&reply), "[(integer) 2, \"1-0\", \"2-0\", [[\"alice\", \"1\"], [\"bob\", \"1\"]]]");
+            assert_eq!(show(&run(shared, &["xack", "s", "g", "1-0", "3-0"])), "(integer) 1");
+            assert_eq!(show(&run(shared, &["xack", "s", "g", "1-0"])), "(integer) 0");
+            let reply = run(shared, &["xpending", "s", "g"]);
+            assert_eq!(show(&reply), "[(integer) 1, \"2-0\", \"2-0\", [[\"bob\", \"1\"]]]");
+            run(shared, &["xack", "s", "g", "2-0"]);
+            assert_eq!(show(&run(shared, &["xpending", "s", "g"])), "[(integer) 0, (nil), (nil), (nil)]");
+        });
+    }
+
+    #[test]
+    fn noack_reads_leave_nothing_pending() {
+        with_group("xreadgroup-noack", |shared| {
+            run(shared, &["xreadgroup", "group", "g", "alice", "noack", "streams", "s", ">"]);
+            assert_eq!(show(&run(shared, &["xpending", "s", "g"])), "[(integer) 0, (nil), (nil), (nil)]");
+        });
+    }
+
+    #[test]
+    fn history_of_a_consumer() {
+        with_group("xreadgroup-history", |shared| {
+            run(shared, &["xreadgroup", "group", "g", "alice", "streams", "s", ">"]);
+            run(shared, &["xack", "s", "g", "1-0"]);
+            let reply = run(shared, &["xreadgroup", "group", "g", "alice", "streams", "s", "0"]);
+            assert_eq!(show(&reply), "[[\"s\", [[\"2-0\", [\"b\", \"2\"]]]]]");
+            // entries deleted since they were delivered have no fields
+            run(shared, &["xdel", "s", "2-0"]);
+            let reply = run(shared, &["xreadgroup", "group", "g", "alice", "streams", "s", "0"]);
+            assert_eq!(show(&reply), "[[\"s\", [[\"2-0\", (nil)]]]]");
+            // the history is replied even when empty
+            let reply = run(shared, &["xreadgroup", "group", "g", "bob", "streams", "s", "0"]);
+            assert_eq!(show(&reply), "[[\"s\", []]]");
+        });
+    }
+
+    #[test]
+    fn pending_entries_change_owner() {
+        with_group("xclaim", |shared| {
+            run(shared, &["xreadgroup", "group", "g", "alice", "streams", "s", ">"]);
+            let reply = run(shared, &["xclaim", "s", "g", "bob", "0", "1-0", "justid"]);
+            assert_eq!(show(&reply), "[\"1-0\"]");
+            let reply = run(shared, &["xautoclaim", "s", "g", "carol", "0", "0", "count", "10"]);
+            let claimed = "[\"0-0\", [[\"1-0\", [\"a\", \"1\"]], [\"2-0\", [\"b\", \"2\"]]], []]";
+            assert_eq!(show(&reply), claimed);
+            let reply = run(shared, &["xpending", "s", "g"]);
+            assert_eq!(show(&reply), "[(integer) 2, \"1-0\", \"2-0\", [[\"carol\", \"2\"]]]");
+            // and the idle time is far from any minimum
+            assert_eq!(show(&run(shared, &["xclaim", "s", "g", "bob", "3600000", "1-0"])), "[]");
+        });
+    }
+
+    #[test]
+    fn group_errors() {
+        with_group("xgroup-errors", |shared| {
+            let reply = run(shared, &["xgroup", "create", "s", "g", "$"]);
+            assert_eq!(show(&reply), "(error) BUSYGROUP Consumer Group name already exists");
+            let reply = run(shared, &["xgroup", "create", "missing", "g", "$"]);
+            assert!(show(&reply).starts_with("(error) ERR The XGROUP subcommand requires the key to exist."));
+            assert_eq!(show(&run(shared, &["xgroup", "create", "missing", "g", "$", "mkstream"])), "OK");
+            assert_eq!(show(&run(shared, &["xlen", "missing"])), "(integer) 0");
+            let reply = run(shared, &["xreadgroup", "group", "nope", "alice", "streams", "s", ">"]);
+            assert_eq!(
+                show(&reply),
+                "(error) NOGROUP No such key 's' or consumer group 'nope' in XREADGROUP with GROUP option"
+            );
+            let reply = run(shared, &["xgroup", "create", "s", "h", "0", "entriesread", "-2"]);
+            assert_eq!(show(&reply), "(error) ERR value for ENTRIESREAD must be positive or -1");
+        });
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        shared::Shared,
+        stream::{StreamId, StreamMeta},
+    },
+    utils,
+};
+
+use super::{
+    xgroup::no_group,
+    xrange::{entry_frame, id_frame},
+    Execable,
+};
+
+/// Entries and pending entries XINFO STREAM FULL lists when no COUNT is given.
+const DEFAULT_FULL_COUNT: usize = 10;
+
+#[derive(Debug)]
+pub enum XInfo {
+    /// `full` holds the number of entries FULL lists, `usize::MAX` for all of them.
+    Stream { key: String, full: Option<usize> },
+    Groups { key: String },
+    Consumers { key: String, group: String },
+}
+
+impl XInfo {
+    pub fn parse(mut parse: Parse) -> Result<XInfo, ParseError> {
+        let sub = match parse.next() {
+            Ok(frame) => frame.into_string()?.to_lowercase(),
+            Err(ParseError::EOF) => return Err("ERR wrong number of arguments for 'xinfo' command".into()),
+            Err(e) => return Err(e),
+        };
+        let wrong_args = || -> ParseError { format!("ERR wrong number of arguments for 'xinfo|{}' command", sub).into() };
+        let args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        match (&sub[..], &args[..]) {
+            ("stream", [key, options @ ..]) => {
+                let full = match options {
+                    [] => None,
+                    [full] if full.eq_ignore_ascii_case("full") => Some(DEFAULT_FULL_COUNT),
+                    [full, option, count] if full.eq_ignore_ascii_case("full") && option.eq_ignore_ascii_case("count") => {
+                        match count.parse::<i64>() {
+                            Ok(count) if count <= 0 => Some(usize::MAX),
+                            Ok(count) => Some(count as usize),
+                            Err(_) => return Err("ERR value is not an integer or out of range".into()),
+                        }
+                    }
+                    _ => return Err("ERR syntax error".into()),
+                };
+                Ok(XInfo::Stream { key: key.clone(), full })
+            }
+            ("groups", [key]) => Ok(XInfo::Groups { key: key.clone() }),
+            ("consumers", [key, group]) => Ok(XInfo::Consumers {
+                key: key.clone(),
+                group: group.clone(),
+            }),
+            ("stream" | "groups" | "consumers", _) => Err(wrong_args()),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try XINFO HELP.", sub).into()),
+        }
+    }
+}
+
+/// Field names each followed by their value.
+fn fields_frame(fields: Vec<(&str, Frame)>) -> Frame {
+    Frame::Array(
+        fields
+            .into_iter()
+            .flat_map(|(name, value)| [Frame::Bulk(name.as_bytes().to_vec()), value])
+            .collect(),
+    )
+}
+
+fn optional(value: Option<u64>) -> Frame {
+    value.map_or(Frame::Nil, |value| Frame::Integer(value as i64))
+}
+
+fn stream(shared: &Shared, key: &str, meta: StreamMeta, full: Option<usize>) -> crate::Result<Frame> {
+    let first_id = shared.first_entry_id(key)?;
+    let mut fields = vec![
+        ("length", Frame::Integer(meta.len as i64)),
+        ("last-generated-id", id_frame(meta.last_id)),
+        ("max-deleted-entry-id", id_frame(meta.max_deleted_id)),
+        ("entries-added", Frame::Integer(meta.entries_added as i64)),
+        ("recorded-first-entry-id", id_frame(first_id.unwrap_or(StreamId::MIN))),
+    ];
+    let groups = shared.stream_groups(key)?;
+    let count = match full {
+        Some(count) => count,
+        None => {
+            let edge = |rev| -> crate::Result<Frame> {
+                let entry = shared.xrange(key, StreamId::MIN, StreamId::MAX, Some(1), rev)?;
+                Ok(entry.into_iter().next().map_or(Frame::Nil, |(id, fields)| entry_frame(id, Some(fields))))
+            };
+            fields.push(("groups", Frame::Integer(groups.len() as i64)));
+            fields.push(("first-entry", edge(false)?));
+            fields.push(("last-entry", edge(true)?));
+            return Ok(fields_frame(fields));
+        }
+    };
+
+    let entries = shared.xrange(key, StreamId::MIN, StreamId::MAX, Some(count), false)?;
+    fields.push((
+        "entries",
+        Frame::Array(entries.into_iter().map(|(id, fields)| entry_frame(id, Some(fields))).collect()),
+    ));
+    let mut group_frames = Vec::with_capacity(groups.len());
+    for (name, group, _) in groups {
+        let pending = shared
+            .xpending(key, &name, StreamId::MIN, StreamId::MAX, count, None, 0)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, pending)| {
+                Frame::Array(vec![
+                    id_frame(id),
+                    Frame::Bulk(pending.consumer.into_bytes()),
+                    Frame::Integer(pending.delivery_time as i64),
+                    Frame::Integer(pending.delivery_count as i64),
+                ])
+            })
+            .collect();
+        let mut consumers = Vec::new();
+        for (consumer, consumer_meta) in shared.stream_consumers(key, &name)? {
+            let pending = shared
+                .xpending(key, &name, StreamId::MIN, StreamId::MAX, count, Some(&consumer), 0)?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(id, pending)| {
+                    Frame::Array(vec![
+                        id_frame(id),
+                        Frame::Integer(pending.delivery_time as i64),
+                        Frame::Integer(pending.delivery_count as i64),
+                    ])
+                })
+                .collect();
+            consumers.push(fields_frame(vec![
+                ("name", Frame::Bulk(consumer.into_bytes())),
+                ("seen-time", Frame::Integer(consumer_meta.seen_time as i64)),
+                ("active-time", consumer_meta.active_time.map_or(Frame::Integer(-1), |time| Frame::Integer(time as i64))),
+                ("pel-count", Frame::Integer(consumer_meta.pending as i64)),
+                ("pending", Frame::Array(pending)),
+            ]));
+        }
+        group_frames.push(fields_frame(vec![
+            ("name", Frame::Bulk(name.into_bytes())),
+            ("last-delivered-id", id_frame(group.last_id)),
+            ("entries-read", optional(group.entries_read)),
+            ("lag", optional(group.lag(&meta, first_id))),
+            ("pel-count", Frame::Integer(group.pending as i64)),
+            ("pending", Frame::Array(pending)),
+            ("consumers", Frame::Array(consumers)),
+        ]));
+    }
+    fields.push(("groups", Frame::Array(group_frames)));
+    Ok(fields_frame(fields))
+}
+
+impl Execable for XInfo {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let (_, meta) = shared.stream_meta(self.keys()[0])?.ok_or("ERR no such key")?;
+        match self {
+            XInfo::Stream { key, full } => Ok(Some(stream(shared, &key, meta, full)?)),
+            XInfo::Groups { key } => {
+                let first_id = shared.first_entry_id(&key)?;
+                let groups = shared
+                    .stream_groups(&key)?
+                    .into_iter()
+                    .map(|(name, group, consumers)| {
+                        fields_frame(vec![
+                            ("name", Frame::Bulk(name.into_bytes())),
+                            ("consumers", Frame::Integer(consumers as i64)),
+                            ("pending", Frame::Integer(group.pending as i64)),
+                            ("last-delivered-id", id_frame(group.last_id)),
+                            ("entries-read", optional(group.entries_read)),
+                            ("lag", optional(group.lag(&meta, first_id))),
+                        ])
+                    })
+                    .collect();
+                Ok(Some(Frame::Array(groups)))
+            }
+            XInfo::Consumers { key, group } => {
+                if shared.stream_group(&key, &group)?.is_none() {
+                    return Err(no_group(&key, &group));
+                }
+                let now = utils::now_millis();
+                let consumers = shared
+                    .stream_consumers(&key, &group)?
+                    .into_iter()
+                    .map(|(name, consumer)| {
+                        let inactive = consumer.active_time.map_or(-1, |time| now.saturating_sub(time) as i64);
+                        fields_frame(vec![
+                            ("name", Frame::Bulk(name.into_bytes())),
+                            ("pending", Frame::Integer(consumer.pending as i64)),
+                            ("idle", Frame::Integer(now.saturating_sub(consumer.seen_time) as i64)),
+                            ("inactive", Frame::Integer(inactive)),
+                        ])
+                    })
+                    .collect();
+                Ok(Some(Frame::Array(consumers)))
+            }
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            XInfo::Stream { key, .. } | XInfo::Groups { key } | XInfo::Consumers { key, .. } => vec![key],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::testing::{run, show, with_shared},
+    };
+
+    fn parse(args: &[&str]) -> Result<XInfo, String> {
+        let frames = std::iter::once("xinfo")
+            .chain(args.iter().copied())
+            .map(|arg| Frame::Bulk(arg.as_bytes().to_vec()))
+            .collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::XINFO(xinfo)) => Ok(xinfo),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn subcommands() {
+        assert!(matches!(parse(&["stream", "s"]), Ok(XInfo::Stream { full: None, .. })));
+        assert!(matches!(parse(&["STREAM", "s", "FULL"]), Ok(XInfo::Stream { full: Some(DEFAULT_FULL_COUNT), .. })));
+        assert!(matches!(parse(&["stream", "s", "full", "count", "3"]), Ok(XInfo::Stream { full: Some(3), .. })));
+        assert!(matches!(parse(&["stream", "s", "full", "count", "0"]), Ok(XInfo::Stream { full: Some(usize::MAX), .. })));
+        assert_eq!(parse(&["stream", "s", "full", "count", "x"]).unwrap_err(), "ERR value is not an integer or out of range");
+        assert_eq!(parse(&["stream", "s", "count", "3"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["stream"]).unwrap_err(), "ERR wrong number of arguments for 'xinfo|stream' command");
+        assert_eq!(parse(&["groups", "s", "g"]).unwrap_err(), "ERR wrong number of arguments for 'xinfo|groups' command");
+        assert_eq!(parse(&["consumers", "s"]).unwrap_err(), "ERR wrong number of arguments for 'xinfo|consumers' command");
+        assert_eq!(parse(&["nope"]).unwrap_err(), "ERR unknown subcommand 'nope'. Try XINFO HELP.");
+        assert_eq!(parse(&[]).unwrap_err(), "ERR wrong number of arguments for 'xinfo' command");
+    }
+
+    #[test]
+    fn stream_summary() {
+        with_shared("xinfo", |shared| {
+            assert_eq!(show(&run(shared, &["xinfo", "stream", "s"])), "(error) ERR no such key");
+            run(shared, &["xadd", "s", "1-1", "a", "1"]);
+            run(shared, &["xadd", "s", "2-1", "b", "2"]);
+            run(shared, &["xadd", "s", "3-1", "c", "3"]);
+            run(shared, &["xdel", "s", "3-1"]);
+            run(shared, &["xgroup", "create", "s", "g", "0"]);
+            run(shared, &["xreadgroup", "group", "g", "alice", "count", "1", "streams", "s", ">"]);
+            assert_eq!(
+                show(&run(shared, &["xinfo", "stream", "s"])),
+                "[\"length\", (integer) 2, \"last-generated-id\", \"3-1\", \"max-deleted-entry-id\", \"3-1\", \
+                 \"entries-added\", (integer) 3, \"recorded-first-entry-id\", \"1-1\", \"groups\", (integer) 1, \
+                 \"first-entry\", [\"1-1\", [\"a\", \"1\"]], \"last-entry\", [\"2-1\", [\"b\", \"2\"]]]"
+            );
+            let full = show(&run(shared, &["xinfo", "stream", "s", "full", "count", "1"]));
+            assert!(full.contains("\"entries\", [[\"1-1\", [\"a\", \"1\"]]], \"groups\", [[\"name\", \"g\", \"last-delivered-id\", \"1-1\""), "{}", full);
+            assert!(full.contains("\"consumers\", [[\"name\", \"alice\", \"seen-time\""), "{}", full);
+
+            let groups = show(&run(shared, &["xinfo", "groups", "s"]));
+            assert!(groups.starts_with("[[\"name\", \"g\", \"consumers\", (integer) 1, \"pending\", (integer) 1, \"last-delivered-id\", \"1-1\""), "{}", groups);
+            let consumers = show(&run(shared, &["xinfo", "consumers", "s", "g"]));
+            assert!(consumers.starts_with("[[\"name\", \"alice\", \"pending\", (integer) 1, \"idle\""), "{}", consumers);
+            assert!(show(&run(shared, &["xinfo", "consumers", "s", "nope"])).starts_with("(error) NOGROUP"));
+        });
+    }
+}
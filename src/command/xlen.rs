@@ -0,0 +1,30 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::shared::Shared,
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct XLen {
+    key: String,
+}
+
+impl XLen {
+    pub fn parse(mut parse: Parse) -> Result<XLen, ParseError> {
+        let key = parse.next()?.into_string()?;
+        parse.fin()?;
+        Ok(XLen { key })
+    }
+}
+
+impl Execable for XLen {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let len = shared.stream_meta(&self.key)?.map_or(0, |(_, meta)| meta.len);
+        Ok(Some(Frame::Integer(len as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{shared::Shared, stream::StreamId},
+    utils,
+};
+
+use super::{xrange::id_frame, Execable};
+
+#[derive(Debug)]
+pub struct XPending {
+    key: String,
+    group: String,
+    /// The extended form, `None` for the summary.
+    range: Option<PendingRange>,
+}
+
+#[derive(Debug)]
+struct PendingRange {
+    min_idle: u64,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: Option<String>,
+}
+
+impl XPending {
+    pub fn parse(mut parse: Parse) -> Result<XPending, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let group = parse.next()?.into_string()?;
+        let args = match parse.remaining_into_string_vec() {
+            Ok(args) => args,
+            Err(ParseError::EOF) => return Ok(XPending { key, group, range: None }),
+            Err(e) => return Err(e),
+        };
+        let (min_idle, args) = match &args[..] {
+            [option, idle, rest @ ..] if option.eq_ignore_ascii_case("idle") => match idle.parse::<i64>() {
+                Ok(idle) => (idle.max(0) as u64, rest),
+                Err(_) => return Err("ERR value is not an integer or out of range".into()),
+            },
+            _ => (0, &args[..]),
+        };
+        let (start, end, count, consumer) = match args {
+            [start, end, count] => (start, end, count, None),
+            [start, end, count, consumer] => (start, end, count, Some(consumer.clone())),
+            _ => return Err("ERR syntax error".into()),
+        };
+        let count = match count.parse::<i64>() {
+            Ok(count) => count.max(0) as usize,
+            Err(_) => return Err("ERR value is not an integer or out of range".into()),
+        };
+        let range = PendingRange {
+            min_idle,
+            start: StreamId::parse_bound(start, true)?,
+            end: StreamId::parse_bound(end, false)?,
+            count,
+            consumer,
+        };
+        Ok(XPending { key, group, range: Some(range) })
+    }
+}
+
+impl Execable for XPending {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let no_group = || format!("NOGROUP No such key '{}' or consumer group '{}'", self.key, self.group);
+        let range = match &self.range {
+            Some(range) => range,
+            None => {
+                let (count, bounds, consumers) = shared.xpending_summary(&self.key, &self.group)?.ok_or_else(no_group)?;
+                let (first, last) = match bounds {
+                    Some((first, last)) => (id_frame(first), id_frame(last)),
+                    None => (Frame::Nil, Frame::Nil),
+                };
+                let consumers = match consumers.is_empty() {
+                    true => Frame::Nil,
+                    false => Frame::Array(
+                        consumers
+                            .into_iter()
+                            .map(|(name, count)| {
+                                Frame::Array(vec![Frame::Bulk(name.into_bytes()), Frame::Bulk(count.to_string().into_bytes())])
+                            })
+                            .collect(),
+                    ),
+                };
+                return Ok(Some(Frame::Array(vec![Frame::Integer(count as i64), first, last, consumers])));
+            }
+        };
+
+        let pending = shared
+            .xpending(&self.key, &self.group, range.start, range.end, range.count, range.consumer.as_deref(), range.min_idle)?
+            .ok_or_else(no_group)?;
+        let now = utils::now_millis();
+        let pending = pending
+            .into_iter()
+            .map(|(id, pending)| {
+                Frame::Array(vec![
+                    id_frame(id),
+                    Frame::Bulk(pending.consumer.into_bytes()),
+                    Frame::Integer(now.saturating_sub(pending.delivery_time) as i64),
+                    Frame::Integer(pending.delivery_count as i64),
+                ])
+            })
+            .collect();
+        Ok(Some(Frame::Array(pending)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        shared::Shared,
+        stream::{Fields, StreamId},
+    },
+};
+
+use super::Execable;
+
+/// XRANGE, and XREVRANGE when `rev` is set.
+#[derive(Debug)]
+pub struct XRange {
+    key: String,
+    start: StreamId,
+    end: StreamId,
+    count: Option<usize>,
+    rev: bool,
+}
+
+impl XRange {
+    pub fn parse(mut parse: Parse, rev: bool) -> Result<XRange, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let first = parse.next()?.into_string()?;
+        let second = parse.next()?.into_string()?;
+        let (start, end) = if rev { (second, first) } else { (first, second) };
+        let start = StreamId::parse_bound(&start, true)?;
+        let end = StreamId::parse_bound(&end, false)?;
+
+        let count = match parse.remaining_into_string_vec() {
+            Err(ParseError::EOF) => None,
+            Err(e) => return Err(e),
+            Ok(args) => match &args[..] {
+                [option, count] if option.eq_ignore_ascii_case("count") => match count.parse::<i64>() {
+                    Ok(count) => Some(count.max(0) as usize),
+                    Err(_) => return Err("ERR value is not an integer or out of range".into()),
+                },
+                _ => return Err("ERR syntax error".into()),
+            },
+        };
+        Ok(XRange { key, start, end, count, rev })
+    }
+}
+
+pub(crate) fn id_frame(id: StreamId) -> Frame {
+    Frame::Bulk(id.to_string().into_bytes())
+}
+
+/// An entry as its ID followed by the flat list of its fields and values, nil for an entry deleted
+/// after it was delivered to a consumer group.
+pub(crate) fn entry_frame(id: StreamId, fields: Option<Fields>) -> Frame {
+    let fields = match fields {
+        Some(fields) => Frame::Array(
+            fields
+                .into_iter()
+                .flat_map(|(field, value)| [Frame::Bulk(field), Frame::Bulk(value)])
+                .collect(),
+        ),
+        None => Frame::Nil,
+    };
+    Frame::Array(vec![id_frame(id), fields])
+}
+
+impl Execable for XRange {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        if self.count == Some(0) {
+            return Ok(Some(Frame::Array(Vec::new())));
+        }
+        let entries = shared.xrange(&self.key, self.start, self.end, self.count, self.rev)?;
+        let entries = entries.into_iter().map(|(id, fields)| entry_frame(id, Some(fields))).collect();
+        Ok(Some(Frame::Array(entries)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
@@ -0,0 +1,284 @@
+use std::time::Duration;
+
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{
+        shared::Shared,
+        stream::{StreamId, INVALID_ID},
+    },
+};
+
+use super::{xrange::entry_frame, Execable};
+
+/// XREAD, and XREADGROUP when `group` holds the group and the consumer.
+#[derive(Debug, Clone)]
+pub struct XRead {
+    group: Option<(String, String)>,
+    no_ack: bool,
+    count: Option<usize>,
+    /// How long to wait for entries, zero for no limit.
+    block: Option<Duration>,
+    streams: Vec<(String, ReadFrom)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ReadFrom {
+    /// `$`, entries added from now on.
+    Last,
+    /// `>`, entries never delivered to the group.
+    New,
+    After(StreamId),
+}
+
+impl XRead {
+    pub fn parse(parse: Parse, group: bool) -> Result<XRead, ParseError> {
+        let name = if group { "xreadgroup" } else { "xread" };
+        let mut args = parse.remaining_into_string_vec()?.into_iter();
+        let (mut count, mut block) = (None, None);
+        let (mut group_consumer, mut no_ack) = (None, false);
+        let streams = loop {
+            let arg = args.next().ok_or("ERR syntax error")?;
+            match &arg.to_lowercase()[..] {
+                "count" => {
+                    count = match args.next().map(|count| count.parse::<i64>()) {
+                        // 0 reads everything, like no COUNT
+                        Some(Ok(count)) => Some(count.max(0) as usize).filter(|count| *count > 0),
+                        _ => return Err("ERR value is not an integer or out of range".into()),
+                    }
+                }
+                "block" => {
+                    block = match args.next().map(|ms| ms.parse::<i64>()) {
+                        Some(Ok(ms)) if ms < 0 => return Err("ERR timeout is negative".into()),
+                        Some(Ok(ms)) => Some(Duration::from_millis(ms as u64)),
+                        _ => return Err("ERR timeout is not an integer or out of range".into()),
+                    }
+                }
+                "group" if group => match (args.next(), args.next()) {
+                    (Some(group), Some(consumer)) => group_consumer = Some((group, consumer)),
+                    _ => return Err("ERR syntax error".into()),
+                },
+                "group" => {
+                    return Err("ERR The GROUP option is only supported by XREADGROUP. You called XREAD instead.".into())
+                }
+                "noack" if group => no_ack = true,
+                "streams" => break args.collect::<Vec<String>>(),
+                _ => return Err("ERR syntax error".into()),
+            }
+        };
+        if group && group_consumer.is_none() {
+            return Err("ERR Missing GROUP option for XREADGROUP".into());
+        }
+        if streams.is_empty() || streams.len() % 2 != 0 {
+            return Err(format!(
+                "ERR Unbalanced '{}' list of streams: for each stream key an ID or '$' must be specified.",
+                name
+            )
+            .into());
+        }
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+        let streams = keys
+            .iter()
+            .zip(ids)
+            .map(|(key, id)| {
+                let from = match &id[..] {
+                    "$" if group => {
+                        return Err(ParseError::from(
+                            "ERR The $ ID is meaningless in the context of XREADGROUP: you want to read the history \
+                             of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID \
+                             would just return an empty result set.",
+                        ))
+                    }
+                    "$" => ReadFrom::Last,
+                    ">" if group => ReadFrom::New,
+                    ">" => {
+                        return Err(ParseError::from(
+                            "ERR The > ID can be specified only when calling XREADGROUP using the GROUP <group> \
+                             <consumer> option.",
+                        ))
+                    }
+                    id => ReadFrom::After(StreamId::parse(id, 0).ok_or(INVALID_ID)?),
+                };
+                Ok((key.clone(), from))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(XRead {
+            group: group_consumer,
+            no_ack,
+            count,
+            block,
+            streams,
+        })
+    }
+
+    /// The time to wait for entries when BLOCK is given, `Some(None)` for no limit.
+    pub fn block_timeout(&self) -> Option<Option<Duration>> {
+        self.block.map(|block| Some(block).filter(|block| !block.is_zero()))
+    }
+
+    /// Resolves `$` to the last ID of each stream, so retries of a blocked read only return
+    /// entries added after it was first run, and returns a copy to retry with.
+    pub fn blocked_copy(&mut self, shared: &Shared) -> XRead {
+        for (key, from) in self.streams.iter_mut() {
+            if let ReadFrom::Last = from {
+                let last = shared.stream_meta(key).ok().flatten().map(|(_, meta)| meta.last_id);
+                *from = ReadFrom::After(last.unwrap_or(StreamId::MIN));
+            }
+        }
+        self.clone()
+    }
+
+    fn read_streams(&self, shared: &Shared) -> crate::Result<Vec<Frame>> {
+        let mut found = Vec::new();
+        for (key, from) in self.streams.iter() {
+            let start = match from {
+                ReadFrom::After(id) => id.next(),
+                _ => None,
+            };
+            let start = match start {
+                Some(start) => start,
+                None => continue,
+            };
+            let entries = shared.xrange(key, start, StreamId::MAX, self.count, false)?;
+            if entries.is_empty() {
+                continue;
+            }
+            let entries = entries.into_iter().map(|(id, fields)| entry_frame(id, Some(fields))).collect();
+            found.push(Frame::Array(vec![Frame::Bulk(key.as_bytes().to_vec()), Frame::Array(entries)]));
+        }
+        Ok(found)
+    }
+
+    fn read_group(&self, shared: &mut Shared, group: &str, consumer: &str) -> crate::Result<Vec<Frame>> {
+        // every group is checked before anything is delivered
+        for (key, _) in self.streams.iter() {
+            if shared.stream_group(key, group)?.is_none() {
+                return Err(format!(
+                    "NOGROUP No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                    key, group
+                )
+                .into());
+            }
+        }
+        let mut found = Vec::new();
+        for (key, from) in self.streams.iter() {
+            let after = match from {
+                ReadFrom::After(id) => Some(*id),
+                _ => None,
+            };
+            let entries = shared
+                .xreadgroup(key, group, consumer, after, self.count, self.no_ack)?
+                .unwrap_or_default();
+            // the history of the consumer is replied even when empty
+            if after.is_none() && entries.is_empty() {
+                continue;
+            }
+            let entries = entries.into_iter().map(|(id, fields)| entry_frame(id, fields)).collect();
+            found.push(Frame::Array(vec![Frame::Bulk(key.as_bytes().to_vec()), Frame::Array(entries)]));
+        }
+        Ok(found)
+    }
+}
+
+impl Execable for XRead {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let found = match &self.group {
+            Some((group, consumer)) => self.read_group(shared, group, consumer)?,
+            None => self.read_streams(shared)?,
+        };
+        // nothing read replies nil, which is what keeps a blocking read waiting
+        match found.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(Frame::Array(found))),
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.streams.iter().map(|(key, _)| key.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::Command,
+        server::shared::testing::{run, show, with_shared},
+    };
+
+    fn parse(args: &[&str]) -> Result<XRead, String> {
+        let frames = args.iter().map(|arg| Frame::Bulk(arg.as_bytes().to_vec())).collect();
+        match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(Command::XREAD(xread) | Command::XREADGROUP(xread)) => Ok(xread),
+            Ok(cmd) => panic!("parsed as {}", cmd.name()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn options() {
+        let xread = parse(&["xread", "count", "0", "block", "0", "streams", "a", "b", "0", "$"]).unwrap();
+        assert_eq!((xread.count, xread.block_timeout()), (None, Some(None)));
+        assert!(matches!(xread.streams[..], [(_, ReadFrom::After(StreamId::MIN)), (_, ReadFrom::Last)]));
+        let xread = parse(&["xread", "COUNT", "2", "BLOCK", "100", "STREAMS", "a", "1-1"]).unwrap();
+        assert_eq!((xread.count, xread.block_timeout()), (Some(2), Some(Some(Duration::from_millis(100)))));
+        assert_eq!(parse(&["xread", "streams", "a", "0"]).unwrap().block_timeout(), None);
+
+        assert_eq!(parse(&["xread", "count", "x", "streams", "a", "0"]).unwrap_err(), "ERR value is not an integer or out of range");
+        assert_eq!(parse(&["xread", "block", "-1", "streams", "a", "0"]).unwrap_err(), "ERR timeout is negative");
+        assert_eq!(parse(&["xread", "block", "x", "streams", "a", "0"]).unwrap_err(), "ERR timeout is not an integer or out of range");
+        assert_eq!(parse(&["xread", "count", "1"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(parse(&["xread", "noack", "streams", "a", "0"]).unwrap_err(), "ERR syntax error");
+        assert_eq!(
+            parse(&["xread", "streams", "a", "b", "0"]).unwrap_err(),
+            "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."
+        );
+        assert_eq!(parse(&["xread", "streams", "a", "x"]).unwrap_err(), INVALID_ID);
+        assert!(parse(&["xread", "streams", "a", ">"]).unwrap_err().starts_with("ERR The > ID can be specified only"));
+        assert!(parse(&["xread", "group", "g", "c", "streams", "a", ">"]).unwrap_err().starts_with("ERR The GROUP option"));
+    }
+
+    #[test]
+    fn group_options() {
+        let xread = parse(&["xreadgroup", "group", "g", "alice", "noack", "streams", "a", ">"]).unwrap();
+        assert_eq!(xread.group, Some(("g".to_string(), "alice".to_string())));
+        assert!(xread.no_ack && matches!(xread.streams[..], [(_, ReadFrom::New)]));
+        assert_eq!(parse(&["xreadgroup", "streams", "a", ">"]).unwrap_err(), "ERR Missing GROUP option for XREADGROUP");
+        assert_eq!(parse(&["xreadgroup", "group", "g"]).unwrap_err(), "ERR syntax error");
+        assert!(parse(&["xreadgroup", "group", "g", "c", "streams", "a", "$"]).unwrap_err().starts_with("ERR The $ ID is meaningless"));
+    }
+
+    #[test]
+    fn reads_after_the_ids() {
+        with_shared("xread", |shared| {
+            run(shared, &["xadd", "a", "1-1", "f", "1"]);
+            run(shared, &["xadd", "a", "2-1", "f", "2"]);
+            run(shared, &["xadd", "b", "3-1", "f", "3"]);
+            assert_eq!(
+                show(&run(shared, &["xread", "count", "1", "streams", "a", "b", "0", "0"])),
+                "[[\"a\", [[\"1-1\", [\"f\", \"1\"]]]], [\"b\", [[\"3-1\", [\"f\", \"3\"]]]]]"
+            );
+            // streams without anything after the ID are left out
+            assert_eq!(
+                show(&run(shared, &["xread", "streams", "a", "b", "1-1", "3-1"])),
+                "[[\"a\", [[\"2-1\", [\"f\", \"2\"]]]]]"
+            );
+            assert_eq!(show(&run(shared, &["xread", "streams", "a", "missing", "$", "0"])), "(nil)");
+        });
+    }
+
+    #[test]
+    fn blocked_reads_wait_for_entries_after_the_last_one() {
+        with_shared("xread-blocked", |shared| {
+            run(shared, &["xadd", "a", "1-1", "f", "1"]);
+            let mut xread = parse(&["xread", "block", "0", "streams", "a", "missing", "$", "$"]).unwrap();
+            let retry = xread.blocked_copy(shared);
+            assert!(matches!(retry.streams[..], [(_, ReadFrom::After(last)), (_, ReadFrom::After(StreamId::MIN))] if last == StreamId::parse("1-1", 0).unwrap()));
+            assert_eq!(show(&retry.clone().apply(shared).unwrap().unwrap_or(Frame::Nil)), "(nil)");
+            run(shared, &["xadd", "a", "2-1", "f", "2"]);
+            assert_eq!(
+                show(&retry.apply(shared).unwrap().unwrap_or(Frame::Nil)),
+                "[[\"a\", [[\"2-1\", [\"f\", \"2\"]]]]]"
+            );
+        });
+    }
+}
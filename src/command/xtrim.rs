@@ -0,0 +1,37 @@
+use crate::{
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{notify, shared::Shared, stream::Trim},
+};
+
+use super::Execable;
+
+#[derive(Debug)]
+pub struct XTrim {
+    key: String,
+    trim: Trim,
+}
+
+impl XTrim {
+    pub fn parse(mut parse: Parse) -> Result<XTrim, ParseError> {
+        let key = parse.next()?.into_string()?;
+        let args = parse.remaining_into_string_vec()?;
+        match Trim::parse(&args)? {
+            Some((trim, used)) if used == args.len() => Ok(XTrim { key, trim }),
+            _ => Err("ERR syntax error".into()),
+        }
+    }
+}
+
+impl Execable for XTrim {
+    fn apply(self, shared: &mut Shared) -> crate::Result<Option<Frame>> {
+        let removed = shared.xtrim(&self.key, self.trim)?;
+        if removed > 0 {
+            shared.notify(notify::STREAM, "xtrim", &self.key);
+        }
+        Ok(Some(Frame::Integer(removed as i64)))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![&self.key]
+    }
+}
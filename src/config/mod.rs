@@ -1,14 +1,57 @@
 use std::net::{SocketAddr, IpAddr};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{RwLock, RwLockReadGuard};
 
+use self::registry::{ConfigParam, PARAMS};
 
-#[derive(Debug)]
+pub mod registry;
+
+#[derive(Debug, Clone)]
 pub struct ServerProperties {
     pub bind: String,
     pub port: u16,
     pub append_only: bool,
     pub append_filename: String,
     pub max_clients: i32,
+    /// Seconds a client may stay idle before it is disconnected, 0 never.
+    pub timeout: u64,
+    /// Seconds between TCP keepalive probes on accepted sockets, 0 disables them.
+    pub tcp_keepalive: u64,
+    /// INCR and friends write a RocksDB merge operand instead of reading the counter first.
+    pub counter_merge: bool,
+    /// Past this size a sparse HyperLogLog is converted to the dense encoding.
+    pub hll_sparse_max_bytes: usize,
+    /// The keyspace notification classes published, see `server::notify`.
+    pub notify_keyspace_events: u16,
+    /// Milliseconds a script runs before the other clients are answered BUSY.
+    pub lua_time_limit: u64,
+    pub log_level: String,
+    pub require_pass: String,
+    pub acl_file: String,
+    pub tls_port: u16,
+    pub tls_cert_file: String,
+    pub tls_key_file: String,
+    pub tls_ca_cert_file: String,
+    /// `yes`, `no` or `optional`
+    pub tls_auth_clients: String,
+    pub unix_socket: String,
+    /// Octal permissions of the unix socket file, 0 keeps the umask default.
+    pub unix_socket_perm: u32,
+    pub rocksdb: RocksdbProperties,
+}
+
+/// RocksDB tunables, named after the option keys accepted by `DB::set_options`.
+#[derive(Debug, Clone)]
+pub struct RocksdbProperties {
+    pub write_buffer_size: u64,
+    pub max_write_buffer_number: i32,
+    pub level0_file_num_compaction_trigger: i32,
+    pub target_file_size_base: u64,
+    pub max_bytes_for_level_base: u64,
+    pub disable_auto_compactions: bool,
+    pub max_background_jobs: i32,
+    pub max_open_files: i32,
 }
 
 impl ServerProperties {
@@ -18,11 +61,440 @@ impl ServerProperties {
             port: 6379,
             append_only: false,
             append_filename: "./ldb_data".to_string(),
-            max_clients: 0,
+            max_clients: 10000,
+            timeout: 0,
+            tcp_keepalive: 300,
+            counter_merge: false,
+            hll_sparse_max_bytes: 3000,
+            notify_keyspace_events: 0,
+            lua_time_limit: 5000,
+            log_level: "debug".to_string(),
+            require_pass: String::new(),
+            acl_file: String::new(),
+            tls_port: 0,
+            tls_cert_file: String::new(),
+            tls_key_file: String::new(),
+            tls_ca_cert_file: String::new(),
+            tls_auth_clients: "yes".to_string(),
+            unix_socket: String::new(),
+            unix_socket_perm: 0,
+            rocksdb: RocksdbProperties {
+                write_buffer_size: 64 * 1024 * 1024,
+                max_write_buffer_number: 2,
+                level0_file_num_compaction_trigger: 4,
+                target_file_size_base: 64 * 1024 * 1024,
+                max_bytes_for_level_base: 256 * 1024 * 1024,
+                disable_auto_compactions: false,
+                max_background_jobs: 2,
+                max_open_files: -1,
+            },
+        }
+    }
+
+    /// The plain TCP address, `None` when `port` is 0 and only other listeners are used.
+    pub(crate) fn get_bind_addr(&self) -> Option<SocketAddr> {
+        match self.port {
+            0 => None,
+            port => Some(SocketAddr::new(IpAddr::from_str(self.bind.as_str()).unwrap(), port)),
+        }
+    }
+
+    /// The ACL file, by default `users.acl` next to the data directory.
+    pub(crate) fn get_acl_file(&self) -> PathBuf {
+        if !self.acl_file.is_empty() {
+            return PathBuf::from(&self.acl_file);
+        }
+        match Path::new(&self.append_filename).parent() {
+            Some(parent) => parent.join("users.acl"),
+            None => PathBuf::from("users.acl"),
+        }
+    }
+
+    pub(crate) fn get_tls_addr(&self) -> Option<SocketAddr> {
+        match self.tls_port {
+            0 => None,
+            port => Some(SocketAddr::new(IpAddr::from_str(self.bind.as_str()).unwrap(), port)),
+        }
+    }
+
+    pub(crate) fn get_unix_socket(&self) -> Option<PathBuf> {
+        match self.unix_socket.is_empty() {
+            true => None,
+            false => Some(PathBuf::from(&self.unix_socket)),
+        }
+    }
+
+    pub(crate) fn get_log_level(&self) -> log::LevelFilter {
+        parse_log_level(&self.log_level).unwrap_or(log::LevelFilter::Debug)
+    }
+}
+
+/// The runtime configuration registry, shared by the server and the db worker.
+///
+/// Values are read through `props()` and changed through `set()`, which validates
+/// every pair before touching the live properties.
+#[derive(Debug)]
+pub struct Config {
+    file: Option<PathBuf>,
+    props: RwLock<ServerProperties>,
+}
+
+impl Config {
+    /// Builds the configuration from the defaults, overridden by the directives of `file` if given.
+    pub fn load(file: Option<&str>) -> crate::Result<Config> {
+        let mut props = ServerProperties::new();
+
+        if let Some(file) = file {
+            let content = match std::fs::read_to_string(file) {
+                Ok(content) => content,
+                Err(err) => return Err(format!("failed to read config file {}, {}", file, err).into()),
+            };
+            for (i, line) in content.lines().enumerate() {
+                if line.trim_start().starts_with('#') {
+                    continue;
+                }
+                let args = match split_args(line) {
+                    Some(args) => args,
+                    None => return Err(format!("config file {} line {}: unbalanced quotes", file, i + 1).into()),
+                };
+                if args.is_empty() {
+                    continue;
+                }
+                let param = match find_param(&args[0]) {
+                    Some(param) if args.len() == 2 => param,
+                    _ => {
+                        return Err(format!(
+                            "config file {} line {}: bad directive or wrong number of arguments '{}'",
+                            file,
+                            i + 1,
+                            line
+                        )
+                        .into())
+                    }
+                };
+                if let Err(err) = (param.set)(&mut props, &args[1]) {
+                    return Err(format!("config file {} line {}: {}", file, i + 1, err).into());
+                }
+            }
+        }
+
+        Ok(Config {
+            file: file.map(PathBuf::from),
+            props: RwLock::new(props),
+        })
+    }
+
+    pub fn props(&self) -> RwLockReadGuard<'_, ServerProperties> {
+        self.props.read().unwrap()
+    }
+
+    /// Returns every `(name, value)` pair whose name matches one of the glob `patterns`.
+    pub fn get(&self, patterns: &[String]) -> Vec<(&'static str, String)> {
+        let props = self.props();
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_lowercase()).collect();
+        PARAMS
+            .iter()
+            .filter(|param| {
+                patterns
+                    .iter()
+                    .any(|p| crate::utils::backtrack_match(param.name.as_bytes(), p.as_bytes()))
+            })
+            .map(|param| (param.name, (param.get)(&props)))
+            .collect()
+    }
+
+    /// Applies all `pairs` or none of them, returning the properties as they were before.
+    pub fn set(&self, pairs: &[(String, String)]) -> Result<ServerProperties, String> {
+        let mut props = self.props.write().unwrap();
+        let mut updated = props.clone();
+        for (name, value) in pairs {
+            let param = match find_param(name) {
+                Some(param) => param,
+                None => {
+                    return Err(format!(
+                        "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                        name
+                    ))
+                }
+            };
+            if !param.modifiable {
+                return Err(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{}') - can't set immutable config",
+                    param.name
+                ));
+            }
+            if let Err(err) = (param.set)(&mut updated, value) {
+                return Err(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                    param.name, err
+                ));
+            }
+        }
+        Ok(std::mem::replace(&mut *props, updated))
+    }
+
+    /// Puts back properties returned by `set()` when applying them failed.
+    pub fn restore(&self, props: ServerProperties) {
+        *self.props.write().unwrap() = props;
+    }
+
+    /// Writes the current values back to the config file, keeping comments and line order.
+    pub fn rewrite(&self) -> crate::Result<()> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Err("ERR The server is running without a config file".into()),
+        };
+        let props = self.props();
+        let defaults = ServerProperties::new();
+
+        let content = match std::fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut written: Vec<&'static str> = Vec::new();
+        let mut lines = Vec::new();
+        for line in content.lines() {
+            let param = split_args(line)
+                .and_then(|args| args.into_iter().next())
+                .and_then(|name| find_param(&name));
+            match param {
+                Some(param) => {
+                    // later duplicates of a directive are dropped, the first one carries the value
+                    if !written.contains(&param.name) {
+                        written.push(param.name);
+                        lines.push(format_directive(param, &props));
+                    }
+                }
+                None => lines.push(line.to_string()),
+            }
+        }
+        for param in PARAMS.iter() {
+            if !written.contains(&param.name) && (param.get)(&props) != (param.get)(&defaults) {
+                lines.push(format_directive(param, &props));
+            }
+        }
+
+        let tmp = tmp_path(file);
+        std::fs::write(&tmp, lines.join("\n") + "\n")?;
+        std::fs::rename(&tmp, file)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn find_param(name: &str) -> Option<&'static ConfigParam> {
+    let name = name.to_lowercase();
+    PARAMS.iter().find(|param| param.name == name)
+}
+
+pub(crate) fn parse_log_level(level: &str) -> Option<log::LevelFilter> {
+    match &level.to_lowercase()[..] {
+        "debug" => Some(log::LevelFilter::Debug),
+        "verbose" | "notice" => Some(log::LevelFilter::Info),
+        "warning" => Some(log::LevelFilter::Warn),
+        "nothing" => Some(log::LevelFilter::Off),
+        _ => None,
+    }
+}
+
+/// Parses a size such as `1024`, `64kb` or `1gb`, the same units redis.conf accepts.
+pub(crate) fn parse_memory(value: &str) -> Result<u64, String> {
+    let lower = value.to_lowercase();
+    let split = lower.find(|c: char| !c.is_ascii_digit()).unwrap_or(lower.len());
+    let (num, unit) = lower.split_at(split);
+    let mul: u64 = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err(format!("argument must be a memory value, got '{}'", value)),
+    };
+    match num.parse::<u64>().ok().and_then(|n| n.checked_mul(mul)) {
+        Some(n) => Ok(n),
+        None => Err(format!("argument must be a memory value, got '{}'", value)),
+    }
+}
+
+pub(crate) fn parse_bool(value: &str) -> Result<bool, String> {
+    match &value.to_lowercase()[..] {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!("argument must be 'yes' or 'no', got '{}'", value)),
+    }
+}
+
+/// Splits a config line into arguments, honouring double and single quotes.
+/// Returns `None` when quotes are unbalanced.
+fn split_args(line: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    loop {
+        while let Some(c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let quote = match chars.peek() {
+            None => return Some(args),
+            Some('"') | Some('\'') => chars.next(),
+            Some(_) => None,
+        };
+        let mut arg = String::new();
+        loop {
+            match (chars.next(), quote) {
+                (None, Some(_)) => return None,
+                (None, None) => break,
+                (Some(c), Some(q)) if c == q => break,
+                (Some('\\'), Some('"')) => match chars.next() {
+                    Some('n') => arg.push('\n'),
+                    Some('t') => arg.push('\t'),
+                    Some(c) => arg.push(c),
+                    None => return None,
+                },
+                (Some(c), None) if c.is_whitespace() => break,
+                (Some(c), _) => arg.push(c),
+            }
         }
+        args.push(arg);
+    }
+}
+
+fn format_directive(param: &ConfigParam, props: &ServerProperties) -> String {
+    let value = (param.get)(props);
+    if value.is_empty() || value.contains(char::is_whitespace) || value.contains('"') {
+        format!("{} \"{}\"", param.name, value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        format!("{} {}", param.name, value)
+    }
+}
+
+fn tmp_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".rewrite");
+    file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::shared::testing::temporary_dir;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    /// Loads a config file of `content`, removed afterwards.
+    fn with_file<R>(content: &str, test: impl FnOnce(&Path, crate::Result<Config>) -> R) -> R {
+        let file = temporary_dir("config");
+        std::fs::write(&file, content).unwrap();
+        let result = test(&file, Config::load(Some(file.to_str().unwrap())));
+        let _ = std::fs::remove_file(&file);
+        result
+    }
+
+    #[test]
+    fn lines_split_into_arguments() {
+        assert_eq!(split_args("  port   6380 "), Some(strings(&["port", "6380"])));
+        assert_eq!(split_args("requirepass \"a b\""), Some(strings(&["requirepass", "a b"])));
+        assert_eq!(split_args("requirepass 'a \"b'"), Some(strings(&["requirepass", "a \"b"])));
+        assert_eq!(split_args(r#"x "tab\there\"""#), Some(strings(&["x", "tab\there\""])));
+        assert_eq!(split_args("x \"\""), Some(strings(&["x", ""])));
+        assert_eq!(split_args(""), Some(Vec::new()));
+        assert_eq!(split_args("x \"open"), None);
+        assert_eq!(split_args("x 'open"), None);
+    }
+
+    #[test]
+    fn memory_and_bool_values() {
+        assert_eq!(parse_memory("1024"), Ok(1024));
+        assert_eq!(parse_memory("1k"), Ok(1000));
+        assert_eq!(parse_memory("64KB"), Ok(64 * 1024));
+        assert_eq!(parse_memory("2gb"), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_memory("1tb").is_err());
+        assert!(parse_memory("kb").is_err());
+        assert!(parse_memory("99999999999gb").is_err());
+        assert_eq!(parse_bool("YES"), Ok(true));
+        assert_eq!(parse_bool("no"), Ok(false));
+        assert!(parse_bool("1").is_err());
     }
 
-    pub(crate) fn get_bind_addr(&self) -> SocketAddr {
-        SocketAddr::new(IpAddr::from_str(self.bind.as_str()).unwrap(), self.port)
+    #[test]
+    fn file_overrides_the_defaults() {
+        with_file("# a comment\nport 6380\n\nrequirepass \"with space\"\n", |_, config| {
+            let config = config.unwrap();
+            assert_eq!(config.props().port, 6380);
+            assert_eq!(config.props().require_pass, "with space");
+            assert_eq!(config.props().timeout, 0);
+        });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn bad_files_are_refused() {
+        with_file("port\n", |_, config| {
+            assert!(config.unwrap_err().to_string().contains("line 1: bad directive or wrong number of arguments"));
+        });
+        with_file("port 6380\nunknown 1\n", |_, config| {
+            assert!(config.unwrap_err().to_string().contains("line 2: bad directive"));
+        });
+        with_file("maxclients 0\n", |_, config| {
+            assert!(config.unwrap_err().to_string().contains("argument must be between 1 and 2147483647"));
+        });
+        with_file("requirepass \"open\n", |_, config| {
+            assert!(config.unwrap_err().to_string().contains("unbalanced quotes"));
+        });
+        assert!(Config::load(Some("/nonexistent/redis.conf")).is_err());
+    }
+
+    #[test]
+    fn get_matches_patterns() {
+        let config = Config::load(None).unwrap();
+        assert_eq!(config.get(&strings(&["PORT"])), vec![("port", "6379".to_string())]);
+        let names: Vec<_> = config.get(&strings(&["tls-*"])).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["tls-port", "tls-cert-file", "tls-key-file", "tls-ca-cert-file", "tls-auth-clients"]);
+        assert!(config.get(&strings(&["nothing*"])).is_empty());
+    }
+
+    #[test]
+    fn set_applies_all_pairs_or_none() {
+        let config = Config::load(None).unwrap();
+        let previous = config.set(&pairs(&[("timeout", "10"), ("loglevel", "WARNING")])).unwrap();
+        assert_eq!(previous.timeout, 0);
+        assert_eq!((config.props().timeout, config.props().log_level.as_str()), (10, "warning"));
+
+        let err = config.set(&pairs(&[("timeout", "20"), ("loglevel", "loud")])).unwrap_err();
+        assert!(err.starts_with("ERR CONFIG SET failed (possibly related to argument 'loglevel')"));
+        assert_eq!(config.props().timeout, 10);
+        let err = config.set(&pairs(&[("port", "1")])).unwrap_err();
+        assert!(err.ends_with("can't set immutable config"));
+        let err = config.set(&pairs(&[("nope", "1")])).unwrap_err();
+        assert_eq!(err, "ERR Unknown option or number of arguments for CONFIG SET - 'nope'");
+
+        config.restore(previous);
+        assert_eq!(config.props().timeout, 0);
+    }
+
+    #[test]
+    fn rewrite_keeps_comments_and_order() {
+        with_file("# first\ntimeout 5\n# second\ntimeout 6\nport 6380\n", |file, config| {
+            let config = config.unwrap();
+            config.set(&pairs(&[("timeout", "7"), ("requirepass", "a b")])).unwrap();
+            config.rewrite().unwrap();
+            let content = std::fs::read_to_string(file).unwrap();
+            assert_eq!(content, "# first\ntimeout 7\n# second\nport 6380\nrequirepass \"a b\"\n");
+            let reloaded = Config::load(file.to_str()).unwrap();
+            assert_eq!(reloaded.props().require_pass, "a b");
+        });
+        let err = Config::load(None).unwrap().rewrite().unwrap_err();
+        assert_eq!(err.to_string(), "ERR The server is running without a config file");
+    }
+}
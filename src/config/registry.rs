@@ -0,0 +1,349 @@
+use crate::config::{parse_bool, parse_log_level, parse_memory, ServerProperties};
+use crate::server::notify;
+
+/// How a changed parameter reaches the running server.
+#[derive(Debug, PartialEq)]
+pub enum Apply {
+    /// Read from the properties whenever it is needed.
+    Lazy,
+    LogLevel,
+    /// Forwarded to `DB::set_options`, the option key is the name without the `rocksdb.` prefix.
+    Rocksdb,
+}
+
+pub struct ConfigParam {
+    pub name: &'static str,
+    pub modifiable: bool,
+    pub apply: Apply,
+    pub get: fn(&ServerProperties) -> String,
+    pub set: fn(&mut ServerProperties, &str) -> Result<(), String>,
+}
+
+impl ConfigParam {
+    pub fn rocksdb_option(&self) -> Option<&'static str> {
+        match self.apply {
+            Apply::Rocksdb => Some(self.name.trim_start_matches("rocksdb.")),
+            _ => None,
+        }
+    }
+}
+
+pub static PARAMS: &[ConfigParam] = &[
+    ConfigParam {
+        name: "bind",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.bind.clone(),
+        set: |p, v| match v.parse::<std::net::IpAddr>() {
+            Ok(_) => {
+                p.bind = v.to_string();
+                Ok(())
+            }
+            Err(_) => Err(format!("invalid bind address '{}'", v)),
+        },
+    },
+    ConfigParam {
+        name: "port",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.port.to_string(),
+        set: |p, v| {
+            p.port = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "appendonly",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| yes_no(p.append_only),
+        set: |p, v| {
+            p.append_only = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "appendfilename",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.append_filename.clone(),
+        set: |p, v| {
+            p.append_filename = v.to_string();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "maxclients",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.max_clients.to_string(),
+        set: |p, v| {
+            match parse_num(v)? {
+                n if n >= 1 => {
+                    p.max_clients = n;
+                    Ok(())
+                }
+                _ => Err("argument must be between 1 and 2147483647 inclusive".into()),
+            }
+        },
+    },
+    ConfigParam {
+        name: "timeout",
+        modifiable: true,
+        apply: Apply::Lazy,
+        get: |p| p.timeout.to_string(),
+        set: |p, v| {
+            p.timeout = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tcp-keepalive",
+        modifiable: true,
+        apply: Apply::Lazy,
+        get: |p| p.tcp_keepalive.to_string(),
+        set: |p, v| {
+            p.tcp_keepalive = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "counter-merge",
+        modifiable: true,
+        apply: Apply::Lazy,
+        get: |p| yes_no(p.counter_merge),
+        set: |p, v| {
+            p.counter_merge = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "hll-sparse-max-bytes",
+        modifiable: true,
+        apply: Apply::Lazy,
+        get: |p| p.hll_sparse_max_bytes.to_string(),
+        set: |p, v| {
+            p.hll_sparse_max_bytes = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "notify-keyspace-events",
+        modifiable: true,
+        apply: Apply::Lazy,
+        get: |p| notify::format_flags(p.notify_keyspace_events),
+        set: |p, v| {
+            p.notify_keyspace_events = notify::parse_flags(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "lua-time-limit",
+        modifiable: true,
+        apply: Apply::Lazy,
+        get: |p| p.lua_time_limit.to_string(),
+        set: |p, v| {
+            p.lua_time_limit = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "loglevel",
+        modifiable: true,
+        apply: Apply::LogLevel,
+        get: |p| p.log_level.clone(),
+        set: |p, v| match parse_log_level(v) {
+            Some(_) => {
+                p.log_level = v.to_lowercase();
+                Ok(())
+            }
+            None => Err("argument(s) must be one of the following: debug, verbose, notice, warning, nothing".into()),
+        },
+    },
+    ConfigParam {
+        name: "requirepass",
+        modifiable: true,
+        apply: Apply::Lazy,
+        get: |p| p.require_pass.clone(),
+        set: |p, v| {
+            p.require_pass = v.to_string();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "aclfile",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.acl_file.clone(),
+        set: |p, v| {
+            p.acl_file = v.to_string();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-port",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.tls_port.to_string(),
+        set: |p, v| {
+            p.tls_port = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-cert-file",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.tls_cert_file.clone(),
+        set: |p, v| {
+            p.tls_cert_file = v.to_string();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-key-file",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.tls_key_file.clone(),
+        set: |p, v| {
+            p.tls_key_file = v.to_string();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-ca-cert-file",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.tls_ca_cert_file.clone(),
+        set: |p, v| {
+            p.tls_ca_cert_file = v.to_string();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-auth-clients",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.tls_auth_clients.clone(),
+        set: |p, v| match &v.to_lowercase()[..] {
+            value @ ("yes" | "no" | "optional") => {
+                p.tls_auth_clients = value.to_string();
+                Ok(())
+            }
+            _ => Err("argument(s) must be one of the following: no, yes, optional".into()),
+        },
+    },
+    ConfigParam {
+        name: "unixsocket",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.unix_socket.clone(),
+        set: |p, v| {
+            p.unix_socket = v.to_string();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "unixsocketperm",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| format!("{:o}", p.unix_socket_perm),
+        set: |p, v| match u32::from_str_radix(v, 8) {
+            Ok(perm) if perm <= 0o777 => {
+                p.unix_socket_perm = perm;
+                Ok(())
+            }
+            _ => Err(format!("argument must be an octal permission mode, got '{}'", v)),
+        },
+    },
+    ConfigParam {
+        name: "rocksdb.write_buffer_size",
+        modifiable: true,
+        apply: Apply::Rocksdb,
+        get: |p| p.rocksdb.write_buffer_size.to_string(),
+        set: |p, v| {
+            p.rocksdb.write_buffer_size = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "rocksdb.max_write_buffer_number",
+        modifiable: true,
+        apply: Apply::Rocksdb,
+        get: |p| p.rocksdb.max_write_buffer_number.to_string(),
+        set: |p, v| {
+            p.rocksdb.max_write_buffer_number = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "rocksdb.level0_file_num_compaction_trigger",
+        modifiable: true,
+        apply: Apply::Rocksdb,
+        get: |p| p.rocksdb.level0_file_num_compaction_trigger.to_string(),
+        set: |p, v| {
+            p.rocksdb.level0_file_num_compaction_trigger = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "rocksdb.target_file_size_base",
+        modifiable: true,
+        apply: Apply::Rocksdb,
+        get: |p| p.rocksdb.target_file_size_base.to_string(),
+        set: |p, v| {
+            p.rocksdb.target_file_size_base = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "rocksdb.max_bytes_for_level_base",
+        modifiable: true,
+        apply: Apply::Rocksdb,
+        get: |p| p.rocksdb.max_bytes_for_level_base.to_string(),
+        set: |p, v| {
+            p.rocksdb.max_bytes_for_level_base = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "rocksdb.disable_auto_compactions",
+        modifiable: true,
+        apply: Apply::Rocksdb,
+        get: |p| yes_no(p.rocksdb.disable_auto_compactions),
+        set: |p, v| {
+            p.rocksdb.disable_auto_compactions = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "rocksdb.max_background_jobs",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.rocksdb.max_background_jobs.to_string(),
+        set: |p, v| {
+            p.rocksdb.max_background_jobs = parse_num(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "rocksdb.max_open_files",
+        modifiable: false,
+        apply: Apply::Lazy,
+        get: |p| p.rocksdb.max_open_files.to_string(),
+        set: |p, v| {
+            p.rocksdb.max_open_files = parse_num(v)?;
+            Ok(())
+        },
+    },
+];
+
+fn parse_num<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("argument couldn't be parsed into an integer, got '{}'", value))
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
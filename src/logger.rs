@@ -1,6 +1,6 @@
 
 
-pub fn setup_logger() -> Result<(), fern::InitError> {
+pub fn setup_logger(level: log::LevelFilter) -> Result<(), fern::InitError> {
     fern::Dispatch::new()
         .format(|out, message, record| {
             out.finish(format_args!(
@@ -11,9 +11,15 @@ pub fn setup_logger() -> Result<(), fern::InitError> {
                 message
             ))
         })
-        .level(log::LevelFilter::Debug)
+        .level(log::LevelFilter::Trace)
         .chain(std::io::stdout())
         .chain(fern::log_file("output.log")?)
         .apply()?;
+    set_level(level);
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Changes the level of the installed logger, used by CONFIG SET loglevel.
+pub fn set_level(level: log::LevelFilter) {
+    log::set_max_level(level);
+}
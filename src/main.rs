@@ -6,8 +6,13 @@ extern crate chrono;
 extern crate lazy_static;
 
 
-use tokio::net::{TcpListener};
-use crate::config::ServerProperties;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::net::{TcpListener, UnixListener};
+use tokio::signal::unix::{signal, SignalKind};
+use crate::config::Config;
 
 mod protocol;
 mod server;
@@ -16,32 +21,123 @@ mod command;
 mod logger;
 mod banner;
 mod utils;
+mod acl;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[tokio::main]
 async fn main() -> crate::Result<()> {
-    
-    logger::setup_logger()?;
+
+    let config_file = std::env::args().nth(1);
+
+    let config = Arc::new(Config::load(config_file.as_deref())?);
+
+    logger::setup_logger(config.props().get_log_level())?;
 
     banner::banner_show("");
-    
-    let props = ServerProperties::new();
 
-    log::info!("{:?}",props);
+    log::info!("{:?}",config.props());
 
-    let listener = match TcpListener::bind(props.get_bind_addr()).await {
-        Ok(value) => value,
-        Err(err) => {
-            log::error!("{}",err);
-            return Ok(());
-        },
+    let bind_addr = config.props().get_bind_addr();
+    let tcp = match bind_addr {
+        Some(bind_addr) => {
+            let listener = match TcpListener::bind(bind_addr).await {
+                Ok(value) => value,
+                Err(err) => {
+                    log::error!("{}",err);
+                    return Ok(());
+                },
+            };
+            log::info!("persistent redis server listen on {}", bind_addr);
+            Some(listener)
+        }
+        None => None,
+    };
+
+    let tls_addr = config.props().get_tls_addr();
+    let tls = match tls_addr {
+        Some(tls_addr) => {
+            let acceptor = match server::tls::acceptor(&config.props()) {
+                Ok(acceptor) => acceptor,
+                Err(err) => {
+                    log::error!("failed to configure tls, {}", err);
+                    return Ok(());
+                }
+            };
+            let tls_listener = match TcpListener::bind(tls_addr).await {
+                Ok(value) => value,
+                Err(err) => {
+                    log::error!("{}",err);
+                    return Ok(());
+                },
+            };
+            log::info!("persistent redis server listen on {} (tls)", tls_addr);
+            Some((tls_listener, acceptor))
+        }
+        None => None,
+    };
+
+    let unix_socket = config.props().get_unix_socket();
+    let unix = match &unix_socket {
+        Some(path) => {
+            let listener = match bind_unix(path, config.props().unix_socket_perm) {
+                Ok(value) => value,
+                Err(err) => {
+                    log::error!("failed to listen on unix socket {}, {}", path.display(), err);
+                    return Ok(());
+                }
+            };
+            log::info!("persistent redis server listen on {}", path.display());
+            Some(listener)
+        }
+        None => None,
     };
 
-    log::info!("persistent redis server listen on {}", props.get_bind_addr());
+    if tcp.is_none() && tls.is_none() && unix.is_none() {
+        log::error!("no listener configured, set port, tls-port or unixsocket");
+        return Ok(());
+    }
 
-    server::run(listener,props, tokio::signal::ctrl_c()).await;
+    let res = server::run(server::Listeners { tcp, tls, unix }, config, shutdown_signal()).await;
+
+    if let Some(path) = unix_socket {
+        let _ = std::fs::remove_file(path);
+    }
+
+    if let Err(err) = res {
+        log::error!("unclean shutdown, {}", err);
+        std::process::exit(1);
+    }
 
     Ok(())
 }
+
+/// Resolves on Ctrl-C or SIGTERM.
+async fn shutdown_signal() {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(err) => {
+            log::warn!("failed to listen for SIGTERM, {}", err);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+/// Binds the unix socket, replacing a stale socket file left by a previous run.
+fn bind_unix(path: &Path, perm: u32) -> crate::Result<UnixListener> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+    if perm != 0 {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(perm))?;
+    }
+    Ok(listener)
+}
@@ -11,30 +11,30 @@ pub enum Frame {
     Bulk(Vec<u8>),
     Array(Vec<Frame>),
     Nil,
+    /// RESP3 out of band data, pub/sub messages. An array for RESP2 clients.
+    Push(Vec<Frame>),
+    /// RESP3 map, a flat array of keys and values for RESP2 clients.
+    Map(Vec<(Frame, Frame)>),
 }
 
 
 impl Frame {
 
 
-    pub fn len(&self) -> usize {
+    /// The frame as RESP2 has it, for connections that didn't switch to RESP3 with HELLO.
+    pub fn into_resp2(self) -> Frame {
         match self {
-            Frame::Str(s) => s.len(),
-            Frame::Error(err) => err.len(),
-            Frame::Integer(i) => format!("{}",i).len(),
-            Frame::Bulk(b) => b.len(),
-            Frame::Array(arr) => {
-                let mut len:usize = 0;
-                for item in arr {
-                    len += item.len()
-                }
-                len
-            },
-            Frame::Nil => 0,
+            Frame::Array(items) | Frame::Push(items) => Frame::Array(items.into_iter().map(Frame::into_resp2).collect()),
+            Frame::Map(pairs) => Frame::Array(
+                pairs
+                    .into_iter()
+                    .flat_map(|(key, value)| [key.into_resp2(), value.into_resp2()])
+                    .collect(),
+            ),
+            frame => frame,
         }
     }
 
-
     pub fn into_vec(self) -> Result<Vec<u8>, ParseError>{
         match self {
             Frame::Bulk(bytes) | Frame::Str(bytes) => Ok(bytes),
@@ -95,7 +95,7 @@ impl Frame {
             Frame::Integer(i) => {
                 let mut buf: BytesMut = BytesMut::new();
                 buf.put_u8(b':');
-                buf.put_slice(&i_to_string(i as usize).into_bytes());
+                buf.put_slice(i.to_string().as_bytes());
                 buf.put_slice(NC);
                 Ok(buf)
             }
@@ -109,15 +109,16 @@ impl Frame {
                 buf.put_slice(NC);
                 Ok(buf)
             }
-            Frame::Array(arr) => {
+            Frame::Array(arr) => encode_items(b'*', arr),
+            Frame::Push(arr) => encode_items(b'>', arr),
+            Frame::Map(pairs) => {
                 let mut buf: BytesMut = BytesMut::new();
-                buf.put_u8(b'*');
-                buf.put_slice(i_to_string(arr.len()).as_bytes());
+                buf.put_u8(b'%');
+                buf.put_slice(i_to_string(pairs.len()).as_bytes());
                 buf.put_slice(NC);
-                for sub in arr {
-                    let b = sub.into_bytes()?;
-                    buf.put_slice(&b[..]);
-                    drop(b);
+                for (key, value) in pairs {
+                    buf.put_slice(&key.into_bytes()?[..]);
+                    buf.put_slice(&value.into_bytes()?[..]);
                 }
                 Ok(buf)
             }
@@ -126,6 +127,19 @@ impl Frame {
     }
 }
 
+fn encode_items(prefix: u8, arr: Vec<Frame>) -> Result<BytesMut, ParseError> {
+    let mut buf: BytesMut = BytesMut::new();
+    buf.put_u8(prefix);
+    buf.put_slice(i_to_string(arr.len()).as_bytes());
+    buf.put_slice(NC);
+    for sub in arr {
+        let b = sub.into_bytes()?;
+        buf.put_slice(&b[..]);
+        drop(b);
+    }
+    Ok(buf)
+}
+
 pub fn parse_frame(src: &mut Cursor<&[u8]>) -> Result<Frame, FrameError> {
     if !src.has_remaining() {
         return Err(FrameError::Incomplete);
@@ -342,3 +356,40 @@ fn i_to_string(n: usize) -> String {
     return n.to_string();
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulk(s: &str) -> Frame {
+        Frame::Bulk(s.as_bytes().to_vec())
+    }
+
+    fn encoded(frame: Frame) -> String {
+        String::from_utf8(frame.into_bytes().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn resp3_frames() {
+        let push = Frame::Push(vec![bulk("message"), bulk("news"), bulk("hi")]);
+        assert_eq!(encoded(push), ">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n");
+        let map = Frame::Map(vec![(bulk("proto"), Frame::Integer(3)), (bulk("modules"), Frame::Array(vec![]))]);
+        assert_eq!(encoded(map), "%2\r\n$5\r\nproto\r\n:3\r\n$7\r\nmodules\r\n*0\r\n");
+    }
+
+    #[test]
+    fn flattened_for_resp2() {
+        let push = Frame::Push(vec![bulk("message"), bulk("news"), bulk("hi")]);
+        assert_eq!(encoded(push.into_resp2()), "*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n");
+        // maps become arrays of their keys and values, nested ones too
+        let map = Frame::Map(vec![
+            (bulk("proto"), Frame::Integer(2)),
+            (bulk("nested"), Frame::Array(vec![Frame::Map(vec![(bulk("k"), Frame::Nil)])])),
+        ]);
+        assert_eq!(
+            encoded(map.into_resp2()),
+            "*4\r\n$5\r\nproto\r\n:2\r\n$6\r\nnested\r\n*1\r\n*2\r\n$1\r\nk\r\n$-1\r\n"
+        );
+        assert_eq!(encoded(bulk("plain").into_resp2()), "$5\r\nplain\r\n");
+    }
+}
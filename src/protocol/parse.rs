@@ -1,16 +1,73 @@
+use crate::command::acl::Acl;
+use crate::command::auth::Auth;
+use crate::command::hello::Hello;
+use crate::command::eval::Eval;
+use crate::command::script::Script;
+use crate::command::fcall::FCall;
+use crate::command::function::Function;
+use crate::command::client::Client;
+use crate::command::config::Config;
 use crate::command::del::Del;
 use crate::command::exists::Exists;
 use crate::command::flushdb::Flushdb;
 use crate::command::get::Get;
 use crate::command::incrby::IncrBy;
+use crate::command::incrbyfloat::IncrByFloat;
 use crate::command::info::Info;
 use crate::command::keys::Keys;
 use crate::command::mset::MSet;
 use crate::command::scan::Scan;
 use crate::command::select::Select;
 use crate::command::set::Set;
+use crate::command::shutdown::Shutdown;
 use crate::command::strlen::StrLen;
 use crate::command::ttl::Ttl;
+use crate::command::append::Append;
+use crate::command::getdel::GetDel;
+use crate::command::getex::GetEx;
+use crate::command::getrange::GetRange;
+use crate::command::getset::GetSet;
+use crate::command::msetnx::MSetNx;
+use crate::command::setex::SetEx;
+use crate::command::setnx::SetNx;
+use crate::command::setrange::SetRange;
+use crate::command::setbit::SetBit;
+use crate::command::getbit::GetBit;
+use crate::command::bitcount::BitCount;
+use crate::command::bitpos::BitPos;
+use crate::command::bitop::BitOp;
+use crate::command::bitfield::BitField;
+use crate::command::lcs::Lcs;
+use crate::command::pfadd::PfAdd;
+use crate::command::pfcount::PfCount;
+use crate::command::pfmerge::PfMerge;
+use crate::command::geoadd::GeoAdd;
+use crate::command::geodist::GeoDist;
+use crate::command::geopos::GeoPos;
+use crate::command::geohash::GeoHash;
+use crate::command::geosearch::GeoSearch;
+use crate::command::xadd::XAdd;
+use crate::command::xrange::XRange;
+use crate::command::xlen::XLen;
+use crate::command::xdel::XDel;
+use crate::command::xtrim::XTrim;
+use crate::command::xread::XRead;
+use crate::command::xgroup::XGroup;
+use crate::command::xack::XAck;
+use crate::command::xpending::XPending;
+use crate::command::xclaim::XClaim;
+use crate::command::xautoclaim::XAutoClaim;
+use crate::command::xinfo::XInfo;
+use crate::command::multi::Multi;
+use crate::command::exec::Exec;
+use crate::command::discard::Discard;
+use crate::command::watch::Watch;
+use crate::command::unwatch::Unwatch;
+use crate::command::subscribe::Subscribe;
+use crate::command::unsubscribe::Unsubscribe;
+use crate::command::publish::Publish;
+use crate::command::pubsub::PubSub;
+use crate::server::pubsub::Kind;
 use crate::command::Command;
 use crate::command::mget::MGet;
 
@@ -47,14 +104,86 @@ impl Parse {
             "ttl" => Command::TTL(Ttl::parse(self,false)?),
             "pttl" => Command::PTTL(Ttl::parse(self,true)?),
             "exists" => Command::EXISTS(Exists::parse(self)?),
-            "incr" =>Command::INCR(IncrBy::parse(self,true)?),
-            "incrby" =>Command::INCR(IncrBy::parse(self,false)?),
+            "incr" =>Command::INCR(IncrBy::parse(self,"incr")?),
+            "incrby" =>Command::INCRBY(IncrBy::parse(self,"incrby")?),
+            "decr" =>Command::DECR(IncrBy::parse(self,"decr")?),
+            "decrby" =>Command::DECRBY(IncrBy::parse(self,"decrby")?),
+            "incrbyfloat" =>Command::INCRBYFLOAT(IncrByFloat::parse(self)?),
             "flushdb" => Command::FLUSHDB(Flushdb::parse(self)?),
             "mget" => Command::MGET(MGet::parse(self)?),
             "strlen" => Command::STRLEN(StrLen::parse(self)?),
             "mset" => Command::MSET(MSet::parse(self)?),
             "keys" => Command::KEYS(Keys::parse(self)?),
             "scan" => Command::SCAN(Scan::parse(self)?),
+            "config" => Command::CONFIG(Config::parse(self)?),
+            "auth" => Command::AUTH(Auth::parse(self)?),
+            "acl" => Command::ACL(Acl::parse(self)?),
+            "client" => Command::CLIENT(Client::parse(self)?),
+            "shutdown" => Command::SHUTDOWN(Shutdown::parse(self)?),
+            "append" => Command::APPEND(Append::parse(self)?),
+            "getrange" => Command::GETRANGE(GetRange::parse(self)?),
+            "setrange" => Command::SETRANGE(SetRange::parse(self)?),
+            "getset" => Command::GETSET(GetSet::parse(self)?),
+            "getdel" => Command::GETDEL(GetDel::parse(self)?),
+            "getex" => Command::GETEX(GetEx::parse(self)?),
+            "setnx" => Command::SETNX(SetNx::parse(self)?),
+            "setex" => Command::SETEX(SetEx::parse(self, false)?),
+            "psetex" => Command::PSETEX(SetEx::parse(self, true)?),
+            "msetnx" => Command::MSETNX(MSetNx::parse(self)?),
+            "setbit" => Command::SETBIT(SetBit::parse(self)?),
+            "getbit" => Command::GETBIT(GetBit::parse(self)?),
+            "bitcount" => Command::BITCOUNT(BitCount::parse(self)?),
+            "bitpos" => Command::BITPOS(BitPos::parse(self)?),
+            "bitop" => Command::BITOP(BitOp::parse(self)?),
+            "bitfield" => Command::BITFIELD(BitField::parse(self, false)?),
+            "bitfield_ro" => Command::BITFIELD_RO(BitField::parse(self, true)?),
+            "lcs" => Command::LCS(Lcs::parse(self)?),
+            "pfadd" => Command::PFADD(PfAdd::parse(self)?),
+            "pfcount" => Command::PFCOUNT(PfCount::parse(self)?),
+            "pfmerge" => Command::PFMERGE(PfMerge::parse(self)?),
+            "geoadd" => Command::GEOADD(GeoAdd::parse(self)?),
+            "geodist" => Command::GEODIST(GeoDist::parse(self)?),
+            "geopos" => Command::GEOPOS(GeoPos::parse(self)?),
+            "geohash" => Command::GEOHASH(GeoHash::parse(self)?),
+            "geosearch" => Command::GEOSEARCH(GeoSearch::parse(self, false)?),
+            "geosearchstore" => Command::GEOSEARCHSTORE(GeoSearch::parse(self, true)?),
+            "xadd" => Command::XADD(XAdd::parse(self)?),
+            "xrange" => Command::XRANGE(XRange::parse(self, false)?),
+            "xrevrange" => Command::XREVRANGE(XRange::parse(self, true)?),
+            "xlen" => Command::XLEN(XLen::parse(self)?),
+            "xdel" => Command::XDEL(XDel::parse(self)?),
+            "xtrim" => Command::XTRIM(XTrim::parse(self)?),
+            "xread" => Command::XREAD(XRead::parse(self, false)?),
+            "xreadgroup" => Command::XREADGROUP(XRead::parse(self, true)?),
+            "xgroup" => Command::XGROUP(XGroup::parse(self)?),
+            "xack" => Command::XACK(XAck::parse(self)?),
+            "xpending" => Command::XPENDING(XPending::parse(self)?),
+            "xclaim" => Command::XCLAIM(XClaim::parse(self)?),
+            "xautoclaim" => Command::XAUTOCLAIM(XAutoClaim::parse(self)?),
+            "xinfo" => Command::XINFO(XInfo::parse(self)?),
+            "multi" => Command::MULTI(Multi::parse(self)?),
+            "exec" => Command::EXEC(Exec::parse(self)?),
+            "discard" => Command::DISCARD(Discard::parse(self)?),
+            "watch" => Command::WATCH(Watch::parse(self)?),
+            "unwatch" => Command::UNWATCH(Unwatch::parse(self)?),
+            "subscribe" => Command::SUBSCRIBE(Subscribe::parse(self, Kind::Channel)?),
+            "unsubscribe" => Command::UNSUBSCRIBE(Unsubscribe::parse(self, Kind::Channel)?),
+            "psubscribe" => Command::PSUBSCRIBE(Subscribe::parse(self, Kind::Pattern)?),
+            "punsubscribe" => Command::PUNSUBSCRIBE(Unsubscribe::parse(self, Kind::Pattern)?),
+            "publish" => Command::PUBLISH(Publish::parse(self, false)?),
+            "pubsub" => Command::PUBSUB(PubSub::parse(self)?),
+            "ssubscribe" => Command::SSUBSCRIBE(Subscribe::parse(self, Kind::Shard)?),
+            "sunsubscribe" => Command::SUNSUBSCRIBE(Unsubscribe::parse(self, Kind::Shard)?),
+            "spublish" => Command::SPUBLISH(Publish::parse(self, true)?),
+            "hello" => Command::HELLO(Hello::parse(self)?),
+            "eval" => Command::EVAL(Eval::parse(self, "eval", false)?),
+            "evalsha" => Command::EVALSHA(Eval::parse(self, "evalsha", false)?),
+            "eval_ro" => Command::EVAL_RO(Eval::parse(self, "eval_ro", true)?),
+            "evalsha_ro" => Command::EVALSHA_RO(Eval::parse(self, "evalsha_ro", true)?),
+            "script" => Command::SCRIPT(Script::parse(self)?),
+            "fcall" => Command::FCALL(FCall::parse(self, "fcall", false)?),
+            "fcall_ro" => Command::FCALL_RO(FCall::parse(self, "fcall_ro", true)?),
+            "function" => Command::FUNCTION(Function::parse(self)?),
             _ => Command::UNKNOWN(command_name, self),
         };
         Ok(cmd)
@@ -0,0 +1,347 @@
+//! Write batches that can be read back before they are written.
+//!
+//! `Shared` builds every change as a `Batch`. Outside transactions a batch becomes a single RocksDB
+//! `WriteBatch` right away. During EXEC batches pile up in an `Overlay` instead, which the
+//! commands that follow read through, and the whole transaction is written as one `WriteBatch`.
+
+use std::{collections::BTreeMap, iter::Peekable, ops::Bound, sync::Arc};
+
+use rocksdb::{BoundColumnFamily, WriteBatch};
+
+use crate::server::encoding;
+
+/// The column family an operation is on, the default one or the sub one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cf {
+    Default,
+    Sub,
+}
+
+#[derive(Debug)]
+enum Op {
+    Put(Cf, Vec<u8>, Vec<u8>),
+    Delete(Cf, Vec<u8>),
+    DeleteRange(Cf, Vec<u8>, Vec<u8>),
+}
+
+/// The writes of one change, applied in order.
+#[derive(Debug, Default)]
+pub struct Batch {
+    ops: Vec<Op>,
+}
+
+impl Batch {
+    pub fn put(&mut self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) {
+        self.ops.push(Op::Put(Cf::Default, key.as_ref().to_vec(), value.as_ref().to_vec()));
+    }
+
+    pub fn delete(&mut self, key: impl AsRef<[u8]>) {
+        self.ops.push(Op::Delete(Cf::Default, key.as_ref().to_vec()));
+    }
+
+    pub fn put_sub(&mut self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) {
+        self.ops.push(Op::Put(Cf::Sub, key.as_ref().to_vec(), value.as_ref().to_vec()));
+    }
+
+    pub fn delete_sub(&mut self, key: impl AsRef<[u8]>) {
+        self.ops.push(Op::Delete(Cf::Sub, key.as_ref().to_vec()));
+    }
+
+    /// Deletes the sub keys from `from` up to `to` exclusive.
+    pub fn delete_sub_range(&mut self, from: impl AsRef<[u8]>, to: impl AsRef<[u8]>) {
+        self.ops.push(Op::DeleteRange(Cf::Sub, from.as_ref().to_vec(), to.as_ref().to_vec()));
+    }
+
+    /// The keys the batch changes, a key appearing once for each of its parts changed.
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.ops.iter().filter_map(|op| match op {
+            Op::Put(Cf::Default, key, _) | Op::Delete(Cf::Default, key) | Op::DeleteRange(Cf::Default, key, _) => {
+                Some(&key[..])
+            }
+            Op::Put(Cf::Sub, sub_key, _) | Op::Delete(Cf::Sub, sub_key) | Op::DeleteRange(Cf::Sub, sub_key, _) => {
+                encoding::sub_key_owner(sub_key)
+            }
+        })
+    }
+
+    pub fn into_write_batch(self, sub: &Arc<BoundColumnFamily<'_>>) -> WriteBatch {
+        let mut batch = WriteBatch::default();
+        for op in self.ops {
+            match op {
+                Op::Put(Cf::Default, key, value) => batch.put(key, value),
+                Op::Put(Cf::Sub, key, value) => batch.put_cf(sub, key, value),
+                Op::Delete(Cf::Default, key) => batch.delete(key),
+                Op::Delete(Cf::Sub, key) => batch.delete_cf(sub, key),
+                Op::DeleteRange(Cf::Default, from, to) => batch.delete_range(from, to),
+                Op::DeleteRange(Cf::Sub, from, to) => batch.delete_range_cf(sub, from, to),
+            }
+        }
+        batch
+    }
+}
+
+/// The writes of one column family not written yet.
+#[derive(Debug, Default)]
+struct Layer {
+    /// Keys written, `None` for deleted ones.
+    entries: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    /// Ranges deleted, hiding what the database holds in them. Keys written after a range was
+    /// deleted are in `entries`.
+    deleted_ranges: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Layer {
+    /// What the layer says about `key`, `None` when the database has to be read.
+    fn get(&self, key: &[u8]) -> Option<Option<&[u8]>> {
+        if let Some(value) = self.entries.get(key) {
+            return Some(value.as_deref());
+        }
+        let deleted = self
+            .deleted_ranges
+            .iter()
+            .any(|(from, to)| key >= from.as_slice() && key < to.as_slice());
+        if deleted {
+            return Some(None);
+        }
+        None
+    }
+
+    fn delete_range(&mut self, from: Vec<u8>, to: Vec<u8>) {
+        if from >= to {
+            return;
+        }
+        let mut removed = self.entries.split_off(&from);
+        let mut after = removed.split_off(&to);
+        self.entries.append(&mut after);
+        self.deleted_ranges.push((from, to));
+    }
+}
+
+/// The writes of a transaction in progress, readable before they are written.
+#[derive(Debug, Default)]
+pub struct Overlay {
+    batch: Batch,
+    default: Layer,
+    sub: Layer,
+}
+
+/// A key and value read from RocksDB or from an overlay.
+pub type Entry = (Box<[u8]>, Box<[u8]>);
+
+/// Keys written by an overlay with their values, `None` for deleted ones.
+type Written<'a> = Box<dyn Iterator<Item = (&'a Vec<u8>, &'a Option<Vec<u8>>)> + 'a>;
+
+impl Overlay {
+    pub fn apply(&mut self, batch: Batch) {
+        for op in batch.ops.iter() {
+            match op {
+                Op::Put(cf, key, value) => {
+                    self.layer_mut(*cf).entries.insert(key.clone(), Some(value.clone()));
+                }
+                Op::Delete(cf, key) => {
+                    self.layer_mut(*cf).entries.insert(key.clone(), None);
+                }
+                Op::DeleteRange(cf, from, to) => self.layer_mut(*cf).delete_range(from.clone(), to.clone()),
+            }
+        }
+        self.batch.ops.extend(batch.ops);
+    }
+
+    /// The value of `key` as written in the overlay, `None` when the database has to be read.
+    pub fn get(&self, cf: Cf, key: &[u8]) -> Option<Option<Vec<u8>>> {
+        self.layer(cf).get(key).map(|value| value.map(<[u8]>::to_vec))
+    }
+
+    /// Merges a database iterator starting at `from` with the overlay, in key order when `forward`
+    /// and in reverse otherwise.
+    pub fn merge<'a>(
+        &'a self,
+        cf: Cf,
+        database: impl Iterator<Item = Entry> + 'a,
+        from: &[u8],
+        forward: bool,
+    ) -> impl Iterator<Item = Entry> + 'a {
+        let layer = self.layer(cf);
+        let overlay: Written<'a> = match forward {
+            true => Box::new(layer.entries.range::<[u8], _>((Bound::Included(from), Bound::Unbounded))),
+            false => Box::new(layer.entries.range::<[u8], _>((Bound::Unbounded, Bound::Included(from))).rev()),
+        };
+        Merged {
+            database: database.filter(move |(key, _)| layer.get(key).is_none()).peekable(),
+            overlay: overlay.peekable(),
+            forward,
+        }
+    }
+
+    pub fn into_batch(self) -> Batch {
+        self.batch
+    }
+
+    fn layer(&self, cf: Cf) -> &Layer {
+        match cf {
+            Cf::Default => &self.default,
+            Cf::Sub => &self.sub,
+        }
+    }
+
+    fn layer_mut(&mut self, cf: Cf) -> &mut Layer {
+        match cf {
+            Cf::Default => &mut self.default,
+            Cf::Sub => &mut self.sub,
+        }
+    }
+}
+
+/// Entries of the database not shadowed by the overlay, merged with those of the overlay.
+struct Merged<'a, D: Iterator<Item = Entry>> {
+    database: Peekable<D>,
+    overlay: Peekable<Written<'a>>,
+    forward: bool,
+}
+
+impl<'a, D: Iterator<Item = Entry>> Iterator for Merged<'a, D> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        loop {
+            let from_overlay = match (self.database.peek(), self.overlay.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => false,
+                (None, Some(_)) => true,
+                (Some((stored, _)), Some((written, _))) => match self.forward {
+                    true => written.as_slice() < &stored[..],
+                    false => written.as_slice() > &stored[..],
+                },
+            };
+            if !from_overlay {
+                return self.database.next();
+            }
+            // deleted keys only hide what the database holds
+            if let Some((key, Some(value))) = self.overlay.next() {
+                return Some((key.clone().into_boxed_slice(), value.clone().into_boxed_slice()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(pairs: &[(&str, &str)]) -> Vec<Entry> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.as_bytes().into(), value.as_bytes().into()))
+            .collect()
+    }
+
+    /// The keys and values of the overlay merged with `database`, from `from` on.
+    fn merged(overlay: &Overlay, database: &[(&str, &str)], from: &str, forward: bool) -> Vec<(String, String)> {
+        let mut database = entries(database);
+        // the database iterator starts at `from` too
+        database.retain(|(key, _)| match forward {
+            true => &key[..] >= from.as_bytes(),
+            false => &key[..] <= from.as_bytes(),
+        });
+        if !forward {
+            database.reverse();
+        }
+        overlay
+            .merge(Cf::Default, database.into_iter(), from.as_bytes(), forward)
+            .map(|(key, value)| (String::from_utf8_lossy(&key).into(), String::from_utf8_lossy(&value).into()))
+            .collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn reads_its_own_writes() {
+        let mut overlay = Overlay::default();
+        assert_eq!(overlay.get(Cf::Default, b"a"), None);
+        let mut batch = Batch::default();
+        batch.put("a", "1");
+        batch.put_sub("a", "sub");
+        batch.delete("b");
+        overlay.apply(batch);
+        assert_eq!(overlay.get(Cf::Default, b"a"), Some(Some(b"1".to_vec())));
+        assert_eq!(overlay.get(Cf::Sub, b"a"), Some(Some(b"sub".to_vec())));
+        assert_eq!(overlay.get(Cf::Default, b"b"), Some(None));
+        assert_eq!(overlay.get(Cf::Default, b"c"), None);
+
+        let mut batch = Batch::default();
+        batch.put("a", "2");
+        batch.delete_sub("a");
+        overlay.apply(batch);
+        assert_eq!(overlay.get(Cf::Default, b"a"), Some(Some(b"2".to_vec())));
+        assert_eq!(overlay.get(Cf::Sub, b"a"), Some(None));
+    }
+
+    #[test]
+    fn delete_range_hides_the_database() {
+        let mut overlay = Overlay::default();
+        let mut batch = Batch::default();
+        batch.put_sub("k:1", "written");
+        batch.delete_sub_range("k:", "k;");
+        batch.put_sub("k:3", "after");
+        overlay.apply(batch);
+        assert_eq!(overlay.get(Cf::Sub, b"k:1"), Some(None));
+        assert_eq!(overlay.get(Cf::Sub, b"k:2"), Some(None));
+        assert_eq!(overlay.get(Cf::Sub, b"k:3"), Some(Some(b"after".to_vec())));
+        // the end of the range is exclusive
+        assert_eq!(overlay.get(Cf::Sub, b"k;"), None);
+        assert_eq!(overlay.get(Cf::Default, b"k:2"), None);
+
+        let database = entries(&[("j", "1"), ("k:2", "2"), ("k:4", "4"), ("l", "5")]);
+        let merged: Vec<Entry> = overlay.merge(Cf::Sub, database.into_iter(), b"", true).collect();
+        assert_eq!(merged, entries(&[("j", "1"), ("k:3", "after"), ("l", "5")]));
+    }
+
+    #[test]
+    fn empty_range_deletes_nothing() {
+        let mut overlay = Overlay::default();
+        let mut batch = Batch::default();
+        batch.put("b", "1");
+        batch.delete_sub_range("c", "a");
+        overlay.apply(batch);
+        assert_eq!(overlay.get(Cf::Default, b"b"), Some(Some(b"1".to_vec())));
+        assert_eq!(overlay.get(Cf::Sub, b"b"), None);
+    }
+
+    #[test]
+    fn iterates_in_key_order_across_overlay_and_database() {
+        let mut overlay = Overlay::default();
+        let mut batch = Batch::default();
+        batch.put("b", "written");
+        batch.put("d", "written");
+        batch.put("c", "replaced");
+        batch.delete("e");
+        overlay.apply(batch);
+        let database = [("a", "1"), ("c", "3"), ("e", "5"), ("f", "6")];
+
+        let expected = [("a", "1"), ("b", "written"), ("c", "replaced"), ("d", "written"), ("f", "6")];
+        assert_eq!(merged(&overlay, &database, "", true), pairs(&expected));
+        assert_eq!(merged(&overlay, &database, "c", true), pairs(&expected[2..]));
+
+        let mut reversed = expected;
+        reversed.reverse();
+        assert_eq!(merged(&overlay, &database, "z", false), pairs(&reversed));
+        assert_eq!(merged(&overlay, &database, "c", false), pairs(&reversed[2..]));
+    }
+
+    #[test]
+    fn writes_everything_in_order() {
+        let mut overlay = Overlay::default();
+        let mut batch = Batch::default();
+        batch.put("a", "1");
+        overlay.apply(batch);
+        let mut batch = Batch::default();
+        batch.delete("b");
+        batch.put_sub(encoding::chunk_key("c", 0), "chunk");
+        overlay.apply(batch);
+        let batch = overlay.into_batch();
+        assert_eq!(batch.ops.len(), 3);
+        assert_eq!(batch.keys().collect::<Vec<_>>(), vec![&b"a"[..], b"b", b"c"]);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+/// Registry of the connected clients, shared by the accept loop and every handler.
+#[derive(Debug, Default)]
+pub struct Clients {
+    next_id: AtomicU64,
+    clients: Mutex<BTreeMap<u64, Arc<Client>>>,
+    pause: Mutex<Option<Pause>>,
+    unpaused: Notify,
+}
+
+/// A CLIENT PAUSE in effect until `deadline`.
+#[derive(Debug, Clone, Copy)]
+pub struct Pause {
+    pub deadline: Instant,
+    /// Only write commands are held, the WRITE mode.
+    pub write_only: bool,
+}
+
+#[derive(Debug)]
+pub struct Client {
+    pub id: u64,
+    pub addr: String,
+    pub laddr: String,
+    created: Instant,
+    state: Mutex<ClientState>,
+    kill: Notify,
+}
+
+/// The parts of a client that change while it is connected.
+#[derive(Debug)]
+pub struct ClientState {
+    pub name: String,
+    pub user: String,
+    pub db: i64,
+    /// The last command name, `NULL` before the first one like redis reports it.
+    pub last_cmd: String,
+    pub last_interaction: Instant,
+    pub no_evict: bool,
+}
+
+impl Clients {
+    pub fn register(&self, addr: String, laddr: String, user: Option<&str>) -> Arc<Client> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let now = Instant::now();
+        let client = Arc::new(Client {
+            id,
+            addr,
+            laddr,
+            created: now,
+            state: Mutex::new(ClientState {
+                name: String::new(),
+                user: user.unwrap_or_default().to_string(),
+                db: 0,
+                last_cmd: "NULL".to_string(),
+                last_interaction: now,
+                no_evict: false,
+            }),
+            kill: Notify::new(),
+        });
+        self.clients.lock().unwrap().insert(id, client.clone());
+        client
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.clients.lock().unwrap().remove(&id);
+    }
+
+    /// Every connected client, ordered by id.
+    pub fn list(&self) -> Vec<Arc<Client>> {
+        self.clients.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn pause(&self, timeout: Duration, write_only: bool) {
+        let deadline = Instant::now() + timeout;
+        let mut pause = self.pause.lock().unwrap();
+        // a second pause can only extend the deadline and widen the mode, as in redis
+        *pause = Some(match *pause {
+            Some(current) if current.deadline > Instant::now() => Pause {
+                deadline: current.deadline.max(deadline),
+                write_only: current.write_only && write_only,
+            },
+            _ => Pause { deadline, write_only },
+        });
+    }
+
+    pub fn unpause(&self) {
+        *self.pause.lock().unwrap() = None;
+        self.unpaused.notify_waiters();
+    }
+
+    /// Waits while a pause holds the command, `write` telling whether it is a write command.
+    pub async fn wait_unpaused(&self, write: bool) {
+        loop {
+            // created before the check so an unpause in between isn't missed
+            let unpaused = self.unpaused.notified();
+            let deadline = match *self.pause.lock().unwrap() {
+                Some(pause) if pause.deadline > Instant::now() && (write || !pause.write_only) => {
+                    pause.deadline
+                }
+                _ => return,
+            };
+            tokio::select! {
+                _ = unpaused => {}
+                _ = tokio::time::sleep_until(deadline.into()) => {}
+            }
+        }
+    }
+}
+
+impl Client {
+    pub fn state(&self) -> MutexGuard<'_, ClientState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Asks the connection to close, it does so before reading the next command.
+    pub fn kill(&self) {
+        self.kill.notify_one();
+    }
+
+    pub async fn killed(&self) {
+        self.kill.notified().await
+    }
+
+    /// The client as a line of CLIENT LIST and CLIENT INFO.
+    pub fn describe(&self) -> String {
+        let state = self.state();
+        let flags = if state.no_evict { "e" } else { "N" };
+        format!(
+            "id={} addr={} laddr={} name={} age={} idle={} flags={} db={} cmd={} user={}",
+            self.id,
+            self.addr,
+            self.laddr,
+            state.name,
+            self.created.elapsed().as_secs(),
+            state.last_interaction.elapsed().as_secs(),
+            flags,
+            state.db,
+            state.last_cmd,
+            state.user,
+        )
+    }
+}
@@ -1,16 +1,21 @@
-use tokio::io::{BufWriter, AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::io::{BufWriter, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use bytes::{BytesMut, Buf};
 use crate::protocol::frame::*;
 use std::io::Cursor;
 
-use std::net::SocketAddr;
 use crate::protocol::FrameError;
 
+/// Any byte stream a client can talk over: a plain or TLS wrapped `TcpStream`, or a `UnixStream`.
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
 pub struct Connection {
-    stream: BufWriter<TcpStream>,
+    stream: BufWriter<Box<dyn Stream>>,
     buffer: BytesMut,
-    addr: SocketAddr,
+    addr: String,
+    /// switched on by `HELLO 3`, frames are written as RESP2 otherwise
+    resp3: bool,
 }
 
 impl Drop for Connection {
@@ -20,12 +25,12 @@ impl Drop for Connection {
 }
 
 impl Connection {
-    pub fn new(tcp_stream: TcpStream) -> Connection {
-        let addr = tcp_stream.peer_addr().unwrap();
+    pub fn new(stream: Box<dyn Stream>, addr: String) -> Connection {
         Connection {
             addr,
-            stream: BufWriter::new(tcp_stream),
+            stream: BufWriter::new(stream),
             buffer: BytesMut::with_capacity(4 * 1024),
+            resp3: false,
         }
     }
 
@@ -39,7 +44,16 @@ impl Connection {
         Ok(self.stream.flush().await?)
     }
 
+    pub fn is_resp3(&self) -> bool {
+        self.resp3
+    }
+
+    pub fn set_resp3(&mut self, resp3: bool) {
+        self.resp3 = resp3;
+    }
+
     pub async fn write_frame(&mut self, frame: Frame) -> crate::Result<()> {
+        let frame = if self.resp3 { frame } else { frame.into_resp2() };
         let b = frame.into_bytes()?;
         let _ = self.stream.write_all(&b[..]).await?;
         Ok(())
@@ -1,11 +1,21 @@
 
+use crate::acl::Acl;
+use crate::command::exec::Exec;
 use crate::command::Command;
+use crate::config::Config;
 use crate::server::*;
+use crate::server::pubsub::Registry;
+use crate::server::scripting::{Running, Scripting};
+use crate::server::stats::Stats;
 
 use super::shared::Shared;
+use tokio::task;
+use tokio::time::Instant;
 
 pub struct Db {
     cmd_sender: mpsc::Sender<(Command, mpsc::Sender<CommandResult>)>,
+    /// the script the worker runs, if any
+    running: Arc<Running>,
 }
 
 unsafe impl Send for Db {}
@@ -17,10 +27,27 @@ impl Drop for Db {
 }
 
 impl Db {
-    pub fn new(append_file: &str,shutdown_complete_tx: broadcast::Receiver<()>) -> (Db, DbWorker) {
-        let hook = ShutdownHook::new(shutdown_complete_tx);
+    /// `stop` makes the worker return without draining its queue, see `SHUTDOWN NOW`.
+    pub fn new(
+        config: Arc<Config>,
+        stats: Arc<Stats>,
+        pubsub: Arc<Registry>,
+        acl: Arc<Acl>,
+        stop: broadcast::Receiver<()>,
+    ) -> (Db, DbWorker) {
+        let hook = ShutdownHook::new(stop);
         let (sender, recv) = mpsc::channel::<(Command, mpsc::Sender<CommandResult>)>(1000);
-        (Db { cmd_sender: sender }, DbWorker::new(append_file,recv, hook))
+        let running = Arc::new(Running::default());
+        let shared = Shared::new(config, stats, pubsub);
+        let mut scripting = Scripting::new(running.clone(), acl);
+        scripting.load_libraries(&shared);
+        let worker = DbWorker::new(shared, scripting, recv, hook);
+        (Db { cmd_sender: sender, running }, worker)
+    }
+
+    /// The script in execution, for BUSY replies and SCRIPT KILL.
+    pub(crate) fn running_script(&self) -> &Running {
+        &self.running
     }
 
     pub(crate) async fn exec(&self, cmd: Command) -> crate::Result<Option<CommandResult>> {
@@ -31,47 +58,197 @@ impl Db {
     }
 }
 
+/// Keys the expire cycle checks at each run, 10 runs a second like redis' `hz`.
+const EXPIRE_CYCLE_KEYS: usize = 200;
+const EXPIRE_CYCLE_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct DbWorker {
     recv: Receiver<(Command, mpsc::Sender<CommandResult>)>,
     shutdown_hook: ShutdownHook,
     shared: Shared,
+    scripting: Scripting,
+    /// blocking commands waiting for data, oldest first
+    blocked: Vec<Blocked>,
+}
+
+/// A blocking command that found no data, retried whenever one of its keys is written.
+struct Blocked {
+    cmd: Command,
+    callback: mpsc::Sender<CommandResult>,
+    /// `None` waits until data arrives or the client goes away
+    deadline: Option<Instant>,
 }
 
 impl DbWorker {
     pub fn new(
-        appendfile :&str,
+        shared: Shared,
+        scripting: Scripting,
         recv: Receiver<(Command, mpsc::Sender<CommandResult>)>,
         shutdown_hook: ShutdownHook,
     ) -> DbWorker {
-        let shared = Shared::new(appendfile);
         DbWorker {
             recv,
             shutdown_hook,
             shared,
+            scripting,
+            blocked: Vec::new(),
         }
     }
 
-    pub async fn run(mut self) -> crate::Result<()> {
-        let mut receiver = self.recv;
-
-            while !self.shutdown_hook.is_shutdown() {
-                let maybe_cmd = tokio::select! {
-                    res = receiver.recv() => res,
-                    _ = self.shutdown_hook.receive() => {
-                        return Ok(());
+    /// Applies commands until every `Db` handle is dropped and the queue is drained,
+    /// then hands the database back so it can be synced.
+    pub async fn run(mut self) -> crate::Result<Shared> {
+        let mut expire_cycle = time::interval(EXPIRE_CYCLE_INTERVAL);
+        expire_cycle.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            let deadline = self.blocked.iter().filter_map(|blocked| blocked.deadline).min();
+            let maybe_cmd = tokio::select! {
+                res = self.recv.recv() => res,
+                _ = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.time_out_blocked().await;
+                    continue;
+                }
+                _ = expire_cycle.tick() => {
+                    // clients that went away while blocked without a deadline would otherwise
+                    // stay until one of their keys is written
+                    self.blocked.retain(|blocked| !blocked.callback.is_closed());
+                    if let Err(err) = self.shared.expire_cycle(EXPIRE_CYCLE_KEYS) {
+                        log::error!("failed to remove expired keys, err = {}", err);
                     }
-                };
+                    continue;
+                }
+                _ = self.shutdown_hook.receive() => {
+                    return Ok(self.shared);
+                }
+            };
 
-                if let Some((cmd, callback)) = maybe_cmd {
-                    let result = match cmd.apply(&mut self.shared).await {
-                        Ok(res) => res,
-                        Err(err) => Frame::Error(format!("{}", err)),
-                    };
+            let (mut cmd, callback) = match maybe_cmd {
+                Some(request) => request,
+                None => return Ok(self.shared),
+            };
 
+            Stats::incr(&self.shared.stats().total_commands_processed);
+
+            // expired keys are deleted before the command sees them, notifying `expired`
+            for key in cmd.keys() {
+                if let Err(err) = self.shared.expire_if_needed(key) {
+                    log::error!("failed to remove expired key {}, err = {}", key, err);
+                }
+            }
+
+            let written: Vec<String> = match cmd.is_write() {
+                false => Vec::new(),
+                true => cmd.keys().iter().map(|key| key.to_string()).collect(),
+            };
+            let timeout = cmd.block_timeout();
+            let retry = timeout.and_then(|_| cmd.blocked_copy(&self.shared));
+
+            let result = match cmd {
+                Command::EXEC(exec) => self.exec_transaction(exec).await,
+                cmd => self.apply(cmd),
+            };
+
+            match retry {
+                // a blocking command replies nil when it found nothing to wait for
+                Some(cmd) if matches!(result, Frame::Nil) => self.blocked.push(Blocked {
+                    cmd,
+                    callback,
+                    deadline: timeout.flatten().map(|timeout| Instant::now() + timeout),
+                }),
+                _ => {
                     let _ = callback.send(CommandResult { frame: result }).await;
                 }
             }
-            Ok(())
+            if !written.is_empty() && !self.blocked.is_empty() {
+                self.retry_blocked(&written).await;
+            }
+        }
+    }
+
+    /// Applies the commands queued by a transaction back to back, their writes going to RocksDB
+    /// in a single batch once all are applied. Blocking commands don't wait in a transaction.
+    /// Replies nil without applying anything when a watched key was modified.
+    async fn exec_transaction(&mut self, exec: Exec) -> Frame {
+        let (commands, watched) = exec.into_parts();
+        let modified = self.shared.is_modified(&watched);
+        // the keys are forgotten whether the transaction runs or not
+        let keys: Vec<String> = watched.into_iter().map(|(key, _)| key).collect();
+        self.shared.unwatch(&keys);
+        if modified {
+            return Frame::Nil;
+        }
+
+        self.shared.begin();
+        let mut frames = Vec::new();
+        for cmd in commands {
+            Stats::incr(&self.shared.stats().total_commands_processed);
+            frames.push(self.apply(cmd));
+        }
+        match self.shared.commit() {
+            Ok(()) => Frame::Array(frames),
+            Err(err) => Frame::Error(format!("{}", err)),
+        }
+    }
+
+    /// Applies a command, scripts going to the scripting engine.
+    fn apply(&mut self, cmd: Command) -> Frame {
+        let result = match cmd {
+            Command::EVAL(eval) | Command::EVALSHA(eval) | Command::EVAL_RO(eval) | Command::EVALSHA_RO(eval) => {
+                // the connections are still served meanwhile, to answer BUSY and SCRIPT KILL
+                return task::block_in_place(|| self.scripting.eval(&mut self.shared, &eval));
+            }
+            Command::FCALL(fcall) | Command::FCALL_RO(fcall) => {
+                return task::block_in_place(|| self.scripting.fcall(&mut self.shared, &fcall));
+            }
+            Command::SCRIPT(script) => return self.scripting.script(script),
+            Command::FUNCTION(function) => return self.scripting.function(&mut self.shared, function),
+            cmd => cmd.apply(&mut self.shared),
+        };
+        match result {
+            Ok(frame) => frame,
+            Err(err) => Frame::Error(format!("{}", err)),
+        }
+    }
+
+    /// Retries the blocked commands waiting on one of `written`, in the order they blocked.
+    async fn retry_blocked(&mut self, written: &[String]) {
+        let mut still_blocked = Vec::with_capacity(self.blocked.len());
+        for mut blocked in std::mem::take(&mut self.blocked) {
+            if blocked.callback.is_closed() {
+                continue;
+            }
+            if !blocked.cmd.keys().iter().any(|key| written.iter().any(|written| written == key)) {
+                still_blocked.push(blocked);
+                continue;
+            }
+            let cmd = match blocked.cmd.blocked_copy(&self.shared) {
+                Some(cmd) => cmd,
+                None => continue,
+            };
+            let frame = match cmd.apply(&mut self.shared) {
+                Ok(frame) => frame,
+                Err(err) => Frame::Error(format!("{}", err)),
+            };
+            match frame {
+                Frame::Nil => still_blocked.push(blocked),
+                frame => {
+                    let _ = blocked.callback.send(CommandResult { frame }).await;
+                }
+            }
+        }
+        self.blocked = still_blocked;
+    }
+
+    /// Replies nil to the blocked commands whose timeout passed.
+    async fn time_out_blocked(&mut self) {
+        let now = Instant::now();
+        let (timed_out, still_blocked) = std::mem::take(&mut self.blocked)
+            .into_iter()
+            .partition(|blocked| matches!(blocked.deadline, Some(deadline) if deadline <= now));
+        self.blocked = still_blocked;
+        for blocked in timed_out {
+            let _ = blocked.callback.send(CommandResult { frame: Frame::Nil }).await;
+        }
     }
 }
 
@@ -0,0 +1,332 @@
+//! Layout of the values stored under each key.
+//!
+//! Every value starts with a header: the data type byte followed by the expire time
+//! as big endian unix milliseconds, 0 when the key doesn't expire. The payload follows.
+//!
+//! Values too large to rewrite on every change keep their parts in the `sub` column family,
+//! under the key prefixed by its length so the parts of one key form a single range.
+
+use crate::utils;
+
+pub const STRING: u8 = 0;
+/// A string split in `CHUNK_SIZE` parts under the sub column family, the payload being its length.
+/// Missing or short chunks read as zero bytes.
+pub const CHUNKED_STRING: u8 = 1;
+/// A sorted set, the payload being its member count. Every member has two sub entries, `m` with
+/// the member mapping to its score and `s` with the sortable score then the member, for ranges.
+pub const ZSET: u8 = 2;
+/// A stream, the payload being its metadata starting with the entry count. Every entry is a sub
+/// entry `e` followed by its ID, consumer groups keep their state under `g`, `c`, `p` and `q`,
+/// see `stream`.
+pub const STREAM: u8 = 3;
+
+/// The column family of the parts of large values.
+pub const SUB_CF: &str = "sub";
+
+/// The column family of the function libraries, their code by library name.
+pub const FUNCTIONS_CF: &str = "functions";
+
+/// The column family of the facts about the database itself, like `FORMAT_KEY`.
+pub const META_CF: &str = "meta";
+
+/// The key of the format version in the meta column family. Databases without it were written
+/// before values had a header, their values being plain strings.
+pub const FORMAT_KEY: &[u8] = b"format";
+
+/// The last key given a header while migrating a database of an older format, in the meta column
+/// family until the migration is done. A migration stopped halfway resumes after it.
+pub const MIGRATED_KEY: &[u8] = b"migrated";
+
+/// The version of the layout described here, bumped on incompatible changes.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// The size of the chunks of a chunked string, bit commands rewrite one chunk at a time.
+pub const CHUNK_SIZE: usize = 4096;
+
+const HEADER_LEN: usize = 9;
+
+/// An empty string that expired at the epoch, what compactions shrink expired values to.
+pub static EXPIRED_STUB: [u8; HEADER_LEN] = [STRING, 0, 0, 0, 0, 0, 0, 0, 1];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+    pub kind: u8,
+    /// Unix time in milliseconds, `None` for persistent keys.
+    pub expire_at: Option<u64>,
+}
+
+impl Header {
+    pub fn new(kind: u8, expire_at: Option<u64>) -> Header {
+        Header { kind, expire_at }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expire_at, Some(at) if at <= now)
+    }
+
+    /// The remaining time to live in milliseconds, -1 for persistent keys.
+    pub fn pttl(&self) -> i64 {
+        match self.expire_at {
+            Some(at) => at.saturating_sub(utils::now_millis()) as i64,
+            None => -1,
+        }
+    }
+}
+
+pub fn encode(header: Header, payload: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(HEADER_LEN + payload.len());
+    raw.push(header.kind);
+    raw.extend_from_slice(&header.expire_at.unwrap_or(0).to_be_bytes());
+    raw.extend_from_slice(payload);
+    raw
+}
+
+pub fn decode(raw: &[u8]) -> crate::Result<(Header, &[u8])> {
+    if raw.len() < HEADER_LEN {
+        return Err("ERR corrupted value, header is too short".into());
+    }
+    let mut expire_at = [0u8; 8];
+    expire_at.copy_from_slice(&raw[1..HEADER_LEN]);
+    let expire_at = match u64::from_be_bytes(expire_at) {
+        0 => None,
+        at => Some(at),
+    };
+    Ok((Header::new(raw[0], expire_at), &raw[HEADER_LEN..]))
+}
+
+/// Whether a raw value has expired, used by the compaction filter.
+pub fn is_expired(raw: &[u8], now: u64) -> bool {
+    match decode(raw) {
+        Ok((header, _)) => header.is_expired(now),
+        Err(_) => false,
+    }
+}
+
+/// Whether values of `kind` have parts in the sub column family.
+pub fn has_sub_entries(kind: u8) -> bool {
+    kind == CHUNKED_STRING || kind == ZSET || kind == STREAM
+}
+
+/// The key of a part of `key` in the sub column family.
+pub fn sub_key(key: &str, suffix: &[u8]) -> Vec<u8> {
+    let mut sub_key = Vec::with_capacity(4 + key.len() + suffix.len());
+    sub_key.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    sub_key.extend_from_slice(key.as_bytes());
+    sub_key.extend_from_slice(suffix);
+    sub_key
+}
+
+/// The key a sub key is a part of.
+pub fn sub_key_owner(sub_key: &[u8]) -> Option<&[u8]> {
+    let mut len = [0u8; 4];
+    len.copy_from_slice(sub_key.get(..4)?);
+    sub_key.get(4..4 + u32::from_be_bytes(len) as usize)
+}
+
+/// The range holding every part of `key`, start inclusive and end exclusive.
+pub fn sub_range(key: &str) -> (Vec<u8>, Vec<u8>) {
+    let start = sub_key(key, &[]);
+    let end = prefix_end(&start);
+    (start, end)
+}
+
+/// The first key after every key starting with `prefix`.
+pub fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    // sub keys start with a length prefix that never is all 0xff, so some byte can always be incremented
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            break;
+        }
+    }
+    end
+}
+
+/// The sub key of a chunk of a chunked string.
+pub fn chunk_key(key: &str, index: usize) -> Vec<u8> {
+    sub_key(key, &(index as u32).to_be_bytes())
+}
+
+/// The chunk index a chunk key ends with.
+pub fn chunk_index(sub_key: &[u8]) -> usize {
+    let mut index = [0u8; 4];
+    index.copy_from_slice(&sub_key[sub_key.len() - 4..]);
+    u32::from_be_bytes(index) as usize
+}
+
+/// The length of a chunked string or the member count of a collection, the payload of both.
+pub fn stored_len(payload: &[u8]) -> usize {
+    let mut len = [0u8; 8];
+    if let Some(bytes) = payload.get(..8) {
+        len.copy_from_slice(bytes);
+    }
+    u64::from_be_bytes(len) as usize
+}
+
+/// The sub key mapping a sorted set member to its score.
+pub fn member_key(key: &str, member: &[u8]) -> Vec<u8> {
+    let mut suffix = Vec::with_capacity(1 + member.len());
+    suffix.push(b'm');
+    suffix.extend_from_slice(member);
+    sub_key(key, &suffix)
+}
+
+/// The sub key ordering a sorted set member by score, `member` empty for the start of a score.
+pub fn score_key(key: &str, score: f64, member: &[u8]) -> Vec<u8> {
+    // adding zero turns -0 into 0, both have to sort as one
+    let bits = (score + 0.0).to_bits();
+    let bits = if score < 0.0 { !bits } else { bits | 1 << 63 };
+    let mut suffix = Vec::with_capacity(9 + member.len());
+    suffix.push(b's');
+    suffix.extend_from_slice(&bits.to_be_bytes());
+    suffix.extend_from_slice(member);
+    sub_key(key, &suffix)
+}
+
+/// The score and member of a score key of `key`.
+pub fn split_score_key<'a>(key: &str, score_key: &'a [u8]) -> (f64, &'a [u8]) {
+    let at = 4 + key.len() + 1;
+    let mut bits = [0u8; 8];
+    bits.copy_from_slice(&score_key[at..at + 8]);
+    let bits = u64::from_be_bytes(bits);
+    let bits = if bits >> 63 == 1 { bits & !(1 << 63) } else { !bits };
+    (f64::from_bits(bits), &score_key[at + 8..])
+}
+
+/// The sub key of a stream entry, `id` being its big endian milliseconds and sequence number.
+pub fn entry_key(key: &str, id: &[u8; 16]) -> Vec<u8> {
+    let mut suffix = Vec::with_capacity(17);
+    suffix.push(b'e');
+    suffix.extend_from_slice(id);
+    sub_key(key, &suffix)
+}
+
+/// The end of the entries of a stream, exclusive.
+pub fn entries_end(key: &str) -> Vec<u8> {
+    sub_key(key, b"f")
+}
+
+/// The ID bytes an entry key, a pending key or a consumer pending key ends with.
+pub fn entry_id(entry_key: &[u8]) -> &[u8] {
+    &entry_key[entry_key.len() - 16..]
+}
+
+/// A name as the part of a sub key it is followed by something else in, length prefixed.
+fn push_name(suffix: &mut Vec<u8>, name: &str) {
+    suffix.extend_from_slice(&(name.len() as u32).to_be_bytes());
+    suffix.extend_from_slice(name.as_bytes());
+}
+
+/// The sub key of the state of a consumer group, `group` empty for the start of every group.
+pub fn group_key(key: &str, group: &str) -> Vec<u8> {
+    let mut suffix = Vec::with_capacity(1 + group.len());
+    suffix.push(b'g');
+    suffix.extend_from_slice(group.as_bytes());
+    sub_key(key, &suffix)
+}
+
+/// The group name a group key ends with.
+pub fn group_name<'a>(key: &str, group_key: &'a [u8]) -> &'a [u8] {
+    &group_key[4 + key.len() + 1..]
+}
+
+/// The sub key of a consumer of a group, `consumer` empty for the start of the group's consumers.
+pub fn consumer_key(key: &str, group: &str, consumer: &str) -> Vec<u8> {
+    let mut suffix = Vec::with_capacity(5 + group.len() + consumer.len());
+    suffix.push(b'c');
+    push_name(&mut suffix, group);
+    suffix.extend_from_slice(consumer.as_bytes());
+    sub_key(key, &suffix)
+}
+
+/// The consumer name a consumer key ends with.
+pub fn consumer_name<'a>(key: &str, group: &str, consumer_key: &'a [u8]) -> &'a [u8] {
+    &consumer_key[4 + key.len() + 5 + group.len()..]
+}
+
+/// The sub key of an entry in the pending entries list of a group, `id` `None` for the start of the list.
+pub fn pending_key(key: &str, group: &str, id: Option<&[u8; 16]>) -> Vec<u8> {
+    let mut suffix = Vec::with_capacity(21 + group.len());
+    suffix.push(b'p');
+    push_name(&mut suffix, group);
+    suffix.extend_from_slice(id.map_or(&[][..], |id| &id[..]));
+    sub_key(key, &suffix)
+}
+
+/// The sub key indexing a pending entry by the consumer it was delivered to. `id` `None` for the
+/// start of the consumer's entries, `consumer` `None` as well for the start of the whole group's.
+pub fn consumer_pending_key(key: &str, group: &str, consumer: Option<&str>, id: Option<&[u8; 16]>) -> Vec<u8> {
+    let consumer_len = consumer.map_or(0, str::len);
+    let mut suffix = Vec::with_capacity(25 + group.len() + consumer_len);
+    suffix.push(b'q');
+    push_name(&mut suffix, group);
+    if let Some(consumer) = consumer {
+        push_name(&mut suffix, consumer);
+    }
+    suffix.extend_from_slice(id.map_or(&[][..], |id| &id[..]));
+    sub_key(key, &suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers() {
+        let raw = encode(Header::new(STRING, Some(1_700_000_000_000)), b"value");
+        assert_eq!(raw.len(), HEADER_LEN + 5);
+        let (header, payload) = decode(&raw).unwrap();
+        assert_eq!((header.kind, header.expire_at, payload), (STRING, Some(1_700_000_000_000), &b"value"[..]));
+        let raw = encode(Header::new(ZSET, None), b"");
+        let (header, payload) = decode(&raw).unwrap();
+        assert_eq!((header.kind, header.expire_at, payload), (ZSET, None, &b""[..]));
+        assert_eq!(decode(&raw[..HEADER_LEN - 1]).unwrap_err().to_string(), "ERR corrupted value, header is too short");
+    }
+
+    #[test]
+    fn expiry() {
+        let raw = encode(Header::new(STRING, Some(1000)), b"v");
+        assert!(!is_expired(&raw, 999));
+        assert!(is_expired(&raw, 1000));
+        assert!(!is_expired(&encode(Header::new(STRING, None), b"v"), u64::MAX));
+        // a value too short for a header is left to the reads to report
+        assert!(!is_expired(b"v", u64::MAX));
+        assert!(is_expired(&EXPIRED_STUB, 1));
+        assert_eq!(decode(&EXPIRED_STUB).unwrap().1, b"");
+        assert_eq!(Header::new(STRING, None).pttl(), -1);
+        assert_eq!(Header::new(STRING, Some(1)).pttl(), 0);
+    }
+
+    #[test]
+    fn score_keys_sort_by_score() {
+        let scores = [f64::NEG_INFINITY, -1e300, -2.5, -1.0, -f64::MIN_POSITIVE, 0.0, f64::MIN_POSITIVE, 1.0, 2.5, 1e300, f64::INFINITY];
+        let keys: Vec<Vec<u8>> = scores.iter().map(|score| score_key("z", *score, b"m")).collect();
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+        for (score, key) in scores.iter().zip(keys.iter()) {
+            assert_eq!(split_score_key("z", key), (*score, &b"m"[..]));
+        }
+        // -0 and 0 are one score, members of a score sort by name
+        assert_eq!(score_key("z", -0.0, b"m"), score_key("z", 0.0, b"m"));
+        assert!(score_key("z", 1.0, b"a") < score_key("z", 1.0, b"b"));
+        assert!(score_key("z", 1.0, b"") < score_key("z", 1.0, b"a"));
+        assert!(member_key("z", b"a") < score_key("z", f64::NEG_INFINITY, b""));
+    }
+
+    #[test]
+    fn sub_keys_of_a_key_form_a_range() {
+        let (start, end) = sub_range("z");
+        for sub_key in [member_key("z", b"m"), score_key("z", 1.0, b"m"), chunk_key("z", 7)] {
+            assert!(start <= sub_key && sub_key < end);
+            assert_eq!(sub_key_owner(&sub_key), Some(&b"z"[..]));
+        }
+        // keys that are prefixes of one another don't share parts
+        let (longer_start, _) = sub_range("zz");
+        assert!(longer_start >= end);
+        assert_eq!(chunk_index(&chunk_key("z", 7)), 7);
+        assert_eq!(prefix_end(&[1, 0xff, 0xff]), [2]);
+        assert_eq!(sub_key_owner(&[0, 0]), None);
+        assert_eq!(stored_len(&42u64.to_be_bytes()), 42);
+        assert_eq!(stored_len(b""), 0);
+    }
+}
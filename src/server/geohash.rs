@@ -0,0 +1,375 @@
+//! Geohash math of the geo commands, done the way redis does so scores, hashes and distances match.
+//!
+//! A position is stored in a sorted set with the 52 bit interleaved geohash as score, latitude on
+//! the even bits and longitude on the odd ones. A search turns into the score ranges of the geohash
+//! box around the center and its 8 neighbors, members in range are then checked one by one.
+
+pub const LONG_MIN: f64 = -180.0;
+pub const LONG_MAX: f64 = 180.0;
+pub const LAT_MIN: f64 = -85.05112878;
+pub const LAT_MAX: f64 = 85.05112878;
+
+const STEP_MAX: u32 = 26;
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+const MERCATOR_MAX: f64 = 20037726.37;
+const ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// What a search covers, in meters.
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Radius(f64),
+    Box(f64, f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HashBits {
+    bits: u64,
+    step: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Area {
+    long_min: f64,
+    long_max: f64,
+    lat_min: f64,
+    lat_max: f64,
+}
+
+pub fn is_valid(long: f64, lat: f64) -> bool {
+    (LONG_MIN..=LONG_MAX).contains(&long) && (LAT_MIN..=LAT_MAX).contains(&lat)
+}
+
+/// The score a position is stored with.
+pub fn score(long: f64, lat: f64) -> f64 {
+    encode(long, lat, (LONG_MIN, LONG_MAX), (LAT_MIN, LAT_MAX), STEP_MAX).bits as f64
+}
+
+/// The position a score stands for, the center of its geohash box.
+pub fn position(score: f64) -> (f64, f64) {
+    let hash = HashBits { bits: score as u64, step: STEP_MAX };
+    let area = decode(hash, (LONG_MIN, LONG_MAX), (LAT_MIN, LAT_MAX));
+    let long = ((area.long_min + area.long_max) / 2.0).clamp(LONG_MIN, LONG_MAX);
+    let lat = ((area.lat_min + area.lat_max) / 2.0).clamp(LAT_MIN, LAT_MAX);
+    (long, lat)
+}
+
+/// The standard 11 character geohash of a score, as GEOHASH replies.
+pub fn hash_string(score: f64) -> String {
+    let (long, lat) = position(score);
+    let hash = encode(long, lat, (-180.0, 180.0), (-90.0, 90.0), STEP_MAX);
+    (0..11)
+        .map(|i| match i {
+            // the score only has 52 bits, the last character is always 0 like in redis
+            10 => '0',
+            _ => ALPHABET[((hash.bits >> (52 - (i + 1) * 5)) & 0x1f) as usize] as char,
+        })
+        .collect()
+}
+
+/// The distance in meters between two positions on the earth, taken as a sphere.
+pub fn distance(long1: f64, lat1: f64, long2: f64, lat2: f64) -> f64 {
+    let v = ((long2.to_radians() - long1.to_radians()) / 2.0).sin();
+    if v == 0.0 {
+        return lat_distance(lat1, lat2);
+    }
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let u = ((lat2 - lat1) / 2.0).sin();
+    let a = u * u + lat1.cos() * lat2.cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+fn lat_distance(lat1: f64, lat2: f64) -> f64 {
+    EARTH_RADIUS_IN_METERS * (lat2.to_radians() - lat1.to_radians()).abs()
+}
+
+/// The distance of a position from the center when the shape around it covers the position.
+pub fn distance_in_shape(long: f64, lat: f64, shape: Shape, point_long: f64, point_lat: f64) -> Option<f64> {
+    match shape {
+        Shape::Radius(radius) => Some(distance(long, lat, point_long, point_lat)).filter(|d| *d <= radius),
+        Shape::Box(width, height) => {
+            if lat_distance(point_lat, lat) > height / 2.0 {
+                return None;
+            }
+            if distance(point_long, point_lat, long, point_lat) > width / 2.0 {
+                return None;
+            }
+            Some(distance(long, lat, point_long, point_lat))
+        }
+    }
+}
+
+/// The score ranges, start inclusive and end exclusive, holding every position the shape covers.
+pub fn search_ranges(long: f64, lat: f64, shape: Shape) -> Vec<(f64, f64)> {
+    let (radius, width, height) = match shape {
+        Shape::Radius(radius) => (radius, radius, radius),
+        Shape::Box(width, height) => ((width / 2.0).hypot(height / 2.0), width / 2.0, height / 2.0),
+    };
+    let lat_delta = (height / EARTH_RADIUS_IN_METERS).to_degrees();
+    let long_delta_top = (width / EARTH_RADIUS_IN_METERS / (lat + lat_delta).to_radians().cos()).to_degrees();
+    let long_delta_bottom = (width / EARTH_RADIUS_IN_METERS / (lat - lat_delta).to_radians().cos()).to_degrees();
+    let long_delta = if lat < 0.0 { long_delta_bottom } else { long_delta_top };
+    let bounds = Area {
+        long_min: long - long_delta,
+        long_max: long + long_delta,
+        lat_min: lat - lat_delta,
+        lat_max: lat + lat_delta,
+    };
+
+    let mut step = estimate_step(radius, lat);
+    let mut hash = encode_wgs84(long, lat, step);
+    let mut neighbors = neighbors_of(hash);
+    // when the center sits near an edge of its box a neighbor may not reach far enough
+    let [north, south, east, west] = [neighbors[0], neighbors[1], neighbors[2], neighbors[3]].map(decode_wgs84);
+    if step > 1
+        && (north.lat_max < bounds.lat_max
+            || south.lat_min > bounds.lat_min
+            || east.long_max < bounds.long_max
+            || west.long_min > bounds.long_min)
+    {
+        step -= 1;
+        hash = encode_wgs84(long, lat, step);
+        neighbors = neighbors_of(hash);
+    }
+
+    // neighbors entirely out of the bounds are skipped
+    let area = decode_wgs84(hash);
+    let [north, south, east, west, north_east, north_west, south_east, south_west] = neighbors.map(Some);
+    let mut areas = vec![Some(hash), north, south, east, west, north_east, north_west, south_east, south_west];
+    if step >= 2 {
+        let mut skip = |indexes: [usize; 3]| indexes.iter().for_each(|i| areas[*i] = None);
+        if area.lat_min < bounds.lat_min {
+            skip([2, 7, 8]);
+        }
+        if area.lat_max > bounds.lat_max {
+            skip([1, 5, 6]);
+        }
+        if area.long_min < bounds.long_min {
+            skip([4, 8, 6]);
+        }
+        if area.long_max > bounds.long_max {
+            skip([3, 7, 5]);
+        }
+    }
+
+    let mut ranges = Vec::new();
+    for hash in areas.into_iter().flatten() {
+        let shift = 52 - hash.step * 2;
+        let range = ((hash.bits << shift) as f64, ((hash.bits + 1) << shift) as f64);
+        // with a large radius neighbors can be the same box
+        if !ranges.contains(&range) {
+            ranges.push(range);
+        }
+    }
+    ranges
+}
+
+/// The coarsest step whose boxes still are about the size of the radius.
+fn estimate_step(mut radius: f64, lat: f64) -> u32 {
+    if radius == 0.0 {
+        return STEP_MAX;
+    }
+    let mut step: i32 = 1;
+    while radius < MERCATOR_MAX {
+        radius *= 2.0;
+        step += 1;
+    }
+    step -= 2;
+    // boxes get narrower towards the poles
+    if !(-66.0..=66.0).contains(&lat) {
+        step -= 1;
+        if !(-80.0..=80.0).contains(&lat) {
+            step -= 1;
+        }
+    }
+    step.clamp(1, STEP_MAX as i32) as u32
+}
+
+fn encode_wgs84(long: f64, lat: f64, step: u32) -> HashBits {
+    encode(long, lat, (LONG_MIN, LONG_MAX), (LAT_MIN, LAT_MAX), step)
+}
+
+fn decode_wgs84(hash: HashBits) -> Area {
+    decode(hash, (LONG_MIN, LONG_MAX), (LAT_MIN, LAT_MAX))
+}
+
+fn encode(long: f64, lat: f64, long_range: (f64, f64), lat_range: (f64, f64), step: u32) -> HashBits {
+    let lat_offset = (lat - lat_range.0) / (lat_range.1 - lat_range.0) * (1u64 << step) as f64;
+    let long_offset = (long - long_range.0) / (long_range.1 - long_range.0) * (1u64 << step) as f64;
+    HashBits {
+        bits: spread(lat_offset as u32) | spread(long_offset as u32) << 1,
+        step,
+    }
+}
+
+fn decode(hash: HashBits, long_range: (f64, f64), lat_range: (f64, f64)) -> Area {
+    let (lat, long) = (squash(hash.bits) as f64, squash(hash.bits >> 1) as f64);
+    let cells = (1u64 << hash.step) as f64;
+    let (long_scale, lat_scale) = (long_range.1 - long_range.0, lat_range.1 - lat_range.0);
+    Area {
+        long_min: long_range.0 + (long / cells) * long_scale,
+        long_max: long_range.0 + ((long + 1.0) / cells) * long_scale,
+        lat_min: lat_range.0 + (lat / cells) * lat_scale,
+        lat_max: lat_range.0 + ((lat + 1.0) / cells) * lat_scale,
+    }
+}
+
+/// North, south, east, west, north east, north west, south east and south west of a box.
+fn neighbors_of(hash: HashBits) -> [HashBits; 8] {
+    let moved = |x: i8, y: i8| move_y(move_x(hash, x), y);
+    [
+        moved(0, 1),
+        moved(0, -1),
+        moved(1, 0),
+        moved(-1, 0),
+        moved(1, 1),
+        moved(-1, 1),
+        moved(1, -1),
+        moved(-1, -1),
+    ]
+}
+
+/// Moves a box east or west, wrapping around.
+fn move_x(hash: HashBits, d: i8) -> HashBits {
+    if d == 0 {
+        return hash;
+    }
+    let mut x = hash.bits & 0xaaaaaaaaaaaaaaaa;
+    let y = hash.bits & 0x5555555555555555;
+    let zz = 0x5555555555555555u64 >> (64 - hash.step * 2);
+    if d > 0 {
+        x = x.wrapping_add(zz + 1);
+    } else {
+        x = (x | zz).wrapping_sub(zz + 1);
+    }
+    x &= 0xaaaaaaaaaaaaaaaau64 >> (64 - hash.step * 2);
+    HashBits { bits: x | y, step: hash.step }
+}
+
+/// Moves a box north or south.
+fn move_y(hash: HashBits, d: i8) -> HashBits {
+    if d == 0 {
+        return hash;
+    }
+    let x = hash.bits & 0xaaaaaaaaaaaaaaaa;
+    let mut y = hash.bits & 0x5555555555555555;
+    let zz = 0xaaaaaaaaaaaaaaaau64 >> (64 - hash.step * 2);
+    if d > 0 {
+        y = y.wrapping_add(zz + 1);
+    } else {
+        y = (y | zz).wrapping_sub(zz + 1);
+    }
+    y &= 0x5555555555555555u64 >> (64 - hash.step * 2);
+    HashBits { bits: x | y, step: hash.step }
+}
+
+/// Spreads the bits of `v` over the even bits of the result.
+fn spread(v: u32) -> u64 {
+    let mut x = v as u64;
+    x = (x | x << 16) & 0x0000ffff0000ffff;
+    x = (x | x << 8) & 0x00ff00ff00ff00ff;
+    x = (x | x << 4) & 0x0f0f0f0f0f0f0f0f;
+    x = (x | x << 2) & 0x3333333333333333;
+    (x | x << 1) & 0x5555555555555555
+}
+
+/// Gathers the even bits of `x`, the reverse of `spread`.
+fn squash(x: u64) -> u32 {
+    let mut x = x & 0x5555555555555555;
+    x = (x | x >> 1) & 0x3333333333333333;
+    x = (x | x >> 2) & 0x0f0f0f0f0f0f0f0f;
+    x = (x | x >> 4) & 0x00ff00ff00ff00ff;
+    x = (x | x >> 8) & 0x0000ffff0000ffff;
+    ((x | x >> 16) & 0x00000000ffffffff) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALERMO: (f64, f64) = (13.361389, 38.115556);
+    const CATANIA: (f64, f64) = (15.087269, 37.502669);
+
+    /// The position a member added at a position is read back at.
+    fn stored((long, lat): (f64, f64)) -> (f64, f64) {
+        position(score(long, lat))
+    }
+
+    #[test]
+    fn scores_and_hashes_like_redis() {
+        assert_eq!(score(PALERMO.0, PALERMO.1), 3479099956230698.0);
+        assert_eq!(score(CATANIA.0, CATANIA.1), 3479447370796909.0);
+        assert_eq!(hash_string(score(PALERMO.0, PALERMO.1)), "sqc8b49rny0");
+        assert_eq!(hash_string(score(CATANIA.0, CATANIA.1)), "sqdtr74hyu0");
+    }
+
+    #[test]
+    fn positions_like_redis() {
+        let (long, lat) = stored(PALERMO);
+        assert_eq!(format!("{:.17}", long), "13.36138933897018433");
+        assert_eq!(format!("{:.17}", lat), "38.11555639549629859");
+    }
+
+    #[test]
+    fn distances_like_redis() {
+        let ((long1, lat1), (long2, lat2)) = (stored(PALERMO), stored(CATANIA));
+        assert_eq!(format!("{:.4}", distance(long1, lat1, long2, lat2)), "166274.1516");
+        assert_eq!(format!("{:.4}", distance(15.0, 37.0, long1, lat1) / 1000.0), "190.4424");
+        assert_eq!(format!("{:.4}", distance(15.0, 37.0, long2, lat2) / 1000.0), "56.4413");
+        assert_eq!(distance(long1, lat1, long1, lat1), 0.0);
+    }
+
+    #[test]
+    fn radius_and_box_filtering() {
+        let ((long1, lat1), (long2, lat2)) = (stored(PALERMO), stored(CATANIA));
+        let within = |shape| {
+            (
+                distance_in_shape(15.0, 37.0, shape, long1, lat1).is_some(),
+                distance_in_shape(15.0, 37.0, shape, long2, lat2).is_some(),
+            )
+        };
+        assert_eq!(within(Shape::Radius(200_000.0)), (true, true));
+        assert_eq!(within(Shape::Radius(100_000.0)), (false, true));
+        assert_eq!(within(Shape::Box(400_000.0, 400_000.0)), (true, true));
+        // Palermo is about 145 km west of the center
+        assert_eq!(within(Shape::Box(250_000.0, 400_000.0)), (false, true));
+    }
+
+    #[test]
+    fn search_ranges_cover_the_members_in_the_shape() {
+        let covered = |shape, score: f64| search_ranges(15.0, 37.0, shape).iter().any(|(from, to)| (*from..*to).contains(&score));
+        for shape in [Shape::Radius(200_000.0), Shape::Box(400_000.0, 400_000.0)] {
+            assert!(covered(shape, score(PALERMO.0, PALERMO.1)));
+            assert!(covered(shape, score(CATANIA.0, CATANIA.1)));
+        }
+    }
+
+    #[test]
+    fn neighbors_are_adjacent_boxes() {
+        let hash = encode_wgs84(PALERMO.0, PALERMO.1, 10);
+        let center = decode_wgs84(hash);
+        let [north, south, east, west, north_east, _, _, south_west] = neighbors_of(hash).map(decode_wgs84);
+        assert_eq!(north.lat_min, center.lat_max);
+        assert_eq!(south.lat_max, center.lat_min);
+        assert_eq!(east.long_min, center.long_max);
+        assert_eq!(west.long_max, center.long_min);
+        assert_eq!((north_east.long_min, north_east.lat_min), (center.long_max, center.lat_max));
+        assert_eq!((south_west.long_max, south_west.lat_max), (center.long_min, center.lat_min));
+        assert_eq!(move_x(move_x(hash, 1), -1), hash);
+        assert_eq!(move_y(move_y(hash, -1), 1), hash);
+    }
+
+    #[test]
+    fn boxes_wrap_around_east_and_west() {
+        let hash = encode_wgs84(179.9, 0.0, 4);
+        let east = decode_wgs84(move_x(hash, 1));
+        assert_eq!(east.long_min, LONG_MIN);
+    }
+
+    #[test]
+    fn spread_and_squash_are_reversed() {
+        for v in [0, 1, 0x5555, 0xffff_ffff, 123_456_789] {
+            assert_eq!(squash(spread(v)), v);
+            assert_eq!(spread(v) & 0xaaaa_aaaa_aaaa_aaaa, 0);
+        }
+    }
+}
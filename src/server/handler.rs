@@ -1,23 +1,76 @@
+use crate::acl::Acl;
+use crate::command::exec::Exec;
+use crate::command::script::Script;
+use crate::command::function::Function;
+use crate::command::unwatch::Unwatch;
+use crate::command::watch::Watch;
+use crate::command::{Command, Execable};
+use crate::config::Config;
 use crate::protocol::ParseError;
 use crate::protocol::parse::Parse;
 use crate::server::db::{CommandResult, Db};
+use crate::server::clients::{Client, Clients};
+use crate::server::pubsub::Subscription;
+use crate::server::shutdown::ShutdownRequest;
 use crate::server::Connection;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
+use tokio::time;
 
 use crate::protocol::frame::Frame;
 
 pub struct Handler {
     pub(crate) db: Arc<Db>,
+    pub(crate) config: Arc<Config>,
+    pub(crate) acl: Arc<Acl>,
+    /// the ACL user, `None` until the connection authenticates
+    pub(crate) user: Option<String>,
+    pub(crate) clients: Arc<Clients>,
+    /// this connection's entry in `clients`
+    pub(crate) client: Arc<Client>,
+    pub(crate) shutdown_request: Arc<ShutdownRequest>,
     pub(crate) connection: Connection,
     pub(crate) shutdown: ShutdownHook,
+    /// the commands queued since MULTI, `None` outside a transaction
+    pub(crate) transaction: Option<Transaction>,
+    /// the keys WATCHed for the next EXEC with their versions at the time
+    pub(crate) watching: Vec<(String, u64)>,
+    /// the channels, patterns and shard channels subscribed to, in subscriber mode when there are some
+    pub(crate) subscription: Subscription,
+}
+
+/// The commands of a connection between MULTI and EXEC.
+#[derive(Default)]
+pub(crate) struct Transaction {
+    commands: Vec<Command>,
+    /// a command could not be queued, EXEC discards the transaction
+    aborted: bool,
 }
 
 impl Handler {
     pub async fn run(&mut self) -> crate::Result<()> {
         while !self.shutdown.is_shutdown() {
+            let timeout = self.config.props().timeout;
+            // subscribers wait for messages, they are never idle
+            let subscribed = self.subscription.count() > 0;
             let maybe_frame = tokio::select! {
                 res = self.connection.read_frame() => res?,
+                message = self.subscription.receive() => match message {
+                    Some(message) => {
+                        self.connection.write_and_flush_frame(message.into_frame()).await?;
+                        continue;
+                    }
+                    None => {
+                        log::warn!("closing client {} for falling behind on published messages", self.client.addr);
+                        return Ok(());
+                    }
+                },
+                _ = self.client.killed() => return Ok(()),
+                _ = time::sleep(Duration::from_secs(timeout)), if timeout > 0 && !subscribed => {
+                    log::debug!("closing idle client {}", self.client.addr);
+                    return Ok(());
+                }
                 _ = self.shutdown.receive() => {
                     // If a shutdown signal is received, return from `run`.
                     // This will result in the task terminating.
@@ -33,10 +86,199 @@ impl Handler {
             log::debug!("request frame : {:?}", frame);
 
             let parse = Parse::new(frame)?;
+            let command = parse.into_command();
 
-            match parse.into_command() {
-                Ok(cmd) => {
-                    if let Some(CommandResult { frame }) = self.db.exec(cmd).await? {
+            {
+                let mut state = self.client.state();
+                state.last_interaction = std::time::Instant::now();
+                if let Ok(cmd) = &command {
+                    state.last_cmd = cmd.name().to_lowercase();
+                }
+            }
+
+            // inside a transaction commands are queued for EXEC instead of run
+            let control = matches!(
+                command,
+                Ok(Command::MULTI(_) | Command::EXEC(_) | Command::DISCARD(_) | Command::WATCH(_))
+            );
+            if self.transaction.is_some() && !control {
+                let frame = self.queue(command);
+                self.connection.write_and_flush_frame(frame).await?;
+                continue;
+            }
+
+            match command {
+                Ok(Command::AUTH(auth)) => {
+                    let frame = auth.authenticate(&self.acl, &self.config, &mut self.user);
+                    if let Some(user) = &self.user {
+                        self.client.state().user = user.clone();
+                    }
+                    self.connection.write_and_flush_frame(frame).await?;
+                }
+                Ok(Command::HELLO(hello)) => {
+                    let mut resp3 = self.connection.is_resp3();
+                    let frame = hello.execute(&self.acl, &self.config, &mut self.user, &self.client, &mut resp3);
+                    if let Some(user) = &self.user {
+                        self.client.state().user = user.clone();
+                    }
+                    // the reply is already in the protocol asked for
+                    self.connection.set_resp3(resp3);
+                    self.connection.write_and_flush_frame(frame).await?;
+                }
+                _ if self.user.is_none() => {
+                    let err = Frame::Error("NOAUTH Authentication required.".to_string());
+                    self.connection.write_and_flush_frame(err).await?;
+                }
+                Ok(mut cmd) => {
+                    let user = self.user.clone().unwrap();
+                    if let Err(err) = self.acl.check(&user, &cmd) {
+                        self.connection.write_and_flush_frame(err).await?;
+                        continue;
+                    }
+                    cmd.set_script_user(&user);
+                    // RESP3 tells messages from replies by their type, subscribers can run anything
+                    if self.subscription.count() > 0 && !self.connection.is_resp3() {
+                        let frame = match cmd {
+                            Command::SUBSCRIBE(_)
+                            | Command::UNSUBSCRIBE(_)
+                            | Command::PSUBSCRIBE(_)
+                            | Command::PUNSUBSCRIBE(_)
+                            | Command::SSUBSCRIBE(_)
+                            | Command::SUNSUBSCRIBE(_) => None,
+                            Command::PING => {
+                                let pong = vec![Frame::Bulk(b"pong".to_vec()), Frame::Bulk(Vec::new())];
+                                Some(Frame::Array(pong))
+                            }
+                            _ => Some(Frame::Error(format!(
+                                "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING are allowed \
+                                 in this context",
+                                cmd.name()
+                            ))),
+                        };
+                        if let Some(frame) = frame {
+                            self.connection.write_and_flush_frame(frame).await?;
+                            continue;
+                        }
+                    }
+                    if let Command::SUBSCRIBE(subscribe) | Command::PSUBSCRIBE(subscribe) | Command::SSUBSCRIBE(subscribe) = cmd
+                    {
+                        for frame in subscribe.execute(&mut self.subscription) {
+                            self.connection.write_frame(frame).await?;
+                        }
+                        self.connection.flush().await?;
+                        continue;
+                    }
+                    if let Command::UNSUBSCRIBE(unsubscribe)
+                    | Command::PUNSUBSCRIBE(unsubscribe)
+                    | Command::SUNSUBSCRIBE(unsubscribe) = cmd
+                    {
+                        for frame in unsubscribe.execute(&mut self.subscription) {
+                            self.connection.write_frame(frame).await?;
+                        }
+                        self.connection.flush().await?;
+                        continue;
+                    }
+                    if let Command::ACL(acl) = cmd {
+                        let frame = acl.execute(&self.acl, &user);
+                        self.connection.write_and_flush_frame(frame).await?;
+                        continue;
+                    }
+                    if let Command::SHUTDOWN(shutdown) = cmd {
+                        match shutdown.execute(&self.shutdown_request, self.db.running_script()) {
+                            Some(frame) => self.connection.write_and_flush_frame(frame).await?,
+                            None => return Ok(()),
+                        }
+                        continue;
+                    }
+                    if let Command::CLIENT(client) = cmd {
+                        let frame = client.execute(&self.clients, &self.client);
+                        self.connection.write_and_flush_frame(frame).await?;
+                        continue;
+                    }
+                    if let Command::SCRIPT(Script::Kill) | Command::FUNCTION(Function::Kill) = cmd {
+                        let frame = self.db.running_script().kill();
+                        self.connection.write_and_flush_frame(frame).await?;
+                        continue;
+                    }
+
+                    if let Command::MULTI(_) = cmd {
+                        let frame = match self.transaction {
+                            Some(_) => Frame::Error("ERR MULTI calls can not be nested".to_string()),
+                            None => {
+                                self.transaction = Some(Transaction::default());
+                                Frame::Str(b"OK".to_vec())
+                            }
+                        };
+                        self.connection.write_and_flush_frame(frame).await?;
+                        continue;
+                    }
+                    if let Command::DISCARD(_) = cmd {
+                        let frame = match self.transaction.take() {
+                            Some(_) => {
+                                self.unwatch().await?;
+                                Frame::Str(b"OK".to_vec())
+                            }
+                            None => Frame::Error("ERR DISCARD without MULTI".to_string()),
+                        };
+                        self.connection.write_and_flush_frame(frame).await?;
+                        continue;
+                    }
+                    if let Command::WATCH(watch) = cmd {
+                        let frame = match self.transaction {
+                            Some(_) => Frame::Error("ERR WATCH inside MULTI is not allowed".to_string()),
+                            None => self.watch(watch).await?,
+                        };
+                        self.connection.write_and_flush_frame(frame).await?;
+                        continue;
+                    }
+                    if let Command::UNWATCH(_) = cmd {
+                        self.unwatch().await?;
+                        self.connection.write_and_flush_frame(Frame::Str(b"OK".to_vec())).await?;
+                        continue;
+                    }
+                    let cmd = match cmd {
+                        Command::EXEC(_) => match self.transaction.take() {
+                            Some(transaction) if !transaction.aborted => {
+                                let watched = std::mem::take(&mut self.watching);
+                                Command::EXEC(Exec::new(transaction.commands, watched))
+                            }
+                            Some(_) => {
+                                self.unwatch().await?;
+                                let err = "EXECABORT Transaction discarded because of previous errors.";
+                                self.connection.write_and_flush_frame(Frame::Error(err.to_string())).await?;
+                                continue;
+                            }
+                            None => {
+                                let err = Frame::Error("ERR EXEC without MULTI".to_string());
+                                self.connection.write_and_flush_frame(err).await?;
+                                continue;
+                            }
+                        },
+                        cmd => cmd,
+                    };
+
+                    // a script running for long holds the db worker, say so rather than wait for it
+                    let limit = Duration::from_millis(self.config.props().lua_time_limit);
+                    if self.db.running_script().is_busy(limit) {
+                        let err = "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN \
+                                   NOSAVE.";
+                        self.connection.write_and_flush_frame(Frame::Error(err.to_string())).await?;
+                        continue;
+                    }
+
+                    // CLIENT PAUSE holds commands here, before they reach the db worker
+                    self.clients.wait_unpaused(cmd.is_write()).await;
+
+                    // a blocked client can still be killed or shut down while it waits
+                    let result = match cmd.block_timeout() {
+                        Some(_) => tokio::select! {
+                            result = self.db.exec(cmd) => result?,
+                            _ = self.client.killed() => return Ok(()),
+                            _ = self.shutdown.receive() => return Ok(()),
+                        },
+                        None => self.db.exec(cmd).await?,
+                    };
+                    if let Some(CommandResult { frame }) = result {
                         self.connection.write_and_flush_frame(frame).await?;
                     } else {
                         self.connection.write_and_flush_frame(Frame::Nil).await?;
@@ -53,6 +295,75 @@ impl Handler {
         }
         Ok(())
     }
+
+    /// Records the versions of the keys of WATCH for the next EXEC.
+    async fn watch(&mut self, watch: Watch) -> crate::Result<Frame> {
+        let keys: Vec<String> = watch.keys().into_iter().map(String::from).collect();
+        match self.db.exec(Command::WATCH(watch)).await? {
+            Some(CommandResult { frame: Frame::Array(versions) }) => {
+                for (key, version) in keys.into_iter().zip(versions) {
+                    if let Frame::Integer(version) = version {
+                        self.watching.push((key, version as u64));
+                    }
+                }
+                Ok(Frame::Str(b"OK".to_vec()))
+            }
+            Some(CommandResult { frame }) => Ok(frame),
+            None => Ok(Frame::Nil),
+        }
+    }
+
+    /// Forgets the keys the connection watches, also done once it closes.
+    pub(crate) async fn unwatch(&mut self) -> crate::Result<()> {
+        if self.watching.is_empty() {
+            return Ok(());
+        }
+        let keys = self.watching.drain(..).map(|(key, _)| key).collect();
+        self.db.exec(Command::UNWATCH(Unwatch::new(keys))).await?;
+        Ok(())
+    }
+
+    /// Queues a command of the transaction in progress. A command that can't be queued is
+    /// answered with its error and makes EXEC discard the transaction.
+    fn queue(&mut self, command: Result<Command, ParseError>) -> Frame {
+        let transaction = self.transaction.as_mut().expect("queuing outside a transaction");
+        let err = match command {
+            Ok(Command::UNKNOWN(name, _)) => Frame::Error(format!("ERR unknown command '{}'", name)),
+            // MULTI is only accepted from an authenticated connection
+            Ok(mut cmd) => match self.acl.check(self.user.as_deref().unwrap_or_default(), &cmd) {
+                Err(err) => err,
+                // the commands run by the connection itself can't wait for EXEC
+                Ok(_) if matches!(
+                    cmd,
+                    Command::AUTH(_)
+                        | Command::ACL(_)
+                        | Command::CLIENT(_)
+                        | Command::SHUTDOWN(_)
+                        | Command::SUBSCRIBE(_)
+                        | Command::UNSUBSCRIBE(_)
+                        | Command::PSUBSCRIBE(_)
+                        | Command::PUNSUBSCRIBE(_)
+                        | Command::SSUBSCRIBE(_)
+                        | Command::SUNSUBSCRIBE(_)
+                        | Command::HELLO(_)
+                        | Command::SCRIPT(Script::Kill)
+                        | Command::FUNCTION(Function::Kill)
+                ) =>
+                {
+                    Frame::Error(format!("ERR {} is not allowed in a transaction", cmd.name()))
+                }
+                Ok(_) => {
+                    cmd.set_script_user(self.user.as_deref().unwrap_or_default());
+                    transaction.commands.push(cmd);
+                    return Frame::Str(b"QUEUED".to_vec());
+                }
+            },
+            Err(ParseError::EOF) => Frame::Error("ERR wrong number of arguments for command".to_string()),
+            Err(err) => Frame::Error(format!("{}", err)),
+        };
+        transaction.aborted = true;
+        err
+    }
 }
 
 pub struct ShutdownHook {
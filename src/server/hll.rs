@@ -0,0 +1,419 @@
+//! HyperLogLog in the string layout of redis, so values can be moved between servers as is.
+//!
+//! A 16 byte header, `HYLL`, the encoding, 3 unused bytes and the cached cardinality as
+//! little endian u64 whose top bit marks it stale, followed by the registers. Dense registers are
+//! 6 bit each, least significant bits first. The sparse encoding is a sequence of runs:
+//! `00xxxxxx` for up to 64 zero registers, `01xxxxxx yyyyyyyy` for up to 16384 and
+//! `1vvvvvxx` for up to 4 registers of the value 1 to 32.
+
+const P: u32 = 14;
+const REGISTERS: usize = 1 << P;
+const BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << BITS) - 1;
+const Q: u32 = 64 - P;
+const HEADER_LEN: usize = 16;
+const DENSE_LEN: usize = HEADER_LEN + (REGISTERS * BITS).div_ceil(8);
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+const SPARSE_VAL_MAX: u8 = 32;
+const HASH_SEED: u64 = 0xadc83b19;
+const ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+pub const WRONGTYPE: &str = "WRONGTYPE Key is not a valid HyperLogLog string value.";
+const CORRUPTED: &str = "INVALIDOBJ Corrupted HLL object detected";
+
+#[derive(Debug, Clone)]
+pub struct Hll {
+    /// One byte per register, encoded only when stored.
+    registers: Vec<u8>,
+    sparse: bool,
+    /// The cardinality stored with the value, `None` once registers changed.
+    cache: Option<u64>,
+}
+
+impl Hll {
+    /// An empty HyperLogLog, sparse until it grows.
+    pub fn new() -> Hll {
+        Hll {
+            registers: vec![0; REGISTERS],
+            sparse: true,
+            cache: Some(0),
+        }
+    }
+
+    pub fn decode(raw: &[u8]) -> crate::Result<Hll> {
+        if raw.len() < HEADER_LEN || &raw[..4] != b"HYLL" || raw[4] > SPARSE {
+            return Err(WRONGTYPE.into());
+        }
+        if raw[4] == DENSE && raw.len() != DENSE_LEN {
+            return Err(WRONGTYPE.into());
+        }
+        let mut cache = [0u8; 8];
+        cache.copy_from_slice(&raw[8..HEADER_LEN]);
+        let cache = match cache[7] & 0x80 {
+            0 => Some(u64::from_le_bytes(cache)),
+            _ => None,
+        };
+        let body = &raw[HEADER_LEN..];
+        let registers = match raw[4] {
+            DENSE => (0..REGISTERS).map(|index| dense_get(body, index)).collect(),
+            _ => sparse_decode(body).ok_or(CORRUPTED)?,
+        };
+        Ok(Hll {
+            registers,
+            sparse: raw[4] == SPARSE,
+            cache,
+        })
+    }
+
+    /// The string value, sparse while the registers allow it and it fits in `sparse_max` bytes.
+    pub fn encode(&mut self, sparse_max: usize) -> Vec<u8> {
+        if self.sparse {
+            match sparse_encode(&self.registers) {
+                Some(body) if HEADER_LEN + body.len() <= sparse_max => return self.with_header(SPARSE, &body),
+                // once dense it stays dense, like redis
+                _ => self.sparse = false,
+            }
+        }
+        let mut body = vec![0u8; DENSE_LEN - HEADER_LEN];
+        for (index, value) in self.registers.iter().enumerate() {
+            dense_set(&mut body, index, *value);
+        }
+        self.with_header(DENSE, &body)
+    }
+
+    /// Adds an element, returns whether a register changed.
+    pub fn add(&mut self, element: &[u8]) -> bool {
+        let hash = murmur_hash64a(element, HASH_SEED);
+        let index = (hash & (REGISTERS as u64 - 1)) as usize;
+        // the run of zeros after the index bits, the bit past them bounding it
+        let count = ((hash >> P) | (1 << Q)).trailing_zeros() as u8 + 1;
+        if self.registers[index] >= count {
+            return false;
+        }
+        self.registers[index] = count;
+        self.cache = None;
+        true
+    }
+
+    /// Takes the larger of each pair of registers, the result is dense if either is.
+    pub fn merge(&mut self, other: &Hll) {
+        for (register, value) in self.registers.iter_mut().zip(other.registers.iter()) {
+            if *value > *register {
+                *register = *value;
+                self.cache = None;
+            }
+        }
+        self.sparse &= other.sparse;
+    }
+
+    /// The estimated cardinality, from the cache when it is still valid.
+    pub fn count(&mut self) -> u64 {
+        if let Some(count) = self.cache {
+            return count;
+        }
+        let count = self.estimate();
+        self.cache = Some(count);
+        count
+    }
+
+    /// Whether the cached cardinality was up to date, otherwise `count` fills it.
+    pub fn is_cached(&self) -> bool {
+        self.cache.is_some()
+    }
+
+    /// The improved estimator of Otmar Ertl that redis uses.
+    fn estimate(&self) -> u64 {
+        let m = REGISTERS as f64;
+        // registers hold up to 63 in the dense encoding, like `reghisto[64]` in redis
+        let mut histogram = [0u32; 64];
+        for value in self.registers.iter() {
+            histogram[*value as usize] += 1;
+        }
+        let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+        for j in (1..=Q as usize).rev() {
+            z += histogram[j] as f64;
+            z *= 0.5;
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+        (ALPHA_INF * m * m / z).round() as u64
+    }
+
+    fn with_header(&self, encoding: u8, body: &[u8]) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(HEADER_LEN + body.len());
+        raw.extend_from_slice(b"HYLL");
+        raw.extend_from_slice(&[encoding, 0, 0, 0]);
+        match self.cache {
+            Some(count) => raw.extend_from_slice(&count.to_le_bytes()),
+            None => raw.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0x80]),
+        }
+        raw.extend_from_slice(body);
+        raw
+    }
+}
+
+impl Default for Hll {
+    fn default() -> Hll {
+        Hll::new()
+    }
+}
+
+fn dense_get(body: &[u8], index: usize) -> u8 {
+    let (byte, shift) = (index * BITS / 8, (index * BITS) & 7);
+    let low = body[byte] as u16 >> shift;
+    let high = body.get(byte + 1).map_or(0, |b| (*b as u16) << (8 - shift));
+    ((low | high) & REGISTER_MAX as u16) as u8
+}
+
+fn dense_set(body: &mut [u8], index: usize, value: u8) {
+    let (byte, shift) = (index * BITS / 8, (index * BITS) & 7);
+    let value = value as u16;
+    body[byte] &= !((REGISTER_MAX as u16) << shift) as u8;
+    body[byte] |= (value << shift) as u8;
+    if let Some(next) = body.get_mut(byte + 1) {
+        *next &= !((REGISTER_MAX as u16) >> (8 - shift)) as u8;
+        *next |= (value >> (8 - shift)) as u8;
+    }
+}
+
+/// The registers of a sparse body, `None` when the runs don't add up to every register.
+fn sparse_decode(body: &[u8]) -> Option<Vec<u8>> {
+    let mut registers = Vec::with_capacity(REGISTERS);
+    let mut i = 0;
+    while i < body.len() {
+        let op = body[i];
+        let (value, run) = match op >> 6 {
+            0 => (0, (op & 0x3f) as usize + 1),
+            1 => {
+                i += 1;
+                (0, (((op & 0x3f) as usize) << 8 | *body.get(i)? as usize) + 1)
+            }
+            _ => (((op >> 2) & 0x1f) + 1, (op & 0x3) as usize + 1),
+        };
+        if registers.len() + run > REGISTERS {
+            return None;
+        }
+        registers.resize(registers.len() + run, value);
+        i += 1;
+    }
+    match registers.len() {
+        REGISTERS => Some(registers),
+        _ => None,
+    }
+}
+
+/// The sparse body of the registers, `None` when a value is too large for it.
+fn sparse_encode(registers: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut i = 0;
+    while i < registers.len() {
+        let value = registers[i];
+        let run = registers[i..].iter().take_while(|v| **v == value).count();
+        i += run;
+        match value {
+            0 => {
+                let mut left = run;
+                while left > 0 {
+                    let len = left.min(REGISTERS);
+                    if len > 64 {
+                        body.push(0x40 | ((len - 1) >> 8) as u8);
+                        body.push(((len - 1) & 0xff) as u8);
+                    } else {
+                        body.push((len - 1) as u8);
+                    }
+                    left -= len;
+                }
+            }
+            value if value > SPARSE_VAL_MAX => return None,
+            value => {
+                let mut left = run;
+                while left > 0 {
+                    let len = left.min(4);
+                    body.push(0x80 | (value - 1) << 2 | (len - 1) as u8);
+                    left -= len;
+                }
+            }
+        }
+    }
+    Some(body)
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let (mut y, mut z) = (1.0, 1.0 - x);
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let (mut y, mut z) = (1.0, x);
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+/// MurmurHash64A, the element hash of redis' HyperLogLog.
+fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, b) in tail.iter().enumerate() {
+            h ^= (*b as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPARSE_MAX: usize = 3000;
+
+    fn with_elements(elements: impl IntoIterator<Item = String>) -> Hll {
+        let mut hll = Hll::new();
+        for element in elements {
+            hll.add(element.as_bytes());
+        }
+        hll
+    }
+
+    #[test]
+    fn sparse_round_trip() {
+        let mut hll = with_elements((0..100).map(|i| format!("element:{}", i)));
+        let raw = hll.encode(SPARSE_MAX);
+        assert_eq!(raw[4], SPARSE);
+        let decoded = Hll::decode(&raw).unwrap();
+        assert!(decoded.sparse);
+        assert_eq!(decoded.registers, hll.registers);
+    }
+
+    #[test]
+    fn dense_round_trip() {
+        let mut hll = with_elements((0..100).map(|i| format!("element:{}", i)));
+        let raw = hll.encode(0);
+        assert_eq!(raw[4], DENSE);
+        assert_eq!(raw.len(), DENSE_LEN);
+        let decoded = Hll::decode(&raw).unwrap();
+        assert!(!decoded.sparse);
+        assert_eq!(decoded.registers, hll.registers);
+    }
+
+    #[test]
+    fn sparse_is_promoted_once_too_large() {
+        let mut hll = with_elements((0..10).map(|i| format!("element:{}", i)));
+        assert_eq!(hll.encode(SPARSE_MAX)[4], SPARSE);
+        hll.add(b"more");
+        assert_eq!(hll.encode(HEADER_LEN)[4], DENSE);
+        // and stays dense even when it would fit again
+        assert_eq!(hll.encode(SPARSE_MAX)[4], DENSE);
+    }
+
+    #[test]
+    fn sparse_is_promoted_for_large_registers() {
+        let mut hll = Hll::new();
+        hll.registers[0] = SPARSE_VAL_MAX + 1;
+        hll.cache = None;
+        assert_eq!(hll.encode(SPARSE_MAX)[4], DENSE);
+    }
+
+    #[test]
+    fn counts_like_redis() {
+        // the examples of the PFCOUNT and PFMERGE documentation
+        let mut hll = with_elements(["a", "b", "c", "d", "e", "f", "g"].map(String::from));
+        assert_eq!(hll.count(), 7);
+        let mut first = with_elements(["foo", "bar", "zap", "a"].map(String::from));
+        let second = with_elements(["a", "b", "c", "foo"].map(String::from));
+        first.merge(&second);
+        assert_eq!(first.count(), 6);
+    }
+
+    #[test]
+    fn estimate_stays_within_the_standard_error() {
+        for cardinality in [1_000u64, 10_000, 100_000] {
+            let mut hll = with_elements((0..cardinality).map(|i| format!("element:{}", i)));
+            let error = (hll.count() as f64 - cardinality as f64).abs() / cardinality as f64;
+            // 0.81% standard error, redis' tests allow 5%
+            assert!(error < 0.05, "{} counted as {}", cardinality, hll.count());
+        }
+    }
+
+    #[test]
+    fn cache_is_kept_until_registers_change() {
+        let mut hll = with_elements(["a", "b"].map(String::from));
+        assert!(!hll.is_cached());
+        hll.count();
+        let mut decoded = Hll::decode(&hll.encode(SPARSE_MAX)).unwrap();
+        assert!(decoded.is_cached());
+        assert!(!decoded.add(b"a"));
+        assert!(decoded.is_cached());
+        assert!(decoded.add(b"c"));
+        assert!(!decoded.is_cached());
+    }
+
+    #[test]
+    fn overflowing_dense_registers_are_counted() {
+        let mut hll = Hll::new();
+        let mut raw = hll.encode(0);
+        for index in 0..REGISTERS {
+            dense_set(&mut raw[HEADER_LEN..], index, REGISTER_MAX);
+        }
+        raw[15] |= 0x80;
+        let mut decoded = Hll::decode(&raw).unwrap();
+        assert!(decoded.registers.iter().all(|value| *value == REGISTER_MAX));
+        decoded.count();
+    }
+
+    #[test]
+    fn corrupt_values_are_rejected() {
+        assert_eq!(Hll::decode(b"not an hll").unwrap_err().to_string(), WRONGTYPE);
+        let mut raw = Hll::new().encode(SPARSE_MAX);
+        raw[4] = 2;
+        assert_eq!(Hll::decode(&raw).unwrap_err().to_string(), WRONGTYPE);
+        // a dense value missing its last byte
+        let mut raw = Hll::new().encode(0);
+        raw.pop();
+        assert_eq!(Hll::decode(&raw).unwrap_err().to_string(), WRONGTYPE);
+        // sparse runs that don't cover every register
+        let mut raw = Hll::new().encode(SPARSE_MAX);
+        raw.pop();
+        assert_eq!(Hll::decode(&raw).unwrap_err().to_string(), CORRUPTED);
+        // or cover more than them
+        let mut raw = Hll::new().encode(SPARSE_MAX);
+        raw.push(0);
+        assert_eq!(Hll::decode(&raw).unwrap_err().to_string(), CORRUPTED);
+    }
+}
@@ -1,14 +1,31 @@
+pub mod batch;
+pub mod clients;
 pub mod connection;
 pub mod db;
+pub mod encoding;
+pub mod geohash;
 pub mod handler;
+pub mod hll;
+pub mod notify;
+pub mod pubsub;
+pub mod scripting;
 pub mod value;
 pub mod shared;
+pub mod shutdown;
+pub mod stats;
+pub mod stream;
+pub mod tls;
 
-use crate::config::ServerProperties;
+use crate::acl::Acl;
+use crate::config::Config;
 use crate::protocol::frame::Frame;
-use crate::server::connection::Connection;
+use crate::server::clients::Clients;
+use crate::server::connection::{Connection, Stream};
 use crate::server::db::{Db};
 use crate::server::handler::{Handler, ShutdownHook};
+use crate::server::pubsub::{Registry, Subscription};
+use crate::server::shutdown::{ShutdownMode, ShutdownRequest};
+use crate::server::stats::Stats;
 
 
 use std::future::Future;
@@ -16,118 +33,302 @@ use std::future::Future;
 use std::sync::{Arc};
 use std::time::Duration;
 
-use tokio::net::{TcpListener, TcpStream};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{TcpListener, TcpStream, UnixListener};
 
 use tokio::sync::mpsc::Receiver;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, Semaphore};
 use tokio::time;
+use tokio_rustls::TlsAcceptor;
 
+/// How long a TLS client has to finish its handshake, it holds a maxclients slot meanwhile.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The sockets the server accepts clients on.
+pub struct Listeners {
+    /// Missing when `port` is 0.
+    pub tcp: Option<TcpListener>,
+    /// The `tls-port` listener and the acceptor doing the handshake.
+    pub tls: Option<(TcpListener, TlsAcceptor)>,
+    pub unix: Option<UnixListener>,
+}
 
 pub struct Server {
-    tcp_listener: TcpListener,
+    listeners: Listeners,
     db: Arc<Db>,
+    config: Arc<Config>,
+    acl: Arc<Acl>,
+    stats: Arc<Stats>,
+    clients: Arc<Clients>,
+    pubsub: Arc<Registry>,
+    limit_connections: Arc<Semaphore>,
+    shutdown_request: Arc<ShutdownRequest>,
     pub notify_shutdown: broadcast::Sender<()>,
-    pub shutdown_complete_rx: broadcast::Receiver<()>,
+    /// Held by every connection task, so the receiver sees them all finish.
+    pub shutdown_complete_tx: mpsc::Sender<()>,
 }
 
 impl Server {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        tcp_listener: TcpListener,
+        listeners: Listeners,
         notify_shutdown: broadcast::Sender<()>,
-        shutdown_complete_rx: broadcast::Receiver<()>,
+        shutdown_complete_tx: mpsc::Sender<()>,
         db: Db,
+        config: Arc<Config>,
+        acl: Arc<Acl>,
+        stats: Arc<Stats>,
+        pubsub: Arc<Registry>,
     ) -> Server {
+        let max_clients = config.props().max_clients as usize;
         Server {
+            config,
+            acl,
+            limit_connections: Arc::new(Semaphore::new(max_clients)),
+            shutdown_request: Arc::new(ShutdownRequest::default()),
             notify_shutdown,
-            shutdown_complete_rx,
-            tcp_listener,
+            shutdown_complete_tx,
+            listeners,
             db: Arc::new(db),
+            stats,
+            clients: Arc::new(Clients::default()),
+            pubsub,
         }
     }
 
     pub async fn run(&mut self) -> crate::Result<()> {
         loop {
-            let socket = self.accept().await?;
+            let (socket, addr, laddr, tls): (Box<dyn Stream>, String, String, Option<TlsAcceptor>) = tokio::select! {
+                res = async { accept(|| self.listeners.tcp.as_ref().unwrap().accept()).await }, if self.listeners.tcp.is_some() => {
+                    let (socket, addr) = res?;
+                    set_keepalive(&socket, self.config.props().tcp_keepalive);
+                    let laddr = local_addr(&socket);
+                    (Box::new(socket), addr.to_string(), laddr, None)
+                }
+                res = async { accept(|| self.listeners.tls.as_ref().unwrap().0.accept()).await }, if self.listeners.tls.is_some() => {
+                    let (socket, addr) = res?;
+                    set_keepalive(&socket, self.config.props().tcp_keepalive);
+                    let laddr = local_addr(&socket);
+                    let acceptor = self.listeners.tls.as_ref().map(|(_, acceptor)| acceptor.clone());
+                    (Box::new(socket), addr.to_string(), laddr, acceptor)
+                }
+                res = async { accept(|| self.listeners.unix.as_ref().unwrap().accept()).await }, if self.listeners.unix.is_some() => {
+                    let (socket, _) = res?;
+                    // peers of a unix socket are unnamed, report the socket path like redis does
+                    let path = format!("{}:0", self.config.props().unix_socket);
+                    (Box::new(socket), path.clone(), path, None)
+                }
+            };
 
-            log::debug!("accept conn {:?}", socket);
+            log::debug!("accept conn {}", addr);
 
-            let connection = Connection::new(socket);
+            Stats::incr(&self.stats.total_connections_received);
 
+            let permit = self.limit_connections.clone().try_acquire_owned().ok();
 
             let db_ptr = Arc::clone(&self.db);
-
-            let mut handler = Handler {
-                db: db_ptr,
-                connection,
-                shutdown: ShutdownHook::new(self.notify_shutdown.subscribe()),
-            };
+            let config = Arc::clone(&self.config);
+            let acl = Arc::clone(&self.acl);
+            let mut shutdown = ShutdownHook::new(self.notify_shutdown.subscribe());
+            let stats = Arc::clone(&self.stats);
+            let clients = Arc::clone(&self.clients);
+            let pubsub = Arc::clone(&self.pubsub);
+            let shutdown_request = Arc::clone(&self.shutdown_request);
+            let shutdown_complete = self.shutdown_complete_tx.clone();
 
             tokio::spawn(async move {
+                // the handshake runs on the connection task so a slow client can't stall accepting
+                let mut connection = match tls {
+                    Some(acceptor) => {
+                        let handshake = tokio::select! {
+                            res = time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(socket)) => res,
+                            _ = shutdown.receive() => return,
+                        };
+                        match handshake {
+                            Ok(Ok(stream)) => Connection::new(Box::new(stream), addr.clone()),
+                            Ok(Err(err)) => {
+                                log::debug!("tls handshake with {} failed, {}", addr, err);
+                                return;
+                            }
+                            Err(_) => {
+                                log::debug!("tls handshake with {} timed out", addr);
+                                return;
+                            }
+                        }
+                    }
+                    None => Connection::new(socket, addr.clone()),
+                };
+
+                let permit = match permit {
+                    Some(permit) => permit,
+                    None => {
+                        Stats::incr(&stats.rejected_connections);
+                        let err = Frame::Error("ERR max number of clients reached".to_string());
+                        let _ = connection.write_and_flush_frame(err).await;
+                        return;
+                    }
+                };
+
+                let user = acl.initial_user(&config);
+                let client = clients.register(addr, laddr, user.as_deref());
+                let id = client.id;
+
+                let mut handler = Handler {
+                    db: db_ptr,
+                    config,
+                    acl,
+                    user,
+                    clients: clients.clone(),
+                    client,
+                    shutdown_request,
+                    connection,
+                    shutdown,
+                    transaction: None,
+                    watching: Vec::new(),
+                    subscription: Subscription::new(id, pubsub),
+                };
+
+                Stats::incr(&stats.connected_clients);
                 if let Err(err) = handler.run().await {
                     log::error!("connection error,message: {}", err);
                 }
+                // the db worker tracks the keys watched until they are released
+                let _ = handler.unwatch().await;
+                Stats::decr(&stats.connected_clients);
+                clients.unregister(id);
+                drop(permit);
+                drop(shutdown_complete);
             });
         }
     }
+}
 
-    async fn accept(&self) -> crate::Result<TcpStream> {
-        let mut backoff = 1;
-        loop {
-            match self.tcp_listener.accept().await {
-                Ok((conn, _)) => return Ok(conn),
-                Err(e) => {
-                    if backoff > 64 {
-                        return Err(e.into());
-                    }
+fn set_keepalive(socket: &TcpStream, secs: u64) {
+    if secs == 0 {
+        return;
+    }
+    // probe every third of the idle time once probing starts, the same as redis on linux
+    let keepalive = TcpKeepalive::new()
+        .with_time(Duration::from_secs(secs))
+        .with_interval(Duration::from_secs((secs / 3).max(1)));
+    if let Err(err) = SockRef::from(socket).set_tcp_keepalive(&keepalive) {
+        log::warn!("failed to set tcp keepalive, {}", err);
+    }
+}
+
+fn local_addr(socket: &TcpStream) -> String {
+    socket.local_addr().map(|addr| addr.to_string()).unwrap_or_default()
+}
+
+async fn accept<T, F>(mut accept: impl FnMut() -> F) -> crate::Result<T>
+where
+    F: Future<Output = std::io::Result<T>>,
+{
+    let mut backoff = 1;
+    loop {
+        match accept().await {
+            Ok(accepted) => return Ok(accepted),
+            Err(e) => {
+                if backoff > 64 {
+                    return Err(e.into());
                 }
             }
-            time::sleep(Duration::from_secs(backoff)).await;
-
-            backoff *= 2
         }
+        time::sleep(Duration::from_secs(backoff)).await;
+
+        backoff *= 2
     }
 }
 
-pub async fn run(tcp_listener: TcpListener, props:ServerProperties,shutdown: impl Future) {
+/// Serves clients until `shutdown` resolves or a client sends SHUTDOWN, then stops accepting,
+/// lets the connections finish their commands, drains the db worker and syncs the database.
+pub async fn run(
+    listeners: Listeners,
+    config: Arc<Config>,
+    shutdown: impl Future,
+) -> crate::Result<()> {
+    let acl_file = config.props().get_acl_file();
+    let acl = match Acl::load(acl_file) {
+        Ok(acl) => Arc::new(acl),
+        Err(err) => {
+            log::error!("failed to load acl users, {}", err);
+            return Ok(());
+        }
+    };
+
     let (notify_shutdown, _) = broadcast::channel(1);
-    let (shutdown_complete_tx, _) = broadcast::channel(1);
+    let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel(1);
+    let (stop_worker, _) = broadcast::channel(1);
 
-    let (db, db_worker) = db::Db::new(&props.append_filename,shutdown_complete_tx.subscribe());
+    let stats = Arc::new(Stats::default());
+    let pubsub = Arc::new(Registry::default());
+
+    let (db, db_worker) = db::Db::new(
+        config.clone(),
+        stats.clone(),
+        pubsub.clone(),
+        acl.clone(),
+        stop_worker.subscribe(),
+    );
+    let mut worker = tokio::spawn(db_worker.run());
 
     let mut server = Server::new(
-        tcp_listener,
+        listeners,
         notify_shutdown,
-        shutdown_complete_tx.subscribe(),
+        shutdown_complete_tx,
         db,
+        config,
+        acl,
+        stats,
+        pubsub,
     );
+    let shutdown_request = server.shutdown_request.clone();
 
-    tokio::select! {
+    let mode = tokio::select! {
         res = server.run() => {
             if let Err(err) = res {
                 log::error!("failed to accept connection {}",err);
             }
+            ShutdownMode::default()
         }
-        worker = db_worker.run() => {
-             if let Err(err) = worker {
-                 log::error!("db exec error {}",err);
-             }
-        }
-        _ = shutdown => {
-            let _ = shutdown_complete_tx.send(());
-            // The shutdown signal has been received.
-            log::info!("shutting down");
+        res = &mut worker => {
+            return match res {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(err)) => Err(format!("db exec error {}", err).into()),
+                Err(err) => Err(format!("db worker failed {}", err).into()),
+            };
         }
-    }
-
+        _ = shutdown => ShutdownMode::default(),
+        mode = shutdown_request.requested() => mode,
+    };
+    log::info!("shutting down");
 
     let Server {
-        mut shutdown_complete_rx,
+        db,
         notify_shutdown,
+        shutdown_complete_tx,
+        clients,
         ..
     } = server;
 
+    // held commands have to run for their connections to finish
+    clients.unpause();
     drop(notify_shutdown);
+    drop(shutdown_complete_tx);
+    // the worker stops once the connections drop their handles to the db too
+    drop(db);
 
+    if mode.now {
+        let _ = stop_worker.send(());
+    } else {
+        let _ = shutdown_complete_rx.recv().await;
+    }
 
-    let _ = shutdown_complete_rx.recv().await;
+    let shared = match worker.await {
+        Ok(res) => res?,
+        Err(err) => return Err(format!("db worker failed {}", err).into()),
+    };
+    shared.sync(mode.save)?;
+    log::info!("database synced, bye");
+    Ok(())
 }
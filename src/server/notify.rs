@@ -0,0 +1,140 @@
+//! Keyspace notifications, the event classes of `notify-keyspace-events`.
+//!
+//! Commands report their changes to `Shared::notify`, which publishes them on the pub/sub bus as
+//! `__keyspace@0__:<key>` messages carrying the event and `__keyevent@0__:<event>` messages carrying
+//! the key, for the classes enabled.
+
+/// `K`, publish on `__keyspace@0__:<key>`
+pub const KEYSPACE: u16 = 1 << 0;
+/// `E`, publish on `__keyevent@0__:<event>`
+pub const KEYEVENT: u16 = 1 << 1;
+pub const GENERIC: u16 = 1 << 2;
+pub const STRING: u16 = 1 << 3;
+pub const LIST: u16 = 1 << 4;
+pub const SET: u16 = 1 << 5;
+pub const HASH: u16 = 1 << 6;
+pub const ZSET: u16 = 1 << 7;
+pub const EXPIRED: u16 = 1 << 8;
+/// Never sent, the server doesn't evict keys.
+pub const EVICTED: u16 = 1 << 9;
+pub const STREAM: u16 = 1 << 10;
+/// `m`, `n` and `d` are accepted like redis does, those events are never sent.
+pub const KEY_MISS: u16 = 1 << 11;
+pub const MODULE: u16 = 1 << 12;
+pub const NEW: u16 = 1 << 13;
+/// The classes `A` stands for.
+pub const ALL: u16 = GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED | STREAM | MODULE;
+
+const CLASSES: [(char, u16); 10] = [
+    ('g', GENERIC),
+    ('$', STRING),
+    ('l', LIST),
+    ('s', SET),
+    ('h', HASH),
+    ('z', ZSET),
+    ('x', EXPIRED),
+    ('e', EVICTED),
+    ('t', STREAM),
+    ('d', MODULE),
+];
+
+/// Parses a `notify-keyspace-events` value such as `KEA` or `Ex`.
+pub fn parse_flags(value: &str) -> Result<u16, String> {
+    let mut flags = 0;
+    for c in value.chars() {
+        flags |= match c {
+            'A' => ALL,
+            'K' => KEYSPACE,
+            'E' => KEYEVENT,
+            'm' => KEY_MISS,
+            'n' => NEW,
+            c => match CLASSES.iter().find(|(class, _)| *class == c) {
+                Some((_, flag)) => *flag,
+                None => return Err("Invalid event class character. Use 'Ag$lshzxeKEtmdn'.".to_string()),
+            },
+        };
+    }
+    Ok(flags)
+}
+
+/// Formats flags back the way redis does, `A` standing for all of its classes.
+pub fn format_flags(flags: u16) -> String {
+    let mut value = String::new();
+    if flags & ALL == ALL {
+        value.push('A');
+    } else {
+        value.extend(CLASSES.iter().filter(|(_, flag)| flags & flag != 0).map(|(class, _)| class));
+    }
+    for (class, flag) in [('K', KEYSPACE), ('E', KEYEVENT), ('m', KEY_MISS), ('n', NEW)] {
+        if flags & flag != 0 {
+            value.push(class);
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::server::{
+        pubsub::{Kind, Registry, Subscription},
+        shared::testing::{open, run, show, temporary_dir},
+    };
+
+    #[test]
+    fn flags() {
+        assert_eq!(parse_flags("KEA"), Ok(KEYSPACE | KEYEVENT | ALL));
+        assert_eq!(parse_flags("Ex"), Ok(KEYEVENT | EXPIRED));
+        assert_eq!(parse_flags(""), Ok(0));
+        assert_eq!(parse_flags("Kq"), Err("Invalid event class character. Use 'Ag$lshzxeKEtmdn'.".to_string()));
+        // the classes come first, `A` when all of them are there
+        assert_eq!(format_flags(parse_flags("KEA").unwrap()), "AKE");
+        assert_eq!(format_flags(parse_flags("Ex").unwrap()), "xE");
+        assert_eq!(format_flags(parse_flags("g$lshzxetdKE").unwrap()), "AKE");
+        assert_eq!(format_flags(parse_flags("nm$K").unwrap()), "$Kmn");
+        assert_eq!(format_flags(0), "");
+    }
+
+    #[test]
+    fn published_for_the_enabled_classes() {
+        let dir = temporary_dir("notify");
+        let registry = Arc::new(Registry::default());
+        let mut shared = open(&dir, registry.clone());
+        let mut subscription = Subscription::new(1, registry);
+        subscription.subscribe(Kind::Pattern, vec![b"__key*".to_vec()]);
+        let mut received = || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+            let mut messages = Vec::new();
+            while let Ok(Some(message)) = runtime
+                .block_on(tokio::time::timeout(std::time::Duration::from_millis(10), subscription.receive()))
+            {
+                messages.push(show(&message.into_frame()));
+            }
+            messages
+        };
+
+        run(&mut shared, &["set", "k", "v"]);
+        assert!(received().is_empty());
+        run(&mut shared, &["config", "set", "notify-keyspace-events", "KE$"]);
+        run(&mut shared, &["set", "k", "v"]);
+        assert_eq!(
+            received(),
+            [
+                "[\"pmessage\", \"__key*\", \"__keyspace@0__:k\", \"set\"]",
+                "[\"pmessage\", \"__key*\", \"__keyevent@0__:set\", \"k\"]",
+            ]
+        );
+        // DEL is a generic event
+        run(&mut shared, &["del", "k"]);
+        assert!(received().is_empty());
+        run(&mut shared, &["config", "set", "notify-keyspace-events", "Eg"]);
+        run(&mut shared, &["set", "k", "v"]);
+        run(&mut shared, &["del", "k"]);
+        assert_eq!(received(), ["[\"pmessage\", \"__key*\", \"__keyevent@0__:del\", \"k\"]"]);
+
+        drop(shared);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::protocol::frame::Frame;
+use crate::utils;
+
+/// What a connection subscribes to. Shard channels are a namespace of their own, as in a
+/// redis cluster of one node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Channel,
+    Pattern,
+    Shard,
+}
+
+impl Kind {
+    /// The SUBSCRIBE command of the kind, also the first element of its replies.
+    pub fn subscribe_command(self) -> &'static str {
+        match self {
+            Kind::Channel => "subscribe",
+            Kind::Pattern => "psubscribe",
+            Kind::Shard => "ssubscribe",
+        }
+    }
+
+    pub fn unsubscribe_command(self) -> &'static str {
+        match self {
+            Kind::Channel => "unsubscribe",
+            Kind::Pattern => "punsubscribe",
+            Kind::Shard => "sunsubscribe",
+        }
+    }
+}
+
+/// A message delivered to a subscribed connection.
+#[derive(Debug, Clone)]
+pub enum Message {
+    Channel { channel: Vec<u8>, payload: Vec<u8> },
+    Pattern { pattern: Vec<u8>, channel: Vec<u8>, payload: Vec<u8> },
+    Shard { channel: Vec<u8>, payload: Vec<u8> },
+}
+
+impl Message {
+    /// The message as a push frame, an array for RESP2 connections.
+    pub fn into_frame(self) -> Frame {
+        match self {
+            Message::Channel { channel, payload } => Frame::Push(vec![
+                Frame::Bulk(b"message".to_vec()),
+                Frame::Bulk(channel),
+                Frame::Bulk(payload),
+            ]),
+            Message::Pattern { pattern, channel, payload } => Frame::Push(vec![
+                Frame::Bulk(b"pmessage".to_vec()),
+                Frame::Bulk(pattern),
+                Frame::Bulk(channel),
+                Frame::Bulk(payload),
+            ]),
+            Message::Shard { channel, payload } => Frame::Push(vec![
+                Frame::Bulk(b"smessage".to_vec()),
+                Frame::Bulk(channel),
+                Frame::Bulk(payload),
+            ]),
+        }
+    }
+}
+
+/// The messages a connection can fall behind by before it is disconnected, the equivalent of the
+/// pubsub output buffer limit of redis.
+const QUEUE_LEN: usize = 4096;
+
+/// The sending half of the message queue of a connection.
+#[derive(Debug, Clone)]
+struct Subscriber {
+    sender: mpsc::Sender<Message>,
+    /// set once the queue was found full, the connection is closed then
+    overflowed: Arc<AtomicBool>,
+}
+
+impl Subscriber {
+    /// Queues a message, false when the connection is gone or too far behind to take it.
+    fn send(&self, message: Message) -> bool {
+        match self.sender.try_send(message) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.overflowed.store(true, Ordering::Relaxed);
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// The channels and patterns subscribed to, shared by every handler and the db worker, which
+/// publishes.
+#[derive(Debug, Default)]
+pub struct Registry {
+    inner: Mutex<Subscribers>,
+}
+
+/// The subscribed connections by client id.
+#[derive(Debug, Default)]
+struct Subscribers {
+    channels: HashMap<Vec<u8>, HashMap<u64, Subscriber>>,
+    patterns: HashMap<Vec<u8>, HashMap<u64, Subscriber>>,
+    shard_channels: HashMap<Vec<u8>, HashMap<u64, Subscriber>>,
+}
+
+impl Subscribers {
+    fn names_mut(&mut self, kind: Kind) -> &mut HashMap<Vec<u8>, HashMap<u64, Subscriber>> {
+        match kind {
+            Kind::Channel => &mut self.channels,
+            Kind::Pattern => &mut self.patterns,
+            Kind::Shard => &mut self.shard_channels,
+        }
+    }
+}
+
+impl Registry {
+    /// Sends `payload` to the subscribers of `channel` and of the patterns matching it, returns
+    /// the number of connections reached.
+    pub fn publish(&self, channel: &[u8], payload: &[u8]) -> usize {
+        let inner = self.inner.lock().unwrap();
+        let mut received = 0;
+        if let Some(subscribers) = inner.channels.get(channel) {
+            for sender in subscribers.values() {
+                let message = Message::Channel { channel: channel.to_vec(), payload: payload.to_vec() };
+                if sender.send(message) {
+                    received += 1;
+                }
+            }
+        }
+        for (pattern, subscribers) in inner.patterns.iter() {
+            if !utils::backtrack_match(channel, pattern) {
+                continue;
+            }
+            for sender in subscribers.values() {
+                let message = Message::Pattern {
+                    pattern: pattern.clone(),
+                    channel: channel.to_vec(),
+                    payload: payload.to_vec(),
+                };
+                if sender.send(message) {
+                    received += 1;
+                }
+            }
+        }
+        received
+    }
+
+    /// Sends `payload` to the subscribers of the shard channel `channel`, returns the number of
+    /// connections reached.
+    pub fn publish_shard(&self, channel: &[u8], payload: &[u8]) -> usize {
+        let inner = self.inner.lock().unwrap();
+        let subscribers = match inner.shard_channels.get(channel) {
+            Some(subscribers) => subscribers,
+            None => return 0,
+        };
+        subscribers
+            .values()
+            .filter(|sender| {
+                let message = Message::Shard { channel: channel.to_vec(), payload: payload.to_vec() };
+                sender.send(message)
+            })
+            .count()
+    }
+
+    /// The channels, or shard channels, with subscribers, those matching `pattern` when given.
+    pub fn channels(&self, shard: bool, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        let inner = self.inner.lock().unwrap();
+        let channels = if shard { &inner.shard_channels } else { &inner.channels };
+        channels
+            .keys()
+            .filter(|channel| pattern.is_none_or(|pattern| utils::backtrack_match(channel, pattern)))
+            .cloned()
+            .collect()
+    }
+
+    /// The number of connections subscribed to `channel`, or to the shard channel, patterns not
+    /// counted.
+    pub fn numsub(&self, shard: bool, channel: &[u8]) -> usize {
+        let inner = self.inner.lock().unwrap();
+        let channels = if shard { &inner.shard_channels } else { &inner.channels };
+        channels.get(channel).map_or(0, HashMap::len)
+    }
+
+    /// The number of patterns subscribed to by some connection.
+    pub fn numpat(&self) -> usize {
+        self.inner.lock().unwrap().patterns.len()
+    }
+
+    fn subscribe(&self, kind: Kind, name: &[u8], id: u64, sender: &Subscriber) {
+        let mut inner = self.inner.lock().unwrap();
+        let names = inner.names_mut(kind);
+        names.entry(name.to_vec()).or_default().insert(id, sender.clone());
+    }
+
+    fn unsubscribe(&self, kind: Kind, name: &[u8], id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        let names = inner.names_mut(kind);
+        if let Some(subscribers) = names.get_mut(name) {
+            subscribers.remove(&id);
+            if subscribers.is_empty() {
+                names.remove(name);
+            }
+        }
+    }
+}
+
+/// The subscriptions of a connection, removed from the registry when it is dropped.
+#[derive(Debug)]
+pub struct Subscription {
+    id: u64,
+    registry: Arc<Registry>,
+    channels: HashSet<Vec<u8>>,
+    patterns: HashSet<Vec<u8>>,
+    shard_channels: HashSet<Vec<u8>>,
+    sender: Subscriber,
+    receiver: mpsc::Receiver<Message>,
+}
+
+impl Subscription {
+    pub fn new(id: u64, registry: Arc<Registry>) -> Subscription {
+        let (sender, receiver) = mpsc::channel(QUEUE_LEN);
+        let sender = Subscriber { sender, overflowed: Arc::new(AtomicBool::new(false)) };
+        Subscription {
+            id,
+            registry,
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            shard_channels: HashSet::new(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// The number of channels, patterns and shard channels subscribed to, the connection is in
+    /// subscriber mode while it isn't 0.
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len() + self.shard_channels.len()
+    }
+
+    /// Subscribes to names of `kind`, replying the count after each one.
+    pub fn subscribe(&mut self, kind: Kind, names: Vec<Vec<u8>>) -> Vec<Frame> {
+        let mut replies = Vec::with_capacity(names.len());
+        for name in names {
+            if self.names_mut(kind).insert(name.clone()) {
+                self.registry.subscribe(kind, &name, self.id, &self.sender);
+            }
+            replies.push(self.reply(kind, kind.subscribe_command(), Some(name)));
+        }
+        replies
+    }
+
+    /// Unsubscribes from names of `kind`, every one subscribed to when `names` is empty.
+    pub fn unsubscribe(&mut self, kind: Kind, names: Vec<Vec<u8>>) -> Vec<Frame> {
+        let command = kind.unsubscribe_command();
+        let names = match names.is_empty() {
+            true => self.names_mut(kind).iter().cloned().collect(),
+            false => names,
+        };
+        if names.is_empty() {
+            return vec![self.reply(kind, command, None)];
+        }
+        let mut replies = Vec::with_capacity(names.len());
+        for name in names {
+            if self.names_mut(kind).remove(&name) {
+                self.registry.unsubscribe(kind, &name, self.id);
+            }
+            replies.push(self.reply(kind, command, Some(name)));
+        }
+        replies
+    }
+
+    /// The next message published to the connection, `None` once it fell too far behind and
+    /// missed some, the connection has to be closed then.
+    pub async fn receive(&mut self) -> Option<Message> {
+        // `self` holds a sender, the channel never closes
+        let message = self.receiver.recv().await.expect("the subscription holds a sender");
+        match self.sender.overflowed.load(Ordering::Relaxed) {
+            true => None,
+            false => Some(message),
+        }
+    }
+
+    fn names_mut(&mut self, kind: Kind) -> &mut HashSet<Vec<u8>> {
+        match kind {
+            Kind::Channel => &mut self.channels,
+            Kind::Pattern => &mut self.patterns,
+            Kind::Shard => &mut self.shard_channels,
+        }
+    }
+
+    /// Shard channels are counted apart from channels and patterns, like in redis.
+    fn reply(&self, kind: Kind, command: &str, name: Option<Vec<u8>>) -> Frame {
+        let count = match kind {
+            Kind::Shard => self.shard_channels.len(),
+            _ => self.channels.len() + self.patterns.len(),
+        };
+        Frame::Push(vec![
+            Frame::Bulk(command.as_bytes().to_vec()),
+            name.map_or(Frame::Nil, Frame::Bulk),
+            Frame::Integer(count as i64),
+        ])
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        for (kind, names) in [
+            (Kind::Channel, &self.channels),
+            (Kind::Pattern, &self.patterns),
+            (Kind::Shard, &self.shard_channels),
+        ] {
+            for name in names.iter() {
+                self.registry.unsubscribe(kind, name, self.id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::shared::testing::show;
+
+    fn names(names: &[&str]) -> Vec<Vec<u8>> {
+        names.iter().map(|name| name.as_bytes().to_vec()).collect()
+    }
+
+    fn shows(frames: &[Frame]) -> Vec<String> {
+        frames.iter().map(show).collect()
+    }
+
+    /// The messages queued for `subscription`, as pushed to the connection.
+    fn received(subscription: &mut Subscription) -> Vec<String> {
+        std::iter::from_fn(|| subscription.receiver.try_recv().ok())
+            .map(|message| show(&message.into_frame()))
+            .collect()
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn replies_count_the_subscriptions() {
+        let mut subscription = Subscription::new(1, Arc::new(Registry::default()));
+        assert_eq!(
+            shows(&subscription.subscribe(Kind::Channel, names(&["a", "b", "a"]))),
+            ["[\"subscribe\", \"a\", (integer) 1]", "[\"subscribe\", \"b\", (integer) 2]", "[\"subscribe\", \"a\", (integer) 2]"]
+        );
+        assert_eq!(shows(&subscription.subscribe(Kind::Pattern, names(&["n*"]))), ["[\"psubscribe\", \"n*\", (integer) 3]"]);
+        // shard channels are counted on their own
+        assert_eq!(shows(&subscription.subscribe(Kind::Shard, names(&["s"]))), ["[\"ssubscribe\", \"s\", (integer) 1]"]);
+        assert_eq!(subscription.count(), 4);
+        assert_eq!(shows(&subscription.unsubscribe(Kind::Channel, names(&["zz"]))), ["[\"unsubscribe\", \"zz\", (integer) 3]"]);
+        assert_eq!(shows(&subscription.unsubscribe(Kind::Channel, names(&["a"]))), ["[\"unsubscribe\", \"a\", (integer) 2]"]);
+        assert_eq!(shows(&subscription.unsubscribe(Kind::Channel, vec![])), ["[\"unsubscribe\", \"b\", (integer) 1]"]);
+        assert_eq!(shows(&subscription.unsubscribe(Kind::Channel, vec![])), ["[\"unsubscribe\", (nil), (integer) 1]"]);
+    }
+
+    #[test]
+    fn published_to_channels_and_matching_patterns() {
+        let registry = Arc::new(Registry::default());
+        let mut first = Subscription::new(1, registry.clone());
+        let mut second = Subscription::new(2, registry.clone());
+        first.subscribe(Kind::Channel, names(&["news"]));
+        first.subscribe(Kind::Pattern, names(&["n*", "x*"]));
+        second.subscribe(Kind::Shard, names(&["news"]));
+
+        assert_eq!(registry.publish(b"news", b"hi"), 2);
+        assert_eq!(received(&mut first), ["[\"message\", \"news\", \"hi\"]", "[\"pmessage\", \"n*\", \"news\", \"hi\"]"]);
+        assert!(received(&mut second).is_empty());
+        assert_eq!(registry.publish_shard(b"news", b"hi"), 1);
+        assert_eq!(received(&mut second), ["[\"smessage\", \"news\", \"hi\"]"]);
+        assert_eq!(registry.publish(b"other", b"hi"), 0);
+    }
+
+    #[test]
+    fn introspection() {
+        let registry = Arc::new(Registry::default());
+        let mut first = Subscription::new(1, registry.clone());
+        let mut second = Subscription::new(2, registry.clone());
+        first.subscribe(Kind::Channel, names(&["news", "sport"]));
+        second.subscribe(Kind::Channel, names(&["news"]));
+        second.subscribe(Kind::Pattern, names(&["n*", "s*"]));
+        second.subscribe(Kind::Shard, names(&["orders"]));
+
+        let mut channels = registry.channels(false, None);
+        channels.sort();
+        assert_eq!(channels, names(&["news", "sport"]));
+        assert_eq!(registry.channels(false, Some(b"s*")), names(&["sport"]));
+        assert_eq!(registry.channels(true, None), names(&["orders"]));
+        assert_eq!((registry.numsub(false, b"news"), registry.numsub(true, b"news")), (2, 0));
+        assert_eq!(registry.numpat(), 2);
+
+        // a closed connection leaves the registry
+        drop(second);
+        assert_eq!((registry.numsub(false, b"news"), registry.numpat()), (1, 0));
+        assert!(registry.channels(true, None).is_empty());
+    }
+
+    #[test]
+    fn disconnected_when_too_far_behind() {
+        let registry = Arc::new(Registry::default());
+        let mut subscription = Subscription::new(1, registry.clone());
+        subscription.subscribe(Kind::Channel, names(&["news"]));
+        for _ in 0..QUEUE_LEN {
+            assert_eq!(registry.publish(b"news", b"hi"), 1);
+        }
+        assert!(block_on(subscription.receive()).is_some());
+        assert_eq!(registry.publish(b"news", b"hi"), 1);
+        // the queue is full again, the message is dropped and the connection has to close
+        assert_eq!(registry.publish(b"news", b"hi"), 0);
+        assert!(block_on(subscription.receive()).is_none());
+    }
+}
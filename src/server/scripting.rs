@@ -0,0 +1,1257 @@
+//! The Lua scripting engine of the db worker.
+//!
+//! Scripts run in the db worker, so no other command runs until they return, and their writes are
+//! committed in a single batch like those of a transaction. `redis.call` parses its arguments into
+//! a `Command`, checks it against the ACL of the user running the script and applies it to
+//! `Shared`. Once a script runs for longer than `lua-time-limit` the
+//! connections answer BUSY, and SCRIPT KILL stops it as long as it hasn't written anything.
+//!
+//! Function libraries run their code once on FUNCTION LOAD, registering the functions FCALL calls
+//! with `redis.register_function`. Their code is saved in the functions column family and loaded
+//! again when the server starts.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use mlua::{
+    Function, HookTriggers, IntoLua, IntoLuaMulti, Lua, LuaOptions, MultiValue, RegistryKey, StdLib, Table, Value,
+};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    acl::{category::ADMIN, Acl},
+    command::{
+        eval::{Eval, Source},
+        fcall::FCall,
+        function::{Function as FunctionCommand, RestorePolicy},
+        script::Script,
+        Command,
+    },
+    protocol::{frame::Frame, parse::Parse, ParseError},
+    server::{shared::Shared, stats::Stats},
+    utils,
+};
+
+/// Lua instructions run between two checks for SCRIPT KILL.
+const HOOK_INSTRUCTIONS: u32 = 100_000;
+
+const KILLED: &str = "ERR Script killed by user with SCRIPT KILL...";
+
+/// The version FUNCTION DUMP payloads start with.
+const DUMP_VERSION: u8 = 1;
+
+/// The script in execution, shared with the connections.
+#[derive(Debug, Default)]
+pub struct Running {
+    state: Mutex<RunState>,
+}
+
+#[derive(Debug, Default)]
+struct RunState {
+    /// `None` when no script is running
+    started: Option<Instant>,
+    /// the script called a write command, it can't be killed anymore
+    written: bool,
+    killed: bool,
+    /// stopped by SHUTDOWN NOSAVE, its writes are dropped
+    aborted: bool,
+}
+
+impl Running {
+    /// Whether a script has been running for `limit` already, commands are answered with BUSY then.
+    pub fn is_busy(&self, limit: Duration) -> bool {
+        matches!(self.state.lock().unwrap().started, Some(started) if started.elapsed() >= limit)
+    }
+
+    /// SCRIPT KILL, the script stops at the next check of the hook.
+    pub fn kill(&self) -> Frame {
+        let mut state = self.state.lock().unwrap();
+        if state.started.is_none() {
+            return Frame::Error("NOTBUSY No scripts in execution right now.".to_string());
+        }
+        if state.written {
+            return Frame::Error(
+                "UNKILLABLE Sorry the script already executed write commands against the dataset. You can either \
+                 wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command."
+                    .to_string(),
+            );
+        }
+        state.killed = true;
+        Frame::Str(b"OK".to_vec())
+    }
+
+    /// SHUTDOWN NOSAVE, the script stops at the next check of the hook even after it wrote.
+    pub fn abort(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.started.is_some() {
+            state.killed = true;
+            state.aborted = true;
+        }
+    }
+
+    fn start(&self) {
+        *self.state.lock().unwrap() = RunState { started: Some(Instant::now()), ..RunState::default() };
+    }
+
+    /// Returns whether the script was aborted.
+    fn finish(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.started = None;
+        state.aborted
+    }
+
+    fn is_killed(&self) -> bool {
+        self.state.lock().unwrap().killed
+    }
+
+    /// Records that the script writes, false when it was killed first.
+    fn start_write(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.written = !state.killed;
+        state.written
+    }
+}
+
+/// A library loaded with FUNCTION LOAD.
+struct Library {
+    code: String,
+    /// the names of its functions, in the order registered
+    functions: Vec<String>,
+}
+
+/// A library compiled with its functions registered, not loaded yet.
+struct Compiled {
+    name: String,
+    code: String,
+    functions: Vec<(String, LibraryFunction)>,
+}
+
+/// A function registered by a library.
+struct LibraryFunction {
+    library: String,
+    callback: RegistryKey,
+    /// flagged no-writes, it can't write and FCALL_RO can call it
+    no_writes: bool,
+}
+
+/// The Lua interpreter with the scripts and function libraries loaded.
+pub struct Scripting {
+    lua: Lua,
+    /// the compiled scripts by the SHA1 of their body
+    scripts: HashMap<String, RegistryKey>,
+    /// the libraries by name
+    libraries: BTreeMap<String, Library>,
+    /// the functions of every library by name, their names are unique across libraries
+    functions: HashMap<String, LibraryFunction>,
+    running: Arc<Running>,
+    acl: Arc<Acl>,
+}
+
+impl Scripting {
+    pub fn new(running: Arc<Running>, acl: Arc<Acl>) -> Scripting {
+        let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH;
+        let lua = Lua::new_with(libs, LuaOptions::default()).expect("the Lua libraries are safe");
+        if let Err(err) = register_redis_lib(&lua) {
+            panic!("failed to initialize the redis Lua library,{}", err);
+        }
+        let killed = running.clone();
+        lua.set_hook(HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS), move |_, _| {
+            match killed.is_killed() {
+                true => Err(mlua::Error::RuntimeError(KILLED.to_string())),
+                false => Ok(()),
+            }
+        });
+        Scripting {
+            lua,
+            scripts: HashMap::new(),
+            libraries: BTreeMap::new(),
+            functions: HashMap::new(),
+            running,
+            acl,
+        }
+    }
+
+    /// Loads the libraries saved by FUNCTION LOAD, when the server starts.
+    pub fn load_libraries(&mut self, shared: &Shared) {
+        let libraries = match shared.function_libraries() {
+            Ok(libraries) => libraries,
+            Err(err) => {
+                log::error!("failed to read the function libraries, err = {}", err);
+                return;
+            }
+        };
+        for (name, code) in libraries {
+            if let Err(err) = self.load_library(&code, false) {
+                log::error!("failed to load function library {}, err = {}", name, err);
+            }
+        }
+    }
+
+    /// Runs EVAL and friends, loading the script first when given its body.
+    pub fn eval(&mut self, shared: &mut Shared, eval: &Eval) -> Frame {
+        let sha = match eval.source() {
+            Source::Body(body) => match self.load(body) {
+                Ok(sha) => sha,
+                Err(err) => return err,
+            },
+            Source::Sha(sha) if self.scripts.contains_key(sha) => sha.clone(),
+            Source::Sha(_) => return Frame::Error("NOSCRIPT No matching script. Please use EVAL.".to_string()),
+        };
+        let function = self.lua.registry_value::<Function>(&self.scripts[&sha]).and_then(|function| {
+            let globals = self.lua.globals();
+            globals.set("KEYS", self.lua.create_sequence_from(eval.keys())?)?;
+            globals.set("ARGV", self.lua_args(eval.args())?)?;
+            Ok(function)
+        });
+        match function {
+            Ok(function) => self.run(shared, function, (), eval.is_read_only(), eval.user()),
+            Err(err) => Frame::Error(error_reply(&err)),
+        }
+    }
+
+    /// Runs FCALL and FCALL_RO, the function being given the keys and the arguments.
+    pub fn fcall(&mut self, shared: &mut Shared, fcall: &FCall) -> Frame {
+        let function = match self.functions.get(fcall.function()) {
+            Some(function) => function,
+            None => return Frame::Error("ERR Function not found".to_string()),
+        };
+        if fcall.is_read_only() && !function.no_writes {
+            return Frame::Error("ERR Can not execute a script with write flag using *_ro command.".to_string());
+        }
+        let call = self.lua.registry_value::<Function>(&function.callback).and_then(|callback| {
+            let keys = self.lua.create_sequence_from(fcall.keys())?;
+            Ok((callback, keys, self.lua_args(fcall.args())?))
+        });
+        match call {
+            Ok((callback, keys, args)) => self.run(shared, callback, (keys, args), function.no_writes, fcall.user()),
+            Err(err) => Frame::Error(error_reply(&err)),
+        }
+    }
+
+    /// FUNCTION LOAD, DELETE, FLUSH, LIST, DUMP and RESTORE, the libraries are saved in `shared`
+    /// for the next start. KILL is answered by the connection like SCRIPT KILL.
+    pub fn function(&mut self, shared: &mut Shared, function: FunctionCommand) -> Frame {
+        let saved = match function {
+            FunctionCommand::Load { code, replace } => {
+                let name = match self.load_library(&code, replace) {
+                    Ok(name) => name,
+                    Err(err) => return Frame::Error(err),
+                };
+                match shared.save_function_library(&name, &code) {
+                    Ok(()) => return Frame::Bulk(name.into_bytes()),
+                    Err(err) => Err(err),
+                }
+            }
+            FunctionCommand::Delete(name) => {
+                if !self.libraries.contains_key(&name) {
+                    return Frame::Error("ERR Library not found".to_string());
+                }
+                self.remove_library(&name);
+                shared.delete_function_library(&name)
+            }
+            FunctionCommand::Flush => self.flush_libraries(shared),
+            FunctionCommand::Dump => {
+                return match shared.function_libraries() {
+                    Ok(libraries) => Frame::Bulk(encode_dump(libraries.iter().map(|(_, code)| code.as_str()))),
+                    Err(err) => Frame::Error(format!("{}", err)),
+                };
+            }
+            FunctionCommand::Restore { payload, policy } => self.restore(shared, &payload, policy),
+            FunctionCommand::Kill => return self.running.kill(),
+            FunctionCommand::List { pattern, with_code } => {
+                let matches = |name: &str| match &pattern {
+                    Some(pattern) => utils::backtrack_match(name.as_bytes(), pattern.as_bytes()),
+                    None => true,
+                };
+                let libraries = self
+                    .libraries
+                    .iter()
+                    .filter(|(name, _)| matches(name))
+                    .map(|(name, library)| self.describe(name, library, with_code))
+                    .collect();
+                return Frame::Array(libraries);
+            }
+        };
+        match saved {
+            Ok(()) => Frame::Str(b"OK".to_vec()),
+            Err(err) => Frame::Error(format!("{}", err)),
+        }
+    }
+
+    /// Calls a script or a function in a single batch of writes, as the running script.
+    fn run<'lua>(
+        &'lua self,
+        shared: &mut Shared,
+        function: Function<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        read_only: bool,
+        user: &str,
+    ) -> Frame {
+        // a script queued in a transaction is part of its batch
+        let nested = shared.in_transaction();
+        if !nested {
+            shared.begin();
+        }
+        self.running.start();
+        let result = self.call(shared, function, args, read_only, user);
+        if self.running.finish() {
+            // the server stops without saving, a transaction the script is part of included
+            shared.discard();
+        } else if !nested {
+            if let Err(err) = shared.commit() {
+                return Frame::Error(format!("{}", err));
+            }
+        }
+        match result {
+            Ok(frame) => frame,
+            Err(err) => Frame::Error(error_reply(&err)),
+        }
+    }
+
+    /// SCRIPT LOAD, EXISTS and FLUSH. KILL is answered by the connection, there is nothing to
+    /// kill when the db worker gets to it.
+    pub fn script(&mut self, script: Script) -> Frame {
+        match script {
+            Script::Load(body) => match self.load(&body) {
+                Ok(sha) => Frame::Bulk(sha.into_bytes()),
+                Err(err) => err,
+            },
+            Script::Exists(shas) => Frame::Array(
+                shas.iter()
+                    .map(|sha| Frame::Integer(self.scripts.contains_key(sha) as i64))
+                    .collect(),
+            ),
+            Script::Flush => {
+                for (_, key) in self.scripts.drain() {
+                    let _ = self.lua.remove_registry_value(key);
+                }
+                Frame::Str(b"OK".to_vec())
+            }
+            Script::Kill => self.running.kill(),
+        }
+    }
+
+    /// Compiles and caches a script, returns its SHA1.
+    fn load(&mut self, body: &str) -> Result<String, Frame> {
+        let sha = sha1_hex(body.as_bytes());
+        if self.scripts.contains_key(&sha) {
+            return Ok(sha);
+        }
+        let compiled = self
+            .lua
+            .load(body)
+            .set_name("@user_script")
+            .into_function()
+            .and_then(|function| self.lua.create_registry_value(function));
+        match compiled {
+            Ok(key) => {
+                self.scripts.insert(sha.clone(), key);
+                Ok(sha)
+            }
+            Err(err) => Err(Frame::Error(error_reply(&err))),
+        }
+    }
+
+    /// Calls a script with `redis.call` and `redis.pcall` bound to `shared` for the duration.
+    fn call<'lua>(
+        &'lua self,
+        shared: &mut Shared,
+        function: Function<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        read_only: bool,
+        user: &str,
+    ) -> mlua::Result<Frame> {
+        let shared = RefCell::new(shared);
+        let redis: Table = self.lua.globals().get("redis")?;
+        self.lua.scope(|scope| {
+            redis.set(
+                "call",
+                scope.create_function(|lua, args: MultiValue| {
+                    match dispatch(&mut shared.borrow_mut(), &self.running, &self.acl, user, read_only, args)? {
+                        Frame::Error(err) => Err(mlua::Error::RuntimeError(err)),
+                        frame => to_lua(lua, frame),
+                    }
+                })?,
+            )?;
+            redis.set(
+                "pcall",
+                scope.create_function(|lua, args: MultiValue| {
+                    let frame = dispatch(&mut shared.borrow_mut(), &self.running, &self.acl, user, read_only, args)?;
+                    to_lua(lua, frame)
+                })?,
+            )?;
+            from_lua(function.call::<_, Value>(args)?)
+        })
+    }
+
+    /// The arguments of a script as a Lua array of strings.
+    fn lua_args(&self, args: &[Vec<u8>]) -> mlua::Result<Table<'_>> {
+        let args = args.iter().map(|arg| self.lua.create_string(arg)).collect::<mlua::Result<Vec<_>>>()?;
+        self.lua.create_sequence_from(args)
+    }
+
+    /// Runs the code of a library to register its functions, in place of the library of the same
+    /// name with `replace`. Returns the name of the library.
+    fn load_library(&mut self, code: &str, replace: bool) -> Result<String, String> {
+        let compiled = self.compile_library(code)?;
+        if !replace && self.libraries.contains_key(&compiled.name) {
+            return Err(format!("ERR Library '{}' already exists", compiled.name));
+        }
+        let taken = compiled.functions.iter().find(|(function, _)| {
+            matches!(self.functions.get(function), Some(existing) if existing.library != compiled.name)
+        });
+        if let Some((function, _)) = taken {
+            return Err(format!("ERR Function {} already exists", function));
+        }
+        let name = compiled.name.clone();
+        self.install(compiled);
+        Ok(name)
+    }
+
+    /// FUNCTION RESTORE, loading every library of a FUNCTION DUMP payload or none of them.
+    fn restore(&mut self, shared: &Shared, payload: &[u8], policy: RestorePolicy) -> crate::Result<()> {
+        let codes = decode_dump(payload).ok_or("ERR payload version or checksum are wrong")?;
+        let mut restored: Vec<Compiled> = Vec::with_capacity(codes.len());
+        for code in codes {
+            let compiled = self.compile_library(&code)?;
+            let exists = policy == RestorePolicy::Append && self.libraries.contains_key(&compiled.name);
+            if exists || restored.iter().any(|other| other.name == compiled.name) {
+                return Err(format!("ERR Library '{}' already exists", compiled.name).into());
+            }
+            restored.push(compiled);
+        }
+
+        // the libraries left are those neither flushed nor replaced by one of the payload
+        let kept = |library: &str| policy != RestorePolicy::Flush && !restored.iter().any(|r| r.name == library);
+        let mut names = HashSet::new();
+        for (function, _) in restored.iter().flat_map(|compiled| compiled.functions.iter()) {
+            let taken = matches!(self.functions.get(function), Some(existing) if kept(&existing.library));
+            if taken || !names.insert(function) {
+                return Err(format!("ERR Function {} already exists", function).into());
+            }
+        }
+
+        if policy == RestorePolicy::Flush {
+            self.flush_libraries(shared)?;
+        }
+        for compiled in restored {
+            shared.save_function_library(&compiled.name, &compiled.code)?;
+            self.install(compiled);
+        }
+        Ok(())
+    }
+
+    /// Runs the code of a library to register its functions, without loading it.
+    fn compile_library(&self, code: &str) -> Result<Compiled, String> {
+        let name = library_name(code)?;
+        let functions = self.register_functions(&name, code).map_err(|err| error_reply(&err))?;
+        if functions.is_empty() {
+            return Err("ERR No functions registered".to_string());
+        }
+        Ok(Compiled { name, code: code.to_string(), functions })
+    }
+
+    /// Loads a compiled library in place of the one of the same name, if any.
+    fn install(&mut self, compiled: Compiled) {
+        self.remove_library(&compiled.name);
+        let mut functions = Vec::with_capacity(compiled.functions.len());
+        for (function, registration) in compiled.functions {
+            functions.push(function.clone());
+            self.functions.insert(function, registration);
+        }
+        self.libraries.insert(compiled.name, Library { code: compiled.code, functions });
+    }
+
+    fn flush_libraries(&mut self, shared: &Shared) -> crate::Result<()> {
+        let names: Vec<String> = self.libraries.keys().cloned().collect();
+        names.iter().try_for_each(|name| {
+            self.remove_library(name);
+            shared.delete_function_library(name)
+        })
+    }
+
+    /// Runs the code of a library with `redis.register_function` bound for the duration.
+    fn register_functions(&self, library: &str, code: &str) -> mlua::Result<Vec<(String, LibraryFunction)>> {
+        // the metadata line isn't Lua, it is left empty to keep the line numbers of errors
+        let body = code.split_once('\n').map_or("", |(_, body)| body);
+        let chunk = self.lua.load(format!("\n{}", body)).set_name("@user_function").into_function()?;
+
+        let registered = RefCell::new(Vec::new());
+        let redis: Table = self.lua.globals().get("redis")?;
+        let result = self.lua.scope(|scope| {
+            redis.set(
+                "register_function",
+                scope.create_function(|lua, args: MultiValue| {
+                    let (name, callback, no_writes) = registration(args)?;
+                    if !is_valid_name(&name) {
+                        let err = "Function names can only contain letters, numbers, or underscores(_) and must be \
+                                   at least one character long";
+                        return Err(mlua::Error::RuntimeError(err.to_string()));
+                    }
+                    if registered.borrow().iter().any(|(registered, _)| *registered == name) {
+                        return Err(mlua::Error::RuntimeError(format!("Function {} already exists", name)));
+                    }
+                    let callback = lua.create_registry_value(callback)?;
+                    let function = LibraryFunction { library: library.to_string(), callback, no_writes };
+                    registered.borrow_mut().push((name, function));
+                    Ok(())
+                })?,
+            )?;
+            chunk.call::<_, ()>(())
+        });
+        redis.set("register_function", Value::Nil)?;
+        result.map(|_| registered.into_inner())
+    }
+
+    fn remove_library(&mut self, name: &str) {
+        if let Some(library) = self.libraries.remove(name) {
+            for function in library.functions {
+                if let Some(function) = self.functions.remove(&function) {
+                    let _ = self.lua.remove_registry_value(function.callback);
+                }
+            }
+        }
+    }
+
+    /// A library as FUNCTION LIST replies it.
+    fn describe(&self, name: &str, library: &Library, with_code: bool) -> Frame {
+        let bulk = |s: &str| Frame::Bulk(s.as_bytes().to_vec());
+        let functions = library
+            .functions
+            .iter()
+            .map(|function| {
+                let flags = match self.functions[function].no_writes {
+                    true => vec![bulk("no-writes")],
+                    false => Vec::new(),
+                };
+                Frame::Map(vec![
+                    (bulk("name"), bulk(function)),
+                    (bulk("description"), Frame::Nil),
+                    (bulk("flags"), Frame::Array(flags)),
+                ])
+            })
+            .collect();
+        let mut fields = vec![
+            (bulk("library_name"), bulk(name)),
+            (bulk("engine"), bulk("LUA")),
+            (bulk("functions"), Frame::Array(functions)),
+        ];
+        if with_code {
+            fields.push((bulk("library_code"), bulk(&library.code)));
+        }
+        Frame::Map(fields)
+    }
+}
+
+/// A FUNCTION DUMP payload: the version, the code of every library prefixed by its length, then
+/// the SHA1 of all that.
+fn encode_dump<'a>(codes: impl Iterator<Item = &'a str>) -> Vec<u8> {
+    let mut payload = vec![DUMP_VERSION];
+    for code in codes {
+        payload.extend_from_slice(&(code.len() as u32).to_be_bytes());
+        payload.extend_from_slice(code.as_bytes());
+    }
+    let checksum = Sha1::digest(&payload);
+    payload.extend_from_slice(&checksum);
+    payload
+}
+
+/// The code of the libraries of a FUNCTION DUMP payload, `None` when it is not one.
+fn decode_dump(payload: &[u8]) -> Option<Vec<String>> {
+    let (body, checksum) = payload.split_at_checked(payload.len().checked_sub(Sha1::output_size())?)?;
+    if Sha1::digest(body)[..] != *checksum || body.first() != Some(&DUMP_VERSION) {
+        return None;
+    }
+    let mut codes = Vec::new();
+    let mut rest = &body[1..];
+    while !rest.is_empty() {
+        let (len, tail) = rest.split_at_checked(4)?;
+        let len = u32::from_be_bytes(len.try_into().ok()?) as usize;
+        let (code, tail) = tail.split_at_checked(len)?;
+        codes.push(String::from_utf8(code.to_vec()).ok()?);
+        rest = tail;
+    }
+    Some(codes)
+}
+
+/// The name of a library from its first line, `#!lua name=<name>`.
+fn library_name(code: &str) -> Result<String, String> {
+    let metadata = code.lines().next().and_then(|line| line.strip_prefix("#!"));
+    let mut metadata = metadata.ok_or("ERR Missing library metadata")?.split_whitespace();
+    let engine = metadata.next().unwrap_or_default();
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(format!("ERR Engine '{}' not found", engine));
+    }
+    let mut name = None;
+    for field in metadata {
+        match field.split_once('=') {
+            Some(("name", value)) => name = Some(value.to_string()),
+            _ => return Err(format!("ERR Invalid metadata value given: {}", field)),
+        }
+    }
+    match name {
+        Some(name) if is_valid_name(&name) => Ok(name),
+        Some(_) => Err("ERR Library names can only contain letters, numbers, or underscores(_) and must be at least \
+                        one character long"
+            .to_string()),
+        None => Err("ERR Library name was not given".to_string()),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The arguments of `redis.register_function`, a name and a callback, or a table of them with the
+/// flags, returned with whether the function is flagged no-writes.
+fn registration(args: MultiValue) -> mlua::Result<(String, Function, bool)> {
+    let invalid = |message: &str| mlua::Error::RuntimeError(message.to_string());
+    let (name, callback, flags) = match args.into_vec().as_slice() {
+        [name, callback] => (name.clone(), callback.clone(), Value::Nil),
+        [Value::Table(named)] => (named.get("function_name")?, named.get("callback")?, named.get("flags")?),
+        [_] => {
+            return Err(invalid(
+                "calling redis.register_function with a single argument is only applicable to Lua table \
+                 (representing named arguments).",
+            ))
+        }
+        _ => return Err(invalid("wrong number of arguments to redis.register_function")),
+    };
+    let name = match name {
+        Value::String(name) => name.to_str()?.to_string(),
+        _ => return Err(invalid("function_name argument given to redis.register_function must be a string")),
+    };
+    let callback = match callback {
+        Value::Function(callback) => callback,
+        _ => return Err(invalid("callback argument given to redis.register_function must be a function")),
+    };
+    let mut no_writes = false;
+    match flags {
+        Value::Nil => {}
+        Value::Table(flags) => {
+            for flag in flags.sequence_values::<mlua::String>() {
+                match flag?.to_str()? {
+                    "no-writes" => no_writes = true,
+                    // accepted for compatibility, they change nothing here
+                    "allow-oom" | "allow-stale" | "no-cluster" | "allow-cross-slot-keys" => {}
+                    _ => return Err(invalid("unknown flag given")),
+                }
+            }
+        }
+        _ => {
+            let err = "flags argument to redis.register_function must be a table representing function flags";
+            return Err(invalid(err));
+        }
+    }
+    Ok((name, callback, no_writes))
+}
+
+/// The `redis` table, `call` and `pcall` are added for each script run.
+fn register_redis_lib(lua: &Lua) -> mlua::Result<()> {
+    let redis = lua.create_table()?;
+    redis.set(
+        "status_reply",
+        lua.create_function(|lua, status: mlua::String| lua.create_table_from([("ok", status)]))?,
+    )?;
+    redis.set(
+        "error_reply",
+        lua.create_function(|lua, err: mlua::String| lua.create_table_from([("err", err)]))?,
+    )?;
+    redis.set("sha1hex", lua.create_function(|_, value: mlua::String| Ok(sha1_hex(value.as_bytes())))?)?;
+    redis.set(
+        "log",
+        lua.create_function(|_, (level, message): (i64, mlua::String)| {
+            let message = message.to_string_lossy();
+            match level {
+                0 => log::debug!("script: {}", message),
+                1 | 2 => log::info!("script: {}", message),
+                _ => log::warn!("script: {}", message),
+            }
+            Ok(())
+        })?,
+    )?;
+    for (level, name) in ["LOG_DEBUG", "LOG_VERBOSE", "LOG_NOTICE", "LOG_WARNING"].iter().enumerate() {
+        redis.set(*name, level)?;
+    }
+    lua.globals().set("redis", redis)
+}
+
+/// Runs a command for `redis.call` as `user`, errors are replied as error frames. Only SCRIPT KILL
+/// raises.
+fn dispatch(
+    shared: &mut Shared,
+    running: &Running,
+    acl: &Acl,
+    user: &str,
+    read_only: bool,
+    args: MultiValue,
+) -> mlua::Result<Frame> {
+    let mut frames = Vec::with_capacity(args.len());
+    for arg in args {
+        let arg = match arg {
+            Value::String(arg) => arg.as_bytes().to_vec(),
+            Value::Integer(arg) => arg.to_string().into_bytes(),
+            Value::Number(arg) => arg.to_string().into_bytes(),
+            _ => {
+                let err = "ERR Lua redis lib command arguments must be strings or integers";
+                return Ok(Frame::Error(err.to_string()));
+            }
+        };
+        frames.push(Frame::Bulk(arg));
+    }
+    if frames.is_empty() {
+        return Ok(Frame::Error("ERR Please specify at least one argument for this redis lib call".to_string()));
+    }
+
+    let cmd = match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+        Ok(Command::UNKNOWN(_, _)) => {
+            return Ok(Frame::Error("ERR Unknown Redis command called from script".to_string()));
+        }
+        Ok(cmd) => cmd,
+        Err(ParseError::EOF) => {
+            return Ok(Frame::Error("ERR wrong number of arguments for command".to_string()));
+        }
+        Err(err) => return Ok(Frame::Error(format!("{}", err))),
+    };
+    if !is_allowed(&cmd) {
+        return Ok(Frame::Error("ERR This Redis command is not allowed from script".to_string()));
+    }
+    // the keys a command touches aren't all declared in KEYS, each call is checked on its own
+    if let Err(err) = acl.check(user, &cmd) {
+        return Ok(err);
+    }
+    if cmd.is_write() {
+        if read_only {
+            return Ok(Frame::Error("ERR Write commands are not allowed from read-only scripts.".to_string()));
+        }
+        if !running.start_write() {
+            return Err(mlua::Error::RuntimeError(KILLED.to_string()));
+        }
+    }
+
+    Stats::incr(&shared.stats().total_commands_processed);
+    for key in cmd.keys() {
+        if let Err(err) = shared.expire_if_needed(key) {
+            log::error!("failed to remove expired key {}, err = {}", key, err);
+        }
+    }
+    match cmd.apply(shared) {
+        Ok(frame) => Ok(frame),
+        Err(err) => Ok(Frame::Error(format!("{}", err))),
+    }
+}
+
+/// Scripts only run commands of the db worker that can't block or nest, and no admin commands.
+fn is_allowed(cmd: &Command) -> bool {
+    let denied = matches!(
+        cmd,
+        Command::AUTH(_)
+            | Command::HELLO(_)
+            | Command::ACL(_)
+            | Command::CLIENT(_)
+            | Command::CONFIG(_)
+            | Command::SHUTDOWN(_)
+            | Command::MULTI(_)
+            | Command::EXEC(_)
+            | Command::DISCARD(_)
+            | Command::WATCH(_)
+            | Command::UNWATCH(_)
+            | Command::SUBSCRIBE(_)
+            | Command::UNSUBSCRIBE(_)
+            | Command::PSUBSCRIBE(_)
+            | Command::PUNSUBSCRIBE(_)
+            | Command::SSUBSCRIBE(_)
+            | Command::SUNSUBSCRIBE(_)
+            | Command::EVAL(_)
+            | Command::EVALSHA(_)
+            | Command::EVAL_RO(_)
+            | Command::EVALSHA_RO(_)
+            | Command::SCRIPT(_)
+            | Command::FCALL(_)
+            | Command::FCALL_RO(_)
+            | Command::FUNCTION(_)
+    );
+    !denied && cmd.categories() & ADMIN == 0
+}
+
+/// Converts a reply to Lua like redis: nil is false, status and error replies are tables with an
+/// `ok` or `err` field.
+fn to_lua(lua: &Lua, frame: Frame) -> mlua::Result<Value<'_>> {
+    match frame {
+        Frame::Integer(n) => n.into_lua(lua),
+        Frame::Bulk(bytes) => Ok(Value::String(lua.create_string(&bytes)?)),
+        Frame::Str(status) => Ok(Value::Table(lua.create_table_from([("ok", lua.create_string(&status)?)])?)),
+        Frame::Error(err) => Ok(Value::Table(lua.create_table_from([("err", lua.create_string(&err)?)])?)),
+        Frame::Nil => Ok(Value::Boolean(false)),
+        Frame::Array(items) | Frame::Push(items) => {
+            let items = items.into_iter().map(|item| to_lua(lua, item)).collect::<mlua::Result<Vec<_>>>()?;
+            Ok(Value::Table(lua.create_sequence_from(items)?))
+        }
+        map @ Frame::Map(_) => to_lua(lua, map.into_resp2()),
+    }
+}
+
+/// Converts what a script returns to a reply like redis: numbers are truncated to integers, true
+/// is 1 and false nil, an array stops at its first nil.
+fn from_lua(value: Value) -> mlua::Result<Frame> {
+    match value {
+        Value::Boolean(true) => Ok(Frame::Integer(1)),
+        Value::Integer(n) => Ok(Frame::Integer(n)),
+        Value::Number(n) => Ok(Frame::Integer(n as i64)),
+        Value::String(bytes) => Ok(Frame::Bulk(bytes.as_bytes().to_vec())),
+        Value::Table(table) => {
+            if let Value::String(err) = table.raw_get("err")? {
+                return Ok(Frame::Error(err.to_string_lossy().into_owned()));
+            }
+            if let Value::String(status) = table.raw_get("ok")? {
+                return Ok(Frame::Str(status.as_bytes().to_vec()));
+            }
+            let mut items = Vec::new();
+            for i in 1.. {
+                match table.raw_get::<_, Value>(i)? {
+                    Value::Nil => break,
+                    item => items.push(from_lua(item)?),
+                }
+            }
+            Ok(Frame::Array(items))
+        }
+        _ => Ok(Frame::Nil),
+    }
+}
+
+/// The reply to a script that failed. Errors of `redis.call` keep their code.
+fn error_reply(err: &mlua::Error) -> String {
+    match err {
+        mlua::Error::CallbackError { cause, .. } | mlua::Error::WithContext { cause, .. } => error_reply(cause),
+        mlua::Error::SyntaxError { message, .. } => format!("ERR Error compiling script (new function): {}", message),
+        mlua::Error::RuntimeError(message) => {
+            let message = message.split("\nstack traceback:").next().unwrap_or_default();
+            let has_code = message
+                .split(' ')
+                .next()
+                .is_some_and(|code| !code.is_empty() && code.chars().all(|c| c.is_ascii_uppercase()));
+            match has_code {
+                true => message.to_string(),
+                false => format!("ERR {}", message),
+            }
+        }
+        err => format!("ERR {}", err),
+    }
+}
+
+fn sha1_hex(bytes: &[u8]) -> String {
+    Sha1::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{
+        acl::DEFAULT_USER,
+        server::{
+            shared::testing::{run, show, temporary_dir, with_shared},
+            shutdown::ShutdownRequest,
+        },
+    };
+
+    fn command(args: &[&str]) -> Command {
+        let args = args.iter().map(|arg| Frame::Bulk(arg.as_bytes().to_vec())).collect();
+        Parse::new(Frame::Array(args)).and_then(Parse::into_command).unwrap()
+    }
+
+    fn scripting() -> (Scripting, Arc<Running>) {
+        let running = Arc::new(Running::default());
+        let acl = Arc::new(Acl::load(temporary_dir("scripting-acl")).unwrap());
+        (Scripting::new(running.clone(), acl), running)
+    }
+
+    /// A scripting engine with the ACL `users` on top of the default user.
+    fn scripting_for(users: &str) -> Scripting {
+        let file = temporary_dir("scripting-acl");
+        std::fs::write(&file, users).unwrap();
+        let acl = Acl::load(file.clone()).unwrap();
+        let _ = std::fs::remove_file(&file);
+        Scripting::new(Arc::new(Running::default()), Arc::new(acl))
+    }
+
+    /// What `code` returns, as replied to the client.
+    fn returned(code: &str) -> String {
+        let lua = Lua::new();
+        show(&from_lua(lua.load(code).eval::<Value>().unwrap()).unwrap())
+    }
+
+    /// A reply as a script sees it, then as the script returns it.
+    fn round_trip(frame: Frame) -> String {
+        let lua = Lua::new();
+        show(&from_lua(to_lua(&lua, frame).unwrap()).unwrap())
+    }
+
+    /// An EVAL of `body` with `args`, keys first, run by the default user.
+    fn eval_command(body: &str, args: &[&str]) -> Eval {
+        let numkeys = args.len().to_string();
+        let mut eval = match command(&[&["eval", body, &numkeys], args].concat()) {
+            Command::EVAL(eval) => eval,
+            cmd => panic!("parsed {:?}", cmd),
+        };
+        eval.set_user(DEFAULT_USER);
+        eval
+    }
+
+    /// Evaluates `body` while `stop` is called from another thread once the script runs.
+    fn eval_stopped(shared: &mut Shared, body: &str, stop: impl FnOnce(&Running) -> Frame + Send + 'static) -> (Frame, Frame) {
+        let (mut scripting, running) = scripting();
+        let eval = eval_command(body, &[]);
+        let stopper = thread::spawn(move || {
+            while !running.is_busy(Duration::ZERO) {
+                thread::yield_now();
+            }
+            thread::sleep(Duration::from_millis(50));
+            stop(&running)
+        });
+        let reply = scripting.eval(shared, &eval);
+        (reply, stopper.join().unwrap())
+    }
+
+    #[test]
+    fn kill_before_a_write() {
+        let running = Running::default();
+        running.start();
+        assert_eq!(show(&running.kill()), "OK");
+        assert!(running.is_killed());
+        assert!(!running.start_write());
+        assert!(!running.finish());
+    }
+
+    #[test]
+    fn kill_after_a_write_is_refused() {
+        let running = Running::default();
+        running.start();
+        assert!(running.start_write());
+        assert!(show(&running.kill()).starts_with("(error) UNKILLABLE"));
+        assert!(!running.is_killed());
+        assert!(!running.finish());
+    }
+
+    #[test]
+    fn abort_after_a_write() {
+        let running = Running::default();
+        running.start();
+        assert!(running.start_write());
+        running.abort();
+        assert!(running.is_killed());
+        assert!(running.finish());
+    }
+
+    #[test]
+    fn nothing_to_kill_or_abort() {
+        let running = Running::default();
+        assert!(show(&running.kill()).starts_with("(error) NOTBUSY"));
+        running.abort();
+        running.start();
+        assert!(!running.is_killed());
+    }
+
+    #[test]
+    fn killed_script_stops_before_writing() {
+        with_shared("script-kill", |shared| {
+            let (reply, kill) = eval_stopped(shared, "while true do end", |running| running.kill());
+            assert_eq!(show(&kill), "OK");
+            assert_eq!(show(&reply), format!("(error) {}", KILLED));
+        });
+    }
+
+    #[test]
+    fn script_that_wrote_only_stops_for_shutdown_nosave() {
+        with_shared("script-nosave", |shared| {
+            let body = "redis.call('set', 'key', 'value') while true do end";
+            let (reply, kill) = eval_stopped(shared, body, |running| {
+                let kill = running.kill();
+                let shutdown = match command(&["shutdown", "nosave"]) {
+                    Command::SHUTDOWN(shutdown) => shutdown,
+                    cmd => panic!("parsed {:?}", cmd),
+                };
+                assert!(shutdown.execute(&ShutdownRequest::default(), running).is_none());
+                kill
+            });
+            assert!(show(&kill).starts_with("(error) UNKILLABLE"));
+            assert_eq!(show(&reply), format!("(error) {}", KILLED));
+            // its writes are dropped with the aborted script
+            assert_eq!(show(&run(shared, &["get", "key"])), "(nil)");
+        });
+    }
+
+    #[test]
+    fn script_in_a_transaction_is_part_of_its_batch() {
+        with_shared("script-in-exec", |shared| {
+            let (mut scripting, _) = scripting();
+            shared.begin();
+            run(shared, &["set", "key", "1"]);
+            let eval = eval_command("redis.call('incr', KEYS[1]) return redis.call('get', KEYS[1])", &["key"]);
+            // the script reads what the transaction wrote so far
+            assert_eq!(show(&scripting.eval(shared, &eval)), "\"2\"");
+            // and leaves its writes to the transaction
+            assert!(shared.in_transaction());
+            assert_eq!(show(&run(shared, &["get", "key"])), "\"2\"");
+            shared.discard();
+            assert_eq!(show(&run(shared, &["get", "key"])), "(nil)");
+        });
+    }
+
+    #[test]
+    fn script_writes_are_committed_together() {
+        with_shared("script-commit", |shared| {
+            let (mut scripting, _) = scripting();
+            let eval = eval_command("redis.call('set', KEYS[1], 'a') redis.call('set', KEYS[2], 'b')", &["one", "two"]);
+            assert_eq!(show(&scripting.eval(shared, &eval)), "(nil)");
+            assert!(!shared.in_transaction());
+            assert_eq!(show(&run(shared, &["mget", "one", "two"])), "[\"a\", \"b\"]");
+        });
+    }
+
+    #[test]
+    fn script_values_to_replies() {
+        assert_eq!(returned("return 3.99"), "(integer) 3");
+        assert_eq!(returned("return -3.99"), "(integer) -3");
+        assert_eq!(returned("return 'x'"), "\"x\"");
+        assert_eq!((returned("return true"), returned("return false")), ("(integer) 1".to_string(), "(nil)".to_string()));
+        assert_eq!(returned("return nil"), "(nil)");
+        // an array stops at its first nil, other keys are ignored
+        assert_eq!(returned("return {1, 'two', {3}, nil, 5}"), "[(integer) 1, \"two\", [(integer) 3]]");
+        assert_eq!(returned("return {1, key = 'ignored'}"), "[(integer) 1]");
+        assert_eq!(returned("return {err = 'MY failure'}"), "(error) MY failure");
+        assert_eq!(returned("return {ok = 'fine'}"), "fine");
+    }
+
+    #[test]
+    fn replies_to_script_values() {
+        assert_eq!(round_trip(Frame::Integer(7)), "(integer) 7");
+        assert_eq!(round_trip(Frame::Bulk(b"v".to_vec())), "\"v\"");
+        assert_eq!(round_trip(Frame::Str(b"OK".to_vec())), "OK");
+        assert_eq!(round_trip(Frame::Error("ERR nope".to_string())), "(error) ERR nope");
+        // nil becomes false, which a script returns as nil again
+        assert_eq!(round_trip(Frame::Nil), "(nil)");
+        let map = Frame::Map(vec![(Frame::Bulk(b"k".to_vec()), Frame::Integer(1))]);
+        assert_eq!(round_trip(map), "[\"k\", (integer) 1]");
+        // a nil inside an array is false, which doesn't end the array on the way back
+        let array = Frame::Array(vec![Frame::Integer(1), Frame::Nil, Frame::Integer(2)]);
+        assert_eq!(round_trip(array), "[(integer) 1, (nil), (integer) 2]");
+    }
+
+    #[test]
+    fn error_replies_keep_their_code() {
+        let runtime = |message: &str| error_reply(&mlua::Error::RuntimeError(message.to_string()));
+        assert_eq!(runtime("WRONGTYPE Operation against a key"), "WRONGTYPE Operation against a key");
+        assert_eq!(runtime("attempt to call a nil value"), "ERR attempt to call a nil value");
+        assert_eq!(runtime("boom\nstack traceback:\n\t[C]: in ?"), "ERR boom");
+        let callback = mlua::Error::CallbackError {
+            traceback: String::new(),
+            cause: Arc::new(mlua::Error::RuntimeError("NOPERM denied".to_string())),
+        };
+        assert_eq!(error_reply(&callback), "NOPERM denied");
+        let lua = Lua::new();
+        let syntax = lua.load("return (").into_function().unwrap_err();
+        assert!(error_reply(&syntax).starts_with("ERR Error compiling script (new function): "));
+    }
+
+    #[test]
+    fn admin_and_connection_commands_are_not_allowed() {
+        for args in [&["config", "get", "maxmemory"][..], &["multi"], &["subscribe", "c"], &["eval", "return 1", "0"], &["acl", "whoami"]] {
+            assert!(!is_allowed(&command(args)), "{:?}", args);
+        }
+        for args in [&["get", "k"][..], &["set", "k", "v"], &["flushdb"], &["publish", "c", "m"]] {
+            assert!(is_allowed(&command(args)), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn calls_are_checked_against_the_acl() {
+        with_shared("script-acl", |shared| {
+            let mut scripting = scripting_for("user alice on nopass ~cache:* +@read +eval\n");
+            let mut eval = eval_command("return redis.pcall('set', KEYS[1], 'v')", &["cache:1"]);
+            eval.set_user("alice");
+            assert_eq!(
+                show(&scripting.eval(shared, &eval)),
+                "(error) NOPERM User alice has no permissions to run the 'set' command"
+            );
+            let mut eval = eval_command("return redis.call('get', 'other')", &[]);
+            eval.set_user("alice");
+            assert_eq!(show(&scripting.eval(shared, &eval)), "(error) NOPERM No permissions to access a key");
+            let mut eval = eval_command("return redis.call('get', KEYS[1])", &["cache:1"]);
+            eval.set_user("alice");
+            assert_eq!(show(&scripting.eval(shared, &eval)), "(nil)");
+        });
+    }
+
+    #[test]
+    fn calls_that_fail() {
+        with_shared("script-call-errors", |shared| {
+            let (mut scripting, _) = scripting();
+            let mut eval = |body: &str| show(&scripting.eval(shared, &eval_command(body, &[])));
+            assert_eq!(eval("return redis.call('nope')"), "(error) ERR Unknown Redis command called from script");
+            assert_eq!(eval("return redis.call('config', 'get', 'x')"), "(error) ERR This Redis command is not allowed from script");
+            assert_eq!(eval("return redis.call()"), "(error) ERR Please specify at least one argument for this redis lib call");
+            assert_eq!(eval("return redis.call('get', {})"), "(error) ERR Lua redis lib command arguments must be strings or integers");
+            // pcall hands the error to the script, call raises it
+            assert_eq!(eval("redis.call('set', 'k', 'v') return redis.pcall('incr', 'k')['err']"), "\"ERR value is not an integer or out of range\"");
+            assert_eq!(eval("redis.call('incr', 'k') return 'unreachable'"), "(error) ERR value is not an integer or out of range");
+        });
+    }
+
+    #[test]
+    fn read_only_scripts_cannot_write() {
+        with_shared("script-ro", |shared| {
+            let (mut scripting, _) = scripting();
+            let mut eval = match command(&["eval_ro", "return redis.call('set', KEYS[1], 'v')", "1", "k"]) {
+                Command::EVAL_RO(eval) => eval,
+                cmd => panic!("parsed {:?}", cmd),
+            };
+            eval.set_user(DEFAULT_USER);
+            assert_eq!(show(&scripting.eval(shared, &eval)), "(error) ERR Write commands are not allowed from read-only scripts.");
+            assert_eq!(show(&run(shared, &["get", "k"])), "(nil)");
+        });
+    }
+
+    /// A library `name` registering a function of each of `functions`.
+    fn library(name: &str, functions: &[&str]) -> String {
+        let registrations: String = functions
+            .iter()
+            .map(|function| format!("redis.register_function('{}', function() return '{}.{}' end)\n", function, name, function))
+            .collect();
+        format!("#!lua name={}\n{}", name, registrations)
+    }
+
+    fn fcall(scripting: &mut Scripting, shared: &mut Shared, args: &[&str]) -> String {
+        let mut fcall = match command(args) {
+            Command::FCALL(fcall) | Command::FCALL_RO(fcall) => fcall,
+            cmd => panic!("parsed {:?}", cmd),
+        };
+        fcall.set_user(DEFAULT_USER);
+        show(&scripting.fcall(shared, &fcall))
+    }
+
+    /// The names of the libraries loaded, from FUNCTION LIST.
+    fn listed(scripting: &mut Scripting, shared: &mut Shared) -> String {
+        match scripting.function(shared, FunctionCommand::List { pattern: None, with_code: false }) {
+            Frame::Array(libraries) => libraries
+                .into_iter()
+                .map(|library| match library {
+                    Frame::Map(mut fields) => show(&fields.remove(0).1),
+                    library => panic!("listed {:?}", library),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            frame => panic!("listed {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn library_metadata() {
+        assert_eq!(library_name("#!lua name=mylib\nreturn"), Ok("mylib".to_string()));
+        assert_eq!(library_name("#!LUA name=my_lib_2"), Ok("my_lib_2".to_string()));
+        assert_eq!(library_name("return 1"), Err("ERR Missing library metadata".to_string()));
+        assert_eq!(library_name("#!js name=mylib"), Err("ERR Engine 'js' not found".to_string()));
+        assert_eq!(library_name("#!lua"), Err("ERR Library name was not given".to_string()));
+        assert_eq!(library_name("#!lua name=a version=1"), Err("ERR Invalid metadata value given: version=1".to_string()));
+        assert!(library_name("#!lua name=my-lib").unwrap_err().starts_with("ERR Library names can only contain letters"));
+        assert!(library_name("#!lua name=").unwrap_err().starts_with("ERR Library names can only contain letters"));
+    }
+
+    #[test]
+    fn dump_payloads() {
+        let codes = [library("one", &["f"]), library("two", &["g"])];
+        let payload = encode_dump(codes.iter().map(String::as_str));
+        assert_eq!(payload[0], DUMP_VERSION);
+        assert_eq!(decode_dump(&payload), Some(codes.to_vec()));
+        assert_eq!(decode_dump(&encode_dump(std::iter::empty())), Some(vec![]));
+
+        let mut corrupt = payload.clone();
+        corrupt[6] ^= 1;
+        assert_eq!(decode_dump(&corrupt), None);
+        assert_eq!(decode_dump(&payload[..payload.len() - 1]), None);
+        assert_eq!(decode_dump(b"short"), None);
+        // a payload of another version, with a valid checksum
+        let mut other = payload[..payload.len() - Sha1::output_size()].to_vec();
+        other[0] = DUMP_VERSION + 1;
+        let checksum = Sha1::digest(&other);
+        other.extend_from_slice(&checksum);
+        assert_eq!(decode_dump(&other), None);
+    }
+
+    #[test]
+    fn libraries_loaded() {
+        let (mut scripting, _) = scripting();
+        assert_eq!(scripting.load_library(&library("one", &["f", "g"]), false), Ok("one".to_string()));
+        assert_eq!(scripting.load_library(&library("one", &["f"]), false), Err("ERR Library 'one' already exists".to_string()));
+        assert_eq!(scripting.load_library(&library("two", &["g"]), false), Err("ERR Function g already exists".to_string()));
+        // replacing a library frees the names of the functions it no longer registers
+        assert_eq!(scripting.load_library(&library("one", &["f"]), true), Ok("one".to_string()));
+        assert_eq!(scripting.load_library(&library("two", &["g"]), false), Ok("two".to_string()));
+        assert_eq!(scripting.libraries["one"].functions, ["f"]);
+
+        assert_eq!(scripting.load_library("#!lua name=none\nreturn 1", false), Err("ERR No functions registered".to_string()));
+        assert_eq!(scripting.load_library(&library("twice", &["h", "h"]), false), Err("ERR Function h already exists".to_string()));
+        assert!(scripting.load_library(&library("bad", &["a-b"]), false).unwrap_err().starts_with("ERR Function names can only contain"));
+        let flagged = "#!lua name=flagged\nredis.register_function{function_name='h', callback=function() end, flags={'nope'}}";
+        assert_eq!(scripting.load_library(flagged, false), Err("ERR unknown flag given".to_string()));
+        assert!(!scripting.libraries.contains_key("flagged") && !scripting.functions.contains_key("h"));
+    }
+
+    #[test]
+    fn functions_called() {
+        with_shared("fcall", |shared| {
+            let (mut scripting, _) = scripting();
+            let code = "#!lua name=lib\n\
+                        redis.register_function('echo', function(keys, args) return {keys[1], args[1]} end)\n\
+                        redis.register_function{function_name='peek', callback=function(keys) return redis.call('get', keys[1]) end, flags={'no-writes'}}";
+            scripting.load_library(code, false).unwrap();
+            assert_eq!(fcall(&mut scripting, shared, &["fcall", "echo", "1", "k", "a"]), "[\"k\", \"a\"]");
+            assert_eq!(fcall(&mut scripting, shared, &["fcall_ro", "peek", "1", "k"]), "(nil)");
+            assert_eq!(
+                fcall(&mut scripting, shared, &["fcall_ro", "echo", "0"]),
+                "(error) ERR Can not execute a script with write flag using *_ro command."
+            );
+            assert_eq!(fcall(&mut scripting, shared, &["fcall", "nope", "0"]), "(error) ERR Function not found");
+        });
+    }
+
+    #[test]
+    fn restore_policies() {
+        with_shared("function-restore", |shared| {
+            let (mut scripting, _) = scripting();
+            for code in [library("one", &["f"]), library("two", &["g"])] {
+                scripting.function(shared, FunctionCommand::Load { code, replace: false });
+            }
+            let payload = match scripting.function(shared, FunctionCommand::Dump) {
+                Frame::Bulk(payload) => payload,
+                frame => panic!("dumped {:?}", frame),
+            };
+            let restore = |scripting: &mut Scripting, shared: &mut Shared, policy| {
+                let payload = payload.clone();
+                show(&scripting.function(shared, FunctionCommand::Restore { payload, policy }))
+            };
+
+            assert_eq!(restore(&mut scripting, shared, RestorePolicy::Append), "(error) ERR Library 'one' already exists");
+            assert_eq!(restore(&mut scripting, shared, RestorePolicy::Replace), "OK");
+            assert_eq!(listed(&mut scripting, shared), "\"one\" \"two\"");
+
+            // a library kept by REPLACE can't have the function of one restored
+            scripting.function(shared, FunctionCommand::Delete("two".to_string()));
+            scripting.function(shared, FunctionCommand::Load { code: library("other", &["g"]), replace: false });
+            assert_eq!(restore(&mut scripting, shared, RestorePolicy::Replace), "(error) ERR Function g already exists");
+            assert_eq!(listed(&mut scripting, shared), "\"one\" \"other\"");
+            assert_eq!(restore(&mut scripting, shared, RestorePolicy::Flush), "OK");
+            assert_eq!(listed(&mut scripting, shared), "\"one\" \"two\"");
+
+            let payload = b"garbage".to_vec();
+            let reply = scripting.function(shared, FunctionCommand::Restore { payload, policy: RestorePolicy::Flush });
+            assert_eq!(show(&reply), "(error) ERR payload version or checksum are wrong");
+            assert_eq!(listed(&mut scripting, shared), "\"one\" \"two\"");
+        });
+    }
+}
@@ -1,43 +1,158 @@
-use rocksdb::{Options, DB as Rocksdb, WriteOptions, WriteBatch, SliceTransform, ReadOptions};
-use std::{path::Path};
+use rocksdb::{
+    BoundColumnFamily, ColumnFamilyDescriptor, CompactionDecision, Direction, IteratorMode, MergeOperands, Options,
+    DB as Rocksdb, WriteBatch, WriteOptions, SliceTransform, ReadOptions,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
-use crate::{server::value::Value, utils};
+use crate::{
+    config::{Config, ServerProperties},
+    server::{
+        batch::{Batch, Cf, Entry, Overlay},
+        encoding::{self, Header},
+        notify,
+        pubsub::Registry,
+        stats::Stats,
+        stream::{
+            self, Claim, ConsumerMeta, Delivered, Fields, GroupMeta, NewId, PendingEntry, StreamId, StreamMeta, Trim, TrimStrategy,
+        },
+        value::Value,
+    },
+    utils,
+};
 
+/// When SET writes, its NX and XX options.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetCondition {
+    Always,
+    IfMissing,
+    IfExists,
+}
+
+pub const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+/// The values given a header per write when migrating a database of an older format.
+const MIGRATION_BATCH: usize = 1000;
+
+/// A key WATCHed by some connections, see `Shared::watch`.
+struct Watched {
+    connections: usize,
+    version: u64,
+}
 
 
 pub struct Shared {
     database: Rocksdb,
     options: Options,
+    config: Arc<Config>,
+    stats: Arc<Stats>,
+    pubsub: Arc<Registry>,
+    /// writes of the transaction in progress, see `begin`
+    overlay: Option<Overlay>,
+    /// modification versions of the keys WATCHed, other keys aren't tracked
+    watched: HashMap<Vec<u8>, Watched>,
+    /// the version of the last modification of a watched key
+    version: u64,
+    /// where the next `expire_cycle` resumes, empty to start over
+    expire_cursor: Vec<u8>,
 }
 
 impl Shared {
-    pub fn new(append_file: &str) -> Shared {
-        let path = Path::new(append_file);
-        let mut opts = Options::default();
+    pub fn new(config: Arc<Config>, stats: Arc<Stats>, pubsub: Arc<Registry>) -> Shared {
+        let props = config.props().clone();
+        let path = Path::new(&props.append_filename);
+        let mut opts = tunable_options(&props);
         opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
         let slice_transform = SliceTransform::create_noop();
         opts.set_prefix_extractor(slice_transform);
-        let database = match Rocksdb::open(&opts, path) {
+
+        // registered whether or not counter-merge is on, operands written earlier still have to be merged
+        opts.set_merge_operator_associative("counter", merge_counter);
+
+        // Removing an expired value could bring back an older version of the key from a lower
+        // level, so compactions only shrink it to a stub that still reads as expired. Values with
+        // sub entries are left whole, their kind is what tells to clear the entries once replaced.
+        // Nothing is changed before the format is checked, the values of an older database having
+        // no header to read the expire time from.
+        let format_checked = Arc::new(AtomicBool::new(false));
+        let filter_enabled = format_checked.clone();
+        opts.set_compaction_filter("expired_keys", move |_level, _key, value| {
+            if filter_enabled.load(Ordering::Acquire)
+                && value.len() > encoding::EXPIRED_STUB.len()
+                && !encoding::has_sub_entries(value[0])
+                && encoding::is_expired(value, utils::now_millis())
+            {
+                CompactionDecision::Change(&encoding::EXPIRED_STUB)
+            } else {
+                CompactionDecision::Keep
+            }
+        });
+
+        let sub = ColumnFamilyDescriptor::new(encoding::SUB_CF, tunable_options(&props));
+        // a handful of small values, read once on open
+        let functions = ColumnFamilyDescriptor::new(encoding::FUNCTIONS_CF, Options::default());
+        let meta = ColumnFamilyDescriptor::new(encoding::META_CF, Options::default());
+        let database = match Rocksdb::open_cf_descriptors(&opts, path, vec![sub, functions, meta]) {
             Ok(some) => some,
             Err(err) => panic!("failed to initialize shared database,{}", err),
         };
+        if let Err(err) = check_format(&database) {
+            panic!("failed to initialize shared database,{}", err);
+        }
+        format_checked.store(true, Ordering::Release);
 
         Shared {
             database,
             options: opts,
+            config,
+            stats,
+            pubsub,
+            overlay: None,
+            watched: HashMap::new(),
+            version: 0,
+            expire_cursor: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &Arc<Config> {
+        &self.config
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn pubsub(&self) -> &Registry {
+        &self.pubsub
+    }
+
+    /// Changes mutable RocksDB options of the open database, see CONFIG SET.
+    pub fn set_db_options(&self, opts: &[(&str, &str)]) -> crate::Result<()> {
+        self.database.set_options(opts)?;
+        match self.database.set_options_cf(&self.sub_cf(), opts) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err.into()),
         }
     }
 
     pub fn scan_for(&self, pattern: Option<&str>, skip: i32, cnt: usize) -> (Vec<Value>, usize) {
-        let iterator = self
-            .database
-            .iterator(rocksdb::IteratorMode::Start)
-            .enumerate();
+        let iterator = self.iter_default().enumerate();
         let mut values = Vec::new();
 
         let skip = if skip < 0 { 0 } else { skip as usize };
+        let now = utils::now_millis();
 
-        for (i, (key, _)) in iterator.skip(skip) {
+        for (i, (key, value)) in iterator.skip(skip) {
+            if encoding::is_expired(&value, now) {
+                continue;
+            }
             if let Some(pattern) = pattern {
                 if pattern == "*" {
                     values.push(Value::Bytes(key.to_vec()));
@@ -62,41 +177,231 @@ impl Shared {
         return 0;
     }
 
-    pub fn default_set(&mut self, key: &str, value: Value) -> crate::Result<Option<()>> {
-        self.set(key, value, false)
+
+    pub fn set(
+        &mut self,
+        key: &str,
+        value: Value,
+        condition: SetCondition,
+        expire_at: Option<u64>,
+    ) -> crate::Result<Option<()>> {
+        let write = match condition {
+            SetCondition::Always => true,
+            SetCondition::IfMissing => !self.is_exists(key),
+            SetCondition::IfExists => self.is_exists(key),
+        };
+        if !write {
+            return Ok(None);
+        }
+        self.put_string(key, value.as_slice(), expire_at)?;
+        Ok(Some(()))
+    }
+
+    /// Stores a string, replacing whatever `key` held.
+    pub fn put_string(&mut self, key: &str, value: &[u8], expire_at: Option<u64>) -> crate::Result<()> {
+        let mut batch = Batch::default();
+        self.clear_sub_entries(&mut batch, key)?;
+        batch.put(key.as_bytes(), encoding::encode(Header::new(encoding::STRING, expire_at), value));
+        self.set_with_sub_key_internal_batch(batch)
     }
 
+    /// Stores every pair in a single write batch. With `nx` nothing is written unless all keys are missing.
+    pub fn mset(&mut self, pairs: &[(String, Vec<u8>)], nx: bool) -> crate::Result<bool> {
+        if nx {
+            for (key, _) in pairs {
+                if self.lookup(key)?.is_some() {
+                    return Ok(false);
+                }
+            }
+        }
+        let mut batch = Batch::default();
+        for (key, value) in pairs {
+            self.clear_sub_entries(&mut batch, key)?;
+            batch.put(key.as_bytes(), encoding::encode(Header::new(encoding::STRING, None), value));
+        }
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(true)
+    }
+
+    /// Adds `delta` to a counter with a blind merge write, the sum being resolved when the key is
+    /// read or compacted. The reply comes from a separate read after the write, so an increment the
+    /// merge operator drops, on a value that isn't an integer or that would overflow, is only seen
+    /// as the value not changing or as the error of a non integer value.
+    pub fn merge_counter(&mut self, key: &str, delta: i64) -> crate::Result<i64> {
+        let operand = encoding::encode(Header::new(encoding::STRING, None), delta.to_string().as_bytes());
+        self.database.merge(key.as_bytes(), operand)?;
+        self.touch_watched(key.as_bytes());
+        match self.get_string(key)? {
+            Some((value, _)) => Ok(Value::Bytes(value).incr(0)?),
+            None => Ok(0),
+        }
+    }
 
-    pub fn set(&mut self, key: &str, value: Value, nx: bool) -> crate::Result<Option<()>> {
-        if (nx && self.is_exists(key)) || !nx {
-            match self.database.put(key.as_bytes(), value.as_slice()) {
-                Ok(_) => Ok(Some(())),
-                Err(e) => Err(e.into()),
+    pub fn get(&self, key: &str) -> crate::Result<Value> {
+        match self.get_string(key)? {
+            Some((value, _)) => Ok(Value::from_u8(value)),
+            None => Ok(Value::None),
+        }
+    }
+
+    /// A string value with its expire time, `None` when the key is missing.
+    pub fn get_string(&self, key: &str) -> crate::Result<Option<(Vec<u8>, Option<u64>)>> {
+        match self.lookup(key)? {
+            Some((header, payload)) if header.kind == encoding::STRING => Ok(Some((payload, header.expire_at))),
+            Some((header, payload)) if header.kind == encoding::CHUNKED_STRING => {
+                let value = self.read_chunks(key, 0, encoding::stored_len(&payload))?;
+                Ok(Some((value, header.expire_at)))
             }
-        } else {
-            return Ok(None);
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
         }
     }
 
+    /// The length of a string, 0 when the key is missing.
+    pub fn string_len(&self, key: &str) -> crate::Result<usize> {
+        match self.lookup(key)? {
+            Some((header, payload)) if header.kind == encoding::STRING => Ok(payload.len()),
+            Some((header, payload)) if header.kind == encoding::CHUNKED_STRING => Ok(encoding::stored_len(&payload)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(0),
+        }
+    }
 
-    pub fn get(&self, key: &str) -> Option<Value> {
-        match self.database.get(key.as_bytes()) {
-            Ok(Some(s)) => Some(Value::from_u8(s.to_vec())),
-            Ok(None) => Some(Value::None),
-            Err(err) => {
-                log::error!(
-                    "an error occurred while determining get,key = {} err = {}",
-                    key,
-                    err
-                );
-                None
+    /// The bytes of a string from `start` up to `end` exclusive, cut short at the end of the string.
+    /// Chunked strings only read the chunks in range.
+    pub fn get_string_range(&self, key: &str, start: usize, end: usize) -> crate::Result<Vec<u8>> {
+        let (header, payload) = match self.lookup(key)? {
+            Some((header, payload)) if header.kind == encoding::STRING || header.kind == encoding::CHUNKED_STRING => {
+                (header, payload)
+            }
+            Some(_) => return Err(WRONGTYPE.into()),
+            None => return Ok(Vec::new()),
+        };
+        if header.kind == encoding::STRING {
+            let end = end.min(payload.len());
+            return Ok(if start < end { payload[start..end].to_vec() } else { Vec::new() });
+        }
+        let end = end.min(encoding::stored_len(&payload));
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        self.read_chunks(key, start, end)
+    }
+
+    /// Overwrites a string from `offset`, padding it with zero bytes when it is shorter, and returns
+    /// the new length. A missing key is created, the expire time is kept. Strings growing past
+    /// `CHUNK_SIZE` are stored in chunks, then only the chunks written to are rewritten.
+    pub fn set_string_range(&mut self, key: &str, offset: usize, bytes: &[u8]) -> crate::Result<usize> {
+        let (header, payload) = match self.lookup(key)? {
+            Some((header, payload)) if header.kind == encoding::STRING || header.kind == encoding::CHUNKED_STRING => {
+                (header, payload)
             }
+            Some(_) => return Err(WRONGTYPE.into()),
+            None => (Header::new(encoding::STRING, None), Vec::new()),
+        };
+        let len = match header.kind {
+            encoding::CHUNKED_STRING => encoding::stored_len(&payload),
+            _ => payload.len(),
+        };
+        if bytes.is_empty() {
+            return Ok(len);
+        }
+        let end = offset + bytes.len();
+        let new_len = len.max(end);
+
+        let mut batch = Batch::default();
+        if header.kind == encoding::STRING && new_len <= encoding::CHUNK_SIZE {
+            let mut value = payload;
+            value.resize(new_len, 0);
+            value[offset..end].copy_from_slice(bytes);
+            self.clear_sub_entries(&mut batch, key)?;
+            batch.put(key.as_bytes(), encoding::encode(header, &value));
+            self.set_with_sub_key_internal_batch(batch)?;
+            return Ok(new_len);
+        }
+
+        let (first, last) = (offset / encoding::CHUNK_SIZE, (end - 1) / encoding::CHUNK_SIZE);
+        let inline = match header.kind {
+            encoding::STRING => {
+                // the string turns chunked, entries left by an earlier value of the key go first
+                let (from, to) = encoding::sub_range(key);
+                batch.delete_sub_range(from, to);
+                for (index, chunk) in payload.chunks(encoding::CHUNK_SIZE).enumerate() {
+                    if (index < first || index > last) && chunk.iter().any(|b| *b != 0) {
+                        batch.put_sub(encoding::chunk_key(key, index), chunk);
+                    }
+                }
+                Some(payload)
+            }
+            _ => None,
+        };
+        for index in first..=last {
+            let at = index * encoding::CHUNK_SIZE;
+            let mut chunk = match &inline {
+                Some(value) => value.get(at..value.len().min(at + encoding::CHUNK_SIZE)).unwrap_or_default().to_vec(),
+                None => self.get_sub(encoding::chunk_key(key, index))?.unwrap_or_default(),
+            };
+            chunk.resize(encoding::CHUNK_SIZE.min(new_len - at), 0);
+            let (lo, hi) = (offset.max(at), end.min(at + encoding::CHUNK_SIZE));
+            chunk[lo - at..hi - at].copy_from_slice(&bytes[lo - offset..hi - offset]);
+            batch.put_sub(encoding::chunk_key(key, index), chunk);
         }
+        let header = Header::new(encoding::CHUNKED_STRING, header.expire_at);
+        batch.put(key.as_bytes(), encoding::encode(header, &(new_len as u64).to_be_bytes()));
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(new_len)
+    }
+
+    /// The time to live in milliseconds, -2 for missing keys and -1 for persistent ones.
+    pub fn pttl(&self, key: &str) -> crate::Result<i64> {
+        match self.lookup(key)? {
+            Some((header, _)) => Ok(header.pttl()),
+            None => Ok(-2),
+        }
+    }
+
+    /// The expire time of a key of any type, `None` when it is missing or persistent.
+    pub fn expire_time(&self, key: &str) -> crate::Result<Option<u64>> {
+        Ok(self.lookup(key)?.and_then(|(header, _)| header.expire_at))
+    }
+
+    /// Changes the expire time of a key of any type, returns false when it is missing.
+    /// A time in the past deletes the key. Notifies `expire`, `persist` or `del`.
+    pub fn set_expire(&mut self, key: &str, expire_at: Option<u64>) -> crate::Result<bool> {
+        let (mut header, payload) = match self.lookup(key)? {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+        if matches!(expire_at, Some(at) if at <= utils::now_millis()) {
+            self.del(key);
+            return Ok(true);
+        }
+        let persisted = header.expire_at.is_some() && expire_at.is_none();
+        header.expire_at = expire_at;
+        let mut batch = Batch::default();
+        batch.put(key.as_bytes(), encoding::encode(header, &payload));
+        self.set_with_sub_key_internal_batch(batch)?;
+        if expire_at.is_some() {
+            self.notify(notify::GENERIC, "expire", key);
+        } else if persisted {
+            self.notify(notify::GENERIC, "persist", key);
+        }
+        Ok(true)
     }
 
     pub fn del(&mut self, key: &str) -> i8 {
-        match self.database.delete(key.as_bytes()) {
-            Ok(()) => 1,
+        let existed = matches!(self.lookup(key), Ok(Some(_)));
+        let mut batch = Batch::default();
+        let result = self.clear_sub_entries(&mut batch, key).and_then(|_| {
+            batch.delete(key.as_bytes());
+            self.set_with_sub_key_internal_batch(batch)
+        });
+        match result {
+            Ok(()) if existed => {
+                self.notify(notify::GENERIC, "del", key);
+                1
+            }
+            Ok(()) => 0,
             Err(err) => {
                 log::error!(
                     "an error occurred while determining delete,key = {} err = {}",
@@ -108,15 +413,151 @@ impl Shared {
         }
     }
 
+    /// Deletes `key` when it expired, before a command reads it, and sends the `expired` event.
+    /// Other reads only skip expired keys.
+    pub fn expire_if_needed(&mut self, key: &str) -> crate::Result<bool> {
+        let expired = match self.get_default(key.as_bytes())? {
+            Some(raw) => encoding::is_expired(&raw, utils::now_millis()),
+            None => false,
+        };
+        if !expired {
+            return Ok(false);
+        }
+        let mut batch = Batch::default();
+        self.clear_sub_entries(&mut batch, key)?;
+        batch.delete(key.as_bytes());
+        self.set_with_sub_key_internal_batch(batch)?;
+        Stats::incr(&self.stats.expired_keys);
+        self.notify(notify::EXPIRED, "expired", key);
+        Ok(true)
+    }
+
+    /// Checks the next `count` keys for expiry, resuming where the previous cycle stopped, so
+    /// expired keys nobody reads are still removed and notified.
+    pub fn expire_cycle(&mut self, count: usize) -> crate::Result<()> {
+        let now = utils::now_millis();
+        let mut expired = Vec::new();
+        // left empty when the last key was reached
+        let from = std::mem::take(&mut self.expire_cursor);
+        let keys = self.database.iterator(IteratorMode::From(&from, Direction::Forward));
+        for (checked, (key, value)) in keys.enumerate() {
+            if checked == count {
+                self.expire_cursor = key.to_vec();
+                break;
+            }
+            if encoding::is_expired(&value, now) {
+                expired.push(String::from_utf8_lossy(&key).into_owned());
+            }
+        }
+        for key in expired {
+            self.expire_if_needed(&key)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes a keyspace notification of `event` on `key` when `class` is enabled by
+    /// `notify-keyspace-events`.
+    pub fn notify(&self, class: u16, event: &str, key: &str) {
+        let flags = self.config.props().notify_keyspace_events;
+        if flags & class == 0 {
+            return;
+        }
+        if flags & notify::KEYSPACE != 0 {
+            let channel = [b"__keyspace@0__:", key.as_bytes()].concat();
+            self.pubsub.publish(&channel, event.as_bytes());
+        }
+        if flags & notify::KEYEVENT != 0 {
+            let channel = [b"__keyevent@0__:", event.as_bytes()].concat();
+            self.pubsub.publish(&channel, key.as_bytes());
+        }
+    }
+
     pub fn flush(&mut self) -> crate::Result<()> {
         Err("invalid operation".into())
     }
 
+    /// Holds the following writes back until `commit`, the reads still seeing them. Used by EXEC.
+    pub fn begin(&mut self) {
+        self.overlay = Some(Overlay::default());
+    }
+
+    /// Writes everything held back since `begin` in a single write batch.
+    pub fn commit(&mut self) -> crate::Result<()> {
+        match self.overlay.take() {
+            Some(overlay) => self.set_with_sub_key_internal_batch(overlay.into_batch()),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops everything held back since `begin`.
+    pub fn discard(&mut self) {
+        self.overlay = None;
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.overlay.is_some()
+    }
+
+    /// Starts tracking the modifications of `keys` for a connection, returns their versions.
+    /// Each call has to be matched by an `unwatch` of the same keys.
+    pub fn watch(&mut self, keys: &[String]) -> Vec<u64> {
+        keys.iter()
+            .map(|key| {
+                let watched = self
+                    .watched
+                    .entry(key.as_bytes().to_vec())
+                    .or_insert(Watched { connections: 0, version: 0 });
+                watched.connections += 1;
+                watched.version
+            })
+            .collect()
+    }
+
+    /// Stops tracking `keys` for a connection.
+    pub fn unwatch(&mut self, keys: &[String]) {
+        for key in keys {
+            if let Some(watched) = self.watched.get_mut(key.as_bytes()) {
+                watched.connections -= 1;
+                if watched.connections == 0 {
+                    self.watched.remove(key.as_bytes());
+                }
+            }
+        }
+    }
+
+    /// Whether one of the keys was modified since it was watched at the given version.
+    pub fn is_modified(&self, watched: &[(String, u64)]) -> bool {
+        watched.iter().any(|(key, version)| {
+            self.watched
+                .get(key.as_bytes())
+                .is_some_and(|watched| watched.version != *version)
+        })
+    }
+
+    /// Makes every applied write durable before the server exits, `memtables` also flushing them to SST files.
+    pub fn sync(&self, memtables: bool) -> crate::Result<()> {
+        self.database.flush_wal(true)?;
+        if memtables {
+            self.database.flush()?;
+        }
+        Ok(())
+    }
+
     pub fn is_exists(&self, key: &str) -> bool {
-        match self.get(key) {
-            Some(_) => true,
-            None => false,
+        matches!(self.lookup(key), Ok(Some(_)))
+    }
+
+    /// The live value of `key` as header and payload, expired keys read as missing.
+    fn lookup(&self, key: &str) -> crate::Result<Option<(Header, Vec<u8>)>> {
+        let raw = match self.get_default(key.as_bytes())? {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let (header, payload) = encoding::decode(&raw)?;
+        if header.is_expired(utils::now_millis()) {
+            return Ok(None);
         }
+        Ok(Some((header, payload.to_vec())))
     }
 }
 
@@ -142,6 +583,800 @@ impl Shared {
 
 }
 
+//bound sorted set operations
+impl Shared {
+
+    /// Adds or updates members with their scores, `condition` telling whether only new members
+    /// or only existing ones are written. Returns the number of members added and of those whose
+    /// score changed. A sorted set is never created empty.
+    /// Stores a sorted set of `members`, replacing whatever `key` held in a single write.
+    /// A member given twice keeps its last score.
+    pub fn zset_replace(&mut self, key: &str, members: &[(f64, Vec<u8>)]) -> crate::Result<()> {
+        let mut batch = Batch::default();
+        self.clear_sub_entries(&mut batch, key)?;
+        let scores: HashMap<&[u8], f64> = members.iter().map(|(score, member)| (&member[..], *score)).collect();
+        for (member, score) in scores.iter() {
+            batch.put_sub(encoding::member_key(key, member), score.to_be_bytes());
+            batch.put_sub(encoding::score_key(key, *score, member), []);
+        }
+        let header = Header::new(encoding::ZSET, None);
+        batch.put(key.as_bytes(), encoding::encode(header, &(scores.len() as u64).to_be_bytes()));
+        self.set_with_sub_key_internal_batch(batch)
+    }
+
+    pub fn zadd(&mut self, key: &str, members: &[(f64, Vec<u8>)], condition: SetCondition) -> crate::Result<(usize, usize)> {
+        let mut batch = Batch::default();
+        let (header, mut card) = match self.lookup(key)? {
+            Some((header, payload)) if header.kind == encoding::ZSET => (header, encoding::stored_len(&payload)),
+            Some(_) => return Err(WRONGTYPE.into()),
+            None => {
+                self.clear_sub_entries(&mut batch, key)?;
+                (Header::new(encoding::ZSET, None), 0)
+            }
+        };
+
+        // scores written earlier in this call, the batch isn't readable yet
+        let mut written: HashMap<&[u8], f64> = HashMap::new();
+        let (mut added, mut changed) = (0, 0);
+        for (score, member) in members {
+            let current = match written.get(&member[..]) {
+                Some(score) => Some(*score),
+                None => self.get_sub(encoding::member_key(key, member))?.map(|raw| decode_score(&raw)),
+            };
+            match current {
+                None if condition != SetCondition::IfExists => {
+                    added += 1;
+                    card += 1;
+                }
+                Some(old) if condition != SetCondition::IfMissing && old != *score => {
+                    batch.delete_sub(encoding::score_key(key, old, member));
+                    changed += 1;
+                }
+                _ => continue,
+            }
+            batch.put_sub(encoding::member_key(key, member), score.to_be_bytes());
+            batch.put_sub(encoding::score_key(key, *score, member), []);
+            written.insert(member, *score);
+        }
+        if card == 0 {
+            return Ok((0, 0));
+        }
+        batch.put(key.as_bytes(), encoding::encode(header, &(card as u64).to_be_bytes()));
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok((added, changed))
+    }
+
+    /// The score of a member, `None` when either is missing.
+    pub fn zscore(&self, key: &str, member: &[u8]) -> crate::Result<Option<f64>> {
+        match self.lookup(key)? {
+            Some((header, _)) if header.kind == encoding::ZSET => {}
+            Some(_) => return Err(WRONGTYPE.into()),
+            None => return Ok(None),
+        }
+        let raw = self.get_sub(encoding::member_key(key, member))?;
+        Ok(raw.map(|raw| decode_score(&raw)))
+    }
+
+    /// The members scoring from `min` up to `max` exclusive, by score then member. Only the score
+    /// range is read from RocksDB.
+    pub fn zrange_by_score(&self, key: &str, min: f64, max: f64) -> crate::Result<Vec<(f64, Vec<u8>)>> {
+        match self.lookup(key)? {
+            Some((header, _)) if header.kind == encoding::ZSET => {}
+            Some(_) => return Err(WRONGTYPE.into()),
+            None => return Ok(Vec::new()),
+        }
+        let from = encoding::score_key(key, min, &[]);
+        let to = encoding::score_key(key, max, &[]);
+        let mut members = Vec::new();
+        for (score_key, _) in self.iter_sub(&from, Direction::Forward) {
+            if *score_key >= *to {
+                break;
+            }
+            let (score, member) = encoding::split_score_key(key, &score_key);
+            members.push((score, member.to_vec()));
+        }
+        Ok(members)
+    }
+}
+
+//bound stream operations
+impl Shared {
+
+    /// The header and metadata of a stream, `None` when it doesn't exist.
+    pub fn stream_meta(&self, key: &str) -> crate::Result<Option<(Header, StreamMeta)>> {
+        match self.lookup(key)? {
+            Some((header, payload)) if header.kind == encoding::STREAM => Ok(Some((header, StreamMeta::decode(&payload)?))),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Appends an entry then trims the stream, returns the ID of the entry or `None` when the
+    /// stream is missing and `create` is false.
+    pub fn xadd(
+        &mut self,
+        key: &str,
+        id: NewId,
+        fields: &[(Vec<u8>, Vec<u8>)],
+        create: bool,
+        trim: Option<Trim>,
+    ) -> crate::Result<Option<StreamId>> {
+        let mut batch = Batch::default();
+        let (header, mut meta) = match self.stream_meta(key)? {
+            Some(stream) => stream,
+            None if !create => return Ok(None),
+            None => {
+                self.clear_sub_entries(&mut batch, key)?;
+                (Header::new(encoding::STREAM, None), StreamMeta::default())
+            }
+        };
+        let id = id.resolve(meta.last_id, utils::now_millis())?;
+        meta.last_id = id;
+        meta.len += 1;
+        meta.entries_added += 1;
+
+        batch.put_sub(encoding::entry_key(key, &id.to_bytes()), stream::encode_fields(fields));
+        if let Some(trim) = trim {
+            self.trim_entries(&mut batch, key, &mut meta, trim, Some(id))?;
+        }
+        batch.put(key.as_bytes(), encoding::encode(header, &meta.encode()));
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(Some(id))
+    }
+
+    /// The entries from `start` to `end` inclusive, in reverse from `end` when `rev` is set. Only
+    /// the range is read from RocksDB.
+    pub fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> crate::Result<Vec<(StreamId, Fields)>> {
+        if self.stream_meta(key)?.is_none() || start > end {
+            return Ok(Vec::new());
+        }
+        let from = encoding::entry_key(key, &start.to_bytes());
+        let to = encoding::entry_key(key, &end.to_bytes());
+        let count = count.unwrap_or(usize::MAX);
+        let iter = match rev {
+            true => self.iter_sub(&to, Direction::Reverse),
+            false => self.iter_sub(&from, Direction::Forward),
+        };
+        let mut entries = Vec::new();
+        for (entry_key, fields) in iter {
+            if entries.len() >= count || *entry_key < *from || *entry_key > *to {
+                break;
+            }
+            entries.push((StreamId::from_bytes(encoding::entry_id(&entry_key)), stream::decode_fields(&fields)));
+        }
+        Ok(entries)
+    }
+
+    /// Removes entries by ID, returns how many existed.
+    pub fn xdel(&mut self, key: &str, ids: &[StreamId]) -> crate::Result<usize> {
+        let (header, mut meta) = match self.stream_meta(key)? {
+            Some(stream) => stream,
+            None => return Ok(0),
+        };
+        let mut batch = Batch::default();
+        let mut deleted = HashSet::new();
+        for id in ids {
+            let entry_key = encoding::entry_key(key, &id.to_bytes());
+            if deleted.contains(id) || self.get_sub(&entry_key)?.is_none() {
+                continue;
+            }
+            batch.delete_sub(entry_key);
+            meta.max_deleted_id = meta.max_deleted_id.max(*id);
+            deleted.insert(*id);
+        }
+        if deleted.is_empty() {
+            return Ok(0);
+        }
+        meta.len -= deleted.len() as u64;
+        batch.put(key.as_bytes(), encoding::encode(header, &meta.encode()));
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(deleted.len())
+    }
+
+    /// Trims a stream, returns the number of entries removed.
+    pub fn xtrim(&mut self, key: &str, trim: Trim) -> crate::Result<usize> {
+        let (header, mut meta) = match self.stream_meta(key)? {
+            Some(stream) => stream,
+            None => return Ok(0),
+        };
+        let mut batch = Batch::default();
+        let removed = self.trim_entries(&mut batch, key, &mut meta, trim, None)?;
+        if removed > 0 {
+            batch.put(key.as_bytes(), encoding::encode(header, &meta.encode()));
+            self.set_with_sub_key_internal_batch(batch)?;
+        }
+        Ok(removed)
+    }
+}
+
+//bound stream consumer group operations
+impl Shared {
+
+    /// Creates a consumer group reading after `id`, the last entry when `None`. The stream is
+    /// created empty when missing. Returns false when the group exists already.
+    pub fn xgroup_create(
+        &mut self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+        entries_read: Option<u64>,
+    ) -> crate::Result<bool> {
+        let mut batch = Batch::default();
+        let meta = match self.stream_meta(key)? {
+            Some((_, meta)) => meta,
+            None => {
+                self.clear_sub_entries(&mut batch, key)?;
+                let meta = StreamMeta::default();
+                batch.put(key.as_bytes(), encoding::encode(Header::new(encoding::STREAM, None), &meta.encode()));
+                meta
+            }
+        };
+        let group_key = encoding::group_key(key, group);
+        if self.get_sub(&group_key)?.is_some() {
+            return Ok(false);
+        }
+        let group = GroupMeta::new(id.unwrap_or(meta.last_id), entries_read);
+        batch.put_sub(group_key, group.encode());
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(true)
+    }
+
+    /// Moves the last delivered ID of a group, to the last entry when `id` is `None`. Returns
+    /// false when the stream or the group is missing.
+    pub fn xgroup_setid(
+        &mut self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+        entries_read: Option<u64>,
+    ) -> crate::Result<bool> {
+        let (_, meta, mut group_meta) = match self.read_group(key, group)? {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+        group_meta.last_id = id.unwrap_or(meta.last_id);
+        group_meta.entries_read = entries_read;
+        let mut batch = Batch::default();
+        batch.put_sub(encoding::group_key(key, group), group_meta.encode());
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(true)
+    }
+
+    /// Removes a group with its consumers and pending entries, returns whether it existed.
+    pub fn xgroup_destroy(&mut self, key: &str, group: &str) -> crate::Result<bool> {
+        if self.read_group(key, group)?.is_none() {
+            return Ok(false);
+        }
+        let mut batch = Batch::default();
+        batch.delete_sub(encoding::group_key(key, group));
+        for prefix in [
+            encoding::consumer_key(key, group, ""),
+            encoding::pending_key(key, group, None),
+            encoding::consumer_pending_key(key, group, None, None),
+        ] {
+            let end = encoding::prefix_end(&prefix);
+            batch.delete_sub_range(prefix, end);
+        }
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(true)
+    }
+
+    /// Adds a consumer to a group, returns whether it was new or `None` when the stream or the
+    /// group is missing.
+    pub fn xgroup_create_consumer(&mut self, key: &str, group: &str, consumer: &str) -> crate::Result<Option<bool>> {
+        if self.read_group(key, group)?.is_none() {
+            return Ok(None);
+        }
+        if self.read_consumer(key, group, consumer)?.is_some() {
+            return Ok(Some(false));
+        }
+        let mut batch = Batch::default();
+        let consumer_meta = ConsumerMeta::new(utils::now_millis());
+        batch.put_sub(encoding::consumer_key(key, group, consumer), consumer_meta.encode());
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(Some(true))
+    }
+
+    /// Removes a consumer and its pending entries from a group, returns the number of pending
+    /// entries it had or `None` when the stream or the group is missing.
+    pub fn xgroup_del_consumer(&mut self, key: &str, group: &str, consumer: &str) -> crate::Result<Option<u64>> {
+        let (_, _, mut group_meta) = match self.read_group(key, group)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        if self.read_consumer(key, group, consumer)?.is_none() {
+            return Ok(Some(0));
+        }
+        let mut batch = Batch::default();
+        let prefix = encoding::consumer_pending_key(key, group, Some(consumer), None);
+        let mut removed = 0;
+        for (pending_key, _) in self.sub_entries(&prefix, &prefix) {
+            let id = StreamId::from_bytes(encoding::entry_id(&pending_key)).to_bytes();
+            batch.delete_sub(encoding::pending_key(key, group, Some(&id)));
+            batch.delete_sub(pending_key);
+            removed += 1;
+        }
+        group_meta.pending -= removed;
+        batch.delete_sub(encoding::consumer_key(key, group, consumer));
+        batch.put_sub(encoding::group_key(key, group), group_meta.encode());
+        self.set_with_sub_key_internal_batch(batch)?;
+        Ok(Some(removed))
+    }
+
+    /// Reads entries for a consumer of a group. With `after` `None` these are the entries never
+    /// delivered to the group, which become pending unless `no_ack` is set. Otherwise they are the
+    /// consumer's own pending entries after that ID, `None` fields standing for deleted entries.
+    /// Returns `None` when the stream or the group is missing.
+    pub fn xreadgroup(
+        &mut self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        after: Option<StreamId>,
+        count: Option<usize>,
+        no_ack: bool,
+    ) -> crate::Result<Option<Delivered>> {
+        let (_, meta, mut group_meta) = match self.read_group(key, group)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let now = utils::now_millis();
+        let mut consumers = HashMap::new();
+        let mut batch = Batch::default();
+        let mut read = Vec::new();
+
+        match after {
+            None => {
+                let first = self.first_entry_id(key)?;
+                let entries = match group_meta.last_id.next() {
+                    Some(start) => self.xrange(key, start, StreamId::MAX, count, false)?,
+                    None => Vec::new(),
+                };
+                for (id, fields) in entries {
+                    group_meta.entries_read = match group_meta.entries_read {
+                        Some(read) if !meta.has_tombstones_from(id) => Some(read + 1),
+                        _ => meta.entries_up_to(id, first),
+                    };
+                    group_meta.last_id = id;
+                    if !no_ack {
+                        let delivered = PendingEntry {
+                            consumer: consumer.to_string(),
+                            delivery_time: now,
+                            delivery_count: 1,
+                        };
+                        self.put_pending(&mut batch, key, group, &mut group_meta, &mut consumers, id, delivered)?;
+                    }
+                    read.push((id, Some(fields)));
+                }
+            }
+            Some(after) => {
+                let prefix = encoding::consumer_pending_key(key, group, Some(consumer), None);
+                let from = match after.next() {
+                    Some(start) => encoding::consumer_pending_key(key, group, Some(consumer), Some(&start.to_bytes())),
+                    None => encoding::prefix_end(&prefix),
+                };
+                for (pending_key, _) in self.sub_entries(&from, &prefix).take(count.unwrap_or(usize::MAX)) {
+                    let id = StreamId::from_bytes(encoding::entry_id(&pending_key));
+                    let pending_key = encoding::pending_key(key, group, Some(&id.to_bytes()));
+                    if let Some(raw) = self.get_sub(&pending_key)? {
+                        let mut pending = PendingEntry::decode(&raw)?;
+                        pending.delivery_time = now;
+                        pending.delivery_count += 1;
+                        batch.put_sub(pending_key, pending.encode());
+                    }
+                    read.push((id, self.stream_entry(key, id)?));
+                }
+            }
+        }
+
+        let reader = self.consumer_entry(&mut consumers, key, group, consumer, now)?;
+        reader.seen_time = now;
+        if after.is_none() && !read.is_empty() {
+            reader.active_time = Some(now);
+        }
+        self.write_group(batch, key, group, &group_meta, consumers)?;
+        Ok(Some(read))
+    }
+
+    /// Removes entries from the pending entries list of a group, returns how many were pending.
+    pub fn xack(&mut self, key: &str, group: &str, ids: &[StreamId]) -> crate::Result<usize> {
+        let (_, _, mut group_meta) = match self.read_group(key, group)? {
+            Some(found) => found,
+            None => return Ok(0),
+        };
+        let now = utils::now_millis();
+        let mut batch = Batch::default();
+        let mut consumers = HashMap::new();
+        let mut acked = HashSet::new();
+        for id in ids {
+            if acked.contains(id) {
+                continue;
+            }
+            let pending_key = encoding::pending_key(key, group, Some(&id.to_bytes()));
+            let pending = match self.get_sub(&pending_key)? {
+                Some(raw) => PendingEntry::decode(&raw)?,
+                None => continue,
+            };
+            self.remove_pending(&mut batch, key, group, &mut group_meta, &mut consumers, *id, &pending, now)?;
+            acked.insert(*id);
+        }
+        if !acked.is_empty() {
+            self.write_group(batch, key, group, &group_meta, consumers)?;
+        }
+        Ok(acked.len())
+    }
+
+    /// The number of pending entries of a group, their lowest and highest ID and how many each
+    /// consumer has, `None` when the stream or the group is missing.
+    #[allow(clippy::type_complexity)]
+    pub fn xpending_summary(
+        &self,
+        key: &str,
+        group: &str,
+    ) -> crate::Result<Option<(u64, Option<(StreamId, StreamId)>, Vec<(String, u64)>)>> {
+        let (_, _, group_meta) = match self.read_group(key, group)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let prefix = encoding::pending_key(key, group, None);
+        let first = self.sub_entries(&prefix, &prefix).next();
+        let end = encoding::prefix_end(&prefix);
+        let last = self
+            .iter_sub(&end, Direction::Reverse)
+            .next()
+            .filter(|(pending_key, _)| pending_key.starts_with(&prefix));
+        let bounds = match (first, last) {
+            (Some((first, _)), Some((last, _))) => Some((
+                StreamId::from_bytes(encoding::entry_id(&first)),
+                StreamId::from_bytes(encoding::entry_id(&last)),
+            )),
+            _ => None,
+        };
+        let consumers = self
+            .stream_consumers(key, group)?
+            .into_iter()
+            .filter(|(_, consumer_meta)| consumer_meta.pending > 0)
+            .map(|(name, consumer_meta)| (name, consumer_meta.pending))
+            .collect();
+        Ok(Some((group_meta.pending, bounds, consumers)))
+    }
+
+    /// The pending entries of a group from `start` to `end` inclusive, only those of `consumer`
+    /// when given and only those idle for `min_idle` milliseconds at least. `None` when the stream
+    /// or the group is missing.
+    #[allow(clippy::too_many_arguments)]
+    pub fn xpending(
+        &self,
+        key: &str,
+        group: &str,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        consumer: Option<&str>,
+        min_idle: u64,
+    ) -> crate::Result<Option<Vec<(StreamId, PendingEntry)>>> {
+        if self.read_group(key, group)?.is_none() {
+            return Ok(None);
+        }
+        let now = utils::now_millis();
+        let prefix = match consumer {
+            Some(consumer) => encoding::consumer_pending_key(key, group, Some(consumer), None),
+            None => encoding::pending_key(key, group, None),
+        };
+        let mut from = prefix.clone();
+        from.extend_from_slice(&start.to_bytes());
+        let mut found = Vec::new();
+        for (pending_key, raw) in self.sub_entries(&from, &prefix) {
+            let id = StreamId::from_bytes(encoding::entry_id(&pending_key));
+            if id > end || found.len() >= count {
+                break;
+            }
+            let pending = match consumer {
+                None => PendingEntry::decode(&raw)?,
+                Some(_) => match self.get_sub(encoding::pending_key(key, group, Some(&id.to_bytes())))? {
+                    Some(raw) => PendingEntry::decode(&raw)?,
+                    None => continue,
+                },
+            };
+            if now.saturating_sub(pending.delivery_time) >= min_idle {
+                found.push((id, pending));
+            }
+        }
+        Ok(Some(found))
+    }
+
+    /// Gives pending entries idle long enough to `consumer`, returns those claimed with their
+    /// fields unless `claim.just_id` is set. Entries deleted from the stream are dropped from the
+    /// pending entries list rather than claimed. `None` when the stream or the group is missing.
+    pub fn xclaim(
+        &mut self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        ids: &[StreamId],
+        claim: Claim,
+        last_id: Option<StreamId>,
+    ) -> crate::Result<Option<Delivered>> {
+        let (_, _, mut group_meta) = match self.read_group(key, group)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        if let Some(last_id) = last_id.filter(|last_id| *last_id > group_meta.last_id) {
+            group_meta.last_id = last_id;
+        }
+        let now = utils::now_millis();
+        let mut batch = Batch::default();
+        let mut consumers = HashMap::new();
+        let mut claimed = Vec::new();
+        for id in ids {
+            let raw = self.get_sub(encoding::pending_key(key, group, Some(&id.to_bytes())))?;
+            let pending = match raw {
+                Some(raw) => Some(PendingEntry::decode(&raw)?),
+                None if claim.force && self.stream_entry(key, *id)?.is_some() => None,
+                None => continue,
+            };
+            if matches!(&pending, Some(pending) if now.saturating_sub(pending.delivery_time) < claim.min_idle) {
+                continue;
+            }
+            let fields = self.claim_pending(&mut batch, key, group, &mut group_meta, &mut consumers, consumer, *id, pending, claim, now)?;
+            if let Some(fields) = fields {
+                claimed.push((*id, fields));
+            }
+        }
+        let claimer = self.consumer_entry(&mut consumers, key, group, consumer, now)?;
+        claimer.seen_time = now;
+        if !claimed.is_empty() {
+            claimer.active_time = Some(now);
+        }
+        self.write_group(batch, key, group, &group_meta, consumers)?;
+        Ok(Some(claimed))
+    }
+
+    /// Claims like `xclaim` the pending entries from `start` on, scanning up to ten times `count`
+    /// of them. Returns the ID to continue from, 0-0 once the scan reached the end, the entries
+    /// claimed and the IDs dropped as deleted. `None` when the stream or the group is missing.
+    pub fn xautoclaim(
+        &mut self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        start: StreamId,
+        count: usize,
+        claim: Claim,
+    ) -> crate::Result<Option<(StreamId, Delivered, Vec<StreamId>)>> {
+        let (_, _, mut group_meta) = match self.read_group(key, group)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let now = utils::now_millis();
+        let prefix = encoding::pending_key(key, group, None);
+        let mut from = prefix.clone();
+        from.extend_from_slice(&start.to_bytes());
+        let mut scanned = Vec::new();
+        let mut next = StreamId::MIN;
+        for (pending_key, raw) in self.sub_entries(&from, &prefix) {
+            let id = StreamId::from_bytes(encoding::entry_id(&pending_key));
+            if scanned.len() == count.saturating_mul(10) {
+                next = id;
+                break;
+            }
+            scanned.push((id, PendingEntry::decode(&raw)?));
+        }
+
+        let mut batch = Batch::default();
+        let mut consumers = HashMap::new();
+        let (mut claimed, mut deleted) = (Vec::new(), Vec::new());
+        let mut scanned = scanned.into_iter();
+        for (id, pending) in scanned.by_ref() {
+            if now.saturating_sub(pending.delivery_time) < claim.min_idle {
+                continue;
+            }
+            match self.claim_pending(&mut batch, key, group, &mut group_meta, &mut consumers, consumer, id, Some(pending), claim, now)? {
+                Some(fields) => claimed.push((id, fields)),
+                None => deleted.push(id),
+            }
+            if claimed.len() == count {
+                break;
+            }
+        }
+        // the scan stopped early, it goes on from the first entry not looked at
+        if let Some((id, _)) = scanned.next() {
+            next = id;
+        }
+        let claimer = self.consumer_entry(&mut consumers, key, group, consumer, now)?;
+        claimer.seen_time = now;
+        if !claimed.is_empty() {
+            claimer.active_time = Some(now);
+        }
+        self.write_group(batch, key, group, &group_meta, consumers)?;
+        Ok(Some((next, claimed, deleted)))
+    }
+
+    /// The groups of a stream by name, with their number of consumers.
+    pub fn stream_groups(&self, key: &str) -> crate::Result<Vec<(String, GroupMeta, usize)>> {
+        if self.stream_meta(key)?.is_none() {
+            return Ok(Vec::new());
+        }
+        let prefix = encoding::group_key(key, "");
+        let mut groups = Vec::new();
+        for (group_key, raw) in self.sub_entries(&prefix, &prefix) {
+            let name = String::from_utf8_lossy(encoding::group_name(key, &group_key)).into_owned();
+            let consumers = self.stream_consumers(key, &name)?.len();
+            groups.push((name, GroupMeta::decode(&raw)?, consumers));
+        }
+        Ok(groups)
+    }
+
+    /// The consumers of a group by name.
+    pub fn stream_consumers(&self, key: &str, group: &str) -> crate::Result<Vec<(String, ConsumerMeta)>> {
+        let prefix = encoding::consumer_key(key, group, "");
+        let mut consumers = Vec::new();
+        for (consumer_key, raw) in self.sub_entries(&prefix, &prefix) {
+            let name = String::from_utf8_lossy(encoding::consumer_name(key, group, &consumer_key)).into_owned();
+            consumers.push((name, ConsumerMeta::decode(&raw)?));
+        }
+        Ok(consumers)
+    }
+
+    /// The state of a group, `None` when the stream or the group is missing.
+    pub fn stream_group(&self, key: &str, group: &str) -> crate::Result<Option<GroupMeta>> {
+        Ok(self.read_group(key, group)?.map(|(_, _, group_meta)| group_meta))
+    }
+
+    /// The ID of the first entry of a stream.
+    pub fn first_entry_id(&self, key: &str) -> crate::Result<Option<StreamId>> {
+        let first = self.xrange(key, StreamId::MIN, StreamId::MAX, Some(1), false)?;
+        Ok(first.first().map(|(id, _)| *id))
+    }
+
+    /// The fields of an entry, `None` when it doesn't exist.
+    fn stream_entry(&self, key: &str, id: StreamId) -> crate::Result<Option<Fields>> {
+        let raw = self.get_sub(encoding::entry_key(key, &id.to_bytes()))?;
+        Ok(raw.map(|raw| stream::decode_fields(&raw)))
+    }
+
+    fn read_group(&self, key: &str, group: &str) -> crate::Result<Option<(Header, StreamMeta, GroupMeta)>> {
+        let (header, meta) = match self.stream_meta(key)? {
+            Some(stream) => stream,
+            None => return Ok(None),
+        };
+        match self.get_sub(encoding::group_key(key, group))? {
+            Some(raw) => Ok(Some((header, meta, GroupMeta::decode(&raw)?))),
+            None => Ok(None),
+        }
+    }
+
+    fn read_consumer(&self, key: &str, group: &str, consumer: &str) -> crate::Result<Option<ConsumerMeta>> {
+        match self.get_sub(encoding::consumer_key(key, group, consumer))? {
+            Some(raw) => Ok(Some(ConsumerMeta::decode(&raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes a batch changing a group, adding the state of the group and of the consumers changed.
+    fn write_group(
+        &mut self,
+        mut batch: Batch,
+        key: &str,
+        group: &str,
+        group_meta: &GroupMeta,
+        consumers: HashMap<String, ConsumerMeta>,
+    ) -> crate::Result<()> {
+        for (name, consumer_meta) in consumers {
+            batch.put_sub(encoding::consumer_key(key, group, &name), consumer_meta.encode());
+        }
+        batch.put_sub(encoding::group_key(key, group), group_meta.encode());
+        self.set_with_sub_key_internal_batch(batch)
+    }
+
+    /// A consumer changed in a batch being built, read or created on first use. `consumers` is
+    /// written to the batch once done.
+    fn consumer_entry<'a>(
+        &self,
+        consumers: &'a mut HashMap<String, ConsumerMeta>,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        now: u64,
+    ) -> crate::Result<&'a mut ConsumerMeta> {
+        if !consumers.contains_key(consumer) {
+            let consumer_meta = self.read_consumer(key, group, consumer)?.unwrap_or_else(|| ConsumerMeta::new(now));
+            consumers.insert(consumer.to_string(), consumer_meta);
+        }
+        Ok(consumers.get_mut(consumer).expect("inserted above"))
+    }
+
+    /// Makes `id` pending for the consumer of `pending`, taking it from the consumer it was
+    /// pending for if any.
+    #[allow(clippy::too_many_arguments)]
+    fn put_pending(
+        &self,
+        batch: &mut Batch,
+        key: &str,
+        group: &str,
+        group_meta: &mut GroupMeta,
+        consumers: &mut HashMap<String, ConsumerMeta>,
+        id: StreamId,
+        pending: PendingEntry,
+    ) -> crate::Result<()> {
+        let now = pending.delivery_time;
+        let pending_key = encoding::pending_key(key, group, Some(&id.to_bytes()));
+        if let Some(raw) = self.get_sub(&pending_key)? {
+            let previous = PendingEntry::decode(&raw)?;
+            self.remove_pending(batch, key, group, group_meta, consumers, id, &previous, now)?;
+        }
+        group_meta.pending += 1;
+        self.consumer_entry(consumers, key, group, &pending.consumer, now)?.pending += 1;
+        batch.put_sub(encoding::consumer_pending_key(key, group, Some(&pending.consumer), Some(&id.to_bytes())), []);
+        batch.put_sub(pending_key, pending.encode());
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn remove_pending(
+        &self,
+        batch: &mut Batch,
+        key: &str,
+        group: &str,
+        group_meta: &mut GroupMeta,
+        consumers: &mut HashMap<String, ConsumerMeta>,
+        id: StreamId,
+        pending: &PendingEntry,
+        now: u64,
+    ) -> crate::Result<()> {
+        let id_bytes = id.to_bytes();
+        batch.delete_sub(encoding::pending_key(key, group, Some(&id_bytes)));
+        batch.delete_sub(encoding::consumer_pending_key(key, group, Some(&pending.consumer), Some(&id_bytes)));
+        group_meta.pending = group_meta.pending.saturating_sub(1);
+        let owner = self.consumer_entry(consumers, key, group, &pending.consumer, now)?;
+        owner.pending = owner.pending.saturating_sub(1);
+        Ok(())
+    }
+
+    /// Claims one pending entry idle long enough, `pending` `None` for one forced in. Returns `None` when the entry
+    /// was deleted, `Some(None)` when claimed with `just_id` and the fields otherwise.
+    #[allow(clippy::too_many_arguments)]
+    fn claim_pending(
+        &self,
+        batch: &mut Batch,
+        key: &str,
+        group: &str,
+        group_meta: &mut GroupMeta,
+        consumers: &mut HashMap<String, ConsumerMeta>,
+        consumer: &str,
+        id: StreamId,
+        pending: Option<PendingEntry>,
+        claim: Claim,
+        now: u64,
+    ) -> crate::Result<Option<Option<Fields>>> {
+        let fields = self.stream_entry(key, id)?;
+        if let (Some(pending), None) = (&pending, &fields) {
+            self.remove_pending(batch, key, group, group_meta, consumers, id, pending, now)?;
+            return Ok(None);
+        }
+        let delivery_count = pending.map_or(0, |pending| pending.delivery_count);
+        let delivery_count = match (claim.retry_count, claim.just_id) {
+            (Some(count), _) => count,
+            (None, true) => delivery_count,
+            (None, false) => delivery_count + 1,
+        };
+        let claimed = PendingEntry {
+            consumer: consumer.to_string(),
+            delivery_time: claim.delivery_time.unwrap_or(now),
+            delivery_count,
+        };
+        self.put_pending(batch, key, group, group_meta, consumers, id, claimed)?;
+        Ok(Some(if claim.just_id { None } else { fields }))
+    }
+}
+
 //bound hash operations {
 impl Shared {
     
@@ -150,14 +1385,468 @@ impl Shared {
     }
 }
 
+/// Sums counter operands into the existing value, keeping its expire time. Operands have the layout
+/// of a string value, so partial merges of operands alone yield a valid value as well.
+/// A value that isn't an integer string is left as is and operands that would overflow are dropped.
+fn merge_counter(_key: &[u8], existing: Option<&[u8]>, operands: &MergeOperands) -> Option<Vec<u8>> {
+    fold_counter(existing, operands)
+}
+
+fn fold_counter<'a>(existing: Option<&[u8]>, operands: impl IntoIterator<Item = &'a [u8]>) -> Option<Vec<u8>> {
+    let parse = |payload: &[u8]| std::str::from_utf8(payload).ok()?.parse::<i64>().ok();
+    let base = match existing.map(encoding::decode) {
+        None => Some((Header::new(encoding::STRING, None), 0)),
+        Some(Ok((header, _))) if header.is_expired(utils::now_millis()) => {
+            Some((Header::new(encoding::STRING, None), 0))
+        }
+        Some(Ok((header, payload))) if header.kind == encoding::STRING => parse(payload).map(|n| (header, n)),
+        Some(_) => None,
+    };
+    let (header, mut count) = match base {
+        Some(base) => base,
+        None => return existing.map(<[u8]>::to_vec),
+    };
+    for operand in operands {
+        let delta = encoding::decode(operand).ok().and_then(|(_, payload)| parse(payload));
+        if let Some(sum) = delta.and_then(|delta| count.checked_add(delta)) {
+            count = sum;
+        }
+    }
+    Some(encoding::encode(header, count.to_string().as_bytes()))
+}
+
+fn decode_score(raw: &[u8]) -> f64 {
+    let mut score = [0u8; 8];
+    score.copy_from_slice(&raw[..8]);
+    f64::from_be_bytes(score)
+}
+
+/// Checks the format version of a database just opened. A database written before values had a
+/// header gets every value rewritten as a persistent string, a newer one is refused.
+fn check_format(database: &Rocksdb) -> crate::Result<()> {
+    let meta = database
+        .cf_handle(encoding::META_CF)
+        .expect("the meta column family is created on open");
+    match database.get_cf(&meta, encoding::FORMAT_KEY)? {
+        Some(version) if version[..] == [encoding::FORMAT_VERSION] => return Ok(()),
+        Some(version) => return Err(format!("unsupported database format version {:?}", version).into()),
+        None => {}
+    }
+
+    // batches of bounded size, each recording the last key it migrated so that a migration
+    // stopped halfway resumes after it instead of wrapping values twice
+    let resume_after = database.get_cf(&meta, encoding::MIGRATED_KEY)?;
+    let iterator = match &resume_after {
+        Some(key) => database.iterator(IteratorMode::From(key, Direction::Forward)),
+        None => database.iterator(IteratorMode::Start),
+    };
+    let mut batch = WriteBatch::default();
+    let mut migrated = 0;
+    for (key, value) in iterator {
+        if resume_after.as_deref() == Some(&key[..]) {
+            continue;
+        }
+        batch.put(&key, encoding::encode(Header::new(encoding::STRING, None), &value));
+        migrated += 1;
+        if batch.len() >= MIGRATION_BATCH {
+            batch.put_cf(&meta, encoding::MIGRATED_KEY, &key);
+            database.write(std::mem::take(&mut batch))?;
+        }
+    }
+    batch.put_cf(&meta, encoding::FORMAT_KEY, [encoding::FORMAT_VERSION]);
+    batch.delete_cf(&meta, encoding::MIGRATED_KEY);
+    database.write(batch)?;
+    if migrated > 0 {
+        log::info!("added headers to {} values of a database of an older format", migrated);
+    }
+    Ok(())
+}
+
+/// The options shared by every column family, from the rocksdb section of the config.
+fn tunable_options(props: &ServerProperties) -> Options {
+    let mut opts = Options::default();
+    let tunables = &props.rocksdb;
+    opts.set_write_buffer_size(tunables.write_buffer_size as usize);
+    opts.set_max_write_buffer_number(tunables.max_write_buffer_number);
+    opts.set_level_zero_file_num_compaction_trigger(tunables.level0_file_num_compaction_trigger);
+    opts.set_target_file_size_base(tunables.target_file_size_base);
+    opts.set_max_bytes_for_level_base(tunables.max_bytes_for_level_base);
+    opts.set_disable_auto_compactions(tunables.disable_auto_compactions);
+    opts.set_max_background_jobs(tunables.max_background_jobs);
+    opts.set_max_open_files(tunables.max_open_files);
+    opts
+}
+
+//function libraries
+impl Shared {
+
+    /// The libraries saved by FUNCTION LOAD, as their name and code.
+    pub fn function_libraries(&self) -> crate::Result<Vec<(String, String)>> {
+        let mut libraries = Vec::new();
+        for (name, code) in self.database.iterator_cf(&self.functions_cf(), IteratorMode::Start) {
+            let name = String::from_utf8(name.into_vec())?;
+            let code = String::from_utf8(code.into_vec())?;
+            libraries.push((name, code));
+        }
+        Ok(libraries)
+    }
+
+    /// Saves a library, they are written right away rather than with the batch of a transaction.
+    pub fn save_function_library(&self, name: &str, code: &str) -> crate::Result<()> {
+        Ok(self.database.put_cf(&self.functions_cf(), name, code)?)
+    }
+
+    pub fn delete_function_library(&self, name: &str) -> crate::Result<()> {
+        Ok(self.database.delete_cf(&self.functions_cf(), name)?)
+    }
+}
+
 //private method implementation
 impl Shared {
+
+    fn sub_cf(&self) -> Arc<BoundColumnFamily<'_>> {
+        self.database
+            .cf_handle(encoding::SUB_CF)
+            .expect("the sub column family is created on open")
+    }
+
+    fn functions_cf(&self) -> Arc<BoundColumnFamily<'_>> {
+        self.database
+            .cf_handle(encoding::FUNCTIONS_CF)
+            .expect("the functions column family is created on open")
+    }
+
+    /// Adds the removal of the sub entries of the current value of `key` to `batch`, expired or not.
+    fn clear_sub_entries(&self, batch: &mut Batch, key: &str) -> crate::Result<()> {
+        let has_sub_entries = match self.get_default(key.as_bytes())? {
+            Some(raw) => encoding::has_sub_entries(encoding::decode(&raw)?.0.kind),
+            None => false,
+        };
+        if has_sub_entries {
+            let (from, to) = encoding::sub_range(key);
+            batch.delete_sub_range(from, to);
+        }
+        Ok(())
+    }
+
+    /// Reads the bytes of a chunked string from `start` up to `end` exclusive, both within its length.
+    fn read_chunks(&self, key: &str, start: usize, end: usize) -> crate::Result<Vec<u8>> {
+        let mut value = vec![0u8; end - start];
+        let from = encoding::chunk_key(key, start / encoding::CHUNK_SIZE);
+        let (_, to) = encoding::sub_range(key);
+        for (sub_key, chunk) in self.iter_sub(&from, Direction::Forward) {
+            if *sub_key >= *to {
+                break;
+            }
+            let at = encoding::chunk_index(&sub_key) * encoding::CHUNK_SIZE;
+            if at >= end {
+                break;
+            }
+            let (lo, hi) = (start.max(at), end.min(at + chunk.len()));
+            if lo < hi {
+                value[lo - start..hi - start].copy_from_slice(&chunk[lo - at..hi - at]);
+            }
+        }
+        Ok(value)
+    }
     
-    fn set_with_sub_key_internal_batch(&mut self,batch :WriteBatch) -> crate::Result<()>{
+    /// The sub entries from `from` on as long as they start with `prefix`.
+    fn sub_entries(&self, from: &[u8], prefix: &[u8]) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> + '_ {
+        let prefix = prefix.to_vec();
+        self.iter_sub(from, Direction::Forward)
+            .take_while(move |(sub_key, _)| sub_key.starts_with(&prefix))
+    }
+
+    /// Adds the removal of the oldest entries `trim` drops to `batch` as one range deletion and
+    /// updates `meta`. `pending` is an entry added to `batch` already, last of the stream, and
+    /// counted in `meta`. Returns the number of entries removed.
+    fn trim_entries(
+        &self,
+        batch: &mut Batch,
+        key: &str,
+        meta: &mut StreamMeta,
+        trim: Trim,
+        pending: Option<StreamId>,
+    ) -> crate::Result<usize> {
+        let limit = trim.limit.unwrap_or(usize::MAX);
+        let from = encoding::entry_key(key, &StreamId::MIN.to_bytes());
+        let to = encoding::entries_end(key);
+        let stored = self
+            .iter_sub(&from, Direction::Forward)
+            .take_while(|(entry_key, _)| **entry_key < *to)
+            .map(|(entry_key, _)| StreamId::from_bytes(encoding::entry_id(&entry_key)));
+
+        let mut removed = 0;
+        let mut last = None;
+        for id in stored.chain(pending) {
+            let remove = match trim.strategy {
+                TrimStrategy::MaxLen(len) => meta.len - removed as u64 > len,
+                TrimStrategy::MinId(min) => id < min,
+            };
+            if !remove || removed == limit {
+                break;
+            }
+            removed += 1;
+            last = Some(id);
+        }
+        let last = match last {
+            Some(last) => last,
+            None => return Ok(0),
+        };
+        let mut end = encoding::entry_key(key, &last.to_bytes());
+        end.push(0);
+        batch.delete_sub_range(from, end);
+        meta.len -= removed as u64;
+        meta.max_deleted_id = meta.max_deleted_id.max(last);
+        Ok(removed)
+    }
+
+    /// Reads `key` from the default column family, including the writes of a transaction in progress.
+    fn get_default(&self, key: &[u8]) -> crate::Result<Option<Vec<u8>>> {
+        if let Some(value) = self.overlay.as_ref().and_then(|overlay| overlay.get(Cf::Default, key)) {
+            return Ok(value);
+        }
+        Ok(self.database.get(key)?)
+    }
+
+    /// Reads `sub_key` from the sub column family, including the writes of a transaction in progress.
+    fn get_sub(&self, sub_key: impl AsRef<[u8]>) -> crate::Result<Option<Vec<u8>>> {
+        let sub_key = sub_key.as_ref();
+        if let Some(value) = self.overlay.as_ref().and_then(|overlay| overlay.get(Cf::Sub, sub_key)) {
+            return Ok(value);
+        }
+        Ok(self.database.get_cf(&self.sub_cf(), sub_key)?)
+    }
 
+    /// Iterates the default column family, including the writes of a transaction in progress.
+    fn iter_default(&self) -> Box<dyn Iterator<Item = Entry> + '_> {
+        let iter = self.database.iterator(IteratorMode::Start);
+        match &self.overlay {
+            Some(overlay) => Box::new(overlay.merge(Cf::Default, iter, &[], true)),
+            None => Box::new(iter),
+        }
+    }
+
+    /// Iterates the sub column family from `from`, including the writes of a transaction in progress.
+    fn iter_sub(&self, from: &[u8], direction: Direction) -> Box<dyn Iterator<Item = Entry> + '_> {
+        let forward = matches!(direction, Direction::Forward);
+        let iter = self.database.iterator_cf(&self.sub_cf(), IteratorMode::From(from, direction));
+        match &self.overlay {
+            Some(overlay) => Box::new(overlay.merge(Cf::Sub, iter, from, forward)),
+            None => Box::new(iter),
+        }
+    }
+
+    /// Gives a new version to `key` when it is watched.
+    fn touch_watched(&mut self, key: &[u8]) {
+        if let Some(watched) = self.watched.get_mut(key) {
+            self.version += 1;
+            watched.version = self.version;
+        }
+    }
+
+    /// Writes `batch`, or holds it back while a transaction is in progress.
+    fn set_with_sub_key_internal_batch(&mut self,batch :Batch) -> crate::Result<()>{
+        if !self.watched.is_empty() {
+            for key in batch.keys() {
+                self.touch_watched(key);
+            }
+        }
+        if let Some(overlay) = &mut self.overlay {
+            overlay.apply(batch);
+            return Ok(());
+        }
+        let batch = batch.into_write_batch(&self.sub_cf());
         match self.database.write_opt(batch, &WriteOptions::default()) {
             Ok(()) => Ok(()),
             Err(err) => return Err(err.into()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str, expire_at: Option<u64>) -> Vec<u8> {
+        encoding::encode(Header::new(encoding::STRING, expire_at), value.as_bytes())
+    }
+
+    fn fold(existing: Option<&[u8]>, deltas: &[&str]) -> Option<Vec<u8>> {
+        let operands: Vec<Vec<u8>> = deltas.iter().map(|delta| string(delta, None)).collect();
+        fold_counter(existing, operands.iter().map(Vec::as_slice))
+    }
+
+    #[test]
+    fn counter_operands_are_summed() {
+        assert_eq!(fold(None, &["1", "2", "-5"]), Some(string("-2", None)));
+        assert_eq!(fold(Some(&string("10", None)), &["5"]), Some(string("15", None)));
+    }
+
+    #[test]
+    fn counter_keeps_the_expire_time() {
+        let expire_at = Some(utils::now_millis() + 60_000);
+        assert_eq!(fold(Some(&string("1", expire_at)), &["1"]), Some(string("2", expire_at)));
+    }
+
+    #[test]
+    fn expired_counter_starts_over() {
+        let expired = string("100", Some(utils::now_millis() - 1));
+        assert_eq!(fold(Some(&expired), &["1"]), Some(string("1", None)));
+    }
+
+    #[test]
+    fn non_integer_value_is_left_as_is() {
+        let value = string("hello", None);
+        assert_eq!(fold(Some(&value), &["1"]), Some(value));
+        let zset = encoding::encode(Header::new(encoding::ZSET, None), b"");
+        assert_eq!(fold(Some(&zset), &["1"]), Some(zset));
+    }
+
+    #[test]
+    fn overflowing_and_invalid_operands_are_dropped() {
+        let max = i64::MAX.to_string();
+        assert_eq!(fold(Some(&string(&max, None)), &["1", "-1"]), Some(string(&(i64::MAX - 1).to_string(), None)));
+        assert_eq!(fold(None, &["1", "one", "2"]), Some(string("3", None)));
+    }
+
+    /// A database of the format before values had a header, migrated up to `migrated` if given.
+    fn old_database(dir: &Path, values: &[(String, Vec<u8>)], migrated: Option<&str>) -> Rocksdb {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let meta = ColumnFamilyDescriptor::new(encoding::META_CF, Options::default());
+        let database = Rocksdb::open_cf_descriptors(&opts, dir, vec![meta]).unwrap();
+        for (key, value) in values {
+            database.put(key, value).unwrap();
+        }
+        if let Some(key) = migrated {
+            let meta = database.cf_handle(encoding::META_CF).unwrap();
+            database.put_cf(&meta, encoding::MIGRATED_KEY, key).unwrap();
+        }
+        database
+    }
+
+    fn assert_migrated(dir: &Path, keys: impl IntoIterator<Item = String>) {
+        let mut shared = testing::open(dir, Arc::new(Registry::default()));
+        for key in keys {
+            let reply = testing::run(&mut shared, &["get", &key]);
+            assert_eq!(testing::show(&reply), format!("\"{}\"", key.replace("key", "value")));
+        }
+        let meta = shared.database.cf_handle(encoding::META_CF).unwrap();
+        assert!(shared.database.get_cf(&meta, encoding::MIGRATED_KEY).unwrap().is_none());
+        let version = shared.database.get_cf(&meta, encoding::FORMAT_KEY).unwrap();
+        assert_eq!(version, Some(vec![encoding::FORMAT_VERSION]));
+    }
+
+    #[test]
+    fn old_format_is_migrated_in_batches() {
+        let dir = testing::temporary_dir("migrate");
+        let keys: Vec<String> = (0..MIGRATION_BATCH * 2 + 1).map(|i| format!("key:{:05}", i)).collect();
+        let values: Vec<_> = keys.iter().map(|key| (key.clone(), key.replace("key", "value").into_bytes())).collect();
+        drop(old_database(&dir, &values, None));
+        assert_migrated(&dir, keys);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stopped_migration_resumes_after_the_last_key() {
+        let dir = testing::temporary_dir("migrate-resume");
+        let wrapped = |value: &str| encoding::encode(Header::new(encoding::STRING, None), value.as_bytes());
+        let values = [
+            ("key:a".to_string(), wrapped("value:a")),
+            ("key:b".to_string(), wrapped("value:b")),
+            ("key:c".to_string(), b"value:c".to_vec()),
+        ];
+        drop(old_database(&dir, &values, Some("key:b")));
+        assert_migrated(&dir, ["key:a", "key:b", "key:c"].map(String::from));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn newer_format_is_refused() {
+        let dir = testing::temporary_dir("migrate-newer");
+        let database = old_database(&dir, &[], None);
+        let meta = encoding::META_CF;
+        database.put_cf(&database.cf_handle(meta).unwrap(), encoding::FORMAT_KEY, [encoding::FORMAT_VERSION + 1]).unwrap();
+        assert!(check_format(&database).is_err());
+        drop(database);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
+
+/// Helpers for the tests that need a database.
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::Shared;
+    use crate::{
+        config::Config,
+        protocol::{frame::Frame, parse::Parse},
+        server::{pubsub::Registry, stats::Stats},
+    };
+
+    /// The directory of a test database, unique within the test run.
+    pub fn temporary_dir(name: &str) -> std::path::PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("cocodayo-{}-{}-{}", name, std::process::id(), n))
+    }
+
+    /// Opens the database at `dir` with the default config.
+    pub fn open(dir: &std::path::Path, pubsub: Arc<Registry>) -> Shared {
+        let config = Config::load(None).unwrap();
+        let mut props = config.props().clone();
+        props.append_filename = dir.to_string_lossy().into_owned();
+        config.restore(props);
+        Shared::new(Arc::new(config), Arc::new(Stats::default()), pubsub)
+    }
+
+    /// Runs `test` on a database of its own, in a temporary directory removed afterwards.
+    pub fn with_shared<R>(name: &str, test: impl FnOnce(&mut Shared) -> R) -> R {
+        let dir = temporary_dir(name);
+        let mut shared = open(&dir, Arc::new(Registry::default()));
+        let result = test(&mut shared);
+        drop(shared);
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    /// Parses and applies a command like the db worker does, errors being replied as frames.
+    pub fn run(shared: &mut Shared, args: &[&str]) -> Frame {
+        let frames = args.iter().map(|arg| Frame::Bulk(arg.as_bytes().to_vec())).collect();
+        let cmd = match Parse::new(Frame::Array(frames)).and_then(Parse::into_command) {
+            Ok(cmd) => cmd,
+            Err(err) => return Frame::Error(err.to_string()),
+        };
+        for key in cmd.keys() {
+            shared.expire_if_needed(key).unwrap();
+        }
+        match cmd.apply(shared) {
+            Ok(frame) => frame,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    /// A reply in the form redis-cli prints it, to compare in assertions.
+    pub fn show(frame: &Frame) -> String {
+        match frame {
+            Frame::Str(s) => String::from_utf8_lossy(s).into_owned(),
+            Frame::Bulk(b) => format!("\"{}\"", String::from_utf8_lossy(b)),
+            Frame::Error(err) => format!("(error) {}", err),
+            Frame::Integer(n) => format!("(integer) {}", n),
+            Frame::Nil => "(nil)".to_string(),
+            Frame::Array(items) | Frame::Push(items) => {
+                format!("[{}]", items.iter().map(show).collect::<Vec<_>>().join(", "))
+            }
+            Frame::Map(pairs) => format!(
+                "{{{}}}",
+                pairs.iter().map(|(k, v)| format!("{}: {}", show(k), show(v))).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
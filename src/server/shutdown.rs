@@ -0,0 +1,41 @@
+use std::sync::Mutex;
+
+use tokio::sync::Notify;
+
+/// How the server stops, see the SHUTDOWN command.
+#[derive(Debug, Clone, Copy)]
+pub struct ShutdownMode {
+    /// Flush the memtables to SST files, otherwise only the WAL is synced.
+    pub save: bool,
+    /// Close the connections without waiting for their commands, dropping the queued ones.
+    pub now: bool,
+}
+
+impl Default for ShutdownMode {
+    fn default() -> ShutdownMode {
+        ShutdownMode { save: true, now: false }
+    }
+}
+
+/// A shutdown asked for by a client, picked up by `server::run`.
+#[derive(Debug, Default)]
+pub struct ShutdownRequest {
+    pending: Mutex<Option<ShutdownMode>>,
+    notify: Notify,
+}
+
+impl ShutdownRequest {
+    pub fn request(&self, mode: ShutdownMode) {
+        *self.pending.lock().unwrap() = Some(mode);
+        self.notify.notify_one();
+    }
+
+    pub async fn requested(&self) -> ShutdownMode {
+        loop {
+            self.notify.notified().await;
+            if let Some(mode) = self.pending.lock().unwrap().take() {
+                return mode;
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Server-wide counters reported by INFO and cleared by CONFIG RESETSTAT.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub total_connections_received: AtomicU64,
+    pub total_commands_processed: AtomicU64,
+    pub rejected_connections: AtomicU64,
+    /// Keys deleted because they expired, when read or by the expire cycle.
+    pub expired_keys: AtomicU64,
+    /// Gauge of the clients currently holding a connection permit, never reset.
+    pub connected_clients: AtomicU64,
+}
+
+impl Stats {
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn decr(counter: &AtomicU64) {
+        counter.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn get(counter: &AtomicU64) -> u64 {
+        counter.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.total_connections_received.store(0, Ordering::Relaxed);
+        self.total_commands_processed.store(0, Ordering::Relaxed);
+        self.rejected_connections.store(0, Ordering::Relaxed);
+        self.expired_keys.store(0, Ordering::Relaxed);
+    }
+}
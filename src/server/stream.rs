@@ -0,0 +1,626 @@
+//! Stream IDs and the layout of stream values.
+//!
+//! The value under the key holds the metadata below, every entry is a sub entry `e` followed by
+//! its ID as big endian milliseconds and sequence number, so IDs sort as entries do and a range
+//! of IDs is a range of sub keys.
+//!
+//! A consumer group is a sub entry `g` with its name, holding `GroupMeta`. Its consumers are `c`
+//! entries and its pending entries list `p` entries by ID, each pending entry also indexed by
+//! consumer under `q` so a consumer's own entries are a range as well.
+
+use std::fmt;
+
+pub const INVALID_ID: &str = "ERR Invalid stream ID specified as stream command argument";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId { ms: u64::MAX, seq: u64::MAX };
+
+    pub fn new(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    /// Parses `ms-seq`, or `ms` alone with `seq` as sequence number.
+    pub fn parse(id: &str, seq: u64) -> Option<StreamId> {
+        match id.split_once('-') {
+            Some((ms, s)) => Some(StreamId::new(ms.parse().ok()?, s.parse().ok()?)),
+            None => Some(StreamId::new(id.parse().ok()?, seq)),
+        }
+    }
+
+    /// Parses a range bound of XRANGE: `-`, `+`, an ID, or one preceded by `(` to exclude it.
+    /// A missing sequence number is 0 for the start and the largest for the end.
+    pub fn parse_bound(id: &str, start: bool) -> Result<StreamId, &'static str> {
+        match id {
+            "-" => return Ok(StreamId::MIN),
+            "+" => return Ok(StreamId::MAX),
+            _ => {}
+        }
+        let (exclusive, id) = match id.strip_prefix('(') {
+            Some(id) => (true, id),
+            None => (false, id),
+        };
+        let seq = if start { 0 } else { u64::MAX };
+        let id = StreamId::parse(id, seq).ok_or(INVALID_ID)?;
+        if !exclusive {
+            return Ok(id);
+        }
+        match start {
+            true => id.next().ok_or("ERR invalid start ID for the interval"),
+            false => id.prev().ok_or("ERR invalid end ID for the interval"),
+        }
+    }
+
+    pub fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => Some(StreamId::new(self.ms.checked_add(1)?, 0)),
+        }
+    }
+
+    pub fn prev(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => Some(StreamId::new(self.ms.checked_sub(1)?, u64::MAX)),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&self.ms.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.seq.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> StreamId {
+        StreamId::new(read_u64(&bytes[..8]), read_u64(&bytes[8..16]))
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// The ID XADD is given.
+#[derive(Debug, Clone, Copy)]
+pub enum NewId {
+    /// `*`, from the current time.
+    Auto,
+    /// `ms-*`, the sequence number being generated.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+impl NewId {
+    pub fn parse(id: &str) -> Option<NewId> {
+        if id == "*" {
+            return Some(NewId::Auto);
+        }
+        if let Some(ms) = id.strip_suffix("-*") {
+            return Some(NewId::AutoSeq(ms.parse().ok()?));
+        }
+        StreamId::parse(id, 0).map(NewId::Explicit)
+    }
+
+    /// The ID of a new entry after `last`, `Err` with the reply when it can't be used.
+    pub fn resolve(self, last: StreamId, now: u64) -> Result<StreamId, &'static str> {
+        let id = match self {
+            NewId::Auto if now > last.ms => Some(StreamId::new(now, 0)),
+            NewId::Auto => last.next(),
+            NewId::AutoSeq(ms) if ms == last.ms => last.seq.checked_add(1).map(|seq| StreamId::new(ms, seq)),
+            NewId::AutoSeq(ms) if ms > last.ms => Some(StreamId::new(ms, 0)),
+            NewId::AutoSeq(_) => None,
+            NewId::Explicit(id) if id == StreamId::MIN => {
+                return Err("ERR The ID specified in XADD must be greater than 0-0")
+            }
+            NewId::Explicit(id) if id > last => Some(id),
+            NewId::Explicit(_) => None,
+        };
+        match (self, id) {
+            (_, Some(id)) => Ok(id),
+            (NewId::Auto, None) => Err("ERR The stream has exhausted the last possible ID, unable to add more items"),
+            _ => Err("ERR The ID specified in XADD is equal or smaller than the target stream top item"),
+        }
+    }
+}
+
+/// How XADD and XTRIM trim a stream.
+#[derive(Debug, Clone, Copy)]
+pub struct Trim {
+    pub strategy: TrimStrategy,
+    /// The most entries removed at once, `None` for no limit.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TrimStrategy {
+    /// Keeps the newest entries.
+    MaxLen(u64),
+    /// Removes the entries with a lower ID.
+    MinId(StreamId),
+}
+
+/// Entries `~` trims at most when no LIMIT is given, 100 times redis' default node size.
+const APPROX_LIMIT: usize = 100 * 100;
+
+impl Trim {
+    /// Parses `MAXLEN|MINID [=|~] threshold [LIMIT count]` from the start of `args`, returning it
+    /// with the number of arguments used, `None` when `args` doesn't start with a strategy.
+    pub fn parse(args: &[String]) -> Result<Option<(Trim, usize)>, String> {
+        let maxlen = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+            Some("maxlen") => true,
+            Some("minid") => false,
+            _ => return Ok(None),
+        };
+        let mut used = 1;
+        let approx = match args.get(used).map(String::as_str) {
+            Some("~") => true,
+            Some("=") => false,
+            _ => {
+                used -= 1;
+                false
+            }
+        };
+        used += 1;
+        let threshold = args.get(used).ok_or("ERR syntax error")?;
+        used += 1;
+        let strategy = match maxlen {
+            true => match threshold.parse::<i64>() {
+                Ok(len) if len >= 0 => TrimStrategy::MaxLen(len as u64),
+                Ok(_) => return Err("ERR The MAXLEN argument must be >= 0.".into()),
+                Err(_) => return Err("ERR value is not an integer or out of range".into()),
+            },
+            false => TrimStrategy::MinId(StreamId::parse(threshold, 0).ok_or(INVALID_ID)?),
+        };
+        let mut limit = if approx { Some(APPROX_LIMIT) } else { None };
+        if matches!(args.get(used), Some(arg) if arg.eq_ignore_ascii_case("limit")) {
+            if !approx {
+                return Err("ERR syntax error, LIMIT cannot be used without the special ~ option".into());
+            }
+            limit = match args.get(used + 1).map(|count| count.parse::<i64>()) {
+                Some(Ok(0)) => None,
+                Some(Ok(count)) if count > 0 => Some(count as usize),
+                Some(Ok(_)) => return Err("ERR The LIMIT argument must be >= 0.".into()),
+                _ => return Err("ERR value is not an integer or out of range".into()),
+            };
+            used += 2;
+        }
+        Ok(Some((Trim { strategy, limit }, used)))
+    }
+}
+
+/// The metadata kept as the value of a stream key.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StreamMeta {
+    pub len: u64,
+    pub last_id: StreamId,
+    /// The largest ID removed by XDEL or trimming.
+    pub max_deleted_id: StreamId,
+    /// Every entry ever added, deleted ones included.
+    pub entries_added: u64,
+}
+
+impl StreamMeta {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(48);
+        raw.extend_from_slice(&self.len.to_be_bytes());
+        raw.extend_from_slice(&self.last_id.to_bytes());
+        raw.extend_from_slice(&self.max_deleted_id.to_bytes());
+        raw.extend_from_slice(&self.entries_added.to_be_bytes());
+        raw
+    }
+
+    pub fn decode(raw: &[u8]) -> crate::Result<StreamMeta> {
+        if raw.len() < 48 {
+            return Err("ERR corrupted stream metadata".into());
+        }
+        Ok(StreamMeta {
+            len: read_u64(&raw[..8]),
+            last_id: StreamId::from_bytes(&raw[8..24]),
+            max_deleted_id: StreamId::from_bytes(&raw[24..40]),
+            entries_added: read_u64(&raw[40..48]),
+        })
+    }
+}
+
+impl StreamMeta {
+    /// Whether entries from `start` on may have been deleted, which makes counting read entries
+    /// by ID impossible.
+    pub fn has_tombstones_from(&self, start: StreamId) -> bool {
+        self.len > 0 && self.max_deleted_id != StreamId::MIN && self.max_deleted_id >= start
+    }
+
+    /// The number of entries ever added up to `id`, `None` when deletions make it unknown.
+    /// `first` is the ID of the first entry.
+    pub fn entries_up_to(&self, id: StreamId, first: Option<StreamId>) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        if id == self.last_id || (self.len == 0 && id < self.last_id) {
+            return Some(self.entries_added);
+        }
+        let first = first?;
+        if id > self.last_id || self.max_deleted_id >= first {
+            return None;
+        }
+        match id.cmp(&first) {
+            std::cmp::Ordering::Less => Some(self.entries_added - self.len),
+            std::cmp::Ordering::Equal => Some(self.entries_added - self.len + 1),
+            std::cmp::Ordering::Greater => None,
+        }
+    }
+}
+
+/// How an unknown `entries_read` is stored.
+const NOT_READ: u64 = u64::MAX;
+
+/// The state of a consumer group.
+#[derive(Debug, Clone, Copy)]
+pub struct GroupMeta {
+    /// The last entry delivered to a consumer.
+    pub last_id: StreamId,
+    /// The number of entries delivered so far, `None` when it isn't known.
+    pub entries_read: Option<u64>,
+    /// The size of the pending entries list.
+    pub pending: u64,
+}
+
+impl GroupMeta {
+    pub fn new(last_id: StreamId, entries_read: Option<u64>) -> GroupMeta {
+        GroupMeta {
+            last_id,
+            entries_read,
+            pending: 0,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(32);
+        raw.extend_from_slice(&self.last_id.to_bytes());
+        raw.extend_from_slice(&self.entries_read.unwrap_or(NOT_READ).to_be_bytes());
+        raw.extend_from_slice(&self.pending.to_be_bytes());
+        raw
+    }
+
+    pub fn decode(raw: &[u8]) -> crate::Result<GroupMeta> {
+        if raw.len() < 32 {
+            return Err("ERR corrupted consumer group".into());
+        }
+        let entries_read = read_u64(&raw[16..24]);
+        Ok(GroupMeta {
+            last_id: StreamId::from_bytes(&raw[..16]),
+            entries_read: Some(entries_read).filter(|read| *read != NOT_READ),
+            pending: read_u64(&raw[24..32]),
+        })
+    }
+
+    /// The number of entries not delivered to the group yet, `None` when it isn't known.
+    pub fn lag(&self, meta: &StreamMeta, first: Option<StreamId>) -> Option<u64> {
+        if meta.entries_added == 0 {
+            return Some(0);
+        }
+        let read = match self.entries_read {
+            Some(read) if !meta.has_tombstones_from(self.last_id) && Some(self.last_id) >= first => Some(read),
+            _ => meta.entries_up_to(self.last_id, first),
+        };
+        read.map(|read| meta.entries_added.saturating_sub(read))
+    }
+}
+
+/// The state of a consumer of a group.
+#[derive(Debug, Clone, Copy)]
+pub struct ConsumerMeta {
+    /// The last time the consumer did anything, in unix milliseconds.
+    pub seen_time: u64,
+    /// The last time it read entries, `None` if it never did.
+    pub active_time: Option<u64>,
+    /// The number of pending entries delivered to it.
+    pub pending: u64,
+}
+
+impl ConsumerMeta {
+    pub fn new(now: u64) -> ConsumerMeta {
+        ConsumerMeta {
+            seen_time: now,
+            active_time: None,
+            pending: 0,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(24);
+        raw.extend_from_slice(&self.seen_time.to_be_bytes());
+        raw.extend_from_slice(&self.active_time.unwrap_or(0).to_be_bytes());
+        raw.extend_from_slice(&self.pending.to_be_bytes());
+        raw
+    }
+
+    pub fn decode(raw: &[u8]) -> crate::Result<ConsumerMeta> {
+        if raw.len() < 24 {
+            return Err("ERR corrupted stream consumer".into());
+        }
+        Ok(ConsumerMeta {
+            seen_time: read_u64(&raw[..8]),
+            active_time: Some(read_u64(&raw[8..16])).filter(|time| *time != 0),
+            pending: read_u64(&raw[16..24]),
+        })
+    }
+}
+
+/// How XCLAIM and XAUTOCLAIM take pending entries over.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Claim {
+    /// Entries idle for less than this many milliseconds are left alone.
+    pub min_idle: u64,
+    /// The delivery time to set, now when `None`.
+    pub delivery_time: Option<u64>,
+    /// The delivery count to set, otherwise it is incremented unless `just_id` is set.
+    pub retry_count: Option<u64>,
+    /// Claims IDs that aren't pending as long as the entry exists.
+    pub force: bool,
+    /// Replies IDs only and leaves delivery counts alone.
+    pub just_id: bool,
+}
+
+/// An entry delivered to a consumer and not acknowledged yet.
+#[derive(Debug, Clone)]
+pub struct PendingEntry {
+    pub consumer: String,
+    /// The last time it was delivered, in unix milliseconds.
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+impl PendingEntry {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(16 + self.consumer.len());
+        raw.extend_from_slice(&self.delivery_time.to_be_bytes());
+        raw.extend_from_slice(&self.delivery_count.to_be_bytes());
+        raw.extend_from_slice(self.consumer.as_bytes());
+        raw
+    }
+
+    pub fn decode(raw: &[u8]) -> crate::Result<PendingEntry> {
+        if raw.len() < 16 {
+            return Err("ERR corrupted pending entry".into());
+        }
+        Ok(PendingEntry {
+            consumer: String::from_utf8_lossy(&raw[16..]).into_owned(),
+            delivery_time: read_u64(&raw[..8]),
+            delivery_count: read_u64(&raw[8..16]),
+        })
+    }
+}
+
+fn read_u64(raw: &[u8]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&raw[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// The field value pairs of an entry.
+pub type Fields = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Entries delivered to a consumer, `None` fields standing for entries deleted since.
+pub type Delivered = Vec<(StreamId, Option<Fields>)>;
+
+/// Fields and values as length prefixed strings one after the other.
+pub fn encode_fields(fields: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut raw = Vec::new();
+    for (field, value) in fields {
+        for part in [field, value] {
+            raw.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            raw.extend_from_slice(part);
+        }
+    }
+    raw
+}
+
+pub fn decode_fields(mut raw: &[u8]) -> Fields {
+    let mut parts = Vec::new();
+    while raw.len() >= 4 {
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&raw[..4]);
+        let len = (u32::from_be_bytes(len) as usize).min(raw.len() - 4);
+        parts.push(raw[4..4 + len].to_vec());
+        raw = &raw[4 + len..];
+    }
+    let mut fields = Vec::with_capacity(parts.len() / 2);
+    let mut parts = parts.into_iter();
+    while let (Some(field), Some(value)) = (parts.next(), parts.next()) {
+        fields.push((field, value));
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn ids_parse_with_a_default_sequence() {
+        assert_eq!(StreamId::parse("5-3", 0), Some(StreamId::new(5, 3)));
+        assert_eq!(StreamId::parse("5", 7), Some(StreamId::new(5, 7)));
+        assert_eq!(StreamId::parse("5-", 0), None);
+        assert_eq!(StreamId::parse("-1", 0), None);
+        assert_eq!(StreamId::parse("a-1", 0), None);
+        assert_eq!(StreamId::new(18446744073709551615, 1).to_string(), "18446744073709551615-1");
+    }
+
+    #[test]
+    fn range_bounds() {
+        assert_eq!(StreamId::parse_bound("-", true), Ok(StreamId::MIN));
+        assert_eq!(StreamId::parse_bound("+", false), Ok(StreamId::MAX));
+        assert_eq!(StreamId::parse_bound("5", true), Ok(StreamId::new(5, 0)));
+        assert_eq!(StreamId::parse_bound("5", false), Ok(StreamId::new(5, u64::MAX)));
+        assert_eq!(StreamId::parse_bound("(5-3", true), Ok(StreamId::new(5, 4)));
+        assert_eq!(StreamId::parse_bound("(5-0", false), Ok(StreamId::new(4, u64::MAX)));
+        assert_eq!(StreamId::parse_bound("(0-0", false), Err("ERR invalid end ID for the interval"));
+        let max = format!("({}", StreamId::MAX);
+        assert_eq!(StreamId::parse_bound(&max, true), Err("ERR invalid start ID for the interval"));
+        assert_eq!(StreamId::parse_bound("x", true), Err(INVALID_ID));
+    }
+
+    #[test]
+    fn ids_sort_as_their_bytes() {
+        let ids = [StreamId::new(1, 2), StreamId::new(1, 10), StreamId::new(2, 0), StreamId::new(256, 0)];
+        for pair in ids.windows(2) {
+            assert!(pair[0] < pair[1]);
+            assert!(pair[0].to_bytes() < pair[1].to_bytes());
+        }
+        for id in ids {
+            assert_eq!(StreamId::from_bytes(&id.to_bytes()), id);
+        }
+    }
+
+    #[test]
+    fn new_ids_follow_the_last_one() {
+        let last = StreamId::new(10, 5);
+        assert_eq!(NewId::parse("*").unwrap().resolve(last, 20), Ok(StreamId::new(20, 0)));
+        // a clock going backwards still gives a greater ID
+        assert_eq!(NewId::parse("*").unwrap().resolve(last, 5), Ok(StreamId::new(10, 6)));
+        assert_eq!(NewId::parse("10-*").unwrap().resolve(last, 0), Ok(StreamId::new(10, 6)));
+        assert_eq!(NewId::parse("11-*").unwrap().resolve(last, 0), Ok(StreamId::new(11, 0)));
+        assert_eq!(NewId::parse("11").unwrap().resolve(last, 0), Ok(StreamId::new(11, 0)));
+        let smaller = "ERR The ID specified in XADD is equal or smaller than the target stream top item";
+        assert_eq!(NewId::parse("9-*").unwrap().resolve(last, 0), Err(smaller));
+        assert_eq!(NewId::parse("10-5").unwrap().resolve(last, 0), Err(smaller));
+        assert_eq!(
+            NewId::parse("0-0").unwrap().resolve(StreamId::MIN, 0),
+            Err("ERR The ID specified in XADD must be greater than 0-0")
+        );
+        assert!(NewId::parse("1-x").is_none());
+        assert!(NewId::parse("*-1").is_none());
+    }
+
+    #[test]
+    fn ids_run_out() {
+        let exhausted = "ERR The stream has exhausted the last possible ID, unable to add more items";
+        assert_eq!(NewId::Auto.resolve(StreamId::MAX, 0), Err(exhausted));
+        let last = StreamId::new(3, u64::MAX);
+        assert!(NewId::AutoSeq(3).resolve(last, 0).is_err());
+        assert_eq!(StreamId::new(3, u64::MAX).next(), Some(StreamId::new(4, 0)));
+        assert_eq!(StreamId::MAX.next(), None);
+        assert_eq!(StreamId::MIN.prev(), None);
+    }
+
+    #[test]
+    fn trim_options() {
+        let (trim, used) = Trim::parse(&args("MAXLEN 10 rest")).unwrap().unwrap();
+        assert!(matches!(trim.strategy, TrimStrategy::MaxLen(10)));
+        assert_eq!((trim.limit, used), (None, 2));
+        let (trim, used) = Trim::parse(&args("maxlen = 10")).unwrap().unwrap();
+        assert_eq!((trim.limit, used), (None, 3));
+        let (trim, used) = Trim::parse(&args("maxlen ~ 10")).unwrap().unwrap();
+        assert_eq!((trim.limit, used), (Some(APPROX_LIMIT), 3));
+        let (trim, used) = Trim::parse(&args("MINID ~ 5-1 LIMIT 3")).unwrap().unwrap();
+        assert!(matches!(trim.strategy, TrimStrategy::MinId(id) if id == StreamId::new(5, 1)));
+        assert_eq!((trim.limit, used), (Some(3), 5));
+        let (trim, _) = Trim::parse(&args("minid ~ 5 limit 0")).unwrap().unwrap();
+        assert_eq!(trim.limit, None);
+        assert!(Trim::parse(&args("count 10")).unwrap().is_none());
+    }
+
+    #[test]
+    fn trim_errors() {
+        let error = |line: &str| Trim::parse(&args(line)).unwrap_err();
+        assert_eq!(error("maxlen -1"), "ERR The MAXLEN argument must be >= 0.");
+        assert_eq!(error("maxlen x"), "ERR value is not an integer or out of range");
+        assert_eq!(error("minid x"), INVALID_ID);
+        assert_eq!(error("maxlen = 1 limit 5"), "ERR syntax error, LIMIT cannot be used without the special ~ option");
+        assert_eq!(error("maxlen ~ 1 limit -1"), "ERR The LIMIT argument must be >= 0.");
+        assert_eq!(Trim::parse(&args("maxlen ~")).unwrap_err(), "ERR syntax error");
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let meta = StreamMeta {
+            len: 3,
+            last_id: StreamId::new(5, 1),
+            max_deleted_id: StreamId::new(2, 0),
+            entries_added: 5,
+        };
+        let decoded = StreamMeta::decode(&meta.encode()).unwrap();
+        assert_eq!((decoded.len, decoded.last_id), (3, StreamId::new(5, 1)));
+        assert_eq!((decoded.max_deleted_id, decoded.entries_added), (StreamId::new(2, 0), 5));
+        assert!(StreamMeta::decode(&[0; 47]).is_err());
+    }
+
+    #[test]
+    fn entries_counted_up_to_an_id() {
+        // 5 entries added, 1-0 and 2-0 trimmed away, 3-0 to 5-0 left
+        let meta = StreamMeta {
+            len: 3,
+            last_id: StreamId::new(5, 0),
+            max_deleted_id: StreamId::new(2, 0),
+            entries_added: 5,
+        };
+        let first = Some(StreamId::new(3, 0));
+        assert_eq!(meta.entries_up_to(StreamId::new(5, 0), first), Some(5));
+        assert_eq!(meta.entries_up_to(StreamId::new(3, 0), first), Some(3));
+        assert_eq!(meta.entries_up_to(StreamId::new(1, 0), first), Some(2));
+        assert_eq!(meta.entries_up_to(StreamId::new(4, 0), first), None);
+        assert_eq!(meta.entries_up_to(StreamId::new(6, 0), first), None);
+        assert!(!meta.has_tombstones_from(StreamId::new(3, 0)));
+        assert!(meta.has_tombstones_from(StreamId::new(1, 0)));
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let fields = vec![(b"name".to_vec(), b"value".to_vec()), (Vec::new(), b"\x00\xff".to_vec())];
+        assert_eq!(decode_fields(&encode_fields(&fields)), fields);
+        assert!(decode_fields(&[]).is_empty());
+    }
+
+    #[test]
+    fn group_state_round_trips() {
+        let mut group = GroupMeta::new(StreamId::new(4, 2), None);
+        group.pending = 3;
+        let decoded = GroupMeta::decode(&group.encode()).unwrap();
+        assert_eq!((decoded.last_id, decoded.entries_read, decoded.pending), (StreamId::new(4, 2), None, 3));
+        let decoded = GroupMeta::decode(&GroupMeta::new(StreamId::MIN, Some(7)).encode()).unwrap();
+        assert_eq!(decoded.entries_read, Some(7));
+
+        let mut consumer = ConsumerMeta::new(100);
+        assert_eq!(ConsumerMeta::decode(&consumer.encode()).unwrap().active_time, None);
+        consumer.active_time = Some(150);
+        consumer.pending = 2;
+        let decoded = ConsumerMeta::decode(&consumer.encode()).unwrap();
+        assert_eq!((decoded.seen_time, decoded.active_time, decoded.pending), (100, Some(150), 2));
+
+        let pending = PendingEntry { consumer: "alice".to_string(), delivery_time: 42, delivery_count: 2 };
+        let decoded = PendingEntry::decode(&pending.encode()).unwrap();
+        assert_eq!((decoded.consumer.as_str(), decoded.delivery_time, decoded.delivery_count), ("alice", 42, 2));
+        assert!(PendingEntry::decode(&[0; 15]).is_err());
+    }
+
+    #[test]
+    fn group_lag() {
+        let meta = StreamMeta {
+            len: 3,
+            last_id: StreamId::new(3, 0),
+            max_deleted_id: StreamId::MIN,
+            entries_added: 3,
+        };
+        let first = Some(StreamId::new(1, 0));
+        assert_eq!(GroupMeta::new(StreamId::MIN, Some(0)).lag(&meta, first), Some(3));
+        assert_eq!(GroupMeta::new(StreamId::new(1, 0), Some(1)).lag(&meta, first), Some(2));
+        assert_eq!(GroupMeta::new(StreamId::new(3, 0), None).lag(&meta, first), Some(0));
+        // an entry in the middle deleted, what the group read can't be counted by ID anymore
+        let meta = StreamMeta { len: 2, max_deleted_id: StreamId::new(2, 0), ..meta };
+        assert_eq!(GroupMeta::new(StreamId::new(1, 0), None).lag(&meta, first), None);
+        assert_eq!(GroupMeta::new(StreamId::new(1, 0), Some(1)).lag(&meta, first), None);
+    }
+}
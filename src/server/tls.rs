@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use tokio_rustls::rustls::server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient};
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::config::ServerProperties;
+
+/// Builds the acceptor for the TLS port from `tls-cert-file`, `tls-key-file` and,
+/// when client certificates are verified, `tls-ca-cert-file`.
+pub fn acceptor(props: &ServerProperties) -> crate::Result<TlsAcceptor> {
+    let certs = load_certs(&props.tls_cert_file)?;
+    let key = load_key(&props.tls_key_file)?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match &props.tls_auth_clients[..] {
+        "no" => builder.with_no_client_auth(),
+        auth => {
+            if props.tls_ca_cert_file.is_empty() {
+                return Err("tls-ca-cert-file is required to verify client certificates, or set tls-auth-clients no".into());
+            }
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(&props.tls_ca_cert_file)? {
+                roots.add(&cert)?;
+            }
+            if auth == "optional" {
+                builder.with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots))
+            } else {
+                builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots))
+            }
+        }
+    };
+
+    let config = builder.with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn load_certs(file: &str) -> crate::Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(open(file)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", file).into());
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(file: &str) -> crate::Result<PrivateKey> {
+    let mut reader = BufReader::new(open(file)?);
+    for item in rustls_pemfile::read_all(&mut reader)? {
+        match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => {}
+        }
+    }
+    Err(format!("no private key found in {}", file).into())
+}
+
+fn open(file: &str) -> crate::Result<File> {
+    match File::open(file) {
+        Ok(f) => Ok(f),
+        Err(err) => Err(format!("failed to open {}, {}", file, err).into()),
+    }
+}
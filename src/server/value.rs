@@ -27,7 +27,7 @@ impl From<String> for ConvertError {
 impl fmt::Display for ConvertError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ConvertError::InvalidNumberFormat => "invalid number format".fmt(fmt),
+            ConvertError::InvalidNumberFormat => "ERR value is not an integer or out of range".fmt(fmt),
             ConvertError::Other(err) => err.fmt(fmt),
         }
     }
@@ -47,22 +47,30 @@ impl Value {
         }
     }
 
-    pub fn incr(&mut self, i: i64) -> Result<i64, ConvertError> {
-        match self {
-            Value::Bytes(b) => {
-                let fmt: Result<i64, ConvertError> =
-                    atoi::atoi::<i64>(b).ok_or_else(|| ConvertError::InvalidNumberFormat);
-
-                match fmt {
-                    Ok(mut num) => {
-                        num += i;
-                        Ok(num)
-                    }
-                    Err(err) => return Err(err),
-                }
-            }
-            _ => Err("WRONGTYPE Operation against a key holding the wrong kind of value".into()),
+    pub fn incr(&self, i: i64) -> Result<i64, ConvertError> {
+        let num = match self {
+            Value::Bytes(b) => std::str::from_utf8(b)
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .ok_or(ConvertError::InvalidNumberFormat)?,
+            Value::None => 0,
+        };
+        match num.checked_add(i) {
+            Some(num) => Ok(num),
+            None => Err("ERR increment or decrement would overflow".into()),
+        }
+    }
+
+    pub fn incr_float(&self, f: f64) -> Result<f64, ConvertError> {
+        let num = match self {
+            Value::Bytes(b) => parse_float(b).ok_or("ERR value is not a valid float")?,
+            Value::None => 0.0,
+        };
+        let num = num + f;
+        if !num.is_finite() {
+            return Err("ERR increment would produce NaN or Infinity".into());
         }
+        Ok(num)
     }
 
     pub fn as_slice(&self) -> &[u8] {
@@ -76,3 +84,83 @@ impl Value {
         Value::Bytes(u8)
     }
 }
+
+/// Parses a float the way redis accepts it, rejecting NaN and surrounding spaces.
+pub fn parse_float(b: &[u8]) -> Option<f64> {
+    let s = std::str::from_utf8(b).ok()?;
+    if s.is_empty() || s.trim() != s {
+        return None;
+    }
+    match s.parse::<f64>() {
+        Ok(f) if !f.is_nan() => Some(f),
+        _ => None,
+    }
+}
+
+/// Formats a float like INCRBYFLOAT replies: fixed point without trailing zeros.
+/// Redis computes in long double, rounding to 15 significant digits gives the same
+/// output for doubles, e.g. `0.3` for `0.1 + 0.2`.
+pub fn format_float(f: f64) -> String {
+    if f == 0.0 {
+        return "0".to_string();
+    }
+    let magnitude = f.abs().log10().floor() as i32;
+    let decimals = (14 - magnitude).clamp(0, 17) as usize;
+    let s = format!("{:.*}", decimals, f);
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    match s {
+        "-0" => "0".to_string(),
+        s => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn increments() {
+        assert_eq!(Value::None.incr(-3).unwrap(), -3);
+        assert_eq!(Value::Bytes(b"41".to_vec()).incr(1).unwrap(), 42);
+        let err = Value::Bytes(b" 41".to_vec()).incr(1).unwrap_err();
+        assert_eq!(err.to_string(), "ERR value is not an integer or out of range");
+        let err = Value::Bytes(i64::MAX.to_string().into_bytes()).incr(1).unwrap_err();
+        assert_eq!(err.to_string(), "ERR increment or decrement would overflow");
+        let err = Value::Bytes(i64::MIN.to_string().into_bytes()).incr(-1).unwrap_err();
+        assert_eq!(err.to_string(), "ERR increment or decrement would overflow");
+    }
+
+    #[test]
+    fn float_increments() {
+        assert_eq!(Value::Bytes(b"10.5".to_vec()).incr_float(0.1).unwrap(), 10.6);
+        let err = Value::Bytes(b"abc".to_vec()).incr_float(1.0).unwrap_err();
+        assert_eq!(err.to_string(), "ERR value is not a valid float");
+        let err = Value::Bytes(b"1e308".to_vec()).incr_float(1e308).unwrap_err();
+        assert_eq!(err.to_string(), "ERR increment would produce NaN or Infinity");
+    }
+
+    #[test]
+    fn floats_parsed_like_redis() {
+        assert_eq!(parse_float(b"5.0e3"), Some(5000.0));
+        assert_eq!(parse_float(b"-.5"), Some(-0.5));
+        assert_eq!(parse_float(b"inf"), Some(f64::INFINITY));
+        for invalid in [&b""[..], b" 1", b"1 ", b"nan", b"1.5x", b"\xff"] {
+            assert_eq!(parse_float(invalid), None);
+        }
+    }
+
+    #[test]
+    fn floats_formatted_like_incrbyfloat() {
+        assert_eq!(format_float(0.1 + 0.2), "0.3");
+        assert_eq!(format_float(10.6), "10.6");
+        assert_eq!(format_float(5200.0), "5200");
+        assert_eq!(format_float(-0.0), "0");
+        assert_eq!(format_float(-1.5), "-1.5");
+        assert_eq!(format_float(1e20), "100000000000000000000");
+        assert_eq!(format_float(1.0 / 3.0), "0.333333333333333");
+    }
+}
@@ -68,4 +68,20 @@
         }
     
         s_idx >= s.len() && p_idx >= p.len()
-    }
\ No newline at end of file
+    }
+
+/// Compares two byte strings in time independent of where they differ, for passwords.
+pub fn secure_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The current unix time in milliseconds.
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}